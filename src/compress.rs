@@ -0,0 +1,154 @@
+//! Optional compression of serialized values for the shared backend
+//! (`compress="lz4"` / `compress="zstd"`).
+//!
+//! A compressed value is a one-byte codec tag followed by the compressed
+//! serialized bytes. Serialized values always start with a `serde` tag
+//! (0..=8), so a reader can tell the two apart without knowing the codec, and
+//! values too small to be worth compressing are stored as they are.
+//!
+//! The codecs are behind the `compression` cargo feature. Without it, asking
+//! for a codec is an error and compressed values cannot be read.
+
+use std::borrow::Cow;
+use std::io;
+
+use crate::shm::{VALUE_CODEC_LZ4, VALUE_CODEC_ZSTD};
+
+/// First byte of an lz4-compressed value.
+const TAG_LZ4: u8 = 0xF1;
+/// First byte of a zstd-compressed value.
+const TAG_ZSTD: u8 = 0xF2;
+
+/// zstd's own default level: a good speed/ratio balance for cache values.
+#[cfg(feature = "compression")]
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    Lz4,
+    Zstd,
+}
+
+impl Codec {
+    /// Parse a `compress=` argument.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        let codec = match name {
+            "lz4" => Codec::Lz4,
+            "zstd" => Codec::Zstd,
+            other => {
+                return Err(format!(
+                    "compress must be 'lz4', 'zstd' or None, got {other:?}"
+                ))
+            }
+        };
+        if cfg!(feature = "compression") {
+            Ok(codec)
+        } else {
+            Err(format!(
+                "compress={name:?} needs warp_cache built with the 'compression' feature"
+            ))
+        }
+    }
+
+    /// The id stored in the cache header (`VALUE_CODEC_*`).
+    pub fn id(self) -> u32 {
+        match self {
+            Codec::Lz4 => VALUE_CODEC_LZ4,
+            Codec::Zstd => VALUE_CODEC_ZSTD,
+        }
+    }
+
+    /// Inverse of `id`; `None` for `VALUE_CODEC_NONE` (or an unknown id).
+    pub fn from_id(id: u32) -> Option<Self> {
+        match id {
+            VALUE_CODEC_LZ4 => Some(Codec::Lz4),
+            VALUE_CODEC_ZSTD => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// `data` compressed with `codec`, if it is longer than `threshold` bytes and
+/// compressing actually shrinks it; otherwise `data` unchanged.
+pub fn encode(codec: Codec, threshold: usize, data: Vec<u8>) -> Vec<u8> {
+    if data.len() <= threshold {
+        return data;
+    }
+    match compress(codec, &data) {
+        Some(compressed) if compressed.len() < data.len() => compressed,
+        _ => data,
+    }
+}
+
+/// Inverse of `encode`. Values that were stored uncompressed come back as is.
+pub fn decode(data: &[u8]) -> io::Result<Cow<'_, [u8]>> {
+    if is_compressed(data) {
+        decompress(data).map(Cow::Owned)
+    } else {
+        Ok(Cow::Borrowed(data))
+    }
+}
+
+/// Whether `decode` has work to do on `data`.
+pub fn is_compressed(data: &[u8]) -> bool {
+    matches!(data.first(), Some(&(TAG_LZ4 | TAG_ZSTD)))
+}
+
+#[cfg(feature = "compression")]
+fn compress(codec: Codec, data: &[u8]) -> Option<Vec<u8>> {
+    let (tag, body) = match codec {
+        Codec::Lz4 => (TAG_LZ4, lz4_flex::compress_prepend_size(data)),
+        Codec::Zstd => (TAG_ZSTD, zstd::bulk::compress(data, ZSTD_LEVEL).ok()?),
+    };
+    let mut out = Vec::with_capacity(1 + body.len());
+    out.push(tag);
+    out.extend_from_slice(&body);
+    Some(out)
+}
+
+#[cfg(not(feature = "compression"))]
+fn compress(_codec: Codec, _data: &[u8]) -> Option<Vec<u8>> {
+    None
+}
+
+#[cfg(feature = "compression")]
+fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let body = &data[1..];
+    if data[0] == TAG_LZ4 {
+        lz4_flex::decompress_size_prepended(body)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    } else {
+        zstd::decode_all(body)
+    }
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_data: &[u8]) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "value is compressed, but warp_cache was built without the 'compression' feature",
+    ))
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_and_thresholds() {
+        let value: Vec<u8> = b"\x06".iter().chain(&[b'a'; 4000]).copied().collect();
+        for codec in [Codec::Lz4, Codec::Zstd] {
+            let encoded = encode(codec, 64, value.clone());
+            assert!(encoded.len() < value.len());
+            assert_eq!(decode(&encoded).unwrap(), &value[..]);
+
+            // At or under the threshold, and when compression doesn't pay off,
+            // the value is stored as is.
+            assert_eq!(encode(codec, value.len(), value.clone()), value);
+            let noise: Vec<u8> = (0..200u32).map(|i| (i * 7919 % 251) as u8).collect();
+            assert_eq!(encode(codec, 0, noise.clone()), noise);
+        }
+        assert!(matches!(decode(&value).unwrap(), Cow::Borrowed(_)));
+        assert!(decode(&[TAG_LZ4, 0xFF, 0xFF]).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! Project-wide defaults for cache arguments, set with `configure()`.
+//!
+//! The constructors of `CachedFunction`, `SharedCachedFunction` and
+//! `SharedDict` take every argument left out (or passed as `None`) from here:
+//! the configured value if there is one, else the built-in default below.
+//! They read it once, so a cache keeps the defaults it was made with.
+
+use pyo3::conversion::FromPyObjectOwned;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
+use pyo3::types::PyDict;
+use pyo3::IntoPyObjectExt;
+
+/// A built-in default, as `cache()` documents it.
+#[derive(Clone, Copy)]
+enum Builtin {
+    None,
+    Bool(bool),
+    Int(i64),
+    Str(&'static str),
+}
+
+/// Every `cache()` argument and its built-in default, in `cache()`'s order.
+/// `get_config()` lists them, and `configure()` accepts no others.
+const PARAMETERS: &[(&str, Builtin)] = &[
+    ("max_size", Builtin::Int(128)),
+    ("ttl", Builtin::None),
+    ("backend", Builtin::Str("memory")),
+    ("max_key_size", Builtin::Int(512)),
+    ("max_value_size", Builtin::Int(4096)),
+    ("stale_ttl", Builtin::None),
+    ("sliding_ttl", Builtin::Bool(false)),
+    ("on_mismatch", Builtin::Str("error")),
+    ("shm_dir", Builtin::None),
+    ("file_mode", Builtin::None),
+    ("allow_insecure", Builtin::Bool(false)),
+    ("persistent", Builtin::Bool(false)),
+    ("sync_interval", Builtin::None),
+    ("compress", Builtin::None),
+    ("compress_threshold", Builtin::Int(1024)),
+    ("serializer", Builtin::None),
+    ("oversize", Builtin::Str("skip")),
+    ("chunk_size", Builtin::None),
+    ("overflow_count", Builtin::Int(0)),
+    ("overflow_size", Builtin::None),
+    ("lock_timeout", Builtin::None),
+    ("checksums", Builtin::Bool(false)),
+    ("lock_memory", Builtin::Bool(false)),
+    ("prefault", Builtin::Bool(false)),
+    ("on_evict", Builtin::None),
+    ("readonly", Builtin::Bool(false)),
+    ("cache_version", Builtin::None),
+    ("tags_func", Builtin::None),
+    ("fallback", Builtin::Str("error")),
+    ("unhashable", Builtin::None),
+    ("record_latencies", Builtin::Bool(false)),
+    ("min_compute_time", Builtin::None),
+    ("compact_keys", Builtin::Bool(false)),
+];
+
+/// The values set with `configure()`, by argument name.
+static CONFIGURED: PyOnceLock<Py<PyDict>> = PyOnceLock::new();
+
+fn configured_table(py: Python<'_>) -> &Bound<'_, PyDict> {
+    CONFIGURED
+        .get_or_init(py, || PyDict::new(py).unbind())
+        .bind(py)
+}
+
+/// The argument `name` in `configure()` stands for: an argument's own name,
+/// or it prefixed with `default_` (`default_strategy` being `backend`).
+fn parameter(name: &str) -> Option<&'static str> {
+    let name = match name {
+        "default_strategy" => "backend",
+        _ => name.strip_prefix("default_").unwrap_or(name),
+    };
+    PARAMETERS
+        .iter()
+        .find(|(parameter, _)| *parameter == name)
+        .map(|(parameter, _)| *parameter)
+}
+
+fn builtin<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyAny>> {
+    let default = PARAMETERS
+        .iter()
+        .find(|(parameter, _)| *parameter == name)
+        .map(|(_, default)| *default)
+        .expect("a parameter in PARAMETERS");
+    match default {
+        Builtin::None => Ok(py.None().into_bound(py)),
+        Builtin::Bool(value) => value.into_bound_py_any(py),
+        Builtin::Int(value) => value.into_bound_py_any(py),
+        Builtin::Str(value) => value.into_bound_py_any(py),
+    }
+}
+
+/// `value` as a `T`, an error naming the argument it's the default for.
+fn extract<'py, T: FromPyObjectOwned<'py>>(name: &str, value: &Bound<'py, PyAny>) -> PyResult<T> {
+    let py = value.py();
+    value.extract::<T>().map_err(|err| {
+        let err: PyErr = err.into();
+        let message = format!("default for {name}: {}", err.value(py));
+        PyErr::from_type(err.get_type(py), message)
+    })
+}
+
+/// `value` if given, else the default configured for `name`, if any.
+pub(crate) fn configured<'py, T: FromPyObjectOwned<'py>>(
+    py: Python<'py>,
+    name: &str,
+    value: Option<T>,
+) -> PyResult<Option<T>> {
+    if value.is_some() {
+        return Ok(value);
+    }
+    match configured_table(py).get_item(name)? {
+        Some(configured) => extract(name, &configured).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// `value` if given, else the default configured for `name`, else its
+/// built-in default.
+pub(crate) fn resolved<'py, T: FromPyObjectOwned<'py>>(
+    py: Python<'py>,
+    name: &str,
+    value: Option<T>,
+) -> PyResult<T> {
+    match configured(py, name, value)? {
+        Some(value) => Ok(value),
+        None => extract(name, &builtin(py, name)?),
+    }
+}
+
+/// Set defaults for cache arguments, by the names `cache()` takes them
+/// under, optionally prefixed with `default_`:
+/// `configure(default_strategy="shared", default_ttl=300)`, where
+/// `strategy` is `backend`.
+///
+/// `CachedFunction`, `SharedCachedFunction` and `SharedDict` take an
+/// argument left out or passed as `None` from here, else from its built-in
+/// default; one passed explicitly always wins. Only caches constructed
+/// afterwards are affected. Arguments a cache doesn't take are ignored by
+/// it, so shared-backend-only defaults don't reach memory-backend caches.
+/// Setting an argument to `None` drops its configured default. Unknown
+/// names raise `TypeError`; values are checked when a cache is made with
+/// them.
+#[pyfunction]
+#[pyo3(signature = (**defaults))]
+pub fn configure(py: Python<'_>, defaults: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+    let Some(defaults) = defaults else {
+        return Ok(());
+    };
+    let mut known = Vec::with_capacity(defaults.len());
+    let mut unknown = Vec::new();
+    for (name, value) in defaults.iter() {
+        let name: String = name.extract()?;
+        match parameter(&name) {
+            Some(parameter) => known.push((parameter, value)),
+            None => unknown.push(name),
+        }
+    }
+    if !unknown.is_empty() {
+        unknown.sort();
+        return Err(PyTypeError::new_err(format!(
+            "configure() got unknown cache() arguments: {}",
+            unknown.join(", ")
+        )));
+    }
+    let table = configured_table(py);
+    for (name, value) in known {
+        if value.is_none() {
+            if table.contains(name)? {
+                table.del_item(name)?;
+            }
+        } else {
+            table.set_item(name, value)?;
+        }
+    }
+    Ok(())
+}
+
+/// The default of every `cache()` argument for the next cache constructed:
+/// the value set with `configure()`, or the built-in default.
+#[pyfunction]
+pub fn get_config(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let table = configured_table(py);
+    let config = PyDict::new(py);
+    for (name, _) in PARAMETERS {
+        match table.get_item(name)? {
+            Some(value) => config.set_item(name, value)?,
+            None => config.set_item(name, builtin(py, name)?)?,
+        }
+    }
+    Ok(config)
+}
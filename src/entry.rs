@@ -1,12 +1,48 @@
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use pyo3::prelude::*;
 
 pub struct SieveEntry {
     pub value: Py<PyAny>,
     pub created_at: Instant,
+    /// Nanoseconds after `created_at` at which a sliding TTL last restarted the
+    /// entry's clock (0 = never). Atomic so hits can bump it under a read guard,
+    /// like `visited`.
+    pub refreshed_nanos: AtomicU64,
     pub visited: AtomicBool,
+    /// Tags for `invalidate_tag`, indexed by the shard's `tags`.
+    pub tags: Vec<String>,
+}
+
+impl SieveEntry {
+    /// Time since the entry was stored, or since its last sliding-TTL refresh.
+    #[inline(always)]
+    pub fn age(&self) -> Duration {
+        self.created_at
+            .elapsed()
+            .saturating_sub(Duration::from_nanos(
+                self.refreshed_nanos.load(Ordering::Relaxed),
+            ))
+    }
+
+    /// Set the SIEVE bit on a hit. Loads first so that hits on an entry
+    /// already marked, the common case, don't write: concurrent hits under a
+    /// shard's read lock then share its cache line instead of bouncing it
+    /// between cores on every call.
+    #[inline(always)]
+    pub fn mark_visited(&self) {
+        if !self.visited.load(Ordering::Relaxed) {
+            self.visited.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Restart the entry's TTL clock (sliding expiration).
+    #[inline(always)]
+    pub fn refresh_age(&self) {
+        let elapsed = self.created_at.elapsed().as_nanos() as u64;
+        self.refreshed_nanos.store(elapsed, Ordering::Relaxed);
+    }
 }
 
 impl Clone for SieveEntry {
@@ -14,7 +50,9 @@ impl Clone for SieveEntry {
         Python::attach(|py| SieveEntry {
             value: self.value.clone_ref(py),
             created_at: self.created_at,
+            refreshed_nanos: AtomicU64::new(self.refreshed_nanos.load(Ordering::Relaxed)),
             visited: AtomicBool::new(self.visited.load(Ordering::Relaxed)),
+            tags: self.tags.clone(),
         })
     }
 }
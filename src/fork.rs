@@ -0,0 +1,175 @@
+//! Putting process-local state back in order in the child of an `os.fork`.
+//!
+//! Servers such as gunicorn and uWSGI fork workers after the caches are
+//! built. The child gets a copy of every lock in the process with only the
+//! forking thread alive, so a lock another thread held at that moment would
+//! stay locked forever, and the child's next call on that cache would hang.
+//! Each cache registers the state that can be caught like that (see
+//! `AfterFork`), and an `os.register_at_fork(after_in_child=...)` hook,
+//! installed on first registration, releases it in the child.
+//!
+//! The mappings of a shared cache need nothing: a fork keeps them, and the
+//! child uses the same files as the parent.
+
+use std::cell::UnsafeCell;
+use std::ops::Deref;
+use std::sync::Weak;
+
+use parking_lot::Mutex;
+#[cfg(Py_GIL_DISABLED)]
+use parking_lot::RwLock;
+use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
+use pyo3::types::PyDict;
+
+/// Process-local state a fork can leave held by a thread the child doesn't
+/// have.
+pub trait AfterFork: Send + Sync {
+    /// Called in the child, where the forking thread is the only thread, so
+    /// nothing else can be using the state.
+    fn after_fork_in_child(&self);
+}
+
+/// Every registered state still alive (and some that aren't, pruned on the
+/// next `register`).
+static LIVE: Mutex<Vec<Weak<dyn AfterFork>>> = Mutex::new(Vec::new());
+
+/// Set once the `os.register_at_fork` hooks are installed.
+static HOOKS: PyOnceLock<()> = PyOnceLock::new();
+
+/// Have `state` put in order in the child of every fork from now on, for as
+/// long as it is alive.
+pub fn register(py: Python<'_>, state: Weak<dyn AfterFork>) -> PyResult<()> {
+    HOOKS.get_or_try_init(py, || install_hooks(py))?;
+    let mut live = LIVE.lock();
+    live.retain(|state| state.strong_count() > 0);
+    live.push(state);
+    Ok(())
+}
+
+/// `os.register_at_fork` with the three hooks below. Platforms without
+/// `fork` (Windows) have no `register_at_fork` and need none.
+fn install_hooks(py: Python<'_>) -> PyResult<()> {
+    let os = py.import("os")?;
+    if !os.hasattr("register_at_fork")? {
+        return Ok(());
+    }
+    let hooks = PyDict::new(py);
+    hooks.set_item("before", wrap_pyfunction!(before_fork, py)?)?;
+    hooks.set_item(
+        "after_in_parent",
+        wrap_pyfunction!(after_fork_in_parent, py)?,
+    )?;
+    hooks.set_item("after_in_child", wrap_pyfunction!(after_fork_in_child, py)?)?;
+    os.call_method("register_at_fork", (), Some(&hooks))?;
+    Ok(())
+}
+
+/// Hold `LIVE` across the fork, so the child doesn't get it mid-`register`.
+#[pyfunction]
+fn before_fork() {
+    std::mem::forget(LIVE.lock());
+}
+
+#[pyfunction]
+fn after_fork_in_parent() {
+    // SAFETY: `before_fork` locked it on this thread and forgot the guard.
+    unsafe { LIVE.force_unlock() };
+}
+
+#[pyfunction]
+fn after_fork_in_child() {
+    // SAFETY: as in the parent; the lock was copied held by this thread.
+    let live = unsafe {
+        LIVE.force_unlock();
+        LIVE.lock()
+    };
+    for state in live.iter().filter_map(Weak::upgrade) {
+        state.after_fork_in_child();
+    }
+}
+
+/// A lock that the child of a fork can get back unlocked
+/// (`release_in_child`), for state an `AfterFork` owns. Derefs to the lock.
+pub struct ForkLock<L>(UnsafeCell<L>);
+
+// SAFETY: shared only as `&L`, except in `release_in_child`, whose caller
+// guarantees no other thread exists.
+unsafe impl<L: Sync> Sync for ForkLock<L> {}
+
+impl<L> ForkLock<L> {
+    pub const fn new(lock: L) -> Self {
+        ForkLock(UnsafeCell::new(lock))
+    }
+}
+
+impl<L> Deref for ForkLock<L> {
+    type Target = L;
+    #[inline(always)]
+    fn deref(&self) -> &L {
+        // SAFETY: only `release_in_child` writes, with no reference live.
+        unsafe { &*self.0.get() }
+    }
+}
+
+impl<T> ForkLock<Mutex<T>> {
+    pub fn into_inner(self) -> T {
+        self.0.into_inner().into_inner()
+    }
+
+    /// Unlock the mutex if a thread the child doesn't have held it, keeping
+    /// its data. The lock is rebuilt rather than unlocked, since unlocking
+    /// would try to wake threads parked on it, which exist only in the parent.
+    ///
+    /// # Safety
+    ///
+    /// Only from `AfterFork::after_fork_in_child`, and only if the calling
+    /// thread holds no guard of this lock.
+    pub unsafe fn release_in_child(&self) {
+        if self.is_locked() {
+            let data = std::ptr::read(self.data_ptr());
+            std::ptr::write(self.0.get(), Mutex::new(data));
+        }
+    }
+}
+
+#[cfg(Py_GIL_DISABLED)]
+impl<T> ForkLock<RwLock<T>> {
+    /// `release_in_child` for a `RwLock`. A writer may have been anywhere in
+    /// its update when the fork came, so data that was locked for writing is
+    /// left alone (leaked) and `fresh()` put in its place.
+    ///
+    /// # Safety
+    ///
+    /// As for the `Mutex` one.
+    pub unsafe fn release_in_child(&self, fresh: impl FnOnce() -> T) {
+        if !self.is_locked() {
+            return;
+        }
+        let data = if self.is_locked_exclusive() {
+            fresh()
+        } else {
+            std::ptr::read(self.data_ptr())
+        };
+        std::ptr::write(self.0.get(), RwLock::new(data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_lock_left_held_is_released_with_its_data() {
+        let lock = ForkLock::new(Mutex::new(vec![1, 2]));
+        lock.lock().push(3);
+        // As if the thread holding it had been lost in a fork.
+        std::mem::forget(lock.lock());
+        unsafe { lock.release_in_child() };
+        assert_eq!(*lock.try_lock().expect("released"), vec![1, 2, 3]);
+
+        // A free lock is left as it is.
+        unsafe { lock.release_in_child() };
+        assert_eq!(lock.into_inner(), vec![1, 2, 3]);
+    }
+}
@@ -1,34 +1,84 @@
+use std::cell::RefCell;
 use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
 use pyo3::ffi;
 use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
+use pyo3::types::{
+    PyByteArray, PyBytes, PyComplex, PyDict, PyFloat, PyFrozenSet, PyList, PySet, PyTuple,
+};
 
+use crate::serde::{self, Decoded};
+
+/// Deepest nesting `contains_nan` and `hashable_key` look into, as deep as
+/// the serde fast path goes.
+const MAX_DEPTH: u32 = 32;
+
+/// Markers heading the tuples `hashable_key` makes of a list or a dict, so
+/// that `[1, 2]` doesn't share an entry with `(1, 2)`. Plain `object()`s,
+/// which compare equal to nothing else.
+static LIST_MARK: PyOnceLock<Py<PyAny>> = PyOnceLock::new();
+static DICT_MARK: PyOnceLock<Py<PyAny>> = PyOnceLock::new();
+
+/// Separates the positional arguments from the keyword ones in the key of a
+/// call with kwargs (see `kwargs_key`), as `functools.lru_cache` does.
+static KWARGS_MARK: PyOnceLock<Py<PyAny>> = PyOnceLock::new();
+
+/// A stored key. `hash` is the key object's Python hash in either form, so
+/// that keys Python calls equal across types (`(1,)` and `(1.0,)`) still
+/// land in the same bucket.
 pub struct CacheKey {
     pub(crate) hash: isize,
-    pub key_obj: Py<PyAny>,
+    repr: KeyRepr,
+}
+
+enum KeyRepr {
+    /// The key object, compared with its `__eq__`.
+    Object(Py<PyAny>),
+    /// The key's `serde::serialize_key` bytes, for a key `compact_bytes`
+    /// takes. Two such keys are equal exactly when their bytes are, which
+    /// needs neither Python nor the argument objects kept alive. Shared by a
+    /// key's copies in `map`, `order` and the tag index.
+    Compact(Arc<[u8]>),
 }
 
 impl CacheKey {
+    /// Build a CacheKey from a pre-computed hash and an already-owned object,
+    /// kept as is.
     #[inline(always)]
-    pub fn new(py: Python<'_>, obj: Py<PyAny>) -> PyResult<Self> {
-        let hash = obj.bind(py).hash()?;
-        Ok(CacheKey { hash, key_obj: obj })
+    pub fn with_hash(hash: isize, obj: Py<PyAny>) -> Self {
+        CacheKey {
+            hash,
+            repr: KeyRepr::Object(obj),
+        }
     }
 
-    /// Build a CacheKey from a pre-computed hash and an already-owned object.
-    #[inline(always)]
-    pub fn with_hash(hash: isize, obj: Py<PyAny>) -> Self {
-        CacheKey { hash, key_obj: obj }
+    /// The key to store for `obj`: its bytes if `compact_bytes` takes it,
+    /// otherwise `obj` itself.
+    pub fn stored(py: Python<'_>, hash: isize, obj: Py<PyAny>) -> Self {
+        match compact_bytes(obj.bind(py)) {
+            Some(bytes) => CacheKey {
+                hash,
+                repr: KeyRepr::Compact(bytes.into()),
+            },
+            None => CacheKey::with_hash(hash, obj),
+        }
     }
-}
 
-impl Clone for CacheKey {
+    /// Another reference to the same key. Every caller already holds the GIL,
+    /// so this takes its token rather than attaching to the interpreter again
+    /// on each copy (which a `Clone` impl would have to).
     #[inline(always)]
-    fn clone(&self) -> Self {
-        Python::attach(|py| CacheKey {
+    pub fn clone_with(&self, py: Python<'_>) -> Self {
+        let repr = match &self.repr {
+            KeyRepr::Object(obj) => KeyRepr::Object(obj.clone_ref(py)),
+            KeyRepr::Compact(bytes) => KeyRepr::Compact(Arc::clone(bytes)),
+        };
+        CacheKey {
             hash: self.hash,
-            key_obj: self.key_obj.clone_ref(py),
-        })
+            repr,
+        }
     }
 }
 
@@ -46,12 +96,26 @@ impl PartialEq for CacheKey {
             return false;
         }
         // SAFETY: PartialEq is only called from HashMap lookups inside
-        // #[pymethods], so the GIL is always held. The arbitrary Python __eq__
-        // this can run may re-enter the cache or hand off the GIL; CachedFunction
-        // serializes that via its reentrancy guard (try_enter, see issue #30), so
-        // no aliasing/reentrant shard guard is taken during this comparison.
+        // #[pymethods], so this thread is attached to the interpreter (holds
+        // the GIL, on builds that have one), which is all the C API needs.
+        // The arbitrary Python __eq__ this can run may re-enter the cache or
+        // hand off the GIL; CachedFunction serializes that via its reentrancy
+        // guard (try_enter, see issue #30), so no aliasing/reentrant shard
+        // guard is taken during this comparison. Other threads of a
+        // free-threaded build are kept off the map by the shard's RwLock.
         // This is the same direct C API call that lru_cache uses.
-        unsafe { rich_compare_eq(self.key_obj.as_ptr(), other.key_obj.as_ptr()) }
+        match (&self.repr, &other.repr) {
+            (KeyRepr::Compact(a), KeyRepr::Compact(b)) => a == b,
+            (KeyRepr::Object(a), KeyRepr::Object(b)) => unsafe {
+                rich_compare_eq(a.as_ptr(), b.as_ptr())
+            },
+            // An object key is one `compact_bytes` turned down, so only Python
+            // can tell whether it equals a compact one (`(1.0,)` and `(1,)`).
+            (KeyRepr::Object(obj), KeyRepr::Compact(bytes))
+            | (KeyRepr::Compact(bytes), KeyRepr::Object(obj)) => unsafe {
+                equals_decoded(obj.as_ptr(), bytes)
+            },
+        }
     }
 }
 
@@ -78,6 +142,12 @@ pub(crate) struct BorrowedArgs {
     pub ptr: *mut ffi::PyObject,
 }
 
+thread_local! {
+    /// Where a lookup's arguments are serialized to be compared with a compact
+    /// key, reused so that a hit doesn't allocate.
+    static LOOKUP_BYTES: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
 impl Hash for BorrowedArgs {
     #[inline(always)]
     fn hash<H: Hasher>(&self, state: &mut H) {
@@ -91,12 +161,24 @@ impl hashbrown::Equivalent<CacheKey> for BorrowedArgs {
         if self.hash != key.hash {
             return false;
         }
-        // SAFETY: Called only inside #[pymethods] where the GIL is held.
+        // SAFETY: Called only inside #[pymethods], attached to the interpreter.
         // `self.ptr` points to a live Python object (the args tuple on the
-        // call stack) and `key.key_obj` is an owned reference in the map. The
+        // call stack) and an object key is an owned reference in the map. The
         // arbitrary Python __eq__ this runs may re-enter; CachedFunction's
         // reentrancy guard prevents a second, aliasing shard guard (issue #30).
-        unsafe { rich_compare_eq(self.ptr, key.key_obj.as_ptr()) }
+        match &key.repr {
+            KeyRepr::Object(obj) => unsafe { rich_compare_eq(self.ptr, obj.as_ptr()) },
+            KeyRepr::Compact(bytes) => {
+                // No Python code runs while the buffer is borrowed; a fallback
+                // to `__eq__`, which could re-enter, runs after.
+                let args = unsafe { Bound::ref_from_ptr(Python::assume_attached(), &self.ptr) };
+                let same = LOOKUP_BYTES.with_borrow_mut(|buf| {
+                    buf.clear();
+                    serde::serialize_compact_key(args, buf).then(|| buf[..] == bytes[..])
+                });
+                same.unwrap_or_else(|| unsafe { equals_decoded(self.ptr, bytes) })
+            }
+        }
     }
 }
 
@@ -106,8 +188,8 @@ impl hashbrown::Equivalent<CacheKey> for BorrowedArgs {
 /// `PyObject_RichCompareBool` returns -1 and leaves a Python exception set when a
 /// key's `__eq__` raises. We must NOT map that to `true`/`false` and silently drop
 /// the exception (issue #36): callers fetch the pending exception after the lookup
-/// and propagate it. Here, -1 is reported as "not equal" so hashbrown stops at this
-/// slot, with the exception left set for the caller.
+/// with `take_compare_error` and propagate it. Here, -1 is reported as "not
+/// equal", with the exception left set for the caller.
 ///
 /// If an exception is already pending (an earlier comparison in the same lookup
 /// raised), we return `false` without calling into Python again — re-entering the
@@ -124,3 +206,190 @@ unsafe fn rich_compare_eq(a: *mut ffi::PyObject, b: *mut ffi::PyObject) -> bool
     // two are both "not equal" for the probe, but -1 leaves the error for the caller.
     ffi::PyObject_RichCompareBool(a, b, ffi::Py_EQ) == 1
 }
+
+/// `obj == key` for a compact key, by decoding its bytes: how an object key
+/// is compared with a compact one, which Python alone can say. Rare, as the
+/// two only meet in a bucket when the key types differ. An error decoding
+/// the bytes is left set, as one from `__eq__` is.
+///
+/// # Safety
+/// `obj` must be a valid live Python object and the GIL must be held.
+unsafe fn equals_decoded(obj: *mut ffi::PyObject, key: &[u8]) -> bool {
+    if !ffi::PyErr_Occurred().is_null() {
+        return false;
+    }
+    let py = Python::assume_attached();
+    match serde::deserialize(py, key) {
+        Ok(Decoded::Value(decoded)) => rich_compare_eq(obj, decoded.as_ptr()),
+        // Only `compact_bytes` writes these, so nothing else can come back.
+        Ok(_) => false,
+        Err(err) => {
+            err.restore(py);
+            false
+        }
+    }
+}
+
+/// The bytes to keep for a key `serde::serialize_compact_key` takes: exact
+/// ints, strs, bytes, `None` and tuples of them. Among such keys, equal objects
+/// give equal bytes and unequal ones different bytes, so they compare without
+/// Python. Floats are left out, since `1.0 == 1` and `-0.0 == 0.0`, as are
+/// subclasses, whose `__eq__` may differ.
+fn compact_bytes(obj: &Bound<'_, PyAny>) -> Option<Vec<u8>> {
+    let mut buf = Vec::new();
+    serde::serialize_compact_key(obj, &mut buf).then_some(buf)
+}
+
+/// Propagate the exception a key's `__eq__` raised during the map operations
+/// just run, if any (see `rich_compare_eq`). Every lookup or mutation of a
+/// shard map must be followed by this before the GIL goes back to Python, or
+/// the exception is left pending for an unrelated later call to trip over.
+#[inline(always)]
+pub(crate) fn take_compare_error(py: Python<'_>) -> PyResult<()> {
+    match PyErr::take(py) {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Whether the arguments of a call hold a float or complex NaN, at top level
+/// or inside a tuple, list, dict, set or frozenset.
+///
+/// NaN never equals itself, so the memory backend could never find such a key
+/// again, while the shared backend, comparing bytes, would. Neither caches
+/// it instead: the call is a miss every time, and nothing is stored.
+pub(crate) fn args_contain_nan(
+    args: &Bound<'_, PyTuple>,
+    kwargs: &Option<Bound<'_, PyDict>>,
+) -> PyResult<bool> {
+    if contains_nan(args.as_any(), 0)? {
+        return Ok(true);
+    }
+    match kwargs {
+        Some(kwargs) => contains_nan(kwargs.as_any(), 0),
+        None => Ok(false),
+    }
+}
+
+fn contains_nan(obj: &Bound<'_, PyAny>, depth: u32) -> PyResult<bool> {
+    if let Ok(f) = obj.cast::<PyFloat>() {
+        return Ok(f.value().is_nan());
+    }
+    if let Ok(c) = obj.cast::<PyComplex>() {
+        return Ok(c.real().is_nan() || c.imag().is_nan());
+    }
+    if depth >= MAX_DEPTH {
+        return Ok(false);
+    }
+    if let Ok(tuple) = obj.cast::<PyTuple>() {
+        for item in tuple.iter() {
+            if contains_nan(&item, depth + 1)? {
+                return Ok(true);
+            }
+        }
+    } else if let Ok(list) = obj.cast::<PyList>() {
+        for item in list.iter() {
+            if contains_nan(&item, depth + 1)? {
+                return Ok(true);
+            }
+        }
+    } else if let Ok(dict) = obj.cast::<PyDict>() {
+        for (key, value) in dict.iter() {
+            if contains_nan(&key, depth + 1)? || contains_nan(&value, depth + 1)? {
+                return Ok(true);
+            }
+        }
+    } else if obj.is_instance_of::<PySet>() || obj.is_instance_of::<PyFrozenSet>() {
+        for item in obj.try_iter()? {
+            if contains_nan(&item?, depth + 1)? {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// A hashable stand-in for `obj`, for `unhashable="serialize"`: plain lists
+/// become tuples headed by a marker, dicts a marker and the frozenset of
+/// their items, sets frozensets (which they equal) and bytearrays bytes, all
+/// the way down through tuples. Objects stand in for each other exactly when
+/// they're equal. Anything else is left as it is, to raise when hashed.
+pub(crate) fn hashable_key<'py>(obj: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+    hashable_at(obj, 0)
+}
+
+fn hashable_at<'py>(obj: &Bound<'py, PyAny>, depth: u32) -> PyResult<Bound<'py, PyAny>> {
+    let py = obj.py();
+    if depth >= MAX_DEPTH {
+        return Ok(obj.clone());
+    }
+    if let Ok(tuple) = obj.cast_exact::<PyTuple>() {
+        let items = tuple
+            .iter()
+            .map(|item| hashable_at(&item, depth + 1))
+            .collect::<PyResult<Vec<_>>>()?;
+        return Ok(PyTuple::new(py, items)?.into_any());
+    }
+    if let Ok(list) = obj.cast_exact::<PyList>() {
+        let mut items = vec![marker(py, &LIST_MARK)?];
+        for item in list.iter() {
+            items.push(hashable_at(&item, depth + 1)?);
+        }
+        return Ok(PyTuple::new(py, items)?.into_any());
+    }
+    if let Ok(dict) = obj.cast_exact::<PyDict>() {
+        let mut pairs = Vec::with_capacity(dict.len());
+        for (key, value) in dict.iter() {
+            pairs.push(PyTuple::new(py, [key, hashable_at(&value, depth + 1)?])?);
+        }
+        let items = PyFrozenSet::new(py, pairs)?.into_any();
+        return Ok(PyTuple::new(py, [marker(py, &DICT_MARK)?, items])?.into_any());
+    }
+    if let Ok(set) = obj.cast_exact::<PySet>() {
+        return Ok(PyFrozenSet::new(py, set.iter())?.into_any());
+    }
+    if let Ok(bytes) = obj.cast_exact::<PyByteArray>() {
+        return Ok(PyBytes::new(py, &bytes.to_vec()).into_any());
+    }
+    Ok(obj.clone())
+}
+
+/// The key of a call with keyword arguments: `(args, KWARGS_MARK,
+/// sorted kwargs items)`. Without the marker, `f((), (("x", 1),))` would
+/// share an entry with `f(x=1)`; no caller can pass it positionally. The
+/// items are sorted with `list.sort` through the C API, the order
+/// `sorted()` gives, so no builtin is looked up per call.
+pub(crate) fn kwargs_key<'py>(
+    py: Python<'py>,
+    args: &Bound<'py, PyTuple>,
+    kwargs: &Bound<'py, PyDict>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let items = kwargs.items();
+    items.sort()?;
+    let kw_tup = items.to_tuple().into_any();
+    let key = PyTuple::new(py, [args.as_any().clone(), kwargs_mark(py)?, kw_tup])?;
+    Ok(key.into_any())
+}
+
+/// The marker `kwargs_key` puts between positional and keyword arguments.
+pub(crate) fn kwargs_mark(py: Python<'_>) -> PyResult<Bound<'_, PyAny>> {
+    marker(py, &KWARGS_MARK)
+}
+
+/// A key `kwargs_key` built, as `(args, kwargs_items)`; any other key as is.
+pub(crate) fn without_kwargs_mark<'py>(key: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+    if let Ok(tuple) = key.cast::<PyTuple>() {
+        if tuple.len() == 3 && tuple.get_item(1)?.is(kwargs_mark(key.py())?) {
+            let pair = PyTuple::new(key.py(), [tuple.get_item(0)?, tuple.get_item(2)?])?;
+            return Ok(pair.into_any());
+        }
+    }
+    Ok(key.clone())
+}
+
+fn marker<'py>(py: Python<'py>, mark: &PyOnceLock<Py<PyAny>>) -> PyResult<Bound<'py, PyAny>> {
+    let mark = mark.get_or_try_init(py, || {
+        PyResult::Ok(py.import("builtins")?.getattr("object")?.call0()?.unbind())
+    })?;
+    Ok(mark.bind(py).clone())
+}
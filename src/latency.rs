@@ -0,0 +1,92 @@
+//! Hit and miss latency histograms (`record_latencies=True`).
+//!
+//! Fixed power-of-two nanosecond buckets of relaxed `AtomicU64`s, so that
+//! timing a call costs two monotonic clock reads, a `leading_zeros` and an
+//! atomic add, with no lock on either backend.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyFloat, PyInt, PyList};
+
+/// Bucket `i` counts durations in `[2^i, 2^(i+1))` ns (bucket 0 also takes
+/// 0 ns); the last one takes everything from `2^(BUCKETS-1)` ns (~9 min) up.
+const BUCKETS: usize = 40;
+
+struct Histogram {
+    counts: [AtomicU64; BUCKETS],
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Histogram {
+            counts: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    #[inline(always)]
+    fn record(&self, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        let bucket = (63 - (nanos | 1).leading_zeros()) as usize;
+        self.counts[bucket.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        for count in &self.counts {
+            count.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self) -> Vec<u64> {
+        self.counts
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect()
+    }
+}
+
+/// The two histograms of a cache: calls answered from the cache, and calls
+/// that ran the wrapped function.
+pub struct Latencies {
+    hit: Histogram,
+    miss: Histogram,
+}
+
+impl Latencies {
+    pub fn new() -> Self {
+        Latencies {
+            hit: Histogram::new(),
+            miss: Histogram::new(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn record(&self, hit: bool, elapsed: Duration) {
+        if hit {
+            self.hit.record(elapsed)
+        } else {
+            self.miss.record(elapsed)
+        }
+    }
+
+    pub fn reset(&self) {
+        self.hit.reset();
+        self.miss.reset();
+    }
+
+    /// `latency_histogram()`: `bounds_ns`, each bucket's exclusive upper
+    /// bound in nanoseconds (`inf` for the last), and the `hit` and `miss`
+    /// counts per bucket.
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let mut bounds: Vec<Bound<'py, PyAny>> = (1..BUCKETS)
+            .map(|i| PyInt::new(py, 1u64 << i).into_any())
+            .collect();
+        bounds.push(PyFloat::new(py, f64::INFINITY).into_any());
+        let dict = PyDict::new(py);
+        dict.set_item("bounds_ns", PyList::new(py, bounds)?)?;
+        dict.set_item("hit", self.hit.snapshot())?;
+        dict.set_item("miss", self.miss.snapshot())?;
+        Ok(dict)
+    }
+}
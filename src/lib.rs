@@ -1,31 +1,46 @@
+mod compress;
+mod config;
 mod entry;
+mod fork;
 mod key;
-mod store;
-
-#[cfg(not(target_os = "windows"))]
+mod latency;
+mod property;
 mod serde;
-#[cfg(not(target_os = "windows"))]
+mod shared_dict;
 mod shared_store;
-#[cfg(not(target_os = "windows"))]
 mod shm;
+mod snapshot;
+mod store;
 
-#[cfg(target_os = "windows")]
-mod shared_store_stub;
-
+use config::{configure, get_config};
+use property::CachedProperty;
 use pyo3::prelude::*;
+use serde::register_serializer;
+use shared_dict::SharedDict;
+use shared_store::{gc_shared, list_shared, SharedCacheInfo, SharedCachedFunction};
 use store::{CacheInfo, CachedFunction};
 
-#[cfg(not(target_os = "windows"))]
-use shared_store::{SharedCacheInfo, SharedCachedFunction};
-
-#[cfg(target_os = "windows")]
-use shared_store_stub::{SharedCacheInfo, SharedCachedFunction};
-
-#[pymodule]
+/// Declared safe to import without the GIL on free-threaded builds: the
+/// memory backend locks its shards itself there (see `store::ShardLock`), and
+/// everything else shared between threads is atomic or behind a lock.
+///
+/// PyO3 refuses to import a module into a second (sub)interpreter, which is
+/// what lets Python objects be looked up once and kept for the process: the
+/// `PyOnceLock` statics in `key` and `serde`, and each shared cache's
+/// `pickle_dumps`/`pickle_loads`. Supporting subinterpreters would mean
+/// moving those into per-module state.
+#[pymodule(gil_used = false)]
 fn _warp_cache_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<CachedFunction>()?;
     m.add_class::<CacheInfo>()?;
+    m.add_class::<CachedProperty>()?;
     m.add_class::<SharedCachedFunction>()?;
     m.add_class::<SharedCacheInfo>()?;
+    m.add_class::<SharedDict>()?;
+    m.add_function(wrap_pyfunction!(configure, m)?)?;
+    m.add_function(wrap_pyfunction!(gc_shared, m)?)?;
+    m.add_function(wrap_pyfunction!(get_config, m)?)?;
+    m.add_function(wrap_pyfunction!(list_shared, m)?)?;
+    m.add_function(wrap_pyfunction!(register_serializer, m)?)?;
     Ok(())
 }
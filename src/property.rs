@@ -0,0 +1,348 @@
+//! `CachedProperty`: `functools.cached_property` with its values kept here
+//! rather than in each instance's `__dict__`, which gives it a TTL, a bound
+//! on how many instances' values are kept, and `cache_info()`.
+//!
+//! A value is keyed by its instance's address and stored with a weak
+//! reference to the instance. The reference's callback drops the value when
+//! the instance goes, before its address can be reused; a value that is
+//! dropped first (evicted, expired or cleared) takes its reference with it,
+//! whose callback then never runs. A value that refers back to its instance
+//! keeps it alive until then, as the store is outside the cycle collector's
+//! view.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::{Duration, Instant};
+
+use hashbrown::HashMap;
+use parking_lot::Mutex;
+use pyo3::prelude::*;
+use pyo3::sync::MutexExt;
+use pyo3::types::{PyCFunction, PyDict, PyTuple, PyType, PyWeakrefReference};
+
+use crate::fork::ForkLock;
+#[cfg(Py_GIL_DISABLED)]
+use crate::fork::{self, AfterFork};
+use crate::store::{parse_seconds, CacheInfo};
+
+/// One instance's value.
+struct Slot {
+    /// Weak reference to the instance, whose callback drops this slot.
+    instance: Py<PyWeakrefReference>,
+    value: Py<PyAny>,
+    created_at: Instant,
+    /// SIEVE bit, as `SieveEntry::visited`.
+    visited: bool,
+    /// Tells this slot's place in `order` from that of an earlier instance
+    /// at the same address.
+    generation: u64,
+}
+
+#[derive(Default)]
+struct Slots {
+    by_instance: HashMap<usize, Slot>,
+    /// The instance address of each slot, by its weak reference's address,
+    /// which is all the callback gets.
+    by_reference: HashMap<usize, usize>,
+    /// Insertion order for SIEVE eviction, with `max_size` only. Slots
+    /// dropped otherwise leave their place behind, skipped when reached.
+    order: VecDeque<(usize, u64)>,
+    hand: usize,
+    next_generation: u64,
+}
+
+impl Slots {
+    fn remove(&mut self, id: usize) -> Option<Slot> {
+        let slot = self.by_instance.remove(&id)?;
+        self.by_reference.remove(&(slot.instance.as_ptr() as usize));
+        Some(slot)
+    }
+
+    /// Store `slot`, evicting one if `max_size` are stored. Returns the
+    /// slot evicted, to be dropped once the lock is released.
+    fn insert(&mut self, id: usize, mut slot: Slot, max_size: Option<usize>) -> Option<Slot> {
+        let evicted = match max_size {
+            Some(max_size) if self.by_instance.len() >= max_size => self.evict_one(),
+            _ => None,
+        };
+        slot.generation = self.next_generation;
+        self.next_generation += 1;
+        if let Some(max_size) = max_size {
+            if self.order.len() >= 2 * max_size {
+                let live = &self.by_instance;
+                self.order.retain(|(id, generation)| {
+                    live.get(id).is_some_and(|s| s.generation == *generation)
+                });
+                self.hand = 0;
+            }
+            self.order.push_back((id, slot.generation));
+        }
+        self.by_reference
+            .insert(slot.instance.as_ptr() as usize, id);
+        self.by_instance.insert(id, slot);
+        evicted
+    }
+
+    /// The SIEVE pass of `CachedFunction::evict_one`, over instances.
+    fn evict_one(&mut self) -> Option<Slot> {
+        while !self.order.is_empty() {
+            if self.hand >= self.order.len() {
+                self.hand = 0;
+            }
+            let (id, generation) = self.order[self.hand];
+            match self.by_instance.get_mut(&id) {
+                Some(slot) if slot.generation == generation && slot.visited => {
+                    slot.visited = false;
+                    self.hand += 1;
+                }
+                Some(slot) if slot.generation == generation => {
+                    self.order.remove(self.hand);
+                    return self.remove(id);
+                }
+                _ => {
+                    self.order.remove(self.hand);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The slots of one descriptor, shared with the weak references' callback
+/// and, on free-threaded builds, `fork::register`.
+struct Store {
+    slots: ForkLock<Mutex<Slots>>,
+}
+
+/// A thread the child of a fork doesn't have may have held the lock; its
+/// slots are kept. GIL builds hold it only attached, so never across a fork.
+#[cfg(Py_GIL_DISABLED)]
+impl AfterFork for Store {
+    fn after_fork_in_child(&self) {
+        // SAFETY: the lock is never held while Python code runs, so not by
+        // the forking thread.
+        unsafe { self.slots.release_in_child() };
+    }
+}
+
+/// A `functools.cached_property` backed by the Rust store; see the module
+/// docs. Every Python call (the getter, a value's `__del__` on eviction)
+/// runs with the lock released, so threads racing on an instance's first
+/// access may each run the getter, as with `functools.cached_property`
+/// since Python 3.12, but all of them get the value that was stored first.
+// `dict` lets the Python decorator apply `functools.update_wrapper`, and
+// `weakref` lets its registry of live caches hold the descriptor.
+#[pyclass(frozen, dict, weakref)]
+pub struct CachedProperty {
+    #[pyo3(get)]
+    func: Py<PyAny>,
+    ttl: Option<Duration>,
+    max_size: Option<usize>,
+    store: Arc<Store>,
+    /// Called with a dead instance's weak reference; drops its slot.
+    on_instance_gone: Py<PyCFunction>,
+    /// The attribute name, from `__set_name__`.
+    name: OnceLock<String>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[pymethods]
+impl CachedProperty {
+    #[new]
+    #[pyo3(signature = (func, ttl=None, max_size=None))]
+    fn new(
+        py: Python<'_>,
+        func: Py<PyAny>,
+        ttl: Option<f64>,
+        max_size: Option<usize>,
+    ) -> PyResult<Self> {
+        if max_size == Some(0) {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "max_size must be >= 1",
+            ));
+        }
+        let ttl = parse_seconds("ttl", ttl)?;
+        let store = Arc::new(Store {
+            slots: ForkLock::new(Mutex::new(Slots::default())),
+        });
+        #[cfg(Py_GIL_DISABLED)]
+        fork::register(py, Arc::downgrade(&store) as Weak<dyn AfterFork>)?;
+
+        let weak_store = Arc::downgrade(&store);
+        let on_instance_gone = PyCFunction::new_closure(
+            py,
+            Some(c"on_instance_gone"),
+            None,
+            move |args: &Bound<'_, PyTuple>, _: Option<&Bound<'_, PyDict>>| -> PyResult<()> {
+                instance_gone(&weak_store, args)
+            },
+        )?;
+        Ok(CachedProperty {
+            func,
+            ttl,
+            max_size,
+            store,
+            on_instance_gone: on_instance_gone.unbind(),
+            name: OnceLock::new(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    fn __set_name__(&self, owner: &Bound<'_, PyType>, name: String) -> PyResult<()> {
+        let set = self.name.get_or_init(|| name.clone());
+        if *set != name {
+            return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                "cannot assign the same cached property to two different names \
+                 ({set:?} and {name:?}, in {})",
+                owner.qualname()?
+            )));
+        }
+        Ok(())
+    }
+
+    fn __get__<'py>(
+        slf: &Bound<'py, Self>,
+        instance: Option<&Bound<'py, PyAny>>,
+        _owner: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Py<PyAny>> {
+        let py = slf.py();
+        let Some(instance) = instance else {
+            return Ok(slf.clone().into_any().unbind());
+        };
+        let this = slf.get();
+        let id = instance.as_ptr() as usize;
+        let expired = {
+            let mut slots = this.store.slots.lock_py_attached(py);
+            match slots.by_instance.get_mut(&id) {
+                Some(slot) if !this.is_expired(slot) => {
+                    slot.visited = true;
+                    this.hits.fetch_add(1, Ordering::Relaxed);
+                    return Ok(slot.value.clone_ref(py));
+                }
+                Some(_) => slots.remove(id),
+                None => None,
+            }
+        };
+        drop(expired);
+        this.misses.fetch_add(1, Ordering::Relaxed);
+
+        let reference = PyWeakrefReference::new_with(instance, this.on_instance_gone.bind(py))
+            .map_err(|err| this.not_weakrefable(instance, err))?;
+        let value = this.func.bind(py).call1((instance,))?.unbind();
+        let slot = Slot {
+            instance: reference.unbind(),
+            value: value.clone_ref(py),
+            created_at: Instant::now(),
+            visited: false,
+            generation: 0,
+        };
+        // Dropped once the lock is released: the slots that lose.
+        let (value, _dropped) = {
+            let mut slots = this.store.slots.lock_py_attached(py);
+            match slots.by_instance.get(&id) {
+                Some(stored) if !this.is_expired(stored) => {
+                    (stored.value.clone_ref(py), [Some(slot), None])
+                }
+                _ => {
+                    let replaced = slots.remove(id);
+                    let evicted = slots.insert(id, slot, this.max_size);
+                    (value, [replaced, evicted])
+                }
+            }
+        };
+        Ok(value)
+    }
+
+    /// The attribute name given by `__set_name__`, None before.
+    #[getter]
+    fn attrname(&self) -> Option<String> {
+        self.name.get().cloned()
+    }
+
+    /// Hits and misses over all instances; `current_size` counts instances
+    /// holding a value, and `max_size` is 0 without a bound.
+    fn cache_info(&self, py: Python<'_>) -> CacheInfo {
+        let current_size = self.store.slots.lock_py_attached(py).by_instance.len();
+        CacheInfo {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            max_size: self.max_size.unwrap_or(0),
+            current_size,
+            backend: "memory".to_string(),
+            uncacheable_calls: 0,
+            skipped_fast: 0,
+        }
+    }
+
+    /// `cache_info()` as a flat dict, under the names `CachedFunction::metrics`
+    /// uses.
+    fn metrics<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.cache_info(py).metrics(py)
+    }
+
+    /// Drop every instance's value and zero the counters.
+    fn cache_clear(&self, py: Python<'_>) {
+        let cleared = std::mem::take(&mut *self.store.slots.lock_py_attached(py));
+        drop(cleared);
+        self.reset_stats();
+    }
+
+    /// Zero the counters, keeping the values.
+    fn reset_stats(&self) {
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+
+    fn __repr__(&self) -> String {
+        match self.name.get() {
+            Some(name) => format!("<CachedProperty {name:?}>"),
+            None => "<CachedProperty>".to_string(),
+        }
+    }
+}
+
+impl CachedProperty {
+    #[inline(always)]
+    fn is_expired(&self, slot: &Slot) -> bool {
+        self.ttl.is_some_and(|ttl| slot.created_at.elapsed() >= ttl)
+    }
+
+    /// The `TypeError` for an instance there can be no weak reference to,
+    /// such as one with `__slots__` and no `__weakref__` among them.
+    fn not_weakrefable(&self, instance: &Bound<'_, PyAny>, err: PyErr) -> PyErr {
+        let py = instance.py();
+        if !err.is_instance_of::<pyo3::exceptions::PyTypeError>(py) {
+            return err;
+        }
+        let type_name = instance
+            .get_type()
+            .qualname()
+            .map_or_else(|_| "?".to_string(), |name| name.to_string());
+        let name = self.name.get().map_or("", String::as_str);
+        pyo3::exceptions::PyTypeError::new_err(format!(
+            "cached property {name:?} needs weak references to {type_name} instances \
+             (add '__weakref__' to its __slots__)"
+        ))
+    }
+}
+
+/// The weak reference callback: drop the slot of the instance that went.
+fn instance_gone(store: &Weak<Store>, args: &Bound<'_, PyTuple>) -> PyResult<()> {
+    let Some(store) = store.upgrade() else {
+        return Ok(());
+    };
+    let reference = args.get_item(0)?.as_ptr() as usize;
+    let py = args.py();
+    let gone = {
+        let mut slots = store.slots.lock_py_attached(py);
+        match slots.by_reference.get(&reference) {
+            Some(&id) => slots.remove(id),
+            None => None,
+        }
+    };
+    drop(gone);
+    Ok(())
+}
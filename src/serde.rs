@@ -1,10 +1,34 @@
 //! Fast-path serialization for common Python primitives.
 //!
-//! Tagged binary format — avoids pickle for None, bool, int, float, str, bytes,
-//! and flat tuples of these types.
+//! Tagged binary format — avoids pickle for None, bool, int (of any size), float, complex, str,
+//! bytes (bytearrays and memoryviews too, read back as bytes), dates, times, datetimes and
+//! timedeltas, decimals, UUIDs, numpy arrays, enum members, and tuples, namedtuples, frozen
+//! dataclasses, lists, dicts, sets and frozensets of these types, plus any type given a
+//! serializer with `register_serializer`. Each payload starts with a format byte (`FORMAT`), so
+//! a release can tell values it knows how to read from ones a newer release wrote.
 
+use std::convert::Infallible;
+use std::fmt;
+
+use pyo3::buffer::PyBuffer;
 use pyo3::prelude::*;
-use pyo3::types::{PyBool, PyBytes, PyFloat, PyInt, PyNone, PyString, PyTuple};
+use pyo3::sync::PyOnceLock;
+use pyo3::types::{
+    IntoPyDict, PyBool, PyByteArray, PyBytes, PyComplex, PyDate, PyDateAccess, PyDateTime, PyDelta,
+    PyDeltaAccess, PyDict, PyFloat, PyFrozenSet, PyInt, PyList, PyMemoryView, PyNone, PySet,
+    PyString, PyTime, PyTimeAccess, PyTuple, PyType, PyTzInfo, PyTzInfoAccess,
+};
+use xxhash_rust::xxh64::xxh64;
+
+use crate::key::kwargs_mark;
+
+/// Format of the payloads `serialize` writes, stored as their first byte
+/// ahead of the tagged element (a pickled payload starts with `TAG_PICKLE`
+/// instead). Bump it whenever an existing tag changes meaning, so that an
+/// older release sees a format it doesn't know and treats the entry as a miss
+/// rather than misreading it. A new tag needs no bump: an older release can't
+/// parse it, which is a miss too. Never 0, which would read as `TAG_PICKLE`.
+pub const FORMAT: u8 = 1;
 
 const TAG_PICKLE: u8 = 0;
 const TAG_NONE: u8 = 1;
@@ -15,30 +39,241 @@ const TAG_F64: u8 = 5;
 const TAG_STR: u8 = 6;
 const TAG_BYTES: u8 = 7;
 const TAG_TUPLE: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_DICT: u8 = 10;
+const TAG_SET: u8 = 11;
+const TAG_FROZENSET: u8 = 12;
+const TAG_BIGINT: u8 = 13;
+const TAG_DATE: u8 = 14;
+const TAG_TIME: u8 = 15;
+const TAG_TIMEDELTA: u8 = 16;
+const TAG_DATETIME: u8 = 17;
+const TAG_DATETIME_TZ: u8 = 18;
+const TAG_DECIMAL: u8 = 19;
+const TAG_UUID: u8 = 20;
+const TAG_NDARRAY: u8 = 21;
+const TAG_NAMEDTUPLE: u8 = 22;
+const TAG_DATACLASS: u8 = 23;
+const TAG_ENUM: u8 = 24;
+const TAG_COMPLEX: u8 = 25;
+const TAG_LONG_TUPLE: u8 = 26;
+const TAG_SMALL_INT: u8 = 27;
+const TAG_VSTR: u8 = 28;
+const TAG_VBYTES: u8 = 29;
+const TAG_CUSTOM: u8 = 30;
+const TAG_KWARGS_MARK: u8 = 31;
+
+/// Ints in this range are `TAG_SMALL_INT`: one byte, `v - SMALL_INT_MIN`.
+const SMALL_INT_MIN: i64 = -32;
+const SMALL_INT_MAX: i64 = 223;
+
+const MICROS_PER_DAY: i64 = 86_400_000_000;
+/// Microseconds from the Unix epoch to `datetime.min` and past `datetime.max`.
+const EPOCH_MIN_MICROS: i64 = -62_135_596_800_000_000;
+const EPOCH_END_MICROS: i64 = 253_402_300_800_000_000;
+
+/// `decimal.Decimal`, imported the first time it's needed.
+static DECIMAL: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+/// `uuid.UUID`, likewise.
+static UUID: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+/// `numpy.ndarray` and `numpy.frombuffer`. Only looked up for an object whose
+/// type is named `ndarray`, so numpy is never imported on its account.
+static NDARRAY: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+static FROMBUFFER: PyOnceLock<Py<PyAny>> = PyOnceLock::new();
+/// `dataclasses.fields`.
+static DATACLASS_FIELDS: PyOnceLock<Py<PyAny>> = PyOnceLock::new();
+/// `enum.Enum`.
+static ENUM: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+/// The serializers added with `register_serializer`: type to `(id, encode)`,
+/// and id to `(name, decode)`. Looked up on every call rather than copied
+/// into caches, so a registration also applies to caches that already exist.
+static ENCODERS: PyOnceLock<Py<PyDict>> = PyOnceLock::new();
+static DECODERS: PyOnceLock<Py<PyDict>> = PyOnceLock::new();
+
+/// Deepest nesting of containers the fast path handles. Anything
+/// deeper (including a list that contains itself) goes to pickle.
+const MAX_DEPTH: u32 = 32;
 
 /// Serialize a Python object to our tagged binary format.
 /// Returns `None` if the type is unsupported (caller should fall back to pickle).
 pub fn serialize(py: Python, obj: &Bound<PyAny>) -> PyResult<Option<Vec<u8>>> {
-    let mut buf = Vec::new();
-    if serialize_element(py, obj, &mut buf)? {
+    serialize_root(py, obj, false)
+}
+
+/// `serialize` for a cache key: dicts are written in a canonical order, their
+/// pairs sorted by serialized key bytes, so that equal dicts give equal bytes
+/// (and so the same key) whatever order their items were inserted in. Likewise
+/// datetimes and times drop `fold`, aware datetimes are written in UTC, and
+/// floats in the form `float_bits` gives them, none of which takes part in
+/// equality.
+pub fn serialize_key(py: Python, obj: &Bound<PyAny>) -> PyResult<Option<Vec<u8>>> {
+    serialize_root(py, obj, true)
+}
+
+/// `serialize_key` into `buf` for a key made only of exact ints (not bools)
+/// that fit in 64 bits, strs, bytes, `None`, the kwargs marker and tuples of
+/// them, giving the same bytes. `false`, with `buf` partly written, for any
+/// other key. It skips every check `serialize_element` makes for the types it
+/// doesn't take, and calls no Python code, so the memory backend can afford it
+/// on every lookup (see `key::CacheKey`).
+pub fn serialize_compact_key(obj: &Bound<PyAny>, buf: &mut Vec<u8>) -> bool {
+    buf.push(FORMAT);
+    compact_element(obj, buf, 0)
+}
+
+fn compact_element(obj: &Bound<PyAny>, buf: &mut Vec<u8>, depth: u32) -> bool {
+    if let Ok(tup) = obj.cast_exact::<PyTuple>() {
+        let len = tup.len();
+        if depth >= MAX_DEPTH {
+            return false;
+        }
+        if len > 255 {
+            let Ok(len) = u32::try_from(len) else {
+                return false;
+            };
+            buf.push(TAG_LONG_TUPLE);
+            buf.extend_from_slice(&len.to_le_bytes());
+        } else {
+            buf.push(TAG_TUPLE);
+            buf.push(len as u8);
+        }
+        return tup
+            .iter()
+            .all(|item| compact_element(&item, buf, depth + 1));
+    }
+    if obj.is_exact_instance_of::<PyInt>() {
+        let Ok(v) = obj.extract::<i64>() else {
+            return false;
+        };
+        push_i64(buf, v);
+        return true;
+    }
+    if let Ok(s) = obj.cast_exact::<PyString>() {
+        // Lone surrogates have no UTF-8 form.
+        let Ok(s) = s.to_str() else {
+            return false;
+        };
+        push_with_varint_len(buf, TAG_VSTR, s.as_bytes());
+        return true;
+    }
+    if let Ok(bytes) = obj.cast_exact::<PyBytes>() {
+        push_with_varint_len(buf, TAG_VBYTES, bytes.as_bytes());
+        return true;
+    }
+    if obj.is_none() {
+        buf.push(TAG_NONE);
+        return true;
+    }
+    if kwargs_mark(obj.py()).is_ok_and(|mark| obj.is(mark)) {
+        buf.push(TAG_KWARGS_MARK);
+        return true;
+    }
+    false
+}
+
+fn serialize_root(py: Python, obj: &Bound<PyAny>, canonical: bool) -> PyResult<Option<Vec<u8>>> {
+    let mut buf = vec![FORMAT];
+    if serialize_element(py, obj, &mut buf, 0, canonical)? {
         Ok(Some(buf))
     } else {
         Ok(None)
     }
 }
 
-/// Deserialize from our tagged binary format.
-/// Returns `None` if the tag is TAG_PICKLE (caller should use pickle.loads on the payload).
-pub fn deserialize(py: Python, data: &[u8]) -> PyResult<Option<Py<PyAny>>> {
-    if data.is_empty() {
-        return Ok(None);
+/// What `deserialize` made of a stored payload.
+pub enum Decoded {
+    /// A fast-path value.
+    Value(Py<PyAny>),
+    /// Pickled: the caller loads `pickle_payload(data)`.
+    Pickled,
+    /// A format this release doesn't know, written by a newer one. The
+    /// caller treats the entry as a miss.
+    UnknownFormat,
+    /// A payload in our format that doesn't parse. Never handed to pickle.
+    Corrupt(Corrupt),
+    /// A value of a type the writer registered (`register_serializer`) under
+    /// this id, but this process hasn't. The caller treats the entry as a miss.
+    Unregistered(u64),
+}
+
+/// Where and how a payload failed to parse.
+#[derive(Debug)]
+pub struct Corrupt {
+    /// Offset of the element that broke, from the start of the payload.
+    pub offset: usize,
+    /// What was wrong with it, such as `truncated str`.
+    pub reason: String,
+}
+
+impl fmt::Display for Corrupt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.reason, self.offset)
     }
-    if data[0] == TAG_PICKLE {
-        return Ok(None);
+}
+
+/// Why reading an element stopped: Python raised, the bytes are corrupt, or
+/// they hold a registered type this process has no decoder for.
+enum ReadError {
+    Py(PyErr),
+    Corrupt(Corrupt),
+    Unregistered(u64),
+}
+
+impl From<PyErr> for ReadError {
+    fn from(err: PyErr) -> Self {
+        ReadError::Py(err)
+    }
+}
+
+// For `into_pyobject` on bools, ints and floats, which can't fail.
+impl From<Infallible> for ReadError {
+    fn from(never: Infallible) -> Self {
+        match never {}
+    }
+}
+
+impl ReadError {
+    /// The same error, for an element `by` bytes further into the payload.
+    fn shifted(self, by: usize) -> Self {
+        match self {
+            ReadError::Corrupt(corrupt) => ReadError::Corrupt(Corrupt {
+                offset: corrupt.offset + by,
+                ..corrupt
+            }),
+            py => py,
+        }
     }
-    match deserialize_one(py, data)? {
-        Some((obj, _consumed)) => Ok(Some(obj)),
-        None => Ok(None),
+}
+
+type ReadResult<T> = Result<T, ReadError>;
+
+/// Deserialize from our tagged binary format, dispatching on the format byte.
+pub fn deserialize(py: Python, data: &[u8]) -> PyResult<Decoded> {
+    let element = match data {
+        [TAG_PICKLE, ..] => return Ok(Decoded::Pickled),
+        [FORMAT, element @ ..] => element,
+        [_, ..] => return Ok(Decoded::UnknownFormat),
+        [] => {
+            return Ok(Decoded::Corrupt(Corrupt {
+                offset: 0,
+                reason: "empty payload".to_string(),
+            }))
+        }
+    };
+    let read = deserialize_one(py, element, 0).and_then(|(obj, consumed)| {
+        if consumed < element.len() {
+            return Err(ReadError::Corrupt(Corrupt {
+                offset: consumed,
+                reason: format!("{} stray bytes after the value", element.len() - consumed),
+            }));
+        }
+        Ok(obj)
+    });
+    match read.map_err(|e| e.shifted(1)) {
+        Ok(obj) => Ok(Decoded::Value(obj)),
+        Err(ReadError::Corrupt(corrupt)) => Ok(Decoded::Corrupt(corrupt)),
+        Err(ReadError::Unregistered(id)) => Ok(Decoded::Unregistered(id)),
+        Err(ReadError::Py(err)) => Err(err),
     }
 }
 
@@ -55,14 +290,103 @@ pub fn pickle_payload(data: &[u8]) -> &[u8] {
     &data[1..]
 }
 
-/// Serialize one element into `buf`. Returns `false` if unsupported.
-fn serialize_element(_py: Python, obj: &Bound<PyAny>, buf: &mut Vec<u8>) -> PyResult<bool> {
+/// Encode an integer exactly as the fast path stores a Python `int`: the
+/// format byte, then 2 bytes for a small one or 9 otherwise.
+pub fn encode_i64(v: i64) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(10);
+    buf.push(FORMAT);
+    push_i64(&mut buf, v);
+    buf
+}
+
+/// Decode a value stored by `encode_i64`. Returns `None` for anything else,
+/// including ints outside i64 (`TAG_BIGINT`).
+pub fn decode_i64(data: &[u8]) -> Option<i64> {
+    match data {
+        [FORMAT, TAG_SMALL_INT, v] => Some(*v as i64 + SMALL_INT_MIN),
+        [FORMAT, TAG_I64, rest @ ..] if rest.len() == 8 => {
+            Some(i64::from_le_bytes(rest.try_into().ok()?))
+        }
+        _ => None,
+    }
+}
+
+fn push_i64(buf: &mut Vec<u8>, v: i64) {
+    if (SMALL_INT_MIN..=SMALL_INT_MAX).contains(&v) {
+        buf.push(TAG_SMALL_INT);
+        buf.push((v - SMALL_INT_MIN) as u8);
+    } else {
+        buf.push(TAG_I64);
+        buf.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+/// Append `n` as a LEB128 varint: 7 bits a byte, low bits first, the high
+/// bit set on every byte but the last.
+fn push_varint(buf: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        buf.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+/// Read a `push_varint` varint from the start of `data`, with the number of
+/// bytes it took. `None` if it runs off the end or past 64 bits.
+fn read_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut n = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(10) {
+        n |= ((byte & 0x7f) as u64).checked_shl(7 * i as u32)?;
+        if byte & 0x80 == 0 {
+            return Some((n, i + 1));
+        }
+    }
+    None
+}
+
+/// Append `tag`, the varint length of `bytes`, then `bytes`.
+fn push_with_varint_len(buf: &mut Vec<u8>, tag: u8, bytes: &[u8]) {
+    buf.push(tag);
+    push_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// The payload of a `push_with_varint_len` at the start of `data`, with the
+/// bytes taken in all, tag included.
+fn read_with_varint_len(data: &[u8]) -> Option<(&[u8], usize)> {
+    let (len, width) = read_varint(&data[1..])?;
+    let start = 1 + width;
+    let end = start.checked_add(usize::try_from(len).ok()?)?;
+    Some((data.get(start..end)?, end))
+}
+
+/// Serialize one element, nested `depth` containers deep, into `buf`, with
+/// dicts and datetimes in canonical form if `canonical` (see `serialize_key`).
+/// Returns `false` if unsupported.
+fn serialize_element(
+    _py: Python,
+    obj: &Bound<PyAny>,
+    buf: &mut Vec<u8>,
+    depth: u32,
+    canonical: bool,
+) -> PyResult<bool> {
     // None
     if obj.is_instance_of::<PyNone>() {
         buf.push(TAG_NONE);
         return Ok(true);
     }
 
+    // An enum member, before int and str so that IntEnum and StrEnum members
+    // keep their class. Only an instance of a class with a metaclass is
+    // checked, which keeps this off the path of everything else.
+    let ty = obj.get_type();
+    if !ty.get_type().is(_py.get_type::<PyType>())
+        && obj.is_instance(ENUM.import(_py, "enum", "Enum")?)?
+        && serialize_enum(obj, &ty, buf)?
+    {
+        return Ok(true);
+    }
+
     // bool before int (bool <: int in Python)
     if obj.is_instance_of::<PyBool>() {
         if obj.is_truthy()? {
@@ -76,19 +400,26 @@ fn serialize_element(_py: Python, obj: &Bound<PyAny>, buf: &mut Vec<u8>) -> PyRe
     // int
     if obj.is_instance_of::<PyInt>() {
         if let Ok(v) = obj.extract::<i64>() {
-            buf.push(TAG_I64);
-            buf.extend_from_slice(&v.to_le_bytes());
+            push_i64(buf, v);
             return Ok(true);
         }
-        // Large int — fall back to pickle
-        return Ok(false);
+        return serialize_bigint(obj, buf);
     }
 
     // float
     if obj.is_instance_of::<PyFloat>() {
         let v: f64 = obj.extract()?;
         buf.push(TAG_F64);
-        buf.extend_from_slice(&v.to_le_bytes());
+        buf.extend_from_slice(&float_bits(v, canonical));
+        return Ok(true);
+    }
+
+    // complex — real then imaginary part, each as for a float
+    if obj.is_instance_of::<PyComplex>() {
+        let c = obj.cast::<PyComplex>()?;
+        buf.push(TAG_COMPLEX);
+        buf.extend_from_slice(&float_bits(c.real(), canonical));
+        buf.extend_from_slice(&float_bits(c.imag(), canonical));
         return Ok(true);
     }
 
@@ -101,35 +432,66 @@ fn serialize_element(_py: Python, obj: &Bound<PyAny>, buf: &mut Vec<u8>) -> PyRe
         let Ok(s) = obj.cast::<PyString>()?.to_cow() else {
             return Ok(false);
         };
-        let bytes = s.as_bytes();
-        buf.push(TAG_STR);
-        buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
-        buf.extend_from_slice(bytes);
+        push_with_varint_len(buf, TAG_VSTR, s.as_bytes());
         return Ok(true);
     }
 
     // bytes
     if obj.is_instance_of::<PyBytes>() {
-        let b = obj.cast::<PyBytes>()?.as_bytes();
-        buf.push(TAG_BYTES);
-        buf.extend_from_slice(&(b.len() as u32).to_le_bytes());
-        buf.extend_from_slice(b);
+        push_with_varint_len(buf, TAG_VBYTES, obj.cast::<PyBytes>()?.as_bytes());
+        return Ok(true);
+    }
+
+    // bytearray and memoryview — their bytes, written as for `bytes`, so
+    // they come back as `bytes`. A memoryview that isn't of unsigned bytes
+    // (`cast("i")`, a view of an int array) isn't equal to the bytes it
+    // holds, so it is left to the fallback rather than sharing their key.
+    if obj.is_instance_of::<PyByteArray>() || obj.is_instance_of::<PyMemoryView>() {
+        let Ok(buffer) = PyBuffer::<u8>::get(obj) else {
+            return Ok(false);
+        };
+        push_with_varint_len(buf, TAG_VBYTES, &buffer.to_vec(_py)?);
         return Ok(true);
     }
 
-    // tuple (flat — only primitives inside)
+    // tuple (of anything above, nested up to MAX_DEPTH). Up to 255 elements
+    // it's `TAG_TUPLE` with a 1-byte count, as it always was, so those keys
+    // are unchanged; past that, `TAG_LONG_TUPLE` with a 4-byte count. A
+    // namedtuple whose class can be imported back is `TAG_NAMEDTUPLE`: the
+    // class's module and qualname, then a 1-byte count and the elements. Any
+    // other tuple subclass is written as a plain tuple.
     if obj.is_instance_of::<PyTuple>() {
         let tup = obj.cast::<PyTuple>()?;
         let len = tup.len();
-        if len > 255 {
+        if len > u32::MAX as usize || depth >= MAX_DEPTH {
             return Ok(false);
         }
+        let class = if obj.is_exact_instance_of::<PyTuple>() || !ty.hasattr("_fields")? {
+            None
+        } else {
+            class_path(&ty)?
+        };
         // Reserve tag + count, then serialize elements
         let start = buf.len();
-        buf.push(TAG_TUPLE);
-        buf.push(len as u8);
+        match class {
+            Some(_) if len > 255 => return Ok(false),
+            Some((module, qualname)) => {
+                buf.push(TAG_NAMEDTUPLE);
+                push_str(buf, &module);
+                push_str(buf, &qualname);
+                buf.push(len as u8);
+            }
+            None if len > 255 => {
+                buf.push(TAG_LONG_TUPLE);
+                buf.extend_from_slice(&(len as u32).to_le_bytes());
+            }
+            None => {
+                buf.push(TAG_TUPLE);
+                buf.push(len as u8);
+            }
+        }
         for item in tup.iter() {
-            if !serialize_element(_py, &item, buf)? {
+            if !serialize_element(_py, &item, buf, depth + 1, canonical)? {
                 // Unsupported element — revert and fall back
                 buf.truncate(start);
                 return Ok(false);
@@ -138,91 +500,1004 @@ fn serialize_element(_py: Python, obj: &Bound<PyAny>, buf: &mut Vec<u8>) -> PyRe
         return Ok(true);
     }
 
-    Ok(false)
+    // list — like a tuple, with a 4-byte count. Not a subclass, which
+    // would come back as a plain list.
+    if obj.is_exact_instance_of::<PyList>() {
+        let list = obj.cast::<PyList>()?;
+        let len = list.len();
+        if len > u32::MAX as usize || depth >= MAX_DEPTH {
+            return Ok(false);
+        }
+        let start = buf.len();
+        buf.push(TAG_LIST);
+        buf.extend_from_slice(&(len as u32).to_le_bytes());
+        // Another thread may resize the list meanwhile (free-threaded
+        // Python): going by index keeps the count written above exact.
+        for i in 0..len {
+            let Ok(item) = list.get_item(i) else {
+                buf.truncate(start);
+                return Ok(false);
+            };
+            if !serialize_element(_py, &item, buf, depth + 1, canonical)? {
+                buf.truncate(start);
+                return Ok(false);
+            }
+        }
+        return Ok(true);
+    }
+
+    // dict — a 4-byte pair count, then each key and its value. Not a
+    // subclass (OrderedDict, defaultdict), which would come back as a dict.
+    if obj.is_exact_instance_of::<PyDict>() {
+        let dict = obj.cast::<PyDict>()?;
+        if depth >= MAX_DEPTH {
+            return Ok(false);
+        }
+        let mut pairs = Vec::with_capacity(dict.len());
+        for (key, value) in dict.iter() {
+            let (mut key_bytes, mut value_bytes) = (Vec::new(), Vec::new());
+            if !serialize_element(_py, &key, &mut key_bytes, depth + 1, canonical)?
+                || !serialize_element(_py, &value, &mut value_bytes, depth + 1, canonical)?
+            {
+                return Ok(false);
+            }
+            pairs.push((key_bytes, value_bytes));
+        }
+        if canonical {
+            pairs.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        }
+        buf.push(TAG_DICT);
+        buf.extend_from_slice(&(pairs.len() as u32).to_le_bytes());
+        for (key_bytes, value_bytes) in pairs {
+            buf.extend_from_slice(&key_bytes);
+            buf.extend_from_slice(&value_bytes);
+        }
+        return Ok(true);
+    }
+
+    // date, time, datetime and timedelta. Not subclasses (pandas.Timestamp
+    // and the like), which would come back as the base type.
+    if obj.is_exact_instance_of::<PyDateTime>() {
+        return serialize_datetime(_py, obj.cast::<PyDateTime>()?, buf, canonical);
+    }
+    if obj.is_exact_instance_of::<PyDate>() {
+        let date = obj.cast::<PyDate>()?;
+        buf.push(TAG_DATE);
+        buf.extend_from_slice(&(date.get_year() as u16).to_le_bytes());
+        buf.push(date.get_month());
+        buf.push(date.get_day());
+        return Ok(true);
+    }
+    if obj.is_exact_instance_of::<PyTime>() {
+        let time = obj.cast::<PyTime>()?;
+        // An aware time can't be compared to anything, so it rarely makes a
+        // key; leave it to pickle rather than encode its tzinfo.
+        if time.get_tzinfo().is_some() {
+            return Ok(false);
+        }
+        buf.push(TAG_TIME);
+        push_time(time, buf, canonical);
+        return Ok(true);
+    }
+    if obj.is_exact_instance_of::<PyDelta>() {
+        // timedelta normalizes its fields, so equal deltas have equal fields.
+        let delta = obj.cast::<PyDelta>()?;
+        buf.push(TAG_TIMEDELTA);
+        buf.extend_from_slice(&delta.get_days().to_le_bytes());
+        buf.extend_from_slice(&delta.get_seconds().to_le_bytes());
+        buf.extend_from_slice(&delta.get_microseconds().to_le_bytes());
+        return Ok(true);
+    }
+
+    // decimal.Decimal — its str(), which is exact and keeps the exponent (so
+    // Decimal("1.10") and Decimal("1.1") stay apart, as their repr does) and
+    // covers NaN, sNaN and the infinities. Not a subclass.
+    if obj
+        .get_type()
+        .is(DECIMAL.import(_py, "decimal", "Decimal")?)
+    {
+        let s = obj.str()?;
+        let bytes = s.to_str()?.as_bytes();
+        buf.push(TAG_DECIMAL);
+        buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        buf.extend_from_slice(bytes);
+        return Ok(true);
+    }
+
+    // uuid.UUID — its 16 bytes, big-endian as `UUID.bytes`. `is_safe` isn't
+    // kept (it doesn't take part in equality). Not a subclass.
+    if obj.get_type().is(UUID.import(_py, "uuid", "UUID")?) {
+        let bytes = obj.getattr("bytes")?;
+        let bytes = bytes.cast::<PyBytes>()?.as_bytes();
+        if bytes.len() != 16 {
+            return Ok(false);
+        }
+        buf.push(TAG_UUID);
+        buf.extend_from_slice(bytes);
+        return Ok(true);
+    }
+
+    // The marker between positional and keyword arguments in a call's key
+    // (see `key::kwargs_key`): the tag alone. Keeps such keys off pickle,
+    // which would write a plain `object()` the same in every process but
+    // read it back as a new one.
+    if obj.is(kwargs_mark(_py)?) {
+        buf.push(TAG_KWARGS_MARK);
+        return Ok(true);
+    }
+
+    // A type given a serializer with `register_serializer`: its id, then the
+    // bytes its encoder returns with a varint length. Exact types only, and
+    // checked after the built-in types above, which keep their own encoding.
+    if let Some(entry) = registry(_py, &ENCODERS).get_item(&ty)? {
+        let (id, encode): (u64, Bound<PyAny>) = entry.extract()?;
+        let encoded = encode.call1((obj,))?;
+        let Ok(bytes) = encoded.cast::<PyBytes>() else {
+            return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                "the serializer registered for {} returned {}, not bytes",
+                ty.qualname()?,
+                encoded.get_type().qualname()?
+            )));
+        };
+        buf.push(TAG_CUSTOM);
+        buf.extend_from_slice(&id.to_le_bytes());
+        push_varint(buf, bytes.as_bytes().len() as u64);
+        buf.extend_from_slice(bytes.as_bytes());
+        return Ok(true);
+    }
+
+    // numpy.ndarray — see `serialize_ndarray`. Not a subclass (matrix,
+    // masked arrays), which would come back as a plain array.
+    if ty.name()? == "ndarray" && ty.is(NDARRAY.import(_py, "numpy", "ndarray")?) {
+        return serialize_ndarray(obj, buf);
+    }
+
+    // A frozen dataclass — see `serialize_dataclass`.
+    if ty.hasattr("__dataclass_params__")? {
+        return serialize_dataclass(_py, obj, &ty, buf, depth, canonical);
+    }
+
+    // set and frozenset — a 4-byte count, then the elements sorted by their
+    // serialized bytes: iteration order follows the per-process str hash, so
+    // equal sets must still give equal bytes. Not subclasses.
+    let set_tag = if obj.is_exact_instance_of::<PySet>() {
+        TAG_SET
+    } else if obj.is_exact_instance_of::<PyFrozenSet>() {
+        TAG_FROZENSET
+    } else {
+        return Ok(false);
+    };
+    if depth >= MAX_DEPTH {
+        return Ok(false);
+    }
+    let mut elems = Vec::new();
+    for item in obj.try_iter()? {
+        let mut elem = Vec::new();
+        if !serialize_element(_py, &item?, &mut elem, depth + 1, canonical)? {
+            return Ok(false);
+        }
+        elems.push(elem);
+    }
+    elems.sort_unstable();
+    buf.push(set_tag);
+    buf.extend_from_slice(&(elems.len() as u32).to_le_bytes());
+    for elem in elems {
+        buf.extend_from_slice(&elem);
+    }
+    Ok(true)
 }
 
-/// Deserialize one element from `data`. Returns `(value, bytes_consumed)`.
-fn deserialize_one(py: Python, data: &[u8]) -> PyResult<Option<(Py<PyAny>, usize)>> {
-    if data.is_empty() {
+/// The bytes of a float. In canonical form -0.0 is written as 0.0, which it
+/// equals, and every NaN with the same bit pattern.
+fn float_bits(v: f64, canonical: bool) -> [u8; 8] {
+    if canonical && v == 0.0 {
+        0.0f64.to_le_bytes()
+    } else if canonical && v.is_nan() {
+        f64::NAN.to_le_bytes()
+    } else {
+        v.to_le_bytes()
+    }
+}
+
+/// Serialize an int outside i64 as `TAG_BIGINT`: a sign byte (1 = negative),
+/// a 4-byte length, then the magnitude's bytes, little-endian.
+fn serialize_bigint(obj: &Bound<PyAny>, buf: &mut Vec<u8>) -> PyResult<bool> {
+    let negative = obj.lt(0)?;
+    let magnitude = obj.abs()?;
+    let bits: u64 = magnitude.call_method0("bit_length")?.extract()?;
+    let Ok(len) = u32::try_from(bits.div_ceil(8)) else {
+        return Ok(false);
+    };
+    let bytes = magnitude.call_method1("to_bytes", (len, "little"))?;
+    let bytes = bytes.cast::<PyBytes>()?.as_bytes();
+    buf.push(TAG_BIGINT);
+    buf.push(u8::from(negative));
+    buf.extend_from_slice(&len.to_le_bytes());
+    buf.extend_from_slice(bytes);
+    Ok(true)
+}
+
+/// Append `s` with a 4-byte length.
+fn push_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// Read back a `push_str` string at `data[offset..]`, with the offset just
+/// past it.
+fn read_str(data: &[u8], offset: usize) -> ReadResult<(&str, usize)> {
+    let len = data
+        .get(offset..offset + 4)
+        .ok_or_else(|| truncated(data[0]))?;
+    let start = offset + 4;
+    let end = start.saturating_add(u32::from_le_bytes(len.try_into().unwrap()) as usize);
+    let bytes = data.get(start..end).ok_or_else(|| truncated(data[0]))?;
+    Ok((utf8(data[0], bytes)?, end))
+}
+
+/// The module and qualified name `ty` can be imported back from, or `None`
+/// if importing them doesn't give `ty` (a class defined inside a function,
+/// or one since replaced under the same name).
+fn class_path(ty: &Bound<PyType>) -> PyResult<Option<(String, String)>> {
+    let (Ok(module), Ok(qualname)) = (
+        ty.getattr("__module__")?.extract::<String>(),
+        ty.getattr("__qualname__")?.extract::<String>(),
+    ) else {
+        return Ok(None);
+    };
+    match resolve_class(ty.py(), &module, &qualname) {
+        Ok(class) if class.is(ty) => Ok(Some((module, qualname))),
+        _ => Ok(None),
+    }
+}
+
+fn resolve_class<'py>(
+    py: Python<'py>,
+    module: &str,
+    qualname: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    let mut class = py.import(module)?.into_any();
+    for name in qualname.split('.') {
+        class = class.getattr(name)?;
+    }
+    Ok(class)
+}
+
+/// The class a `TAG_NAMEDTUPLE` or `TAG_DATACLASS` names. Raises
+/// `ImportError` if it can no longer be imported (moved or renamed since
+/// the entry was stored), rather than rebuilding something else.
+fn import_class<'py>(py: Python<'py>, module: &str, qualname: &str) -> PyResult<Bound<'py, PyAny>> {
+    resolve_class(py, module, qualname).map_err(|e| {
+        let err = pyo3::exceptions::PyImportError::new_err(format!(
+            "cached value is a {module}.{qualname}, which can no longer be imported"
+        ));
+        err.set_cause(py, Some(e));
+        err
+    })
+}
+
+/// `import_class`, for a `tag` element: the class only if it is the kind
+/// `tag` stands for (a namedtuple class, a frozen dataclass or an `Enum`),
+/// since decoding calls it. An entry naming anything else, such as
+/// `os.system`, is corrupt, and whatever it names is never called.
+fn import_tag_class<'py>(
+    py: Python<'py>,
+    tag: u8,
+    module: &str,
+    qualname: &str,
+) -> ReadResult<Bound<'py, PyType>> {
+    let class = import_class(py, module, qualname)?;
+    let Ok(ty) = class.cast_into::<PyType>() else {
+        return Err(not_a_class(tag, module, qualname));
+    };
+    let fits = match tag {
+        TAG_NAMEDTUPLE => ty.is_subclass_of::<PyTuple>()? && ty.hasattr("_fields")?,
+        TAG_DATACLASS => ty
+            .getattr("__dataclass_params__")
+            .and_then(|params| params.getattr("frozen")?.is_truthy())
+            .unwrap_or(false),
+        _ => ty.is_subclass(ENUM.import(py, "enum", "Enum")?)?,
+    };
+    if !fits {
+        return Err(not_a_class(tag, module, qualname));
+    }
+    Ok(ty)
+}
+
+fn not_a_class(tag: u8, module: &str, qualname: &str) -> ReadError {
+    let kind = match tag {
+        TAG_DATACLASS => "frozen dataclass",
+        TAG_ENUM => "Enum",
+        _ => tag_name(tag),
+    };
+    corrupt(format!(
+        "{} naming {module}.{qualname}, which isn't a {kind} class",
+        tag_name(tag)
+    ))
+}
+
+/// Serialize an enum member as `TAG_ENUM`: its class's module and qualname,
+/// then its name. Returns `false`, leaving `buf` alone, if looking the name up
+/// in the class wouldn't give this member back (a combination of flags, or a
+/// class that can't be imported).
+fn serialize_enum(obj: &Bound<PyAny>, ty: &Bound<PyType>, buf: &mut Vec<u8>) -> PyResult<bool> {
+    let Ok(name) = obj.getattr("_name_")?.extract::<String>() else {
+        return Ok(false);
+    };
+    if !ty.get_item(&name).is_ok_and(|member| member.is(obj)) {
+        return Ok(false);
+    }
+    let Some((module, qualname)) = class_path(ty)? else {
+        return Ok(false);
+    };
+    buf.push(TAG_ENUM);
+    push_str(buf, &module);
+    push_str(buf, &qualname);
+    push_str(buf, &name);
+    Ok(true)
+}
+
+/// Serialize a frozen dataclass as `TAG_DATACLASS`: its class's module and
+/// qualname, a 4-byte field count, then each field's name and value. It's
+/// rebuilt the way pickle does it, without calling `__init__`. A dataclass
+/// that isn't frozen, whose class can't be imported back, that has
+/// attributes beyond its fields, or that customizes its own pickling, is
+/// left to pickle.
+fn serialize_dataclass(
+    py: Python,
+    obj: &Bound<PyAny>,
+    ty: &Bound<PyType>,
+    buf: &mut Vec<u8>,
+    depth: u32,
+    canonical: bool,
+) -> PyResult<bool> {
+    if depth >= MAX_DEPTH
+        || !ty
+            .getattr("__dataclass_params__")?
+            .getattr("frozen")?
+            .is_truthy()?
+    {
+        return Ok(false);
+    }
+    let object = py.get_type::<PyAny>();
+    for method in ["__reduce__", "__reduce_ex__"] {
+        if !ty.getattr(method)?.is(object.getattr(method)?) {
+            return Ok(false);
+        }
+    }
+    let Some((module, qualname)) = class_path(ty)? else {
+        return Ok(false);
+    };
+    let fields = DATACLASS_FIELDS
+        .import(py, "dataclasses", "fields")?
+        .call1((obj,))?
+        .cast_into::<PyTuple>()?;
+    if let Ok(attrs) = obj.getattr("__dict__") {
+        if attrs.len()? != fields.len() {
+            return Ok(false);
+        }
+    }
+
+    let start = buf.len();
+    buf.push(TAG_DATACLASS);
+    push_str(buf, &module);
+    push_str(buf, &qualname);
+    buf.extend_from_slice(&(fields.len() as u32).to_le_bytes());
+    for field in fields.iter() {
+        let name = field.getattr("name")?;
+        let name = name.cast::<PyString>()?.to_cow()?;
+        push_str(buf, &name);
+        if !serialize_element(py, &obj.getattr(&*name)?, buf, depth + 1, canonical)? {
+            buf.truncate(start);
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Deserialize a `TAG_DATACLASS`: `cls.__new__(cls)`, then each field set
+/// with `object.__setattr__` (a frozen dataclass's own refuses).
+fn deserialize_dataclass(py: Python, data: &[u8], depth: u32) -> ReadResult<(Py<PyAny>, usize)> {
+    let (module, offset) = read_str(data, 1)?;
+    let (qualname, offset) = read_str(data, offset)?;
+    let count = data
+        .get(offset..offset + 4)
+        .ok_or_else(|| truncated(TAG_DATACLASS))?;
+    let count = u32::from_le_bytes(count.try_into().unwrap());
+    let mut offset = offset + 4;
+
+    let class = import_tag_class(py, TAG_DATACLASS, module, qualname)?;
+    let obj = class.call_method1("__new__", (&class,))?;
+    let setattr = py.get_type::<PyAny>().getattr("__setattr__")?;
+    for _ in 0..count {
+        let (name, next) = read_str(data, offset)?;
+        let (value, consumed) =
+            deserialize_one(py, &data[next..], depth + 1).map_err(|e| e.shifted(next))?;
+        setattr.call1((&obj, name, value))?;
+        offset = next + consumed;
+    }
+    Ok((obj.unbind(), offset))
+}
+
+/// Serialize a numpy array as `TAG_NDARRAY`: an order byte (0 = C, 1 =
+/// Fortran), `dtype.str` with a 1-byte length, a 1-byte ndim and an 8-byte
+/// length per dimension, then a 4-byte length and the raw data, copied
+/// through the buffer protocol. Arrays that aren't contiguous, are empty, or
+/// have an object or structured dtype are left to pickle.
+fn serialize_ndarray(arr: &Bound<PyAny>, buf: &mut Vec<u8>) -> PyResult<bool> {
+    let dtype = arr.getattr("dtype")?;
+    if dtype.getattr("hasobject")?.is_truthy()? || !dtype.getattr("fields")?.is_none() {
+        return Ok(false);
+    }
+    let flags = arr.getattr("flags")?;
+    let fortran = if flags.getattr("c_contiguous")?.is_truthy()? {
+        false
+    } else if flags.getattr("f_contiguous")?.is_truthy()? {
+        true
+    } else {
+        return Ok(false);
+    };
+    let dtype_str = dtype.getattr("str")?.str()?;
+    let dtype_str = dtype_str.to_str()?.as_bytes();
+    let shape: Vec<u64> = arr.getattr("shape")?.extract()?;
+    let nbytes: usize = arr.getattr("nbytes")?.extract()?;
+    if dtype_str.len() > 255 || shape.len() > 255 || nbytes == 0 || nbytes > u32::MAX as usize {
+        return Ok(false);
+    }
+    // A Fortran-order array's transpose is C-contiguous over the same
+    // memory. Flattened and viewed as bytes, either exports a plain `B`
+    // buffer whatever the dtype.
+    let base = if fortran {
+        arr.getattr("T")?
+    } else {
+        arr.clone()
+    };
+    let raw = base
+        .call_method1("reshape", (-1,))?
+        .call_method1("view", ("u1",))?;
+    let buffer = PyBuffer::<u8>::get(&raw)?;
+
+    buf.push(TAG_NDARRAY);
+    buf.push(u8::from(fortran));
+    buf.push(dtype_str.len() as u8);
+    buf.extend_from_slice(dtype_str);
+    buf.push(shape.len() as u8);
+    for dim in &shape {
+        buf.extend_from_slice(&dim.to_le_bytes());
+    }
+    buf.extend_from_slice(&(nbytes as u32).to_le_bytes());
+    let start = buf.len();
+    buf.resize(start + nbytes, 0);
+    buffer.copy_to_slice(arr.py(), &mut buf[start..])?;
+    Ok(true)
+}
+
+/// Deserialize a `TAG_NDARRAY` into a writable array over a copy of its data.
+fn deserialize_ndarray(py: Python, data: &[u8]) -> ReadResult<(Py<PyAny>, usize)> {
+    let mut offset = 1;
+    let take = |offset: &mut usize, len: usize| -> ReadResult<&[u8]> {
+        let end = offset
+            .checked_add(len)
+            .ok_or_else(|| truncated(TAG_NDARRAY))?;
+        let bytes = data
+            .get(*offset..end)
+            .ok_or_else(|| truncated(TAG_NDARRAY))?;
+        *offset = end;
+        Ok(bytes)
+    };
+    let order = take(&mut offset, 2)?;
+    let (fortran, dtype_len) = (order[0], order[1]);
+    let dtype = utf8(TAG_NDARRAY, take(&mut offset, dtype_len as usize)?)?;
+    let ndim = take(&mut offset, 1)?[0];
+    let mut shape = Vec::with_capacity(ndim as usize);
+    for _ in 0..ndim {
+        let dim = take(&mut offset, 8)?;
+        shape.push(u64::from_le_bytes(dim.try_into().unwrap()));
+    }
+    let len = take(&mut offset, 4)?;
+    let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+    let raw = take(&mut offset, len)?;
+
+    let kwargs = [("dtype", dtype)].into_py_dict(py)?;
+    let flat = FROMBUFFER
+        .import(py, "numpy", "frombuffer")?
+        .call((PyByteArray::new(py, raw),), Some(&kwargs))?;
+    let arr = if fortran != 0 {
+        shape.reverse();
+        flat.call_method1("reshape", (shape,))?.getattr("T")?
+    } else {
+        flat.call_method1("reshape", (shape,))?
+    };
+    Ok((arr.unbind(), offset))
+}
+
+/// Append hour, minute, second, 4-byte microsecond and fold (0 if `canonical`).
+fn push_time(time: &impl PyTimeAccess, buf: &mut Vec<u8>, canonical: bool) {
+    buf.push(time.get_hour());
+    buf.push(time.get_minute());
+    buf.push(time.get_second());
+    buf.extend_from_slice(&time.get_microsecond().to_le_bytes());
+    buf.push(u8::from(time.get_fold() && !canonical));
+}
+
+/// Serialize a datetime. A naive one is `TAG_DATETIME`: its date as for
+/// `TAG_DATE`, then its time as for `TAG_TIME`. One with a fixed-offset
+/// `datetime.timezone` is `TAG_DATETIME_TZ`: 8-byte microseconds since the
+/// Unix epoch in UTC, then the 8-byte offset in microseconds (0 if
+/// `canonical`). Any other tzinfo (zoneinfo, pytz, a named timezone) can't be
+/// rebuilt from an offset, so it's left to pickle, and so is a key whose UTC
+/// time falls outside the years 1 to 9999.
+fn serialize_datetime(
+    py: Python,
+    dt: &Bound<PyDateTime>,
+    buf: &mut Vec<u8>,
+    canonical: bool,
+) -> PyResult<bool> {
+    let Some(tz) = dt.get_tzinfo() else {
+        buf.push(TAG_DATETIME);
+        buf.extend_from_slice(&(dt.get_year() as u16).to_le_bytes());
+        buf.push(dt.get_month());
+        buf.push(dt.get_day());
+        push_time(dt, buf, canonical);
+        return Ok(true);
+    };
+    let utc = PyTzInfo::utc(py)?;
+    if !tz.get_type().is(utc.get_type()) {
+        return Ok(false);
+    }
+    let offset = dt.call_method0("utcoffset")?.cast_into::<PyDelta>()?;
+    let plain = PyTzInfo::fixed_offset(py, &offset)?;
+    let none = py.None();
+    if !tz
+        .call_method1("tzname", (&none,))?
+        .eq(plain.call_method1("tzname", (&none,))?)?
+    {
+        return Ok(false);
+    }
+    let epoch = PyDateTime::new(py, 1970, 1, 1, 0, 0, 0, 0, Some(&utc))?;
+    let since_epoch = delta_micros(&dt.sub(epoch)?.cast_into::<PyDelta>()?);
+    if canonical && !(EPOCH_MIN_MICROS..EPOCH_END_MICROS).contains(&since_epoch) {
+        return Ok(false);
+    }
+    buf.push(TAG_DATETIME_TZ);
+    buf.extend_from_slice(&since_epoch.to_le_bytes());
+    let offset = if canonical { 0 } else { delta_micros(&offset) };
+    buf.extend_from_slice(&offset.to_le_bytes());
+    Ok(true)
+}
+
+fn delta_micros(delta: &Bound<PyDelta>) -> i64 {
+    delta.get_days() as i64 * MICROS_PER_DAY
+        + delta.get_seconds() as i64 * 1_000_000
+        + delta.get_microseconds() as i64
+}
+
+/// A timedelta of `micros` microseconds, or `None` if that's out of range.
+fn delta_from_micros(py: Python<'_>, micros: i64) -> PyResult<Option<Bound<'_, PyDelta>>> {
+    let Ok(days) = i32::try_from(micros.div_euclid(MICROS_PER_DAY)) else {
         return Ok(None);
+    };
+    let rest = micros.rem_euclid(MICROS_PER_DAY);
+    let delta = PyDelta::new(
+        py,
+        days,
+        (rest / 1_000_000) as i32,
+        (rest % 1_000_000) as i32,
+        false,
+    )?;
+    Ok(Some(delta))
+}
+
+/// Deserialize one element, nested `depth` containers deep, from `data`.
+/// Returns `(value, bytes_consumed)`, or a `ReadError::Corrupt` with its
+/// offset relative to `data`.
+fn deserialize_one(py: Python, data: &[u8], depth: u32) -> ReadResult<(Py<PyAny>, usize)> {
+    let Some(&tag) = data.first() else {
+        return Err(corrupt("missing element".to_string()));
+    };
+    if depth > MAX_DEPTH {
+        return Err(corrupt(format!(
+            "{} nested over {MAX_DEPTH} deep",
+            tag_name(tag)
+        )));
     }
 
-    match data[0] {
-        TAG_NONE => Ok(Some((py.None(), 1))),
+    match tag {
+        TAG_NONE => Ok((py.None(), 1)),
 
         TAG_FALSE => {
             let obj = false.into_pyobject(py)?.to_owned().into_any().unbind();
-            Ok(Some((obj, 1)))
+            Ok((obj, 1))
         }
 
         TAG_TRUE => {
             let obj = true.into_pyobject(py)?.to_owned().into_any().unbind();
-            Ok(Some((obj, 1)))
+            Ok((obj, 1))
+        }
+
+        TAG_SMALL_INT => {
+            need(data, 2)?;
+            let v = data[1] as i64 + SMALL_INT_MIN;
+            Ok((v.into_pyobject(py)?.into_any().unbind(), 2))
         }
 
         TAG_I64 => {
-            if data.len() < 9 {
-                return Ok(None);
-            }
+            need(data, 9)?;
             let v = i64::from_le_bytes(data[1..9].try_into().unwrap());
-            Ok(Some((v.into_pyobject(py)?.into_any().unbind(), 9)))
+            Ok((v.into_pyobject(py)?.into_any().unbind(), 9))
         }
 
-        TAG_F64 => {
-            if data.len() < 9 {
-                return Ok(None);
+        TAG_BIGINT => {
+            need(data, 6)?;
+            let len = u32::from_le_bytes(data[2..6].try_into().unwrap()) as usize;
+            need(data, 6 + len)?;
+            let magnitude = PyBytes::new(py, &data[6..6 + len]);
+            let mut value = py
+                .get_type::<PyInt>()
+                .call_method1("from_bytes", (magnitude, "little"))?;
+            if data[1] != 0 {
+                value = value.neg()?;
             }
+            Ok((value.unbind(), 6 + len))
+        }
+
+        TAG_DATE => {
+            need(data, 5)?;
+            let year = u16::from_le_bytes(data[1..3].try_into().unwrap());
+            let date = PyDate::new(py, year as i32, data[3], data[4]).map_err(|_| invalid(tag))?;
+            Ok((date.into_any().unbind(), 5))
+        }
+
+        TAG_TIME => {
+            need(data, 9)?;
+            let micros = u32::from_le_bytes(data[4..8].try_into().unwrap());
+            let time =
+                PyTime::new_with_fold(py, data[1], data[2], data[3], micros, None, data[8] != 0)
+                    .map_err(|_| invalid(tag))?;
+            Ok((time.into_any().unbind(), 9))
+        }
+
+        TAG_TIMEDELTA => {
+            need(data, 13)?;
+            let field = |at: usize| i32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+            let delta =
+                PyDelta::new(py, field(1), field(5), field(9), true).map_err(|_| invalid(tag))?;
+            Ok((delta.into_any().unbind(), 13))
+        }
+
+        TAG_DATETIME => {
+            need(data, 13)?;
+            let year = u16::from_le_bytes(data[1..3].try_into().unwrap());
+            let micros = u32::from_le_bytes(data[8..12].try_into().unwrap());
+            let dt = PyDateTime::new_with_fold(
+                py,
+                year as i32,
+                data[3],
+                data[4],
+                data[5],
+                data[6],
+                data[7],
+                micros,
+                None,
+                data[12] != 0,
+            )
+            .map_err(|_| invalid(tag))?;
+            Ok((dt.into_any().unbind(), 13))
+        }
+
+        TAG_DATETIME_TZ => {
+            need(data, 17)?;
+            let since_epoch = i64::from_le_bytes(data[1..9].try_into().unwrap());
+            let offset = i64::from_le_bytes(data[9..17].try_into().unwrap());
+            // Add the local time since the epoch to the epoch in the target
+            // zone: unlike converting from UTC, that never leaves the range
+            // of `datetime` for a value that was in it.
+            let (Some(offset), Some(local)) = (
+                delta_from_micros(py, offset).map_err(|_| invalid(tag))?,
+                delta_from_micros(py, since_epoch.saturating_add(offset))
+                    .map_err(|_| invalid(tag))?,
+            ) else {
+                return Err(invalid(tag));
+            };
+            let tz = PyTzInfo::fixed_offset(py, offset).map_err(|_| invalid(tag))?;
+            let epoch = PyDateTime::new(py, 1970, 1, 1, 0, 0, 0, 0, Some(&tz))?;
+            let dt = epoch.add(local).map_err(|_| invalid(tag))?;
+            Ok((dt.unbind(), 17))
+        }
+
+        TAG_F64 => {
+            need(data, 9)?;
             let v = f64::from_le_bytes(data[1..9].try_into().unwrap());
-            Ok(Some((v.into_pyobject(py)?.into_any().unbind(), 9)))
+            Ok((v.into_pyobject(py)?.into_any().unbind(), 9))
+        }
+
+        TAG_COMPLEX => {
+            need(data, 17)?;
+            let real = f64::from_le_bytes(data[1..9].try_into().unwrap());
+            let imag = f64::from_le_bytes(data[9..17].try_into().unwrap());
+            let c = PyComplex::from_doubles(py, real, imag);
+            Ok((c.into_any().unbind(), 17))
+        }
+
+        TAG_VSTR => {
+            let (bytes, consumed) = read_with_varint_len(data).ok_or_else(|| truncated(tag))?;
+            let s = utf8(tag, bytes)?;
+            Ok((PyString::new(py, s).into_any().unbind(), consumed))
+        }
+
+        TAG_VBYTES => {
+            let (bytes, consumed) = read_with_varint_len(data).ok_or_else(|| truncated(tag))?;
+            Ok((PyBytes::new(py, bytes).into_any().unbind(), consumed))
         }
 
+        // Written before TAG_VSTR and TAG_VBYTES, with a 4-byte length.
         TAG_STR => {
-            if data.len() < 5 {
-                return Ok(None);
-            }
+            need(data, 5)?;
             let len = u32::from_le_bytes(data[1..5].try_into().unwrap()) as usize;
-            if data.len() < 5 + len {
-                return Ok(None);
-            }
-            let s = std::str::from_utf8(&data[5..5 + len])
-                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
-            Ok(Some((PyString::new(py, s).into_any().unbind(), 5 + len)))
+            need(data, 5 + len)?;
+            let s = utf8(tag, &data[5..5 + len])?;
+            Ok((PyString::new(py, s).into_any().unbind(), 5 + len))
         }
 
         TAG_BYTES => {
-            if data.len() < 5 {
-                return Ok(None);
-            }
+            need(data, 5)?;
             let len = u32::from_le_bytes(data[1..5].try_into().unwrap()) as usize;
-            if data.len() < 5 + len {
-                return Ok(None);
-            }
-            Ok(Some((
+            need(data, 5 + len)?;
+            Ok((
                 PyBytes::new(py, &data[5..5 + len]).into_any().unbind(),
                 5 + len,
-            )))
+            ))
+        }
+
+        TAG_DECIMAL => {
+            need(data, 5)?;
+            let len = u32::from_le_bytes(data[1..5].try_into().unwrap()) as usize;
+            need(data, 5 + len)?;
+            let s = utf8(tag, &data[5..5 + len])?;
+            let decimal = DECIMAL
+                .import(py, "decimal", "Decimal")?
+                .call1((s,))
+                .map_err(|_| invalid(tag))?;
+            Ok((decimal.unbind(), 5 + len))
         }
 
+        TAG_UUID => {
+            need(data, 17)?;
+            let kwargs = [("bytes", PyBytes::new(py, &data[1..17]))].into_py_dict(py)?;
+            let uuid = UUID.import(py, "uuid", "UUID")?.call((), Some(&kwargs))?;
+            Ok((uuid.unbind(), 17))
+        }
+
+        TAG_NDARRAY => deserialize_ndarray(py, data),
+
         TAG_TUPLE => {
-            if data.len() < 2 {
-                return Ok(None);
-            }
+            need(data, 2)?;
             let count = data[1] as usize;
-            let mut offset = 2usize;
-            let mut elems: Vec<Py<PyAny>> = Vec::with_capacity(count);
-            for _ in 0..count {
-                match deserialize_one(py, &data[offset..])? {
-                    Some((val, consumed)) => {
-                        elems.push(val);
-                        offset += consumed;
-                    }
-                    None => return Ok(None),
-                }
-            }
+            let (elems, offset) = deserialize_elements(py, data, 2, count, depth)?;
             let tup = PyTuple::new(py, elems)?;
-            Ok(Some((tup.into_any().unbind(), offset)))
+            Ok((tup.into_any().unbind(), offset))
         }
 
-        _ => Ok(None),
+        TAG_LONG_TUPLE => {
+            need(data, 5)?;
+            let count = u32::from_le_bytes(data[1..5].try_into().unwrap()) as usize;
+            let (elems, offset) = deserialize_elements(py, data, 5, count, depth)?;
+            let tup = PyTuple::new(py, elems)?;
+            Ok((tup.into_any().unbind(), offset))
+        }
+
+        TAG_NAMEDTUPLE => {
+            let (module, offset) = read_str(data, 1)?;
+            let (qualname, offset) = read_str(data, offset)?;
+            let &count = data.get(offset).ok_or_else(|| truncated(tag))?;
+            let class = import_tag_class(py, tag, module, qualname)?;
+            let (elems, offset) =
+                deserialize_elements(py, data, offset + 1, count as usize, depth)?;
+            let tup = class.call1(PyTuple::new(py, elems)?)?;
+            Ok((tup.unbind(), offset))
+        }
+
+        TAG_DATACLASS => deserialize_dataclass(py, data, depth),
+
+        TAG_ENUM => {
+            let (module, offset) = read_str(data, 1)?;
+            let (qualname, offset) = read_str(data, offset)?;
+            let (name, offset) = read_str(data, offset)?;
+            let member = import_tag_class(py, tag, module, qualname)?
+                .get_item(name)
+                .map_err(|e| {
+                    let err = pyo3::exceptions::PyKeyError::new_err(format!(
+                        "cached value is {module}.{qualname}.{name}, which no longer exists"
+                    ));
+                    err.set_cause(py, Some(e));
+                    err
+                })?;
+            Ok((member.unbind(), offset))
+        }
+
+        TAG_LIST => {
+            need(data, 5)?;
+            let count = u32::from_le_bytes(data[1..5].try_into().unwrap()) as usize;
+            let (elems, offset) = deserialize_elements(py, data, 5, count, depth)?;
+            let list = PyList::new(py, elems)?;
+            Ok((list.into_any().unbind(), offset))
+        }
+
+        TAG_DICT => {
+            need(data, 5)?;
+            let count = u32::from_le_bytes(data[1..5].try_into().unwrap()) as usize;
+            // Keys and values alternate, like the elements of a 2 * count list.
+            let (elems, offset) =
+                deserialize_elements(py, data, 5, count.saturating_mul(2), depth)?;
+            let dict = PyDict::new(py);
+            let mut elems = elems.into_iter();
+            while let (Some(key), Some(value)) = (elems.next(), elems.next()) {
+                dict.set_item(key, value)?;
+            }
+            Ok((dict.into_any().unbind(), offset))
+        }
+
+        TAG_SET | TAG_FROZENSET => {
+            need(data, 5)?;
+            let count = u32::from_le_bytes(data[1..5].try_into().unwrap()) as usize;
+            let (elems, offset) = deserialize_elements(py, data, 5, count, depth)?;
+            let set = if tag == TAG_SET {
+                PySet::new(py, elems)?.into_any()
+            } else {
+                PyFrozenSet::new(py, elems)?.into_any()
+            };
+            Ok((set.unbind(), offset))
+        }
+
+        TAG_CUSTOM => {
+            need(data, 9)?;
+            let id = u64::from_le_bytes(data[1..9].try_into().unwrap());
+            let (len, width) = read_varint(&data[9..]).ok_or_else(|| truncated(tag))?;
+            let start = 9 + width;
+            let bytes = usize::try_from(len)
+                .ok()
+                .and_then(|len| data.get(start..start.checked_add(len)?))
+                .ok_or_else(|| truncated(tag))?;
+            let Some(entry) = registry(py, &DECODERS).get_item(id)? else {
+                return Err(ReadError::Unregistered(id));
+            };
+            let obj = entry.get_item(1)?.call1((PyBytes::new(py, bytes),))?;
+            Ok((obj.unbind(), start + bytes.len()))
+        }
+
+        TAG_KWARGS_MARK => Ok((kwargs_mark(py)?.unbind(), 1)),
+
+        _ => Err(corrupt(format!("unknown tag {tag}"))),
+    }
+}
+
+/// One of the `register_serializer` tables, created empty on first use.
+fn registry<'py>(py: Python<'py>, table: &'py PyOnceLock<Py<PyDict>>) -> &'py Bound<'py, PyDict> {
+    table.get_or_init(py, || PyDict::new(py).unbind()).bind(py)
+}
+
+/// Store instances of `cls` as the bytes `encode(obj)` returns instead of
+/// pickling them, and read them back with `decode(data)`.
+///
+/// The stored form carries a 64-bit id hashed from `name` (by default the
+/// class's `module.qualname`), so every process sharing a cache must register
+/// the type under the same name; one that hasn't reads its values as misses.
+/// Only exact instances of `cls` are covered, not subclasses, and types the
+/// fast path already handles keep their built-in encoding. Registering `cls`
+/// again replaces its serializer.
+#[pyfunction]
+#[pyo3(signature = (cls, encode, decode, *, name=None))]
+pub fn register_serializer(
+    py: Python<'_>,
+    cls: &Bound<'_, PyType>,
+    encode: &Bound<'_, PyAny>,
+    decode: &Bound<'_, PyAny>,
+    name: Option<String>,
+) -> PyResult<()> {
+    if !encode.is_callable() || !decode.is_callable() {
+        return Err(pyo3::exceptions::PyTypeError::new_err(
+            "encode and decode must be callable",
+        ));
     }
+    let name = match name {
+        Some(name) => name,
+        None => format!("{}.{}", cls.module()?, cls.qualname()?),
+    };
+    let id = xxh64(name.as_bytes(), 0);
+    let decoders = registry(py, &DECODERS);
+    if let Some(entry) = decoders.get_item(id)? {
+        let other: String = entry.get_item(0)?.extract()?;
+        if other != name {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "serializer name {name:?} hashes to the same id as {other:?}; pick another name"
+            )));
+        }
+    }
+    decoders.set_item(id, (name, decode))?;
+    registry(py, &ENCODERS).set_item(cls, (id, encode))?;
+    Ok(())
+}
+
+/// Deserialize the `count` elements of a tuple or list at `depth` whose first
+/// element starts at `data[offset..]`. Returns them with the offset just past
+/// the last one.
+fn deserialize_elements(
+    py: Python,
+    data: &[u8],
+    mut offset: usize,
+    count: usize,
+    depth: u32,
+) -> ReadResult<(Vec<Py<PyAny>>, usize)> {
+    // Every element takes at least a byte, so a corrupt count can't size a
+    // huge allocation.
+    let mut elems: Vec<Py<PyAny>> = Vec::with_capacity(count.min(data.len() - offset));
+    for _ in 0..count {
+        if offset == data.len() {
+            return Err(truncated(data[0]));
+        }
+        let (val, consumed) =
+            deserialize_one(py, &data[offset..], depth + 1).map_err(|e| e.shifted(offset))?;
+        elems.push(val);
+        offset += consumed;
+    }
+    Ok((elems, offset))
+}
+
+/// The type a tag stands for, for `Corrupt::reason`.
+fn tag_name(tag: u8) -> &'static str {
+    match tag {
+        TAG_NONE => "None",
+        TAG_FALSE | TAG_TRUE => "bool",
+        TAG_SMALL_INT | TAG_I64 | TAG_BIGINT => "int",
+        TAG_F64 => "float",
+        TAG_COMPLEX => "complex",
+        TAG_STR | TAG_VSTR => "str",
+        TAG_BYTES | TAG_VBYTES => "bytes",
+        TAG_DATE => "date",
+        TAG_TIME => "time",
+        TAG_TIMEDELTA => "timedelta",
+        TAG_DATETIME | TAG_DATETIME_TZ => "datetime",
+        TAG_DECIMAL => "Decimal",
+        TAG_UUID => "UUID",
+        TAG_NDARRAY => "ndarray",
+        TAG_TUPLE | TAG_LONG_TUPLE => "tuple",
+        TAG_NAMEDTUPLE => "namedtuple",
+        TAG_DATACLASS => "dataclass",
+        TAG_ENUM => "enum member",
+        TAG_CUSTOM => "registered type",
+        TAG_KWARGS_MARK => "keyword marker",
+        TAG_LIST => "list",
+        TAG_DICT => "dict",
+        TAG_SET => "set",
+        TAG_FROZENSET => "frozenset",
+        _ => "element",
+    }
+}
+
+/// Unless `data` holds at least `len` bytes, a `truncated` error for the
+/// element it starts with.
+fn need(data: &[u8], len: usize) -> ReadResult<()> {
+    if data.len() < len {
+        return Err(truncated(data[0]));
+    }
+    Ok(())
+}
+
+/// `bytes` as the UTF-8 text of a `tag` element.
+fn utf8(tag: u8, bytes: &[u8]) -> ReadResult<&str> {
+    std::str::from_utf8(bytes).map_err(|_| corrupt(format!("{} with invalid UTF-8", tag_name(tag))))
+}
+
+fn corrupt(reason: String) -> ReadError {
+    ReadError::Corrupt(Corrupt { offset: 0, reason })
+}
+
+/// An element with tag `tag` that ends before its last field.
+fn truncated(tag: u8) -> ReadError {
+    corrupt(format!("truncated {}", tag_name(tag)))
+}
+
+/// An element with tag `tag` whose fields don't make a valid value.
+fn invalid(tag: u8) -> ReadError {
+    corrupt(format!("invalid {}", tag_name(tag)))
 }
@@ -0,0 +1,268 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+
+use crate::config;
+use crate::shared_store::{
+    check_max_size, check_serde_format, check_size_limits, check_ttl, compare_and_swap,
+    deserialize, fork_safe, hash_key, incr, lock_error, negate, open_error, parse_lock_timeout,
+    parse_on_mismatch, serialize, serialize_key, without_gil, SharedCacheInfo,
+};
+use crate::shm::{FileOptions, InsertToken, Oversize, ShmCache, ShmGetResult, VALUE_CODEC_NONE};
+
+/// A cross-process dict backed by the shared-memory cache.
+///
+/// Same storage as `SharedCachedFunction` — SIEVE eviction at `max_size`,
+/// optional TTL, fixed key/value size limits — but keyed by any serializable
+/// object instead of call arguments, with no function attached. Processes
+/// that open the same `name` see the same entries.
+#[pyclass(frozen)]
+pub struct SharedDict {
+    pickle_dumps: Py<PyAny>,
+    pickle_loads: Py<PyAny>,
+    cache: Arc<ShmCache>,
+    max_key_size: usize,
+    max_value_size: usize,
+}
+
+#[pymethods]
+impl SharedDict {
+    #[new]
+    // Arguments left out or None take their defaults from `config`.
+    #[pyo3(signature = (name, max_size=None, ttl=None, max_key_size=None, max_value_size=None, on_mismatch=None, shm_dir=None, lock_timeout=None, checksums=None, readonly=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        name: &str,
+        max_size: Option<usize>,
+        ttl: Option<f64>,
+        max_key_size: Option<usize>,
+        max_value_size: Option<usize>,
+        on_mismatch: Option<String>,
+        shm_dir: Option<PathBuf>,
+        lock_timeout: Option<f64>,
+        checksums: Option<bool>,
+        readonly: Option<bool>,
+    ) -> PyResult<Self> {
+        let ttl = config::configured(py, "ttl", ttl)?;
+        let on_mismatch: String = config::resolved(py, "on_mismatch", on_mismatch)?;
+        let shm_dir = config::configured(py, "shm_dir", shm_dir)?;
+        let lock_timeout = config::configured(py, "lock_timeout", lock_timeout)?;
+        let checksums = config::resolved(py, "checksums", checksums)?;
+        let readonly = config::resolved(py, "readonly", readonly)?;
+        let capacity = check_max_size(config::resolved(py, "max_size", max_size)?)?;
+        let (key_limit, value_limit) = check_size_limits(
+            config::resolved(py, "max_key_size", max_key_size)?,
+            config::resolved(py, "max_value_size", max_value_size)?,
+        )?;
+        check_ttl("ttl", ttl)?;
+        let on_mismatch = parse_on_mismatch(&on_mismatch, readonly)?;
+        let lock_timeout = parse_lock_timeout(lock_timeout)?;
+
+        let pickle = py.import("pickle")?;
+        let cache = ShmCache::create_or_open(
+            name,
+            capacity,
+            key_limit,
+            value_limit,
+            ttl,
+            None,
+            false,
+            checksums,
+            VALUE_CODEC_NONE,
+            "pickle",
+            "",
+            None,
+            0,
+            0,
+            on_mismatch,
+            &FileOptions {
+                base_dir: shm_dir.as_deref(),
+                lock_timeout,
+                readonly,
+                ..Default::default()
+            },
+        )
+        .map_err(open_error)?;
+        check_serde_format(py, &cache, name)?;
+        // With OnMismatch::OpenExisting the stored limits win over the requested ones.
+        let config = cache.config();
+        let cache = fork_safe(py, cache)?;
+
+        Ok(SharedDict {
+            pickle_dumps: pickle.getattr("dumps")?.unbind(),
+            pickle_loads: pickle.getattr("loads")?.unbind(),
+            cache,
+            max_key_size: config.max_key_size as usize,
+            max_value_size: config.max_value_size as usize,
+        })
+    }
+
+    /// The value stored for `key`, or `default` if there is none (or it expired).
+    #[pyo3(signature = (key, default=None))]
+    fn get<'py>(
+        &self,
+        py: Python<'py>,
+        key: &Bound<'py, PyAny>,
+        default: Option<Py<PyAny>>,
+    ) -> PyResult<Py<PyAny>> {
+        let (key_hash, key_bytes) = self.make_key(py, key)?;
+        match self.lookup(py, key_hash, &key_bytes) {
+            ShmGetResult::Hit(vb) => {
+                Ok(
+                    deserialize(py, &self.cache, &self.pickle_loads, &vb, Some(&key_bytes))?
+                        .or(default)
+                        .unwrap_or_else(|| py.None()),
+                )
+            }
+            ShmGetResult::Miss(_) => Ok(default.unwrap_or_else(|| py.None())),
+        }
+    }
+
+    /// Store `value` under `key`, evicting another entry if the dict is full.
+    ///
+    /// Raises `ValueError` if the serialized key or value exceeds the size
+    /// limits, rather than silently dropping the write.
+    fn set<'py>(
+        &self,
+        py: Python<'py>,
+        key: &Bound<'py, PyAny>,
+        value: &Bound<'py, PyAny>,
+    ) -> PyResult<()> {
+        let (key_hash, key_bytes) = self.make_key(py, key)?;
+        let value_bytes = serialize(py, &self.pickle_dumps, value)?;
+        if key_bytes.len() > self.max_key_size {
+            self.cache.record_oversize_skip(Oversize::Key);
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "key is {} bytes serialized, over max_key_size={}",
+                key_bytes.len(),
+                self.max_key_size
+            )));
+        }
+        if value_bytes.len() > self.max_value_size {
+            self.cache.record_oversize_skip(Oversize::Value);
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "value is {} bytes serialized, over max_value_size={}",
+                value_bytes.len(),
+                self.max_value_size
+            )));
+        }
+        without_gil(py, &self.cache, value_bytes.len(), |cache| {
+            cache.insert(key_hash, &key_bytes, &value_bytes, None)
+        })
+        .map_err(lock_error)
+    }
+
+    /// Remove `key`. Returns True if it was present.
+    fn delete<'py>(&self, py: Python<'py>, key: &Bound<'py, PyAny>) -> PyResult<bool> {
+        let (key_hash, key_bytes) = self.make_key(py, key)?;
+        self.cache.remove(key_hash, &key_bytes).map_err(lock_error)
+    }
+
+    /// Store `new` under `key` only if its current value is `expected`,
+    /// atomically across processes. Returns True if it was stored.
+    ///
+    /// `expected=None` means "only if the key is absent". Values are compared
+    /// in serialized form. Raises `ValueError` if `new` exceeds the size limits.
+    fn cas<'py>(
+        &self,
+        py: Python<'py>,
+        key: &Bound<'py, PyAny>,
+        expected: Option<Bound<'py, PyAny>>,
+        new: &Bound<'py, PyAny>,
+    ) -> PyResult<bool> {
+        let (key_hash, key_bytes) = self.make_key(py, key)?;
+        let expected_bytes = expected
+            .map(|e| serialize(py, &self.pickle_dumps, &e))
+            .transpose()?;
+        let new_bytes = serialize(py, &self.pickle_dumps, new)?;
+        compare_and_swap(
+            &self.cache,
+            key_hash,
+            &key_bytes,
+            expected_bytes.as_deref(),
+            &new_bytes,
+            self.max_key_size,
+            self.max_value_size,
+        )
+    }
+
+    /// Atomically add `amount` to the integer stored under `key` and return
+    /// the new value; a missing or expired key starts at `initial`.
+    ///
+    /// Raises `TypeError` if the stored value is not an int that fits in 64
+    /// bits, and `OverflowError` (leaving it unchanged) if the result would not.
+    #[pyo3(signature = (key, amount=1, initial=0))]
+    fn incr<'py>(
+        &self,
+        py: Python<'py>,
+        key: &Bound<'py, PyAny>,
+        amount: i64,
+        initial: i64,
+    ) -> PyResult<i64> {
+        let (key_hash, key_bytes) = self.make_key(py, key)?;
+        incr(
+            &self.cache,
+            key_hash,
+            &key_bytes,
+            self.max_key_size,
+            self.max_value_size,
+            amount,
+            initial,
+        )
+    }
+
+    /// `incr()` with the amount negated.
+    #[pyo3(signature = (key, amount=1, initial=0))]
+    fn decr<'py>(
+        &self,
+        py: Python<'py>,
+        key: &Bound<'py, PyAny>,
+        amount: i64,
+        initial: i64,
+    ) -> PyResult<i64> {
+        self.incr(py, key, negate(amount)?, initial)
+    }
+
+    fn __contains__<'py>(&self, py: Python<'py>, key: &Bound<'py, PyAny>) -> PyResult<bool> {
+        let (key_hash, key_bytes) = self.make_key(py, key)?;
+        Ok(matches!(
+            self.lookup(py, key_hash, &key_bytes),
+            ShmGetResult::Hit(_)
+        ))
+    }
+
+    /// Number of stored entries, including expired ones not yet reclaimed.
+    fn __len__(&self) -> usize {
+        self.cache.info().current_size
+    }
+
+    fn clear(&self) -> PyResult<()> {
+        self.cache.clear().map_err(lock_error)
+    }
+
+    fn info(&self) -> SharedCacheInfo {
+        SharedCacheInfo::from(self.cache.info())
+    }
+}
+
+impl SharedDict {
+    /// Serialize a key and hash its bytes (deterministic across processes).
+    fn make_key<'py>(&self, py: Python<'py>, key: &Bound<'py, PyAny>) -> PyResult<(u64, Vec<u8>)> {
+        let key_bytes = serialize_key(py, &self.pickle_dumps, key)?;
+        Ok((hash_key(&key_bytes), key_bytes))
+    }
+
+    /// `ShmCache::get`, without the GIL if it may have to wait or copy a lot.
+    /// A key over `max_key_size` misses without touching the cache.
+    fn lookup(&self, py: Python<'_>, key_hash: u64, key_bytes: &[u8]) -> ShmGetResult {
+        if key_bytes.len() > self.max_key_size {
+            self.cache.record_miss();
+            return ShmGetResult::Miss(InsertToken::default());
+        }
+        without_gil(py, &self.cache, self.max_value_size, |cache| {
+            cache.get(key_hash, key_bytes)
+        })
+    }
+}
@@ -1,12 +1,27 @@
-use std::hash::{BuildHasher, Hasher};
-
-use ahash::RandomState;
+use std::borrow::Cow;
+use std::ffi::CString;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyTuple};
+use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyString, PyTuple};
+use xxhash_rust::xxh64::{xxh64, Xxh64};
 
+use crate::compress::{self, Codec};
+use crate::config;
+use crate::fork::{self, AfterFork};
+use crate::key::{args_contain_nan, kwargs_key, without_kwargs_mark};
+use crate::latency::Latencies;
 use crate::serde;
-use crate::shm::{ShmCache, ShmGetResult};
+use crate::shm::{
+    self, FileOptions, IncrError, InsertToken, OnMismatch, Oversize, ShmCache, ShmCacheInfo,
+    ShmGetResult, CACHE_VERSION_LEN, MAX_TAGS,
+};
+use crate::snapshot::{self, SnapshotEntry};
+use crate::store::{hit_rate, parse_seconds, too_fast};
 
 /// Cache info for the shared backend, exposed to Python.
 #[pyclass(frozen)]
@@ -20,103 +35,422 @@ pub struct SharedCacheInfo {
     #[pyo3(get)]
     pub current_size: usize,
     #[pyo3(get)]
-    pub oversize_skips: u64,
+    pub oversize_key_skips: u64,
+    #[pyo3(get)]
+    pub oversize_value_skips: u64,
+    #[pyo3(get)]
+    pub lock_timeouts: u64,
+    #[pyo3(get)]
+    pub corruption_detected: u64,
+    #[pyo3(get)]
+    pub corrupt_values: u64,
+    #[pyo3(get)]
+    pub skipped_fast: u64,
+    #[pyo3(get)]
+    pub insert_failures: u64,
+    #[pyo3(get)]
+    pub expired_reclaimed: u64,
+    #[pyo3(get)]
+    pub evictions: u64,
+    #[pyo3(get)]
+    pub expirations: u64,
+    #[pyo3(get)]
+    pub bytes_used: u64,
+    #[pyo3(get)]
+    pub bytes_capacity: u64,
+    #[pyo3(get)]
+    pub fill_ratio: f64,
+    #[pyo3(get)]
+    pub avg_value_len: f64,
+    #[pyo3(get)]
+    pub overflow_used: usize,
+    #[pyo3(get)]
+    pub memory_locked: bool,
+    #[pyo3(get)]
+    pub allocated_bytes: u64,
+    #[pyo3(get)]
+    pub cache_version: Option<String>,
+    #[pyo3(get)]
+    pub remaps: u64,
 }
 
 #[pymethods]
 impl SharedCacheInfo {
+    /// Key and value skips together.
+    #[getter]
+    fn oversize_skips(&self) -> u64 {
+        self.oversize_key_skips + self.oversize_value_skips
+    }
+
     fn __repr__(&self) -> String {
         format!(
-            "SharedCacheInfo(hits={}, misses={}, max_size={}, current_size={}, oversize_skips={})",
-            self.hits, self.misses, self.max_size, self.current_size, self.oversize_skips
+            "SharedCacheInfo(hits={}, misses={}, max_size={}, current_size={}, \
+             oversize_key_skips={}, oversize_value_skips={}, lock_timeouts={}, corruption_detected={}, corrupt_values={}, skipped_fast={}, insert_failures={}, expired_reclaimed={}, \
+             evictions={}, expirations={}, bytes_used={}, bytes_capacity={}, fill_ratio={:.3}, \
+             avg_value_len={:.1}, overflow_used={}, memory_locked={}, allocated_bytes={}, \
+             cache_version={}, remaps={})",
+            self.hits,
+            self.misses,
+            self.max_size,
+            self.current_size,
+            self.oversize_key_skips,
+            self.oversize_value_skips,
+            self.lock_timeouts,
+            self.corruption_detected,
+            self.corrupt_values,
+            self.skipped_fast,
+            self.insert_failures,
+            self.expired_reclaimed,
+            self.evictions,
+            self.expirations,
+            self.bytes_used,
+            self.bytes_capacity,
+            self.fill_ratio,
+            self.avg_value_len,
+            self.overflow_used,
+            if self.memory_locked { "True" } else { "False" },
+            self.allocated_bytes,
+            match &self.cache_version {
+                Some(version) => format!("{version:?}"),
+                None => "None".to_string(),
+            },
+            self.remaps
         )
     }
 }
 
+impl SharedCacheInfo {
+    /// What `SharedCachedFunction::metrics` returns.
+    fn metrics<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let metrics = PyDict::new(py);
+        metrics.set_item("hits", self.hits)?;
+        metrics.set_item("misses", self.misses)?;
+        metrics.set_item("hit_rate", hit_rate(self.hits, self.misses))?;
+        metrics.set_item("evictions", self.evictions)?;
+        metrics.set_item("expirations", self.expirations)?;
+        metrics.set_item("current_size", self.current_size)?;
+        metrics.set_item("max_size", self.max_size)?;
+        metrics.set_item("oversize_skips", self.oversize_skips())?;
+        metrics.set_item("lock_timeouts", self.lock_timeouts)?;
+        metrics.set_item("skipped_fast", self.skipped_fast)?;
+        metrics.set_item("bytes_used", self.bytes_used)?;
+        metrics.set_item("bytes_capacity", self.bytes_capacity)?;
+        Ok(metrics)
+    }
+}
+
+impl From<ShmCacheInfo> for SharedCacheInfo {
+    fn from(info: ShmCacheInfo) -> Self {
+        SharedCacheInfo {
+            hits: info.hits,
+            misses: info.misses,
+            max_size: info.max_size,
+            current_size: info.current_size,
+            oversize_key_skips: info.oversize_key_skips,
+            oversize_value_skips: info.oversize_value_skips,
+            lock_timeouts: info.lock_timeouts,
+            corruption_detected: info.corruption_detected,
+            corrupt_values: info.corrupt_values,
+            skipped_fast: info.skipped_fast,
+            insert_failures: info.insert_failures,
+            expired_reclaimed: info.expired_reclaimed,
+            evictions: info.evictions,
+            expirations: info.expirations,
+            bytes_used: info.bytes_used,
+            bytes_capacity: info.bytes_capacity,
+            fill_ratio: if info.bytes_capacity > 0 {
+                info.bytes_used as f64 / info.bytes_capacity as f64
+            } else {
+                0.0
+            },
+            avg_value_len: if info.current_size > 0 {
+                info.value_bytes_used as f64 / info.current_size as f64
+            } else {
+                0.0
+            },
+            overflow_used: info.overflow_used,
+            memory_locked: info.memory_locked,
+            allocated_bytes: info.allocated_bytes,
+            cache_version: info.cache_version,
+            remaps: info.remaps,
+        }
+    }
+}
+
 /// A cached function using the shared-memory (mmap) backend.
 ///
 /// Parallel to `CachedFunction` but stores serialized bytes in shared
 /// memory accessible across processes.
 // `dict` gives instances a __dict__ so the Python decorator can apply
-// functools.wraps for introspection (#43). See store.rs CachedFunction.
-#[pyclass(frozen, dict)]
+// functools.wraps for introspection (#43), and `weakref` lets its registry of
+// live caches hold them. See store.rs CachedFunction.
+#[pyclass(frozen, dict, weakref)]
 pub struct SharedCachedFunction {
     fn_obj: Py<PyAny>,
+    /// Fallback for what the `serde` fast path can't encode: pickle's pair, or
+    /// the `serializer=` one.
     pickle_dumps: Py<PyAny>,
     pickle_loads: Py<PyAny>,
-    cache: ShmCache,
+    cache: Arc<ShmCache>,
     max_key_size: usize,
     max_value_size: usize,
-    hash_state: RandomState,
+    stale_enabled: bool,
+    /// Codec for values over `compress_threshold` bytes, `None` = store as is.
+    codec: Option<Codec>,
+    compress_threshold: usize,
+    oversize: OversizePolicy,
+    /// Set once `OversizePolicy::Warn` has warned, so it warns only once.
+    oversize_warned: AtomicBool,
+    /// Called with `(key, value)` for each entry this process evicts.
+    on_evict: Option<Py<PyAny>>,
+    /// Called as `tags_func(args, kwargs, result)` for the tags of each
+    /// stored result.
+    tags_func: Option<Py<PyAny>>,
+    /// Hit and miss timings of `__call__` in this process, with
+    /// `record_latencies`.
+    latencies: Option<Latencies>,
+    /// Misses shorter than this are returned but not stored.
+    min_compute_time: Option<Duration>,
+}
+
+/// What happens to a key or value over the size limits (`oversize=`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OversizePolicy {
+    /// Don't cache it; only `oversize_skips` counts it.
+    Skip,
+    /// Don't cache it, and emit a `UserWarning` the first time it happens.
+    Warn,
+    /// Raise `ValueError`.
+    Raise,
 }
 
 #[pymethods]
 impl SharedCachedFunction {
     #[new]
-    #[pyo3(signature = (fn_obj, max_size, ttl=None, max_key_size=512, max_value_size=4096, shm_name=None))]
+    // Arguments left out or None take their defaults from `config`.
+    #[pyo3(signature = (fn_obj, max_size=None, ttl=None, max_key_size=None, max_value_size=None, shm_name=None, stale_ttl=None, sliding_ttl=None, on_mismatch=None, shm_dir=None, file_mode=None, allow_insecure=None, persistent=None, sync_interval=None, compress=None, compress_threshold=None, serializer=None, oversize=None, chunk_size=None, overflow_count=None, overflow_size=None, lock_timeout=None, checksums=None, lock_memory=None, prefault=None, on_evict=None, readonly=None, cache_version=None, tags_func=None, record_latencies=None, min_compute_time=None))]
     #[allow(clippy::too_many_arguments)]
     fn new(
         py: Python<'_>,
         fn_obj: Py<PyAny>,
-        max_size: usize,
+        max_size: Option<usize>,
         ttl: Option<f64>,
-        max_key_size: usize,
-        max_value_size: usize,
+        max_key_size: Option<usize>,
+        max_value_size: Option<usize>,
         shm_name: Option<String>,
+        stale_ttl: Option<f64>,
+        sliding_ttl: Option<bool>,
+        on_mismatch: Option<String>,
+        shm_dir: Option<PathBuf>,
+        file_mode: Option<u32>,
+        allow_insecure: Option<bool>,
+        persistent: Option<bool>,
+        sync_interval: Option<f64>,
+        compress: Option<String>,
+        compress_threshold: Option<usize>,
+        serializer: Option<(Py<PyAny>, Py<PyAny>)>,
+        oversize: Option<String>,
+        chunk_size: Option<u32>,
+        overflow_count: Option<u32>,
+        overflow_size: Option<u32>,
+        lock_timeout: Option<f64>,
+        checksums: Option<bool>,
+        lock_memory: Option<bool>,
+        prefault: Option<bool>,
+        on_evict: Option<Py<PyAny>>,
+        readonly: Option<bool>,
+        cache_version: Option<Bound<'_, PyAny>>,
+        tags_func: Option<Py<PyAny>>,
+        record_latencies: Option<bool>,
+        min_compute_time: Option<f64>,
     ) -> PyResult<Self> {
-        if max_size == 0 {
+        let max_size = config::resolved(py, "max_size", max_size)?;
+        let ttl = config::configured(py, "ttl", ttl)?;
+        let max_key_size = config::resolved(py, "max_key_size", max_key_size)?;
+        let max_value_size: usize = config::resolved(py, "max_value_size", max_value_size)?;
+        let stale_ttl = config::configured(py, "stale_ttl", stale_ttl)?;
+        let sliding_ttl = config::resolved(py, "sliding_ttl", sliding_ttl)?;
+        let on_mismatch: String = config::resolved(py, "on_mismatch", on_mismatch)?;
+        let shm_dir = config::configured(py, "shm_dir", shm_dir)?;
+        let file_mode = config::configured(py, "file_mode", file_mode)?;
+        let allow_insecure = config::resolved(py, "allow_insecure", allow_insecure)?;
+        let persistent = config::resolved(py, "persistent", persistent)?;
+        let sync_interval = config::configured(py, "sync_interval", sync_interval)?;
+        let compress: Option<String> = config::configured(py, "compress", compress)?;
+        let compress_threshold = config::resolved(py, "compress_threshold", compress_threshold)?;
+        let serializer = config::configured(py, "serializer", serializer)?;
+        let oversize: String = config::resolved(py, "oversize", oversize)?;
+        let chunk_size = config::configured(py, "chunk_size", chunk_size)?;
+        let overflow_count = config::resolved(py, "overflow_count", overflow_count)?;
+        let overflow_size = config::configured(py, "overflow_size", overflow_size)?;
+        let lock_timeout = config::configured(py, "lock_timeout", lock_timeout)?;
+        let checksums = config::resolved(py, "checksums", checksums)?;
+        let lock_memory = config::resolved(py, "lock_memory", lock_memory)?;
+        let prefault = config::resolved(py, "prefault", prefault)?;
+        let on_evict = config::configured(py, "on_evict", on_evict)?;
+        let readonly = config::resolved(py, "readonly", readonly)?;
+        let cache_version = config::configured(py, "cache_version", cache_version)?;
+        let tags_func = config::configured(py, "tags_func", tags_func)?;
+        let record_latencies: bool = config::resolved(py, "record_latencies", record_latencies)?;
+        let min_compute_time = config::configured(py, "min_compute_time", min_compute_time)?;
+        if let Some(callback) = &on_evict {
+            if !callback.bind(py).is_callable() {
+                return Err(pyo3::exceptions::PyTypeError::new_err(
+                    "on_evict must be callable",
+                ));
+            }
+        }
+        check_tags_func(py, tags_func.as_ref())?;
+        check_ttl("ttl", ttl)?;
+        let min_compute_time = parse_seconds("min_compute_time", min_compute_time)?;
+        if let Some(t) = stale_ttl {
+            if t.is_nan() || t <= 0.0 {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "stale_ttl must be a positive number of seconds (got {t})"
+                )));
+            }
+        }
+        let sync_interval = match sync_interval {
+            None => None,
+            Some(_) if !persistent => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "sync_interval requires persistent=True",
+                ))
+            }
+            Some(t) if t.is_finite() && t > 0.0 => Some(Duration::from_secs_f64(t)),
+            Some(t) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "sync_interval must be a positive number of seconds (got {t})"
+                )))
+            }
+        };
+        let lock_timeout = parse_lock_timeout(lock_timeout)?;
+        if chunk_size == Some(0) {
             return Err(pyo3::exceptions::PyValueError::new_err(
-                "max_size must be >= 1 for the shared backend",
+                "chunk_size must be a positive number of bytes",
             ));
         }
-        // The hash table is sized at 2x capacity rounded up to a power of two, which
-        // must fit in u32 — so capacity itself must be <= 2^30 (#41). Reject larger
-        // values here, before the `as u32` cast below silently truncates a usize that
-        // doesn't fit (e.g. 2^32 -> a tiny cache) and before region.rs overflows
-        // `capacity * 2` (panic in debug, 1-bucket table in release).
-        const MAX_SHARED_MAX_SIZE: usize = 1 << 30;
-        if max_size > MAX_SHARED_MAX_SIZE {
-            return Err(pyo3::exceptions::PyValueError::new_err(format!(
-                "max_size must be <= {MAX_SHARED_MAX_SIZE} for the shared backend (got {max_size})"
-            )));
+        let overflow_size = match (overflow_count, overflow_size) {
+            (0, None) => 0,
+            (0, Some(_)) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "overflow_size requires overflow_count",
+                ))
+            }
+            (_, Some(size)) if size as usize > max_value_size => size,
+            (_, _) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "overflow_count needs an overflow_size over max_value_size={max_value_size}"
+                )))
+            }
+        };
+        let capacity = check_max_size(max_size)?;
+        let (key_limit, value_limit) = check_size_limits(max_key_size, max_value_size)?;
+        let on_mismatch = parse_on_mismatch(&on_mismatch, readonly)?;
+        let cache_version = parse_cache_version(cache_version.as_ref())?;
+        let oversize = match oversize.as_str() {
+            "skip" => OversizePolicy::Skip,
+            "warn" => OversizePolicy::Warn,
+            "raise" => OversizePolicy::Raise,
+            other => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "oversize must be 'skip', 'warn' or 'raise' (got {other:?})"
+                )))
+            }
+        };
+        let codec = compress
+            .as_deref()
+            .map(Codec::parse)
+            .transpose()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+
+        if let Some(mode) = file_mode {
+            // Every attached process opens the files read-write, so the owner
+            // bits can't be dropped.
+            if mode & !0o777 != 0 || mode & 0o600 != 0o600 {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "file_mode must be a permission mode including owner read/write \
+                     (e.g. 0o600), got {mode:#o}"
+                )));
+            }
         }
 
         let pickle = py.import("pickle")?;
-        let pickle_dumps = pickle.getattr("dumps")?.unbind();
-        let pickle_loads = pickle.getattr("loads")?.unbind();
+        let (pickle_dumps, pickle_loads) = match serializer {
+            Some((dumps, loads)) => {
+                if !dumps.bind(py).is_callable() || !loads.bind(py).is_callable() {
+                    return Err(pyo3::exceptions::PyTypeError::new_err(
+                        "serializer must be a (dumps, loads) pair of callables",
+                    ));
+                }
+                (dumps, loads)
+            }
+            None => (
+                pickle.getattr("dumps")?.unbind(),
+                pickle.getattr("loads")?.unbind(),
+            ),
+        };
+        let serializer = serializer_name(&pickle, pickle_dumps.bind(py))?;
 
         // Derive a deterministic name from the function
         let name = match shm_name {
             Some(n) => n,
-            None => derive_shm_name(py, &fn_obj)?,
+            None => derive_shm_name(py, &fn_obj, cache_version.as_deref())?,
         };
 
-        let cache = ShmCache::create_or_open(
+        let mut cache = ShmCache::create_or_open(
             &name,
-            max_size as u32,
-            max_key_size as u32,
-            max_value_size as u32,
+            capacity,
+            key_limit,
+            value_limit,
             ttl,
+            stale_ttl,
+            sliding_ttl,
+            checksums,
+            codec.map_or(shm::VALUE_CODEC_NONE, Codec::id),
+            &serializer,
+            cache_version.as_deref().unwrap_or_default(),
+            chunk_size,
+            overflow_count,
+            overflow_size,
+            on_mismatch,
+            &FileOptions {
+                base_dir: shm_dir.as_deref(),
+                file_mode,
+                allow_insecure,
+                persistent,
+                sync_interval,
+                lock_timeout,
+                lock_memory,
+                prefault,
+                readonly,
+            },
         )
-        .map_err(|e| {
-            pyo3::exceptions::PyOSError::new_err(format!("Failed to create shared cache: {e}"))
-        })?;
-
-        let hash_state = RandomState::with_seeds(
-            0x517cc1b727220a95,
-            0x6c62272e07bb0142,
-            0x0f1e2d3c4b5a6978,
-            0xa1b2c3d4e5f60718,
-        );
+        .map_err(open_error)?;
+        check_serde_format(py, &cache, &name)?;
+        if on_evict.is_some() {
+            cache.keep_evicted();
+        }
+        // With OnMismatch::OpenExisting the stored limits win over the requested ones.
+        let config = cache.config();
+        let cache = fork_safe(py, cache)?;
 
         Ok(SharedCachedFunction {
             fn_obj,
             pickle_dumps,
             pickle_loads,
             cache,
-            max_key_size,
-            max_value_size,
-            hash_state,
+            max_key_size: config.max_key_size as usize,
+            max_value_size: config.value_limit() as usize,
+            stale_enabled: config.stale_ttl_nanos != 0,
+            codec: Codec::from_id(config.value_codec),
+            compress_threshold,
+            oversize,
+            oversize_warned: AtomicBool::new(false),
+            on_evict,
+            tags_func,
+            latencies: record_latencies.then(Latencies::new),
+            min_compute_time,
         })
     }
 
@@ -127,32 +461,13 @@ impl SharedCachedFunction {
         args: Bound<'py, PyTuple>,
         kwargs: Option<Bound<'py, PyDict>>,
     ) -> PyResult<Py<PyAny>> {
-        let (key_hash, key_bytes) = self.make_key(py, &args, &kwargs)?;
-
-        // Check key size limit (no lock needed — uses cached struct field)
-        if key_bytes.len() > self.max_key_size {
-            self.cache.record_oversize_skip();
-            return self
-                .fn_obj
-                .bind(py)
-                .call(args, kwargs.as_ref())
-                .map(|r| r.unbind());
-        }
-
-        // Lookup in shared cache (lock-free via seqlock)
-        match self.cache.get(key_hash, &key_bytes) {
-            ShmGetResult::Hit(vb) => {
-                return self.deserialize_value(py, &vb);
-            }
-            ShmGetResult::Miss => {}
-        }
-
-        // Cache miss: call the wrapped function
-        let result = self.fn_obj.bind(py).call(args, kwargs.as_ref())?;
-
-        self.store_result(py, key_hash, &key_bytes, &result)?;
-
-        Ok(result.unbind())
+        let Some(latencies) = &self.latencies else {
+            return self.call_cached(py, args, kwargs).map(|(value, _)| value);
+        };
+        let start = Instant::now();
+        let (value, hit) = self.call_cached(py, args, kwargs)?;
+        latencies.record(hit, start.elapsed());
+        Ok(value)
     }
 
     /// Cache lookup only. Returns the cached value or None on miss.
@@ -165,12 +480,9 @@ impl SharedCachedFunction {
     ) -> PyResult<Option<Py<PyAny>>> {
         let (key_hash, key_bytes) = self.make_key(py, &args, &kwargs)?;
 
-        match self.cache.get(key_hash, &key_bytes) {
-            ShmGetResult::Hit(vb) => {
-                let value = self.deserialize_value(py, &vb)?;
-                Ok(Some(value))
-            }
-            ShmGetResult::Miss => Ok(None),
+        match self.lookup(py, key_hash, &key_bytes) {
+            ShmGetResult::Hit(vb) => self.deserialize_value(py, &vb, Some(&key_bytes)),
+            ShmGetResult::Miss(_) => Ok(None),
         }
     }
 
@@ -184,47 +496,741 @@ impl SharedCachedFunction {
     ) -> PyResult<(bool, Py<PyAny>)> {
         let (key_hash, key_bytes) = self.make_key(py, &args, &kwargs)?;
 
-        match self.cache.get(key_hash, &key_bytes) {
+        match self.lookup(py, key_hash, &key_bytes) {
             ShmGetResult::Hit(vb) => {
-                let value = self.deserialize_value(py, &vb)?;
-                Ok((true, value))
+                Ok(match self.deserialize_value(py, &vb, Some(&key_bytes))? {
+                    Some(value) => (true, value),
+                    None => (false, py.None()),
+                })
             }
-            ShmGetResult::Miss => Ok((false, py.None())),
+            ShmGetResult::Miss(_) => Ok((false, py.None())),
         }
     }
 
+    /// Look up many argument tuples at once. Returns a list aligned with
+    /// `keys`, holding the cached value or None on miss.
+    ///
+    /// All keys are serialized up front, then looked up in a single seqlock
+    /// read pass (see `ShmCache::get_batch`).
+    fn get_many<'py>(
+        &self,
+        py: Python<'py>,
+        keys: Vec<Bound<'py, PyTuple>>,
+    ) -> PyResult<Vec<Option<Py<PyAny>>>> {
+        let serialized = keys
+            .iter()
+            .map(|args| self.make_key(py, args, &None))
+            .collect::<PyResult<Vec<_>>>()?;
+        let batch: Vec<(u64, &[u8])> = serialized
+            .iter()
+            .map(|(key_hash, key_bytes)| (*key_hash, key_bytes.as_slice()))
+            .collect();
+
+        let len = self.max_value_size.saturating_mul(batch.len());
+        without_gil(py, &self.cache, len, |cache| cache.get_batch(&batch))
+            .into_iter()
+            .zip(&batch)
+            .map(|(hit, (_, key_bytes))| {
+                hit.map(|vb| self.deserialize_value(py, &vb, Some(key_bytes)))
+                    .transpose()
+                    .map(Option::flatten)
+            })
+            .collect()
+    }
+
     /// Store a value in the cache for the given arguments.
-    #[pyo3(signature = (value, *args, **kwargs))]
+    ///
+    /// `_warp_ttl` (seconds) gives this entry its own TTL, overriding the
+    /// cache-wide one; it is not part of the key. Overwriting the entry later
+    /// without it reverts to the cache-wide TTL.
+    ///
+    /// `_warp_tags` (at most `MAX_TAGS` str) tags the entry for
+    /// `invalidate_tag`, in place of what `tags_func` would return. Neither
+    /// is part of the key.
+    ///
+    /// `_warp_compute_time` is as in `CachedFunction::set`: a value computed
+    /// in under `min_compute_time` is counted in `skipped_fast` and not
+    /// stored. Without it, `set()` always stores.
+    #[pyo3(signature = (value, *args, _warp_ttl=None, _warp_tags=None, _warp_compute_time=None, **kwargs))]
+    #[allow(clippy::too_many_arguments)]
     fn set<'py>(
         &self,
         py: Python<'py>,
         value: Py<PyAny>,
         args: Bound<'py, PyTuple>,
+        _warp_ttl: Option<f64>,
+        _warp_tags: Option<Bound<'py, PyAny>>,
+        _warp_compute_time: Option<f64>,
         kwargs: Option<Bound<'py, PyDict>>,
     ) -> PyResult<()> {
+        let elapsed = _warp_compute_time.and_then(|t| Duration::try_from_secs_f64(t).ok());
+        if too_fast(self.min_compute_time, elapsed) {
+            self.cache.record_skipped_fast();
+            return Ok(());
+        }
+        let ttl_nanos = match _warp_ttl {
+            Some(t) if t.is_nan() || t <= 0.0 => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "_warp_ttl must be a positive number of seconds (got {t})"
+                )));
+            }
+            // Sub-nanosecond TTLs round up so they never mean "use the default".
+            Some(t) => Some(((t * 1_000_000_000.0) as u64).max(1)),
+            None => None,
+        };
+        // Not stored under a key with a NaN in it, as for a call.
+        if args_contain_nan(&args, &kwargs)? {
+            return Ok(());
+        }
         let (key_hash, key_bytes) = self.make_key(py, &args, &kwargs)?;
         let result = value.bind(py);
-        self.store_result(py, key_hash, &key_bytes, result)?;
+        let tags = self.entry_tags(py, &args, &kwargs, result, _warp_tags.as_ref())?;
+        self.store_result(
+            py,
+            key_hash,
+            &key_bytes,
+            result,
+            ttl_nanos,
+            &tags,
+            InsertToken::default(),
+        )?;
         Ok(())
     }
 
+    /// Remove every entry tagged `tag`, by `_warp_tags` or `tags_func`, in
+    /// every process. Returns how many were removed.
+    fn invalidate_tag(&self, tag: &str) -> PyResult<usize> {
+        self.cache.invalidate_tag(tag_hash(tag)).map_err(lock_error)
+    }
+
+    /// Recompute and overwrite the cached value for the arguments.
+    ///
+    /// Calls the wrapped function unconditionally and stores the result in
+    /// place, which also restarts the entry's TTL. Other processes see the new
+    /// value on their next lookup. Oversize results are handled per
+    /// `oversize`, as with a normal miss.
+    #[pyo3(signature = (*args, **kwargs))]
+    fn refresh<'py>(
+        &self,
+        py: Python<'py>,
+        args: Bound<'py, PyTuple>,
+        kwargs: Option<Bound<'py, PyDict>>,
+    ) -> PyResult<Py<PyAny>> {
+        let (key_hash, key_bytes) = self.make_key(py, &args, &kwargs)?;
+        let result = self.fn_obj.bind(py).call(&args, kwargs.as_ref())?;
+        let tags = self.entry_tags(py, &args, &kwargs, &result, None)?;
+        self.store_result(
+            py,
+            key_hash,
+            &key_bytes,
+            &result,
+            None,
+            &tags,
+            InsertToken::default(),
+        )?;
+        Ok(result.unbind())
+    }
+
+    /// Extend the TTL of a cached entry by resetting its age to now.
+    ///
+    /// The value and eviction order are left as they are, so this is a cheap
+    /// keepalive for sliding-expiration workflows. Returns True if the entry
+    /// existed and had not expired.
+    #[pyo3(signature = (*args, **kwargs))]
+    fn touch<'py>(
+        &self,
+        py: Python<'py>,
+        args: Bound<'py, PyTuple>,
+        kwargs: Option<Bound<'py, PyDict>>,
+    ) -> PyResult<bool> {
+        let (key_hash, key_bytes) = self.make_key(py, &args, &kwargs)?;
+        self.cache.touch(key_hash, &key_bytes).map_err(lock_error)
+    }
+
+    /// Atomically add `amount` to the integer cached for the arguments and
+    /// return the new value; a missing or expired entry starts at `initial`.
+    ///
+    /// Safe to use as a cross-process counter: the read, add and write happen
+    /// under one write lock. Raises `TypeError` if the cached value is not an
+    /// int that fits in 64 bits, and `OverflowError` (leaving it unchanged) if
+    /// the result would not.
+    #[pyo3(signature = (*args, amount=1, initial=0, **kwargs))]
+    fn incr<'py>(
+        &self,
+        py: Python<'py>,
+        args: Bound<'py, PyTuple>,
+        amount: i64,
+        initial: i64,
+        kwargs: Option<Bound<'py, PyDict>>,
+    ) -> PyResult<i64> {
+        let (key_hash, key_bytes) = self.make_key(py, &args, &kwargs)?;
+        let result = incr(
+            &self.cache,
+            key_hash,
+            &key_bytes,
+            self.max_key_size,
+            self.max_value_size,
+            amount,
+            initial,
+        );
+        self.report_evicted(py);
+        result
+    }
+
+    /// `incr()` with the amount negated.
+    #[pyo3(signature = (*args, amount=1, initial=0, **kwargs))]
+    fn decr<'py>(
+        &self,
+        py: Python<'py>,
+        args: Bound<'py, PyTuple>,
+        amount: i64,
+        initial: i64,
+        kwargs: Option<Bound<'py, PyDict>>,
+    ) -> PyResult<i64> {
+        self.incr(py, args, negate(amount)?, initial, kwargs)
+    }
+
+    /// Store `new` for the arguments only if the cached value is currently
+    /// `expected`, atomically across processes. Returns True if it was stored.
+    ///
+    /// `expected=None` means "only if nothing is cached" (an add). Values are
+    /// compared in serialized form, so `expected` must serialize to the same
+    /// bytes as the stored value. Raises `ValueError` if `new` exceeds the size
+    /// limits.
+    #[pyo3(signature = (expected, new, *args, **kwargs))]
+    fn cas<'py>(
+        &self,
+        py: Python<'py>,
+        expected: Option<Bound<'py, PyAny>>,
+        new: Bound<'py, PyAny>,
+        args: Bound<'py, PyTuple>,
+        kwargs: Option<Bound<'py, PyDict>>,
+    ) -> PyResult<bool> {
+        let (key_hash, key_bytes) = self.make_key(py, &args, &kwargs)?;
+        let expected_bytes = expected.map(|e| self.encode_value(py, &e)).transpose()?;
+        let new_bytes = self.encode_value(py, &new)?;
+        self.cache.record_value_size(new_bytes.len());
+        let swapped = compare_and_swap(
+            &self.cache,
+            key_hash,
+            &key_bytes,
+            expected_bytes.as_deref(),
+            &new_bytes,
+            self.max_key_size,
+            self.max_value_size,
+        );
+        self.report_evicted(py);
+        swapped
+    }
+
+    /// Snapshot of the cached keys across all processes, in eviction order.
+    ///
+    /// Each key is the argument tuple the entry was cached under; calls with
+    /// keyword arguments appear as `(args, ((name, value), ...))`. Does not
+    /// affect hit/miss stats or eviction order. Keys in a serde format this
+    /// release can't read are left out.
+    fn keys(&self, py: Python<'_>) -> PyResult<Vec<Py<PyAny>>> {
+        self.cache
+            .keys()
+            .map_err(lock_error)?
+            .iter()
+            .filter_map(|kb| self.deserialize_key(py, kb, Some(kb)).transpose())
+            .collect()
+    }
+
+    /// Snapshot of `(key, value)` pairs for every live entry, in eviction order.
+    ///
+    /// Expired entries are skipped, as are entries this release can't read.
+    /// Keys have the same shape as `keys()`, so a dump can be replayed into a
+    /// fresh cache with `set(value, *key)` for positional-only keys.
+    fn items(&self, py: Python<'_>) -> PyResult<Vec<(Py<PyAny>, Py<PyAny>)>> {
+        // Copy out under the lock first; deserialization (which may run pickle)
+        // happens after it is released.
+        self.cache
+            .items()
+            .map_err(lock_error)?
+            .iter()
+            .filter_map(|(kb, vb)| self.deserialize_entry(py, kb, vb, true).transpose())
+            .collect()
+    }
+
+    /// Return the cached value for the arguments, or store `value` and return
+    /// it if there is none — atomically across processes.
+    ///
+    /// Unlike a `get()` followed by `set()`, there is no window in which another
+    /// process can insert between the lookup and the store: whichever caller gets
+    /// the write lock first wins, and everyone else gets that value back. If
+    /// `value` exceeds the size limits it is handled per `oversize`; when not
+    /// raising, it is returned uncached unless an existing entry is found.
+    #[pyo3(signature = (value, *args, **kwargs))]
+    fn setdefault<'py>(
+        &self,
+        py: Python<'py>,
+        value: Py<PyAny>,
+        args: Bound<'py, PyTuple>,
+        kwargs: Option<Bound<'py, PyDict>>,
+    ) -> PyResult<Py<PyAny>> {
+        let (key_hash, key_bytes) = self.make_key(py, &args, &kwargs)?;
+        let value_bytes = self.encode_value(py, value.bind(py))?;
+
+        if !self.check_size(py, key_bytes.len(), value_bytes.len())? {
+            return match self.lookup(py, key_hash, &key_bytes) {
+                ShmGetResult::Hit(vb) => Ok(self
+                    .deserialize_value(py, &vb, Some(&key_bytes))?
+                    .unwrap_or(value)),
+                ShmGetResult::Miss(_) => Ok(value),
+            };
+        }
+
+        let found = without_gil(py, &self.cache, value_bytes.len(), |cache| {
+            cache.get_or_insert(key_hash, &key_bytes, &value_bytes)
+        })
+        .map_err(lock_error)?;
+        self.report_evicted(py);
+        match found {
+            (_, true) => Ok(value),
+            (existing, false) => Ok(self
+                .deserialize_value(py, &existing, Some(&key_bytes))?
+                .unwrap_or(value)),
+        }
+    }
+
+    /// Store many `(args_tuple, value)` pairs under one write-lock acquisition.
+    ///
+    /// Everything is serialized before the lock is taken. Pairs whose key or
+    /// value exceeds the size limits are handled per `oversize`: skipped and
+    /// counted in `oversize_skips`, or with `"raise"`, nothing is stored.
+    /// Returns `{"inserted": n, "oversize_skipped": m}`.
+    fn set_many<'py>(
+        &self,
+        py: Python<'py>,
+        pairs: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let mut serialized: Vec<(u64, Vec<u8>, Vec<u8>)> = Vec::new();
+        let mut oversize_skipped = 0usize;
+        for pair in pairs.try_iter()? {
+            let (args, value): (Bound<'py, PyTuple>, Bound<'py, PyAny>) = pair?.extract()?;
+            let (key_hash, key_bytes) = self.make_key(py, &args, &None)?;
+            let value_bytes = self.encode_value(py, &value)?;
+            if !self.check_size(py, key_bytes.len(), value_bytes.len())? {
+                oversize_skipped += 1;
+                continue;
+            }
+            serialized.push((key_hash, key_bytes, value_bytes));
+        }
+
+        let batch: Vec<(u64, &[u8], &[u8])> = serialized
+            .iter()
+            .map(|(key_hash, key_bytes, value_bytes)| {
+                (*key_hash, key_bytes.as_slice(), value_bytes.as_slice())
+            })
+            .collect();
+        if !batch.is_empty() {
+            let len = batch.iter().map(|(_, _, value)| value.len()).sum();
+            without_gil(py, &self.cache, len, |cache| cache.insert_batch(&batch))
+                .map_err(lock_error)?;
+            self.report_evicted(py);
+        }
+
+        let summary = PyDict::new(py);
+        summary.set_item("inserted", batch.len())?;
+        summary.set_item("oversize_skipped", oversize_skipped)?;
+        Ok(summary)
+    }
+
     fn cache_info(&self) -> SharedCacheInfo {
-        let info = self.cache.info();
-        SharedCacheInfo {
-            hits: info.hits,
-            misses: info.misses,
-            max_size: info.max_size,
-            current_size: info.current_size,
-            oversize_skips: info.oversize_skips,
+        SharedCacheInfo::from(self.cache.info())
+    }
+
+    /// `cache_info()` as a flat dict of metric names to numbers, for exporters
+    /// such as Prometheus: `hits`, `misses`, `hit_rate`, `evictions`,
+    /// `expirations`, `current_size`, `max_size`, `oversize_skips`,
+    /// `lock_timeouts`, `skipped_fast`, `bytes_used` and `bytes_capacity`. The names are
+    /// stable. Counts are for every process sharing the cache.
+    fn metrics<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.cache_info().metrics(py)
+    }
+
+    /// `cache_info()` with the hits, misses and oversize skips of this
+    /// function object only, rather than of every process sharing the cache.
+    /// Counted since it was created, or since the last `cache_clear()` or
+    /// `reset_stats()` on it.
+    fn local_cache_info(&self) -> SharedCacheInfo {
+        SharedCacheInfo::from(self.cache.local_info())
+    }
+
+    /// How much this process has waited on the cache's seqlock, to tell a
+    /// writer-heavy slowdown (readers retrying) from a multi-writer one
+    /// (writers spinning): `read_waits` (pauses of reads waiting out a
+    /// writer), `read_retries` (lock-free reads a writer invalidated),
+    /// `write_spins` (pauses and lost compare-and-swaps of writers waiting
+    /// for the lock) and `write_locks` (write lock acquisitions). Counted per
+    /// process, never in the shared file; zeroed by `reset_stats()` and
+    /// `cache_clear()`.
+    fn contention_info<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let info = self.cache.contention_info();
+        let dict = PyDict::new(py);
+        dict.set_item("read_waits", info.read_waits)?;
+        dict.set_item("read_retries", info.read_retries)?;
+        dict.set_item("write_spins", info.write_spins)?;
+        dict.set_item("write_locks", info.write_locks)?;
+        Ok(dict)
+    }
+
+    /// Remove every entry. With `release_memory`, also give the cache file's
+    /// slab pages back to the OS (Linux), so an empty cache stops holding
+    /// shared memory; they are allocated again as it refills.
+    #[pyo3(signature = (release_memory=false))]
+    fn cache_clear(&self, release_memory: bool) -> PyResult<()> {
+        if release_memory {
+            self.cache.clear_releasing_memory()
+        } else {
+            self.cache.clear()
         }
+        .map_err(lock_error)
     }
 
-    fn cache_clear(&self) {
-        self.cache.clear();
+    /// Raw state of the shared cache, for bug reports: a dict with `header`
+    /// (layout, list and config fields), `slots` (one dict per slot, up to
+    /// `max_slots`), and the slot indices of the `eviction_order` (oldest
+    /// first) and `free_list`, each also capped at `max_slots`.
+    ///
+    /// Taken under the write lock, so it blocks every process for as long as
+    /// the copy takes.
+    #[pyo3(signature = (max_slots=1000))]
+    fn debug_info<'py>(&self, py: Python<'py>, max_slots: usize) -> PyResult<Bound<'py, PyDict>> {
+        let info = self.cache.debug_info(max_slots).map_err(lock_error)?;
+        let header = PyDict::new(py);
+        for (name, value) in info.header {
+            header.set_item(name, value)?;
+        }
+        let slots = info
+            .slots
+            .iter()
+            .map(|slot| {
+                let dict = PyDict::new(py);
+                dict.set_item("index", slot.index)?;
+                dict.set_item("occupied", slot.occupied)?;
+                dict.set_item("key_hash", slot.key_hash)?;
+                dict.set_item("key_len", slot.key_len)?;
+                dict.set_item("value_len", slot.value_len)?;
+                dict.set_item("age", slot.age_nanos as f64 / 1e9)?;
+                let ttl = (slot.ttl_nanos > 0).then(|| slot.ttl_nanos as f64 / 1e9);
+                dict.set_item("ttl", ttl)?;
+                dict.set_item("visited", slot.visited)?;
+                dict.set_item("prev", slot.prev)?;
+                dict.set_item("next", slot.next)?;
+                dict.set_item("chain", slot.chain)?;
+                dict.set_item("overflow", slot.overflow)?;
+                Ok(dict)
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+
+        let dump = PyDict::new(py);
+        dump.set_item("header", header)?;
+        dump.set_item("slots", slots)?;
+        dump.set_item("eviction_order", info.eviction_order)?;
+        dump.set_item("free_list", info.free_list)?;
+        Ok(dump)
+    }
+
+    /// Check the shared cache's internal invariants (hash table, eviction and
+    /// free lists, slot chains, overflow arena, `current_size`) under the
+    /// write lock. Returns a list of violations; empty means healthy.
+    fn verify(&self) -> PyResult<Vec<String>> {
+        self.cache.verify().map_err(lock_error)
+    }
+
+    /// Zero the counters in `cache_info()` (for every process),
+    /// `local_cache_info()` and the latency histograms, keeping the cached
+    /// entries. With `local_only`, only this function object's local counters
+    /// and histograms are zeroed.
+    #[pyo3(signature = (local_only=false))]
+    fn reset_stats(&self, local_only: bool) -> PyResult<()> {
+        if let Some(latencies) = &self.latencies {
+            latencies.reset();
+        }
+        if local_only {
+            self.cache.reset_local_stats();
+            Ok(())
+        } else {
+            self.cache.reset_stats().map_err(lock_error)
+        }
+    }
+
+    /// The sizes of the serialized (and compressed) values every process has
+    /// offered to the cache, stored or skipped as oversize, for choosing
+    /// `max_value_size`: a dict with `buckets`, `(upper_bound, count)` pairs
+    /// over 16 buckets doubling up to at least 4× the largest storable value
+    /// (bounds inclusive, in bytes, the last `inf`), and `largest`, the
+    /// biggest value seen. Zeroed by `reset_stats()`.
+    fn value_size_histogram<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let (buckets, largest) = self.cache.value_size_histogram();
+        let buckets = buckets
+            .into_iter()
+            .map(|(bound, count)| {
+                let bound = match bound {
+                    Some(bound) => PyInt::new(py, bound).into_any(),
+                    None => PyFloat::new(py, f64::INFINITY).into_any(),
+                };
+                (bound, count)
+            })
+            .collect::<Vec<_>>();
+        let dict = PyDict::new(py);
+        dict.set_item("buckets", buckets)?;
+        dict.set_item("largest", largest)?;
+        Ok(dict)
+    }
+
+    /// With `record_latencies`, this process's hit and miss latency
+    /// histograms of calls (see `Latencies::to_dict`); otherwise None.
+    fn latency_histogram<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        self.latencies
+            .as_ref()
+            .map(|latencies| latencies.to_dict(py))
+            .transpose()
+    }
+
+    /// Record one call's latency from outside `__call__` (the async wrapper,
+    /// which times its own calls). A no-op without `record_latencies`.
+    fn _record_latency(&self, hit: bool, nanos: u64) {
+        if let Some(latencies) = &self.latencies {
+            latencies.record(hit, Duration::from_nanos(nanos));
+        }
+    }
+
+    /// Rebuild the shared cache with room for `max_size` entries, keeping live
+    /// entries (the most recently inserted when shrinking). Other processes
+    /// attached to the cache switch over on their next access.
+    fn cache_resize(&self, max_size: usize) -> PyResult<()> {
+        let capacity = check_max_size(max_size)?;
+        self.cache.resize(capacity).map_err(|e| match e.kind() {
+            io::ErrorKind::TimedOut => lock_error(e),
+            _ => {
+                pyo3::exceptions::PyOSError::new_err(format!("Failed to resize shared cache: {e}"))
+            }
+        })
+    }
+
+    /// Write every live entry to a snapshot file at `path`, replacing it
+    /// atomically. The entries are copied out under the write lock and written
+    /// after it is released. Returns the number of entries saved.
+    fn save(&self, path: PathBuf) -> PyResult<usize> {
+        // Values are written uncompressed, so the snapshot loads into a cache
+        // with any (or no) codec.
+        let entries: Vec<SnapshotEntry> = self
+            .cache
+            .entries()
+            .map_err(lock_error)?
+            .into_iter()
+            .map(|(key, value, remaining_ttl_nanos)| {
+                Ok(SnapshotEntry {
+                    key,
+                    value: compress::decode(&value)
+                        .map_err(decompress_error)?
+                        .into_owned(),
+                    remaining_ttl_nanos,
+                })
+            })
+            .collect::<PyResult<_>>()?;
+        snapshot::write(&path, &entries).map_err(|e| {
+            pyo3::exceptions::PyOSError::new_err(format!(
+                "Failed to save snapshot to {}: {e}",
+                path.display()
+            ))
+        })?;
+        Ok(entries.len())
+    }
+
+    /// Insert the entries of a snapshot written by `save`, in their saved order,
+    /// each with the TTL it had left minus the time since the save.
+    ///
+    /// Entries that have expired since, or that exceed this cache's size
+    /// limits, are skipped; a full cache evicts as usual. Returns
+    /// `{"inserted": n, "oversize_skipped": m, "expired_skipped": k}`.
+    fn load<'py>(&self, py: Python<'py>, path: PathBuf) -> PyResult<Bound<'py, PyDict>> {
+        let (entries, expired_skipped) = snapshot::read(&path).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData => pyo3::exceptions::PyValueError::new_err(format!(
+                "Invalid snapshot {}: {e}",
+                path.display()
+            )),
+            _ => pyo3::exceptions::PyOSError::new_err(format!(
+                "Failed to load snapshot from {}: {e}",
+                path.display()
+            )),
+        })?;
+
+        let mut inserted = 0usize;
+        let mut oversize_skipped = 0usize;
+        for entry in entries {
+            let value = self.compress_value(py, entry.value);
+            if let Some(kind) = oversize(
+                entry.key.len(),
+                self.max_key_size,
+                value.len(),
+                self.max_value_size,
+            ) {
+                self.cache.record_oversize_skip(kind);
+                oversize_skipped += 1;
+                continue;
+            }
+            let key_hash = hash_key(&entry.key);
+            let ttl_nanos = (entry.remaining_ttl_nanos != 0).then_some(entry.remaining_ttl_nanos);
+            self.cache
+                .insert(key_hash, &entry.key, &value, ttl_nanos)
+                .map_err(lock_error)?;
+            inserted += 1;
+        }
+        self.report_evicted(py);
+
+        let summary = PyDict::new(py);
+        summary.set_item("inserted", inserted)?;
+        summary.set_item("oversize_skipped", oversize_skipped)?;
+        summary.set_item("expired_skipped", expired_skipped)?;
+        Ok(summary)
+    }
+
+    /// Flush the cache file to disk and wait for the write to finish. Only
+    /// meaningful for `persistent` caches; the others live in memory anyway.
+    fn sync(&self) -> PyResult<()> {
+        self.cache.sync().map_err(|e| match e.kind() {
+            io::ErrorKind::TimedOut => lock_error(e),
+            _ => pyo3::exceptions::PyOSError::new_err(format!("Failed to sync shared cache: {e}")),
+        })
+    }
+}
+
+/// Validate a shared-backend `max_size` and narrow it to the region's u32.
+pub(crate) fn check_max_size(max_size: usize) -> PyResult<u32> {
+    if max_size == 0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "max_size must be >= 1 for the shared backend",
+        ));
+    }
+    // The hash table is sized at 2x capacity rounded up to a power of two, which
+    // must fit in u32 — so capacity itself must be <= 2^30 (#41). Reject larger
+    // values here, before the `as u32` cast below silently truncates a usize that
+    // doesn't fit (e.g. 2^32 -> a tiny cache) and before region.rs overflows
+    // `capacity * 2` (panic in debug, 1-bucket table in release).
+    const MAX_SHARED_MAX_SIZE: usize = 1 << 30;
+    if max_size > MAX_SHARED_MAX_SIZE {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "max_size must be <= {MAX_SHARED_MAX_SIZE} for the shared backend (got {max_size})"
+        )));
+    }
+    Ok(max_size as u32)
+}
+
+/// Validate the shared backend's `max_key_size`/`max_value_size` and narrow
+/// them to the region's u32. Whether a slot of both still fits is up to
+/// `ShmCache::create_or_open`.
+pub(crate) fn check_size_limits(
+    max_key_size: usize,
+    max_value_size: usize,
+) -> PyResult<(u32, u32)> {
+    let narrow = |name: &str, size: usize| match u32::try_from(size) {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "{name} must be between 1 and {} bytes (got {size})",
+            u32::MAX
+        ))),
+    };
+    Ok((
+        narrow("max_key_size", max_key_size)?,
+        narrow("max_value_size", max_value_size)?,
+    ))
+}
+
+/// Validate a `ttl` argument: None, or a positive, finite number of seconds.
+pub(crate) fn check_ttl(name: &str, ttl: Option<f64>) -> PyResult<()> {
+    match ttl {
+        Some(t) if !(t.is_finite() && t > 0.0) => Err(pyo3::exceptions::PyValueError::new_err(
+            format!("{name} must be a positive number of seconds (got {t})"),
+        )),
+        _ => Ok(()),
     }
 }
 
 impl SharedCachedFunction {
+    /// The body of `__call__`: the result, and whether it came from the cache.
+    #[inline(always)]
+    fn call_cached<'py>(
+        &self,
+        py: Python<'py>,
+        args: Bound<'py, PyTuple>,
+        kwargs: Option<Bound<'py, PyDict>>,
+    ) -> PyResult<(Py<PyAny>, bool)> {
+        // A key with a NaN in it is never cached, as in the memory backend.
+        if args_contain_nan(&args, &kwargs)? {
+            self.cache.record_miss();
+            return self
+                .fn_obj
+                .bind(py)
+                .call(args, kwargs.as_ref())
+                .map(|r| (r.unbind(), false));
+        }
+        let (key_hash, key_bytes) = self.make_key(py, &args, &kwargs)?;
+
+        // Check key size limit (no lock needed — uses cached struct field)
+        if !self.check_size(py, key_bytes.len(), 0)? {
+            return self
+                .fn_obj
+                .bind(py)
+                .call(args, kwargs.as_ref())
+                .map(|r| (r.unbind(), false));
+        }
+
+        // Lookup in shared cache (lock-free via seqlock). A stale entry is a
+        // hit for every caller but the one that claims its refresh.
+        let token = match without_gil(py, &self.cache, self.max_value_size, |cache| {
+            cache.get_or_claim_refresh(key_hash, &key_bytes)
+        }) {
+            ShmGetResult::Hit(vb) => match self.deserialize_value(py, &vb, Some(&key_bytes))? {
+                Some(value) => return Ok((value, true)),
+                // Written in a serde format this release can't read: recompute.
+                None => InsertToken::default(),
+            },
+            ShmGetResult::Miss(token) => token,
+        };
+
+        // Cache miss: call the wrapped function. If it raises or the result
+        // isn't stored, hand back any refresh claim so others can retry.
+        let start = self.min_compute_time.map(|_| Instant::now());
+        let result = match self.fn_obj.bind(py).call(&args, kwargs.as_ref()) {
+            Ok(result) => result,
+            Err(e) => {
+                self.release_refresh_if_stale(key_hash, &key_bytes);
+                return Err(e);
+            }
+        };
+        // A read-only handle computes misses but leaves storing them to the
+        // cache's owner.
+        if self.cache.is_readonly() {
+            return Ok((result.unbind(), false));
+        }
+        if too_fast(self.min_compute_time, start.map(|s| s.elapsed())) {
+            self.cache.record_skipped_fast();
+            self.release_refresh_if_stale(key_hash, &key_bytes);
+            return Ok((result.unbind(), false));
+        }
+
+        let stored = self
+            .entry_tags(py, &args, &kwargs, &result, None)
+            .and_then(|tags| {
+                self.store_result(py, key_hash, &key_bytes, &result, None, &tags, token)
+            });
+        match stored {
+            Ok(true) => {}
+            Ok(false) => self.release_refresh_if_stale(key_hash, &key_bytes),
+            Err(e) => {
+                self.release_refresh_if_stale(key_hash, &key_bytes);
+                return Err(e);
+            }
+        }
+
+        Ok((result.unbind(), false))
+    }
+
     /// Build (key_hash, serialized_key_bytes) from call args.
     fn make_key<'py>(
         &self,
@@ -233,82 +1239,753 @@ impl SharedCachedFunction {
         kwargs: &Option<Bound<'py, PyDict>>,
     ) -> PyResult<(u64, Vec<u8>)> {
         let key_obj: Py<PyAny> = match kwargs {
-            Some(ref kw) if !kw.is_empty() => {
-                let builtins = py.import("builtins")?;
-                let items = kw.call_method0("items")?;
-                let sorted_items = builtins.call_method1("sorted", (items,))?;
-                let kw_tup = builtins.getattr("tuple")?.call1((sorted_items,))?;
-                let combined = PyTuple::new(py, [args.as_any().clone(), kw_tup])?;
-                combined.unbind().into()
-            }
+            Some(ref kw) if !kw.is_empty() => kwargs_key(py, args, kw)?.unbind(),
             _ => args.clone().unbind().into(),
         };
 
         // Serialize key first (deterministic bytes for cross-process hashing)
-        let key_bound = key_obj.bind(py);
-        let key_bytes = if let Some(bytes) = serde::serialize(py, key_bound)? {
-            bytes
-        } else {
-            // Fallback: pickle
-            let pickle_obj = self.pickle_dumps.bind(py).call1((&key_obj,))?;
-            let pickle_bytes: &[u8] = pickle_obj.extract()?;
-            serde::wrap_pickle(pickle_bytes)
-        };
-
-        // Hash the serialized bytes — deterministic across processes
-        // (Python's hash() is randomized per-process for str/bytes)
-        let key_hash = {
-            let mut hasher = self.hash_state.build_hasher();
-            hasher.write(&key_bytes);
-            hasher.finish()
-        };
+        let key_bytes = serialize_key(py, &self.pickle_dumps, key_obj.bind(py))?;
 
+        let key_hash = hash_key(&key_bytes);
         Ok((key_hash, key_bytes))
     }
 
+    /// `ShmCache::get`, without the GIL if it may have to wait or copy a lot.
+    /// A key over `max_key_size` can never have been stored, so it misses
+    /// without touching the cache.
+    fn lookup(&self, py: Python<'_>, key_hash: u64, key_bytes: &[u8]) -> ShmGetResult {
+        if key_bytes.len() > self.max_key_size {
+            self.cache.record_miss();
+            return ShmGetResult::Miss(InsertToken::default());
+        }
+        without_gil(py, &self.cache, self.max_value_size, |cache| {
+            cache.get(key_hash, key_bytes)
+        })
+    }
+
     /// Serialize and store a result, checking value size limits.
+    /// `ttl_nanos` is the per-entry TTL override, if any, and `tags` the
+    /// entry's tag hashes; `token` comes from the lookup that missed, if
+    /// there was one. Returns whether the result
+    /// was stored (false if it was skipped as oversize, an error if the
+    /// `oversize` policy is to raise).
+    #[allow(clippy::too_many_arguments)]
     fn store_result<'py>(
         &self,
         py: Python<'py>,
         key_hash: u64,
         key_bytes: &[u8],
         result: &Bound<'py, PyAny>,
-    ) -> PyResult<()> {
-        // Fast path: serialize value without pickle
-        let value_bytes = if let Some(bytes) = serde::serialize(py, result)? {
-            bytes
+        ttl_nanos: Option<u64>,
+        tags: &[u64],
+        token: InsertToken,
+    ) -> PyResult<bool> {
+        let value_bytes = self.encode_value(py, result)?;
+
+        // Check size limits on the stored (possibly compressed) length. No
+        // lock — uses cached struct fields.
+        if !self.check_size(py, key_bytes.len(), value_bytes.len())? {
+            return Ok(false);
+        }
+
+        // Insert acquires seqlock write lock internally
+        without_gil(py, &self.cache, value_bytes.len(), |cache| {
+            cache.insert_with_token(token, key_hash, key_bytes, &value_bytes, ttl_nanos, tags)
+        })
+        .map_err(lock_error)?;
+        self.report_evicted(py);
+        Ok(true)
+    }
+
+    /// Hashes of the tags for a result about to be stored: `explicit`
+    /// (`_warp_tags=`) if given, else what `tags_func` returns, else none.
+    /// `ValueError` if there are more than `MAX_TAGS`.
+    fn entry_tags<'py>(
+        &self,
+        py: Python<'py>,
+        args: &Bound<'py, PyTuple>,
+        kwargs: &Option<Bound<'py, PyDict>>,
+        result: &Bound<'py, PyAny>,
+        explicit: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Vec<u64>> {
+        let tags = match (explicit, &self.tags_func) {
+            (Some(tags), _) => parse_tags(tags)?,
+            (None, Some(tags_func)) => call_tags_func(py, tags_func, args, kwargs, result)?,
+            (None, None) => return Ok(Vec::new()),
+        };
+        if tags.len() > MAX_TAGS {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "the shared backend allows at most {MAX_TAGS} tags per entry (got {})",
+                tags.len()
+            )));
+        }
+        Ok(tags.iter().map(|tag| tag_hash(tag)).collect())
+    }
+
+    /// Hand the entries this process just evicted to `on_evict`, if set. Runs
+    /// after the write lock is released; an exception from the callback is
+    /// reported through `sys.unraisablehook` rather than failing the write.
+    fn report_evicted(&self, py: Python<'_>) {
+        let Some(on_evict) = &self.on_evict else {
+            return;
+        };
+        let on_evict = on_evict.bind(py);
+        for (key_bytes, value_bytes) in self.cache.take_evicted() {
+            // An entry this release can't read is evicted silently.
+            let called = self
+                .deserialize_entry(py, &key_bytes, &value_bytes, false)
+                .and_then(|entry| match entry {
+                    Some(pair) => on_evict.call1(pair).map(drop),
+                    None => Ok(()),
+                });
+            if let Err(e) = called {
+                e.write_unraisable(py, Some(on_evict));
+            }
+        }
+    }
+
+    /// Whether a key and value of these serialized lengths fit the size limits
+    /// (pass 0 to check just the key). If not, counts an oversize skip and then
+    /// applies the `oversize` policy: `Ok(false)` to skip, possibly after
+    /// warning, or a `ValueError`. A value's size goes into
+    /// `value_size_histogram()` either way.
+    fn check_size(&self, py: Python<'_>, key_len: usize, value_len: usize) -> PyResult<bool> {
+        if value_len > 0 {
+            self.cache.record_value_size(value_len);
+        }
+        let Some(kind) = oversize(key_len, self.max_key_size, value_len, self.max_value_size)
+        else {
+            return Ok(true);
+        };
+        self.cache.record_oversize_skip(kind);
+        let msg = match kind {
+            Oversize::Key => format!(
+                "key is {key_len} bytes serialized, over max_key_size={}",
+                self.max_key_size
+            ),
+            Oversize::Value => format!(
+                "value is {value_len} bytes serialized, over max_value_size={}",
+                self.max_value_size
+            ),
+        };
+
+        let name = || {
+            self.fn_obj
+                .bind(py)
+                .getattr("__qualname__")
+                .and_then(|q| q.extract::<String>())
+                .unwrap_or_else(|_| "cached function".to_string())
+        };
+        match self.oversize {
+            OversizePolicy::Skip => Ok(false),
+            OversizePolicy::Warn => {
+                if !self.oversize_warned.swap(true, Ordering::Relaxed) {
+                    let msg = CString::new(format!(
+                        "{}: {msg}, so it is not cached (further oversize skips are \
+                         only counted in cache_info().oversize_skips)",
+                        name()
+                    ))
+                    .unwrap_or_default();
+                    let category = py.get_type::<pyo3::exceptions::PyUserWarning>();
+                    PyErr::warn(py, &category, &msg, 1)?;
+                }
+                Ok(false)
+            }
+            OversizePolicy::Raise => Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "{}: {msg}",
+                name()
+            ))),
+        }
+    }
+
+    /// Release a refresh claim after a failed recompute. Only caches with a
+    /// stale window can hand out claims, so the write lock is skipped otherwise.
+    /// If the lock times out the claim is left to expire with the stale window.
+    fn release_refresh_if_stale(&self, key_hash: u64, key_bytes: &[u8]) {
+        if self.stale_enabled {
+            let _ = self.cache.release_refresh(key_hash, key_bytes);
+        }
+    }
+
+    /// Serialize a value for storage: fast path, falling back to pickle.
+    fn serialize_value<'py>(
+        &self,
+        py: Python<'py>,
+        value: &Bound<'py, PyAny>,
+    ) -> PyResult<Vec<u8>> {
+        serialize(py, &self.pickle_dumps, value)
+    }
+
+    /// Serialize a value and, past `compress_threshold`, compress it — the
+    /// bytes actually stored, and so the length checked against the limit.
+    fn encode_value<'py>(&self, py: Python<'py>, value: &Bound<'py, PyAny>) -> PyResult<Vec<u8>> {
+        let bytes = self.serialize_value(py, value)?;
+        Ok(self.compress_value(py, bytes))
+    }
+
+    /// `bytes` compressed with this cache's codec, if it has one and they are
+    /// over `compress_threshold`. Without the GIL if they are long.
+    fn compress_value(&self, py: Python<'_>, bytes: Vec<u8>) -> Vec<u8> {
+        let threshold = self.compress_threshold;
+        match self.codec {
+            Some(codec) => detach_if_long(py, bytes.len(), move || {
+                compress::encode(codec, threshold, bytes)
+            }),
+            None => bytes,
+        }
+    }
+
+    /// Deserialize a key or value of the entry under `entry_key` from shared
+    /// memory bytes, decompressing it first if it was stored compressed.
+    /// `None` if it is unreadable (see `deserialize`).
+    fn deserialize_value(
+        &self,
+        py: Python,
+        data: &[u8],
+        entry_key: Option<&[u8]>,
+    ) -> PyResult<Option<Py<PyAny>>> {
+        let data = if compress::is_compressed(data) {
+            detach_if_long(py, data.len(), || compress::decode(data)).map_err(decompress_error)?
         } else {
-            let pickle_obj = self.pickle_dumps.bind(py).call1((result,))?;
-            let pickle_bytes: &[u8] = pickle_obj.extract()?;
-            serde::wrap_pickle(pickle_bytes)
+            Cow::Borrowed(data)
         };
+        deserialize(py, &self.cache, &self.pickle_loads, &data, entry_key)
+    }
 
-        // Check size limits (no lock — uses cached struct fields)
-        if key_bytes.len() > self.max_key_size || value_bytes.len() > self.max_value_size {
-            self.cache.record_oversize_skip();
-            return Ok(());
+    /// `deserialize_value` for a call's key, as the caller sees it: a call
+    /// with keyword arguments comes back as `(args, kwargs_items)`, without the
+    /// marker `kwargs_key` stores between them.
+    fn deserialize_key(
+        &self,
+        py: Python,
+        data: &[u8],
+        entry_key: Option<&[u8]>,
+    ) -> PyResult<Option<Py<PyAny>>> {
+        let Some(key) = self.deserialize_value(py, data, entry_key)? else {
+            return Ok(None);
+        };
+        Ok(Some(without_kwargs_mark(key.bind(py))?.unbind()))
+    }
+
+    /// `deserialize_value` for a key and its value, `None` if either is
+    /// unreadable. `live` says whether the entry is still in the cache, to be
+    /// dropped if corrupt.
+    fn deserialize_entry(
+        &self,
+        py: Python,
+        key_bytes: &[u8],
+        value_bytes: &[u8],
+        live: bool,
+    ) -> PyResult<Option<(Py<PyAny>, Py<PyAny>)>> {
+        let entry_key = live.then_some(key_bytes);
+        let Some(key) = self.deserialize_key(py, key_bytes, entry_key)? else {
+            return Ok(None);
+        };
+        Ok(self
+            .deserialize_value(py, value_bytes, entry_key)?
+            .map(|value| (key, value)))
+    }
+}
+
+/// Serialize a key or value into the bytes stored in shared memory: the
+/// `serde` fast path, falling back to `pickle_dumps`.
+pub(crate) fn serialize<'py>(
+    py: Python<'py>,
+    pickle_dumps: &Py<PyAny>,
+    value: &Bound<'py, PyAny>,
+) -> PyResult<Vec<u8>> {
+    match serde::serialize(py, value)? {
+        Some(bytes) => Ok(bytes),
+        None => pickled(py, pickle_dumps, value),
+    }
+}
+
+/// `serialize` for a key: the fast path writes dicts in a canonical order
+/// (`serde::serialize_key`), so equal keys hash the same.
+pub(crate) fn serialize_key<'py>(
+    py: Python<'py>,
+    pickle_dumps: &Py<PyAny>,
+    key: &Bound<'py, PyAny>,
+) -> PyResult<Vec<u8>> {
+    match serde::serialize_key(py, key)? {
+        Some(bytes) => Ok(bytes),
+        None => pickled(py, pickle_dumps, key),
+    }
+}
+
+/// `value` through `pickle_dumps`, tagged as pickled.
+fn pickled<'py>(
+    py: Python<'py>,
+    pickle_dumps: &Py<PyAny>,
+    value: &Bound<'py, PyAny>,
+) -> PyResult<Vec<u8>> {
+    let pickle_obj = pickle_dumps.bind(py).call1((value,))?;
+    let pickle_bytes: &[u8] = pickle_obj.extract()?;
+    // `pickle_obj` is an immutable `bytes` kept alive here, so its buffer can
+    // be copied while other threads run.
+    Ok(detach_if_long(py, pickle_bytes.len(), || {
+        serde::wrap_pickle(pickle_bytes)
+    }))
+}
+
+/// Inverse of `serialize`, for bytes `cache` holds under `entry_key` (`None`
+/// once the entry is gone, as for one just evicted). `None` if they can't be
+/// read: in a serde format this release doesn't know (see `serde::FORMAT`),
+/// or corrupt. Callers treat the entry as a miss and recompute it rather than
+/// fail the read. Corrupt bytes never reach pickle: the entry is counted in
+/// `corrupt_values`, dropped, and reported with a `RuntimeWarning` saying
+/// where they went wrong.
+pub(crate) fn deserialize(
+    py: Python,
+    cache: &ShmCache,
+    pickle_loads: &Py<PyAny>,
+    data: &[u8],
+    entry_key: Option<&[u8]>,
+) -> PyResult<Option<Py<PyAny>>> {
+    match serde::deserialize(py, data)? {
+        serde::Decoded::Value(obj) => Ok(Some(obj)),
+        serde::Decoded::Pickled => {
+            let payload = serde::pickle_payload(data);
+            Ok(Some(pickle_loads.bind(py).call1((payload,))?.unbind()))
+        }
+        serde::Decoded::UnknownFormat => Ok(None),
+        serde::Decoded::Unregistered(id) => {
+            // Left in place: the processes that registered the type read it.
+            let msg = CString::new(format!(
+                "shared cache {:?} holds a value of a type registered with id {id:#018x} \
+                 in another process but not in this one; register_serializer() it here too",
+                cache.name()
+            ))
+            .unwrap_or_default();
+            let category = py.get_type::<pyo3::exceptions::PyRuntimeWarning>();
+            PyErr::warn(py, &category, &msg, 1)?;
+            Ok(None)
         }
+        serde::Decoded::Corrupt(corrupt) => {
+            cache.record_corrupt_value();
+            if let Some(key) = entry_key {
+                // Best effort: a read-only handle can't, and after a lock
+                // timeout the next reader to hit it tries again.
+                let _ = cache.remove(hash_key(key), key);
+            }
+            let msg = CString::new(format!(
+                "shared cache {:?} dropped an entry that doesn't deserialize: {corrupt}",
+                cache.name()
+            ))
+            .unwrap_or_default();
+            let category = py.get_type::<pyo3::exceptions::PyRuntimeWarning>();
+            PyErr::warn(py, &category, &msg, 1)?;
+            Ok(None)
+        }
+    }
+}
 
-        // Insert acquires seqlock write lock internally
-        self.cache.insert(key_hash, key_bytes, &value_bytes);
-        Ok(())
+/// Record this release's serde format in the cache header, warning if
+/// another process has written (or writes) values in a different one: each
+/// side reads the other's values as misses, so the processes keep
+/// recomputing what they share.
+pub(crate) fn check_serde_format(py: Python<'_>, cache: &ShmCache, name: &str) -> PyResult<()> {
+    let ours = serde::FORMAT as u32;
+    let newest = cache.record_serde_format(ours);
+    if newest == 0 || newest == ours {
+        return Ok(());
     }
+    let msg = CString::new(if newest > ours {
+        format!(
+            "shared cache {name:?} holds values in serde format {newest}, newer than this \
+             release reads (format {ours}); they will be treated as misses"
+        )
+    } else {
+        format!(
+            "shared cache {name:?} was written in serde format {newest}, older than this \
+             release writes (format {ours}); processes still on it will miss on these values"
+        )
+    })
+    .unwrap_or_default();
+    let category = py.get_type::<pyo3::exceptions::PyRuntimeWarning>();
+    PyErr::warn(py, &category, &msg, 1)
+}
+
+/// Hash of serialized key bytes: XXH64 with seed 0.
+///
+/// The hash decides where a key lives in the shared hash table, so every
+/// process must compute the same one: it comes from the key's serialized
+/// bytes (Python's `hash()` is randomized per process) through a fixed,
+/// specified algorithm that doesn't vary with the build or the CPU.
+pub(crate) fn hash_key(key_bytes: &[u8]) -> u64 {
+    xxh64(key_bytes, 0)
+}
+
+/// Hash of a tag as stored in `SlotHeader::tags`, where 0 marks an unused
+/// entry. Like `hash_key`, it must be the same in every process.
+fn tag_hash(tag: &str) -> u64 {
+    xxh64(tag.as_bytes(), 0).max(1)
+}
 
-    /// Deserialize a value from shared memory bytes.
-    fn deserialize_value(&self, py: Python, data: &[u8]) -> PyResult<Py<PyAny>> {
-        // Fast path
-        if let Some(obj) = serde::deserialize(py, data)? {
-            return Ok(obj);
+/// Validate a `tags_func` argument: None or a callable.
+pub(crate) fn check_tags_func(py: Python<'_>, tags_func: Option<&Py<PyAny>>) -> PyResult<()> {
+    match tags_func {
+        Some(tags_func) if !tags_func.bind(py).is_callable() => Err(
+            pyo3::exceptions::PyTypeError::new_err("tags_func must be callable"),
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Validate a list of tags (`_warp_tags=`, or what `tags_func` returned): an
+/// iterable of str, other than a single str. Duplicates are dropped.
+pub(crate) fn parse_tags(tags: &Bound<'_, PyAny>) -> PyResult<Vec<String>> {
+    if tags.is_instance_of::<PyString>() {
+        return Err(pyo3::exceptions::PyTypeError::new_err(
+            "tags must be a list of str, not a str",
+        ));
+    }
+    let mut parsed: Vec<String> = Vec::new();
+    for tag in tags.try_iter()? {
+        let tag = tag?;
+        let Ok(text) = tag.cast::<PyString>() else {
+            return Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                "tags must be str, got {}",
+                tag.get_type().name()?
+            )));
+        };
+        let text = text.to_str()?;
+        if !parsed.iter().any(|t| t == text) {
+            parsed.push(text.to_owned());
+        }
+    }
+    Ok(parsed)
+}
+
+/// `parse_tags(tags_func(args, kwargs, result))`, with `kwargs` a dict even
+/// when the call had none.
+pub(crate) fn call_tags_func<'py>(
+    py: Python<'py>,
+    tags_func: &Py<PyAny>,
+    args: &Bound<'py, PyTuple>,
+    kwargs: &Option<Bound<'py, PyDict>>,
+    result: &Bound<'py, PyAny>,
+) -> PyResult<Vec<String>> {
+    let kwargs = kwargs.clone().unwrap_or_else(|| PyDict::new(py));
+    parse_tags(&tags_func.bind(py).call1((args, kwargs, result))?)
+}
+
+/// Validate a `cache_version` argument: a non-empty str, or an int, which is
+/// used in its decimal form. It must fit in the cache header, so that two
+/// different versions are never stored alike.
+pub(crate) fn parse_cache_version(version: Option<&Bound<'_, PyAny>>) -> PyResult<Option<String>> {
+    let Some(version) = version else {
+        return Ok(None);
+    };
+    let text = if version.is_instance_of::<PyBool>() {
+        None
+    } else if let Ok(text) = version.cast::<PyString>() {
+        Some(text.to_str()?.to_owned())
+    } else if version.is_instance_of::<PyInt>() {
+        Some(version.str()?.to_str()?.to_owned())
+    } else {
+        None
+    };
+    match text {
+        None => Err(pyo3::exceptions::PyTypeError::new_err(format!(
+            "cache_version must be a str or an int, got {}",
+            version.get_type().name()?
+        ))),
+        Some(text) if text.is_empty() || text.len() > CACHE_VERSION_LEN => {
+            Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "cache_version must be 1 to {CACHE_VERSION_LEN} bytes long (got {})",
+                text.len()
+            )))
+        }
+        Some(text) => Ok(Some(text)),
+    }
+}
+
+/// Parse the `on_mismatch` argument of a handle, read-only if `readonly`.
+pub(crate) fn parse_on_mismatch(on_mismatch: &str, readonly: bool) -> PyResult<OnMismatch> {
+    let parsed = match on_mismatch {
+        "error" => OnMismatch::Error,
+        "recreate" => OnMismatch::Recreate,
+        "open_existing" => OnMismatch::OpenExisting,
+        other => {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "on_mismatch must be 'error', 'recreate' or 'open_existing' (got {other:?})"
+            )))
         }
-        // Fallback: pickle (skip TAG_PICKLE byte)
-        let payload = serde::pickle_payload(data);
-        let value = self.pickle_loads.bind(py).call1((payload,))?;
-        Ok(value.unbind())
+    };
+    // A read-only handle must never replace the cache it reads.
+    if readonly && parsed == OnMismatch::Recreate {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "on_mismatch='recreate' can't be combined with readonly=True",
+        ));
+    }
+    Ok(parsed)
+}
+
+/// Run `ShmCache::incr`, first checking the counter fits the size limits.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn incr(
+    cache: &ShmCache,
+    key_hash: u64,
+    key_bytes: &[u8],
+    max_key_size: usize,
+    max_value_size: usize,
+    amount: i64,
+    initial: i64,
+) -> PyResult<i64> {
+    // The widest a counter gets.
+    let value_len = serde::encode_i64(i64::MAX).len();
+    if let Some(kind) = oversize(key_bytes.len(), max_key_size, value_len, max_value_size) {
+        cache.record_oversize_skip(kind);
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "counter does not fit: key is {} bytes (max_key_size={max_key_size}), \
+             value is {value_len} bytes (max_value_size={max_value_size})",
+            key_bytes.len()
+        )));
+    }
+    cache
+        .incr(key_hash, key_bytes, amount, initial)
+        .map_err(lock_error)?
+        .map_err(|e| match e {
+            IncrError::NotAnInt => {
+                pyo3::exceptions::PyTypeError::new_err("cached value is not a 64-bit int")
+            }
+            IncrError::Overflow => {
+                pyo3::exceptions::PyOverflowError::new_err("counter would overflow a 64-bit int")
+            }
+        })
+}
+
+/// Which limit, if any, a key and value of these serialized lengths are over
+/// (the key's is checked first).
+fn oversize(
+    key_len: usize,
+    max_key_size: usize,
+    value_len: usize,
+    max_value_size: usize,
+) -> Option<Oversize> {
+    if key_len > max_key_size {
+        Some(Oversize::Key)
+    } else if value_len > max_value_size {
+        Some(Oversize::Value)
+    } else {
+        None
+    }
+}
+
+/// Run `ShmCache::compare_and_swap`, first checking the new value fits the
+/// size limits.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn compare_and_swap(
+    cache: &ShmCache,
+    key_hash: u64,
+    key_bytes: &[u8],
+    expected_bytes: Option<&[u8]>,
+    new_bytes: &[u8],
+    max_key_size: usize,
+    max_value_size: usize,
+) -> PyResult<bool> {
+    if let Some(kind) = oversize(
+        key_bytes.len(),
+        max_key_size,
+        new_bytes.len(),
+        max_value_size,
+    ) {
+        cache.record_oversize_skip(kind);
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "new value does not fit: key is {} bytes (max_key_size={max_key_size}), \
+             value is {} bytes (max_value_size={max_value_size})",
+            key_bytes.len(),
+            new_bytes.len()
+        )));
+    }
+    cache
+        .compare_and_swap(key_hash, key_bytes, expected_bytes, new_bytes)
+        .map_err(lock_error)
+}
+
+/// Negate a `decr()` amount, which fails only for the most negative `i64`.
+pub(crate) fn negate(amount: i64) -> PyResult<i64> {
+    amount
+        .checked_neg()
+        .ok_or_else(|| pyo3::exceptions::PyOverflowError::new_err("amount is out of range"))
+}
+
+/// Map a failure to open a shared cache to a Python exception: a parameter
+/// mismatch is a `ValueError`, anything else an `OSError`.
+pub(crate) fn open_error(e: io::Error) -> PyErr {
+    match e.kind() {
+        io::ErrorKind::InvalidInput => pyo3::exceptions::PyValueError::new_err(format!(
+            "{e}; pass on_mismatch='recreate' to replace it or 'open_existing' to use it as is"
+        )),
+        // A file in another layout version; the message says what to do.
+        io::ErrorKind::Unsupported => pyo3::exceptions::PyValueError::new_err(e.to_string()),
+        // Limits that add up to more than a region can hold.
+        io::ErrorKind::FileTooLarge => pyo3::exceptions::PyValueError::new_err(e.to_string()),
+        // A read-only open of a cache nobody has created.
+        io::ErrorKind::NotFound => pyo3::exceptions::PyFileNotFoundError::new_err(e.to_string()),
+        _ => pyo3::exceptions::PyOSError::new_err(format!("Failed to create shared cache: {e}")),
+    }
+}
+
+/// Values at least this long are copied in or out of a cache without the GIL.
+const DETACH_LEN: usize = 64 * 1024;
+
+/// Run `f` on `cache`, releasing the GIL if it may take a while: another
+/// process holds the lock, or up to `len` value bytes may be copied (at least
+/// `DETACH_LEN`). Other Python threads then keep running while this one waits.
+///
+/// Short uncontended operations keep the GIL: releasing it costs more than
+/// they do, and taking it back can mean waiting out another thread's switch
+/// interval. A writer that grabs the lock just after the check is waited on
+/// with the GIL held, which only matters if it then stalls.
+pub(crate) fn without_gil<T: Send>(
+    py: Python<'_>,
+    cache: &ShmCache,
+    len: usize,
+    f: impl FnOnce(&ShmCache) -> T + Send,
+) -> T {
+    if len >= DETACH_LEN || cache.is_busy() {
+        py.detach(|| f(cache))
+    } else {
+        f(cache)
+    }
+}
+
+/// Run `f` without the GIL if it works through at least `DETACH_LEN` bytes:
+/// compressing, decompressing or copying a value that no Python object can
+/// see yet. The GIL is back before `f`'s result is used.
+fn detach_if_long<T: Send>(py: Python<'_>, len: usize, f: impl FnOnce() -> T + Send) -> T {
+    if len >= DETACH_LEN {
+        py.detach(f)
+    } else {
+        f()
+    }
+}
+
+/// `cache`, registered to have its process-local locks released in the child
+/// of a fork (see `fork`).
+pub(crate) fn fork_safe(py: Python<'_>, cache: ShmCache) -> PyResult<Arc<ShmCache>> {
+    let cache = Arc::new(cache);
+    fork::register(py, Arc::downgrade(&cache) as Weak<dyn AfterFork>)?;
+    Ok(cache)
+}
+
+/// Parse the `lock_timeout` argument (seconds, or None to wait indefinitely).
+pub(crate) fn parse_lock_timeout(lock_timeout: Option<f64>) -> PyResult<Option<Duration>> {
+    match lock_timeout {
+        None => Ok(None),
+        Some(t) if t.is_finite() && t > 0.0 => Ok(Some(Duration::from_secs_f64(t))),
+        Some(t) => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "lock_timeout must be a positive number of seconds (got {t})"
+        ))),
     }
 }
 
-/// Derive a deterministic shared memory name from the function's module and qualname.
-fn derive_shm_name(py: Python<'_>, fn_obj: &Py<PyAny>) -> PyResult<String> {
+/// A write that gave up waiting for the shared lock (`lock_timeout`). The
+/// message names the cache.
+pub(crate) fn lock_error(e: io::Error) -> PyErr {
+    pyo3::exceptions::PyRuntimeError::new_err(e.to_string())
+}
+
+/// A stored value that claims to be compressed but can't be decompressed.
+fn decompress_error(e: io::Error) -> PyErr {
+    pyo3::exceptions::PyValueError::new_err(format!("Failed to decompress cached value: {e}"))
+}
+
+/// Remove shared cache files that no process has open and that have not been
+/// written for `max_age_seconds`. Returns the names of the removed caches (with
+/// `dry_run`, the ones that would be removed).
+#[pyfunction]
+#[pyo3(signature = (max_age_seconds, dry_run=false, shm_dir=None))]
+pub fn gc_shared(
+    max_age_seconds: f64,
+    dry_run: bool,
+    shm_dir: Option<PathBuf>,
+) -> PyResult<Vec<String>> {
+    if !max_age_seconds.is_finite() || max_age_seconds < 0.0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "max_age_seconds must be a non-negative number (got {max_age_seconds})"
+        )));
+    }
+    shm::remove_orphans(
+        shm_dir.as_deref(),
+        Duration::from_secs_f64(max_age_seconds),
+        dry_run,
+    )
+    .map_err(|e| {
+        pyo3::exceptions::PyOSError::new_err(format!("Failed to collect shared cache files: {e}"))
+    })
+}
+
+/// Describe every shared cache in the cache directory: one dict per cache,
+/// sorted by name, read from the file headers without taking any lock.
+///
+/// Valid caches report `name`, `strategy`, `capacity`, `current_size`, `hits`,
+/// `misses`, `oversize_skips`, `ttl` (seconds, or None) and `file_size`, with
+/// `invalid` False. Files that can't be read as a cache are listed with their
+/// `name` and `file_size`, `invalid` True and a `reason`.
+#[pyfunction]
+#[pyo3(signature = (shm_dir=None))]
+pub fn list_shared(py: Python<'_>, shm_dir: Option<PathBuf>) -> PyResult<Vec<Bound<'_, PyDict>>> {
+    let listings = shm::list_regions(shm_dir.as_deref()).map_err(|e| {
+        pyo3::exceptions::PyOSError::new_err(format!("Failed to list shared caches: {e}"))
+    })?;
+    listings
+        .into_iter()
+        .map(|listing| {
+            let dict = PyDict::new(py);
+            dict.set_item("name", listing.name)?;
+            dict.set_item("file_size", listing.file_size)?;
+            match listing.stats {
+                Ok(stats) => {
+                    dict.set_item("invalid", false)?;
+                    dict.set_item("strategy", "sieve")?;
+                    dict.set_item("capacity", stats.capacity)?;
+                    dict.set_item("current_size", stats.current_size)?;
+                    dict.set_item("hits", stats.hits)?;
+                    dict.set_item("misses", stats.misses)?;
+                    dict.set_item("oversize_skips", stats.oversize_skips)?;
+                    let ttl = (stats.ttl_nanos > 0).then(|| stats.ttl_nanos as f64 / 1e9);
+                    dict.set_item("ttl", ttl)?;
+                    dict.set_item("cache_version", stats.cache_version)?;
+                }
+                Err(reason) => {
+                    dict.set_item("invalid", true)?;
+                    dict.set_item("reason", reason)?;
+                }
+            }
+            Ok(dict)
+        })
+        .collect()
+}
+
+/// Name recorded in the cache header for a fallback serializer: `"pickle"`
+/// for pickle's own `dumps`, otherwise the `module.qualname` of `dumps`.
+fn serializer_name(pickle: &Bound<'_, PyModule>, dumps: &Bound<'_, PyAny>) -> PyResult<String> {
+    if dumps.is(&pickle.getattr("dumps")?) {
+        return Ok("pickle".to_string());
+    }
+    let module = dumps
+        .getattr("__module__")
+        .and_then(|m| m.extract::<String>())
+        .unwrap_or_else(|_| "unknown".to_string());
+    let qualname = dumps
+        .getattr("__qualname__")
+        .or_else(|_| dumps.get_type().getattr("__qualname__"))
+        .and_then(|q| q.extract::<String>())
+        .unwrap_or_else(|_| "unknown".to_string());
+    Ok(format!("{module}.{qualname}"))
+}
+
+/// Derive a deterministic shared memory name from the function's module and
+/// qualname, and its `cache_version`: a new version gets a new region, and the
+/// old one is left for `gc_shared`.
+fn derive_shm_name(
+    py: Python<'_>,
+    fn_obj: &Py<PyAny>,
+    cache_version: Option<&str>,
+) -> PyResult<String> {
     let bound = fn_obj.bind(py);
     let module = bound
         .getattr("__module__")
@@ -319,17 +1996,29 @@ fn derive_shm_name(py: Python<'_>, fn_obj: &Py<PyAny>) -> PyResult<String> {
         .and_then(|q| q.extract::<String>())
         .unwrap_or_else(|_| "unknown".to_string());
 
-    // Hash for uniqueness (fixed-seed ahash for cross-process determinism)
-    let state = RandomState::with_seeds(
-        0x517cc1b727220a95,
-        0x6c62272e07bb0142,
-        0x0f1e2d3c4b5a6978,
-        0xa1b2c3d4e5f60718,
-    );
-    let mut hasher = state.build_hasher();
-    hasher.write(module.as_bytes());
-    hasher.write(qualname.as_bytes());
-    let hash = hasher.finish();
+    // Hash for uniqueness (XXH64, so every process derives the same name)
+    let mut hasher = Xxh64::new(0);
+    hasher.update(module.as_bytes());
+    hasher.update(qualname.as_bytes());
+    // Only with a version, so names without one stay as they were
+    if let Some(version) = cache_version {
+        hasher.update(b"\0");
+        hasher.update(version.as_bytes());
+    }
+    let hash = hasher.digest();
 
     Ok(format!("warp_cache_{module}_{qualname}_{hash:016x}"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_hash_is_plain_xxh64() {
+        // Published XXH64 test vectors (seed 0): the hash must never change
+        // with the build, or processes stop finding each other's keys.
+        assert_eq!(hash_key(b""), 0xef46_db37_51d8_e999);
+        assert_eq!(hash_key(b"abc"), 0x44bc_2cf5_ad77_0999);
+    }
+}
@@ -0,0 +1,137 @@
+/// Values that span several slots.
+///
+/// An entry's head slot holds its key and as much of its value as fits; with
+/// `chunk_size` set, the rest goes into `SLOT_CONTINUATION` slots linked
+/// through `SlotHeader::chain`, each holding a full data area of value bytes.
+/// Without it every slot is sized for the largest key and value, so no entry
+/// ever needs more than its head and the chain is never followed.
+use super::layout::{SlotHeader, SLOT_HEADER_SIZE};
+
+/// Key and value bytes one slot can hold.
+#[inline]
+pub fn data_size(slot_size: u32) -> usize {
+    slot_size as usize - SLOT_HEADER_SIZE
+}
+
+/// Number of slots (head included) an entry with this key and value needs.
+#[inline]
+pub fn slots_needed(slot_size: u32, key_len: usize, value_len: usize) -> usize {
+    let data = data_size(slot_size);
+    let total = key_len + value_len;
+    if total <= data || data == 0 {
+        1
+    } else {
+        1 + (total - data).div_ceil(data)
+    }
+}
+
+/// Copy out the value of the entry whose head slot is at `head`, following
+/// its chain.
+///
+/// Every chain index is bounds-checked against `capacity`, so a torn
+/// optimistic read yields `None` (or garbage the seqlock rejects) rather than
+/// a read outside the slab.
+///
+/// # Safety
+/// `slab_base` must point to a slab of `capacity` slots of `slot_size` bytes
+/// and `head` to one of them. `key_len + value_len` must be bounded by the
+/// caller (a corrupt length would otherwise size a huge allocation).
+pub unsafe fn read_value(
+    slab_base: *const u8,
+    slot_size: u32,
+    capacity: u32,
+    head: *const u8,
+    key_len: usize,
+    value_len: usize,
+) -> Option<Vec<u8>> {
+    let data = data_size(slot_size);
+    if key_len > data {
+        return None;
+    }
+    let first = value_len.min(data - key_len);
+    let mut value = Vec::with_capacity(value_len);
+    value.extend_from_slice(std::slice::from_raw_parts(
+        head.add(SLOT_HEADER_SIZE + key_len),
+        first,
+    ));
+
+    let mut next = (*(head as *const SlotHeader)).chain;
+    while value.len() < value_len {
+        if next < 0 || next as u32 >= capacity || data == 0 {
+            return None;
+        }
+        let slot_ptr = slab_base.add(next as usize * slot_size as usize);
+        let len = (value_len - value.len()).min(data);
+        value.extend_from_slice(std::slice::from_raw_parts(
+            slot_ptr.add(SLOT_HEADER_SIZE),
+            len,
+        ));
+        next = (*(slot_ptr as *const SlotHeader)).chain;
+    }
+    Some(value)
+}
+
+/// Write `value` into the entry whose head slot is at `head`, whose chain
+/// must already have `slots_needed(slot_size, key_len, value.len())` slots.
+///
+/// # Safety
+/// Caller must hold the write lock; `slab_base` and `head` as for `read_value`.
+pub unsafe fn write_value(
+    slab_base: *mut u8,
+    slot_size: u32,
+    head: *mut u8,
+    key_len: usize,
+    value: &[u8],
+) {
+    let data = data_size(slot_size);
+    let first = value.len().min(data - key_len);
+    std::ptr::copy_nonoverlapping(value.as_ptr(), head.add(SLOT_HEADER_SIZE + key_len), first);
+
+    let mut written = first;
+    let mut next = (*(head as *const SlotHeader)).chain;
+    while written < value.len() {
+        let slot_ptr = slab_base.add(next as usize * slot_size as usize);
+        let len = (value.len() - written).min(data);
+        std::ptr::copy_nonoverlapping(
+            value.as_ptr().add(written),
+            slot_ptr.add(SLOT_HEADER_SIZE),
+            len,
+        );
+        written += len;
+        next = (*(slot_ptr as *const SlotHeader)).chain;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shm::layout::SLOT_NONE;
+
+    #[test]
+    fn value_round_trips_across_a_chain() {
+        // 3 slots with 16 data bytes each: key (4) + 12 value bytes in the
+        // head, then 16 + 2 in two continuations.
+        let slot_size = (SLOT_HEADER_SIZE + 16) as u32;
+        assert_eq!(slots_needed(slot_size, 4, 12), 1);
+        assert_eq!(slots_needed(slot_size, 4, 30), 3);
+
+        let mut slab = vec![0u8; 3 * slot_size as usize];
+        let base = slab.as_mut_ptr();
+        unsafe {
+            let link = |idx: usize, next: i32| {
+                (*(base.add(idx * slot_size as usize) as *mut SlotHeader)).chain = next;
+            };
+            link(0, 2);
+            link(2, 1);
+            link(1, SLOT_NONE);
+
+            let value: Vec<u8> = (0..30).collect();
+            write_value(base, slot_size, base, 4, &value);
+            assert_eq!(read_value(base, slot_size, 3, base, 4, 30), Some(value));
+
+            // A chain pointing outside the slab is rejected, not followed.
+            link(2, 7);
+            assert_eq!(read_value(base, slot_size, 3, base, 4, 30), None);
+        }
+    }
+}
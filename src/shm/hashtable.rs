@@ -54,10 +54,16 @@ pub unsafe fn ht_lookup(
 }
 
 /// Insert a mapping from `key_hash` → `slot_index` into the hash table.
+/// Returns false, leaving the table unchanged, if every bucket is taken.
 ///
 /// # Safety
 /// Same requirements as `ht_lookup`.
-pub unsafe fn ht_insert(ht_base: *mut u8, ht_capacity: u32, key_hash: u64, slot_index: i32) {
+pub unsafe fn ht_insert(
+    ht_base: *mut u8,
+    ht_capacity: u32,
+    key_hash: u64,
+    slot_index: i32,
+) -> bool {
     let mask = ht_capacity.wrapping_sub(1);
     let mut idx = (key_hash as u32) & mask;
 
@@ -67,14 +73,14 @@ pub unsafe fn ht_insert(ht_base: *mut u8, ht_capacity: u32, key_hash: u64, slot_
         if bucket.slot_index == BUCKET_EMPTY {
             bucket.hash = key_hash;
             bucket.slot_index = slot_index;
-            return;
+            return true;
         }
 
         idx = (idx + 1) & mask;
     }
 
-    // Table full — should never happen because we size at 2× capacity
-    debug_assert!(false, "hash table is full");
+    // Table full: sized at 2× capacity, so only a corrupted table gets here
+    false
 }
 
 /// Remove the entry matching `key_hash` + `key_bytes` from the hash table.
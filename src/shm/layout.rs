@@ -3,17 +3,68 @@
 //! All structs use fixed-size fields and explicit padding so the
 //! layout is identical across compilations and processes.
 
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicU32, AtomicU64};
 
 /// Magic bytes at the start of the header to validate the mapping.
 pub const MAGIC: [u8; 8] = *b"FCACHE01";
 
 /// Layout version — bumped when the format changes.
-/// v1 = pthread_rwlock, v2 = seqlock, v3 = SIEVE eviction.
-pub const VERSION: u32 = 3;
+/// v1 = pthread_rwlock, v2 = seqlock, v3 = SIEVE eviction,
+/// v4 = expired_reclaimed counter, v5 = evictions/expirations counters,
+/// v6 = bytes_used/value_bytes_used accounting, v7 = per-slot ttl_nanos,
+/// v8 = stale-while-revalidate (stale_ttl_nanos, per-slot refresh_claimed),
+/// v9 = sliding_ttl flag, v10 = durable flag and pending_slot journal,
+/// v11 = value_codec, v12 = serializer id, v13 = chunk_size and slot chains,
+/// v14 = overflow arena, v15 = XXH64 key hashes, v16 = boot_id,
+/// v17 = lock owner PID and repair flag (in the lock file), v18 = lock_timeouts,
+/// v19 = per-slot checksums and corruption_detected, v20 = oversize skips
+/// split into key and value counters (header grown to 264 bytes),
+/// v21 = insert_failures (header grown to 272 bytes), v22 = fresh_start
+/// (header grown to 280 bytes), v23 = cache_version (header grown to 344 bytes),
+/// v24 = per-slot tag hashes (slot header grown to 104 bytes), v25 = list
+/// fast path in `serde` (a value an older release would hand to pickle),
+/// v26 = dict fast path, v27 = set and frozenset fast path, v28 = ints
+/// outside i64 on the fast path, v29 = date, time, datetime and timedelta,
+/// v30 = decimal.Decimal, v31 = uuid.UUID, v32 = numpy arrays,
+/// v33 = namedtuples and frozen dataclasses, v34 = enum members, v35 = complex,
+/// v36 = tuples over 255 elements, v37 = 2-byte small ints and varint
+/// str/bytes lengths, v38 = serde format byte on every fast-path payload and
+/// `serde_format` in the header. Changes to the serde format itself bump that
+/// byte (`serde::FORMAT`) from here on, not the layout. v39 = corrupt_values
+/// (header grown to 352 bytes), v40 = value size histogram (header grown to
+/// 488 bytes), v41 = creator and last writer pids and times (header grown to
+/// 512 bytes), v42 = skipped_fast (header grown to 520 bytes).
+pub const VERSION: u32 = 42;
 
 /// Size of the fixed header at the start of the region.
-pub const HEADER_SIZE: usize = 256;
+pub const HEADER_SIZE: usize = 520;
+
+/// `Header::value_codec` values. The shared backend only records and compares
+/// the id; compressing is done by the layer above (`crate::compress`).
+pub const VALUE_CODEC_NONE: u32 = 0;
+pub const VALUE_CODEC_LZ4: u32 = 1;
+pub const VALUE_CODEC_ZSTD: u32 = 2;
+
+/// Room for the name of the serializer values fall back to (`Header::serializer`);
+/// longer names are truncated.
+pub const SERIALIZER_ID_LEN: usize = 48;
+
+/// Room for the caller's `cache_version` (`Header::cache_version`); the
+/// Python layer rejects longer ones, so two versions never share an id.
+pub const CACHE_VERSION_LEN: usize = 64;
+
+/// Buckets in `Header::value_sizes`: 15 doubling ones reaching at least 4×
+/// the largest storable value, then one for everything bigger.
+pub const VALUE_SIZE_BUCKETS: usize = 16;
+
+/// Tags an entry can carry (`SlotHeader::tags`); the Python layer rejects
+/// more.
+pub const MAX_TAGS: usize = 4;
+
+/// `SlotHeader::occupied` of a slot holding the rest of a chained entry's
+/// value. Such slots are reachable only through their entry's `chain`: they
+/// are in neither the hash table nor the eviction list.
+pub const SLOT_CONTINUATION: u32 = 2;
 
 /// Sentinel value meaning "no slot" in prev/next linked-list pointers.
 pub const SLOT_NONE: i32 = -1;
@@ -24,20 +75,22 @@ pub const BUCKET_EMPTY: i32 = -1;
 /// Header lives at offset 0 of the mmap region.
 ///
 /// Fields are ordered u64-first to avoid implicit alignment padding
-/// in `#[repr(C)]`.
+/// in `#[repr(C)]`. Fields added after v3 are carved out of the trailing
+/// padding instead, 8-byte aligned, so `version` and every older field keep
+/// their offsets and an old file is rejected by its version, never misread.
 #[repr(C)]
 #[derive(Debug)]
 pub struct Header {
     // 8-byte aligned group
-    pub magic: [u8; 8],      // 0..8
-    pub ttl_nanos: u64,      // 8..16   (0 = no TTL)
-    pub hits: u64,           // 16..24
-    pub misses: u64,         // 24..32
-    pub oversize_skips: u64, // 32..40
+    pub magic: [u8; 8],          // 0..8
+    pub ttl_nanos: u64,          // 8..16   (0 = no TTL)
+    pub hits: u64,               // 16..24
+    pub misses: u64,             // 24..32
+    pub oversize_key_skips: u64, // 32..40 (`oversize_skips` before v20)
 
     // 4-byte aligned group
     pub version: u32,        // 40..44
-    pub strategy: u32,       // 44..48  (0=LRU, 1=MRU, 2=FIFO, 3=LFU)
+    pub strategy: u32,       // 44..48  (always 0 = SIEVE)
     pub capacity: u32,       // 48..52  (max_size)
     pub ht_capacity: u32,    // 52..56  (hash-table bucket count)
     pub slot_size: u32,      // 56..60
@@ -49,8 +102,74 @@ pub struct Header {
     pub free_head: i32,      // 80..84
     pub sieve_hand: i32,     // 84..88  (SIEVE eviction hand position)
 
-    // Explicit padding to 256 bytes: 256 - 88 = 168
-    pub _pad: [u8; 168],
+    // Appended 8-byte aligned group (v4+)
+    pub expired_reclaimed: u64, // 88..96  (expired slots reused by insert)
+    pub evictions: u64,         // 96..104 (live entries evicted by SIEVE)
+    pub expirations: u64,       // 104..112 (entries removed because their TTL lapsed)
+    pub bytes_used: u64,        // 112..120 (sum of key_len + value_len, occupied slots)
+    pub value_bytes_used: u64,  // 120..128 (sum of value_len, occupied slots)
+    pub stale_ttl_nanos: u64,   // 128..136 (grace period past TTL serving stale, 0 = off)
+    pub sliding_ttl: u64,       // 136..144 (1 = hits reset created_at_nanos)
+    pub durable: u64,           // 144..152 (1 = on-disk file, wall-clock timestamps)
+
+    // Appended 4-byte aligned group (v10+)
+    pub pending_slot: i32, // 152..156 (slot being written by insert, SLOT_NONE = idle)
+    pub value_codec: u32,  // 156..160 (VALUE_CODEC_*, how values are compressed; v11+)
+
+    // Appended byte group (v12+)
+    pub serializer: [u8; SERIALIZER_ID_LEN], // 160..208 (fallback serializer name, NUL-padded)
+
+    // Appended 4-byte aligned group (v13+)
+    pub chunk_size: u32, // 208..212 (value bytes per slot when entries chain, 0 = one slot each)
+
+    // Appended 4-byte aligned group (v14+)
+    pub overflow_count: u32, // 212..216 (chunks in the overflow arena, 0 = none)
+    pub overflow_size: u32,  // 216..220 (bytes per overflow chunk, a multiple of 8)
+    pub overflow_free: i32,  // 220..224 (overflow chunk free list, SLOT_NONE = empty)
+    pub overflow_used: u32,  // 224..228 (overflow chunks holding a value)
+
+    // Appended 4-byte aligned group (v19+), in what was padding before `boot_id`
+    pub checksums: u32, // 228..232 (1 = slots carry a checksum verified on every hit)
+
+    // Appended 8-byte aligned group (v16+)
+    pub boot_id: u64, // 232..240 (boot the monotonic timestamps are from, 0 = unknown)
+
+    // Appended 8-byte aligned group (v18+)
+    pub lock_timeouts: u64, // 240..248 (lookups given up as misses after lock_timeout)
+
+    // Appended 8-byte aligned group (v19+)
+    pub corruption_detected: u64, // 248..256 (hits whose checksum didn't match)
+
+    // Appended 8-byte aligned group (v20+), past the original 256 bytes
+    pub oversize_value_skips: u64, // 256..264 (values too big to store)
+
+    // Appended 8-byte aligned group (v21+)
+    pub insert_failures: u64, // 264..272 (inserts dropped because the hash table was full)
+
+    // Appended 4-byte aligned group (v22+)
+    pub fresh_start: u32, // 272..276 (slots from here on are free and zeroed, on no list)
+
+    // Appended 4-byte aligned group (v38+), in what was padding before `cache_version`
+    pub serde_format: u32, // 276..280 (newest serde format any writer recorded, 0 = none)
+
+    // Appended byte group (v23+)
+    pub cache_version: [u8; CACHE_VERSION_LEN], // 280..344 (caller's version, NUL-padded, empty = none)
+
+    // Appended 8-byte aligned group (v39+)
+    pub corrupt_values: u64, // 344..352 (hits whose bytes the caller couldn't deserialize)
+
+    // Appended 8-byte aligned group (v40+)
+    pub value_sizes: [u64; VALUE_SIZE_BUCKETS], // 352..480 (values offered for storage, by size)
+    pub largest_value: u64, // 480..488 (largest serialized value offered for storage)
+
+    // Appended 8-byte aligned group (v41+), for diagnostics only
+    pub creator_pid: u32,          // 488..492 (process that created the file)
+    pub last_writer_pid: u32,      // 492..496 (process that last took the write lock)
+    pub last_write_nanos: u64,     // 496..504 (wall clock ns since the epoch it did so)
+    pub created_at_wallclock: u64, // 504..512 (wall clock ns since the epoch of creation)
+
+    // Appended 8-byte aligned group (v42+)
+    pub skipped_fast: u64, // 512..520 (misses not stored for taking under min_compute_time)
 }
 
 // Compile-time assertion that Header is exactly HEADER_SIZE bytes.
@@ -72,7 +191,7 @@ impl Bucket {
 const _: () = assert!(std::mem::size_of::<Bucket>() == 16);
 
 /// Per-slot header inside the slab arena. Followed by key_bytes then value_bytes.
-pub const SLOT_HEADER_SIZE: usize = 64;
+pub const SLOT_HEADER_SIZE: usize = 104;
 
 /// Fields ordered u64-first to avoid implicit alignment padding.
 #[repr(C)]
@@ -80,30 +199,64 @@ pub const SLOT_HEADER_SIZE: usize = 64;
 pub struct SlotHeader {
     // 8-byte aligned group
     pub key_hash: u64,         // 0..8
-    pub created_at_nanos: u64, // 8..16  (monotonic nanos)
+    pub created_at_nanos: u64, // 8..16  (monotonic nanos; wall-clock when durable)
     // SIEVE bit: 0=unvisited, 1=visited. Atomic because the lock-free reader sets it
     // (no write lock) while a writer concurrently reuses the slot — a data race on a
     // plain field (#37). AtomicU64 is layout-identical to u64 (same size/align), so the
     // cross-process #[repr(C)] layout and zero-init (AtomicU64(0) == unvisited) are unchanged.
     pub visited: AtomicU64, // 16..24
-    pub unique_id: u64,     // 24..32 (unused, kept for layout stability)
 
     // 4-byte aligned group
-    pub occupied: u32,  // 32..36 (1 = occupied, 0 = free)
-    pub key_len: u32,   // 36..40
+    pub chain: i32, // 24..28 (next slot holding this entry's value, v13+; SLOT_NONE = last)
+    pub overflow: i32, // 28..32 (overflow chunk holding the value, v14+; SLOT_NONE = inline)
+    pub occupied: u32, // 32..36 (1 = entry, SLOT_CONTINUATION = chained value, 0 = free)
+    pub key_len: u32, // 36..40
     pub value_len: u32, // 40..44
-    pub prev: i32,      // 44..48 (eviction list previous)
-    pub next: i32,      // 48..52 (eviction list next)
+    pub prev: i32,  // 44..48 (eviction list previous)
+    pub next: i32,  // 48..52 (eviction list next)
+    // Stale-while-revalidate: 1 once a caller has claimed the recompute of this
+    // stale entry. Atomic because lock-free readers check it (see `visited`).
+    pub refresh_claimed: AtomicU32, // 52..56
 
-    // Explicit padding to 64 bytes: 64 - 52 = 12
-    pub _pad: [u8; 12],
+    // Appended 8-byte aligned group (v7+), carved out of the trailing padding
+    pub ttl_nanos: u64, // 56..64 (per-entry TTL, 0 = use the header TTL)
+
+    // Appended 4-byte aligned group (v19+), growing the header past 64 bytes
+    pub checksum: u32, // 64..68 (XXH32 of key + value when `Header::checksums`, else 0)
+    pub _pad: u32,     // 68..72
+
+    // Appended 8-byte aligned group (v24+)
+    pub tags: [u64; MAX_TAGS], // 72..104 (hashes of the entry's tags, 0 = unused)
 }
 
 const _: () = assert!(std::mem::size_of::<SlotHeader>() == SLOT_HEADER_SIZE);
 
 /// Compute the total size of the mmap region.
-pub fn region_size(capacity: u32, ht_capacity: u32, slot_size: u32) -> usize {
-    HEADER_SIZE + (ht_capacity as usize * Bucket::SIZE) + (capacity as usize * slot_size as usize)
+pub fn region_size(
+    capacity: u32,
+    ht_capacity: u32,
+    slot_size: u32,
+    overflow_count: u32,
+    overflow_size: u32,
+) -> usize {
+    overflow_offset(capacity, ht_capacity, slot_size)
+        + overflow_count as usize * overflow_size as usize
+}
+
+/// `region_size`, or `None` if it doesn't fit in an `isize` (only possible
+/// on 32-bit targets, where `region_size` would wrap).
+pub fn checked_region_size(
+    capacity: u32,
+    ht_capacity: u32,
+    slot_size: u32,
+    overflow_count: u32,
+    overflow_size: u32,
+) -> Option<usize> {
+    let size = HEADER_SIZE as u128
+        + ht_capacity as u128 * Bucket::SIZE as u128
+        + capacity as u128 * slot_size as u128
+        + overflow_count as u128 * overflow_size as u128;
+    isize::try_from(size).ok().map(|size| size as usize)
 }
 
 /// Offset of the hash-table array from the start of the region.
@@ -116,6 +269,11 @@ pub fn slab_offset(ht_capacity: u32) -> usize {
     HEADER_SIZE + (ht_capacity as usize * Bucket::SIZE)
 }
 
+/// Offset of the overflow arena, which follows the slab.
+pub fn overflow_offset(capacity: u32, ht_capacity: u32, slot_size: u32) -> usize {
+    slab_offset(ht_capacity) + capacity as usize * slot_size as usize
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -123,8 +281,67 @@ mod tests {
     #[test]
     fn region_size_calculation() {
         // capacity=4, ht_capacity=8 (2×), slot_size=128
-        // 256 (header) + 8×16 (buckets) + 4×128 (slots) = 256 + 128 + 512 = 896
-        assert_eq!(region_size(4, 8, 128), 896);
+        // 520 (header) + 8×16 (buckets) + 4×128 (slots) = 520 + 128 + 512 = 1160
+        assert_eq!(region_size(4, 8, 128, 0, 0), 1160);
+        // plus 2 overflow chunks of 1024 bytes after the slab
+        assert_eq!(overflow_offset(4, 8, 128), 1160);
+        assert_eq!(region_size(4, 8, 128, 2, 1024), 1160 + 2048);
+        assert_eq!(checked_region_size(4, 8, 128, 2, 1024), Some(1160 + 2048));
+        let max = u32::MAX;
+        assert_eq!(checked_region_size(max, max, max, max, max), None);
+    }
+
+    #[test]
+    fn header_field_offsets() {
+        // `version` must never move (old files are rejected by it), and ShmCache
+        // updates the counters through hard-coded atomic offsets (shm/mod.rs).
+        assert_eq!(std::mem::offset_of!(Header, version), 40);
+        assert_eq!(std::mem::offset_of!(Header, hits), 16);
+        assert_eq!(std::mem::offset_of!(Header, misses), 24);
+        assert_eq!(std::mem::offset_of!(Header, oversize_key_skips), 32);
+        assert_eq!(std::mem::offset_of!(Header, expired_reclaimed), 88);
+        assert_eq!(std::mem::offset_of!(Header, evictions), 96);
+        assert_eq!(std::mem::offset_of!(Header, expirations), 104);
+        assert_eq!(std::mem::offset_of!(Header, bytes_used), 112);
+        assert_eq!(std::mem::offset_of!(Header, value_bytes_used), 120);
+        assert_eq!(std::mem::offset_of!(Header, stale_ttl_nanos), 128);
+        assert_eq!(std::mem::offset_of!(Header, sliding_ttl), 136);
+        assert_eq!(std::mem::offset_of!(Header, durable), 144);
+        assert_eq!(std::mem::offset_of!(Header, pending_slot), 152);
+        assert_eq!(std::mem::offset_of!(Header, value_codec), 156);
+        assert_eq!(std::mem::offset_of!(Header, serializer), 160);
+        assert_eq!(std::mem::offset_of!(Header, chunk_size), 208);
+        assert_eq!(std::mem::offset_of!(Header, overflow_count), 212);
+        assert_eq!(std::mem::offset_of!(Header, overflow_used), 224);
+        assert_eq!(std::mem::offset_of!(Header, boot_id), 232);
+        assert_eq!(std::mem::offset_of!(Header, lock_timeouts), 240);
+        assert_eq!(std::mem::offset_of!(Header, checksums), 228);
+        assert_eq!(std::mem::offset_of!(Header, corruption_detected), 248);
+        assert_eq!(std::mem::offset_of!(Header, oversize_value_skips), 256);
+        assert_eq!(std::mem::offset_of!(Header, insert_failures), 264);
+        assert_eq!(std::mem::offset_of!(Header, fresh_start), 272);
+        assert_eq!(std::mem::offset_of!(Header, serde_format), 276);
+        assert_eq!(std::mem::offset_of!(Header, cache_version), 280);
+        assert_eq!(std::mem::offset_of!(Header, corrupt_values), 344);
+        assert_eq!(std::mem::offset_of!(Header, value_sizes), 352);
+        assert_eq!(std::mem::offset_of!(Header, largest_value), 480);
+        assert_eq!(std::mem::offset_of!(Header, creator_pid), 488);
+        assert_eq!(std::mem::offset_of!(Header, last_writer_pid), 492);
+        assert_eq!(std::mem::offset_of!(Header, last_write_nanos), 496);
+        assert_eq!(std::mem::offset_of!(Header, created_at_wallclock), 504);
+        assert_eq!(std::mem::offset_of!(Header, skipped_fast), 512);
+    }
+
+    #[test]
+    fn slot_header_field_offsets() {
+        assert_eq!(std::mem::offset_of!(SlotHeader, created_at_nanos), 8);
+        assert_eq!(std::mem::offset_of!(SlotHeader, chain), 24);
+        assert_eq!(std::mem::offset_of!(SlotHeader, overflow), 28);
+        assert_eq!(std::mem::offset_of!(SlotHeader, next), 48);
+        assert_eq!(std::mem::offset_of!(SlotHeader, refresh_claimed), 52);
+        assert_eq!(std::mem::offset_of!(SlotHeader, ttl_nanos), 56);
+        assert_eq!(std::mem::offset_of!(SlotHeader, checksum), 64);
+        assert_eq!(std::mem::offset_of!(SlotHeader, tags), 72);
     }
 
     #[test]
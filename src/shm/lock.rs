@@ -1,20 +1,56 @@
 /// Seqlock for shared memory: optimistic lock-free reads + TTAS spinlock for writers.
 ///
 /// Layout in shared memory (64 bytes, one cache line):
-///   [seq_counter: u64][write_lock: u32][padding to 64]
+///   [seq_counter: u64][owner: u64][generation: u64][repair: u32][padding to 64]
+///
+/// `owner` is 0 while the write lock is free and the holder's `owner_id` while
+/// it is held, so a waiter that has spun for long can tell a holder that died
+/// mid-write (SIGKILL) from a slow one, and take the lock over. `repair` is
+/// then set until a `ShmCache` write section rebuilds what the dead writer may
+/// have left half-updated.
+///
+/// `generation` counts data-file replacements (resizes). The lock file itself is
+/// never replaced, so every process can watch it to notice a new data file.
 ///
 /// Readers check seq before/after reading — no kernel calls, ~10-20ns.
-/// Writers acquire a TTAS spinlock then bump seq odd→even.
+/// Writers acquire a TTAS spinlock then bump seq odd→even. Waiting on a held
+/// lock spins briefly, then yields, then sleeps with exponential backoff, and
+/// can be bounded by a timeout.
 use std::io;
 use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 /// Size reserved for the lock in the mmap region — one cache line.
 pub const LOCK_SIZE: usize = 64;
 
+/// How long a waiter spins on a held lock between checks that its holder is
+/// still alive. Write sections take microseconds; the long ones (resize,
+/// `sync`) belong to live processes and are never taken over.
+const OWNER_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Busy spins before a waiter starts yielding its core.
+const SPIN_LIMIT: u32 = 1024;
+/// `yield_now` calls after the spins before a waiter starts sleeping.
+const YIELD_LIMIT: u32 = 64;
+/// Backoff sleeps start here and double up to `MAX_SLEEP`.
+const MIN_SLEEP: Duration = Duration::from_micros(10);
+const MAX_SLEEP: Duration = Duration::from_millis(1);
+
+/// Why `read_begin` stopped waiting for the writer to finish.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReadBlocked {
+    /// The writer died mid-write: `write_lock` takes the lock over.
+    DeadWriter,
+    /// The timeout passed with the writer still at work.
+    TimedOut,
+}
+
 /// A seqlock stored in shared memory for cross-process use.
 pub struct ShmSeqLock {
     seq_ptr: *const AtomicU64,
-    write_lock_ptr: *const AtomicU32,
+    owner_ptr: *const AtomicU64,
+    generation_ptr: *const AtomicU64,
+    repair_ptr: *const AtomicU32,
 }
 
 unsafe impl Send for ShmSeqLock {}
@@ -29,17 +65,15 @@ impl ShmSeqLock {
         // Zero the region (caller should have done this, but be safe)
         std::ptr::write_bytes(ptr, 0, LOCK_SIZE);
 
-        let seq_ptr = ptr as *const AtomicU64;
-        let write_lock_ptr = ptr.add(8) as *const AtomicU32;
+        let lock = Self::from_existing(ptr);
 
         // Explicitly store initial values
-        (*seq_ptr).store(0, Ordering::Relaxed);
-        (*write_lock_ptr).store(0, Ordering::Relaxed);
+        (*lock.seq_ptr).store(0, Ordering::Relaxed);
+        (*lock.owner_ptr).store(0, Ordering::Relaxed);
+        (*lock.generation_ptr).store(0, Ordering::Relaxed);
+        (*lock.repair_ptr).store(0, Ordering::Relaxed);
 
-        Ok(ShmSeqLock {
-            seq_ptr,
-            write_lock_ptr,
-        })
+        Ok(lock)
     }
 
     /// Attach to an already-initialized seqlock at the given memory location.
@@ -49,21 +83,51 @@ impl ShmSeqLock {
     pub unsafe fn from_existing(ptr: *mut u8) -> Self {
         ShmSeqLock {
             seq_ptr: ptr as *const AtomicU64,
-            write_lock_ptr: ptr.add(8) as *const AtomicU32,
+            owner_ptr: ptr.add(8) as *const AtomicU64,
+            generation_ptr: ptr.add(16) as *const AtomicU64,
+            repair_ptr: ptr.add(24) as *const AtomicU32,
         }
     }
 
+    /// Start of the lock in its mapping, telling one lock file from another.
+    #[inline]
+    pub fn as_ptr(&self) -> *const u8 {
+        self.seq_ptr as *const u8
+    }
+
     /// Begin an optimistic read. Returns the sequence number.
-    /// Spins until the sequence is even (no writer active).
+    /// Spins until the sequence is even (no writer active), adding the pauses
+    /// that took to `waits`.
+    ///
+    /// Gives up after `timeout`, if any, or as soon as the writer turns out
+    /// to have died mid-write, so the sequence will never turn even: the
+    /// caller should then `write_lock` to take the lock over and repair the
+    /// data, and try again.
     #[inline]
-    pub fn read_begin(&self) -> u64 {
-        loop {
+    pub fn read_begin(
+        &self,
+        timeout: Option<Duration>,
+        waits: &AtomicU64,
+    ) -> Result<u64, ReadBlocked> {
+        let mut wait = Wait::new(timeout);
+        let result = loop {
             let seq = unsafe { &*self.seq_ptr }.load(Ordering::Acquire);
             if seq & 1 == 0 {
-                return seq;
+                break Ok(seq);
             }
-            std::hint::spin_loop();
-        }
+            match wait.pause() {
+                Waited::Briefly => {}
+                Waited::TimedOut => break Err(ReadBlocked::TimedOut),
+                Waited::Long => {
+                    let owner = unsafe { &*self.owner_ptr }.load(Ordering::Relaxed);
+                    if owner != 0 && !owner_alive(owner) {
+                        break Err(ReadBlocked::DeadWriter);
+                    }
+                }
+            }
+        };
+        wait.count_into(waits, 0);
+        result
     }
 
     /// Validate that no writer modified data since `read_begin()` returned `seq`.
@@ -77,24 +141,66 @@ impl ShmSeqLock {
         current == seq
     }
 
-    /// Acquire the write lock. Blocks (spins) until acquired.
-    #[inline]
+    /// Current sequence number (even = no writer active). Each completed write
+    /// section advances it by 2.
+    pub fn sequence(&self) -> u64 {
+        unsafe { &*self.seq_ptr }.load(Ordering::Acquire)
+    }
+
+    /// Acquire the write lock. Blocks until acquired.
+    #[cfg(test)]
     pub fn write_lock(&self) {
-        let lock = unsafe { &*self.write_lock_ptr };
+        self.write_lock_within(None, &AtomicU64::new(0));
+    }
+
+    /// Acquire the write lock, waiting at most `timeout` (if any). Returns
+    /// false if it timed out. The pauses and failed CAS attempts it took are
+    /// added to `spins`.
+    ///
+    /// A lock whose holder has died is taken over as by `adopt_abandoned`,
+    /// with `repair` set for the caller (see `take_repair`).
+    #[inline]
+    pub fn write_lock_within(&self, timeout: Option<Duration>, spins: &AtomicU64) -> bool {
+        let lock = unsafe { &*self.owner_ptr };
+        let me = owner_id();
+        let mut wait = Wait::new(timeout);
+        let mut failed_cas = 0;
         // TTAS (Test-and-Test-and-Set) spinlock
         loop {
             // Test: spin on load (cache-friendly, no bus traffic)
-            while lock.load(Ordering::Relaxed) != 0 {
-                std::hint::spin_loop();
+            let mut owner = lock.load(Ordering::Relaxed);
+            while owner != 0 {
+                match wait.pause() {
+                    Waited::Briefly => {}
+                    Waited::TimedOut => {
+                        wait.count_into(spins, failed_cas);
+                        return false;
+                    }
+                    Waited::Long => {
+                        if !owner_alive(owner)
+                            && lock
+                                .compare_exchange(owner, me, Ordering::Acquire, Ordering::Relaxed)
+                                .is_ok()
+                        {
+                            unsafe { &*self.repair_ptr }.store(1, Ordering::Relaxed);
+                            self.enter_abandoned();
+                            wait.count_into(spins, failed_cas);
+                            return true;
+                        }
+                    }
+                }
+                owner = lock.load(Ordering::Relaxed);
             }
             // Test-and-Set: try to acquire
             if lock
-                .compare_exchange_weak(0, 1, Ordering::Acquire, Ordering::Relaxed)
+                .compare_exchange_weak(0, me, Ordering::Acquire, Ordering::Relaxed)
                 .is_ok()
             {
                 break;
             }
+            failed_cas += 1;
         }
+        wait.count_into(spins, failed_cas);
         // Bump seq to odd — signals "writer active"
         let seq = unsafe { &*self.seq_ptr };
         let prev = seq.load(Ordering::Relaxed);
@@ -108,6 +214,64 @@ impl ShmSeqLock {
         // seqlock writer-enter construction (the exit-side Release at write_unlock
         // orders data writes before going even, but cannot cover the entry side).
         std::sync::atomic::fence(Ordering::Release);
+        true
+    }
+
+    /// Current data-file generation. Acquire pairs with the Release in
+    /// `bump_generation`, so a reader that sees the new value also sees the
+    /// renamed file.
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        unsafe { &*self.generation_ptr }.load(Ordering::Acquire)
+    }
+
+    /// Announce a replaced data file. Caller must hold the write lock.
+    pub fn bump_generation(&self) -> u64 {
+        unsafe { &*self.generation_ptr }.fetch_add(1, Ordering::Release) + 1
+    }
+
+    /// Whether a writer holds the lock or is mid-way through a write section.
+    pub fn is_write_locked(&self) -> bool {
+        unsafe { &*self.owner_ptr }.load(Ordering::Acquire) != 0
+            || unsafe { &*self.seq_ptr }.load(Ordering::Acquire) & 1 != 0
+    }
+
+    /// Take over a lock left held by a process that died mid-write: the caller
+    /// ends up holding it with seq odd, as if it had called `write_lock`, and
+    /// must repair the data before `write_unlock`.
+    ///
+    /// Only sound when no live process can hold the lock.
+    pub fn adopt_abandoned(&self) {
+        unsafe { &*self.owner_ptr }.store(owner_id(), Ordering::Relaxed);
+        self.enter_abandoned();
+    }
+
+    /// Make seq odd for a lock just taken from a dead holder, which may have
+    /// died on either side of its own odd/even bumps.
+    fn enter_abandoned(&self) {
+        let seq = unsafe { &*self.seq_ptr };
+        let prev = seq.load(Ordering::Relaxed);
+        seq.store(prev | 1, Ordering::Release);
+        std::sync::atomic::fence(Ordering::Release);
+    }
+
+    /// Whether the data still needs repairing after `write_lock` took the lock
+    /// from a dead holder; clears the flag. Caller must hold the write lock.
+    #[inline]
+    pub fn take_repair(&self) -> bool {
+        let repair = unsafe { &*self.repair_ptr };
+        let pending = repair.load(Ordering::Relaxed) != 0;
+        if pending {
+            repair.store(0, Ordering::Relaxed);
+        }
+        pending
+    }
+
+    /// Pretend process `pid` (of this PID namespace) holds the write lock.
+    #[cfg(test)]
+    pub fn hand_to(&self, pid: u32) {
+        let owner = (owner_id() & !u64::from(u32::MAX)) | u64::from(pid);
+        unsafe { &*self.owner_ptr }.store(owner, Ordering::Relaxed);
     }
 
     /// Release the write lock.
@@ -119,7 +283,243 @@ impl ShmSeqLock {
         seq.store(prev + 1, Ordering::Release);
 
         // Release the spinlock
-        unsafe { &*self.write_lock_ptr }.store(0, Ordering::Release);
+        unsafe { &*self.owner_ptr }.store(0, Ordering::Release);
+    }
+}
+
+/// What a waiter should do after one `Wait::pause`.
+enum Waited {
+    /// Keep waiting.
+    Briefly,
+    /// Another `OWNER_CHECK_INTERVAL` has passed: check the holder is alive.
+    Long,
+    /// The timeout has passed.
+    TimedOut,
+}
+
+/// Backoff and deadline bookkeeping for a waiter on a held lock.
+///
+/// The clock is only read once the busy spins are over, so the common short
+/// wait costs nothing extra; the timeout counts from then.
+struct Wait {
+    pauses: u32,
+    timeout: Option<Duration>,
+    /// Set on the first clock read: when the timeout runs out, and when the
+    /// holder was last checked.
+    deadline: Option<Instant>,
+    last_check: Option<Instant>,
+    sleep: Duration,
+}
+
+impl Wait {
+    fn new(timeout: Option<Duration>) -> Self {
+        Wait {
+            pauses: 0,
+            timeout,
+            deadline: None,
+            last_check: None,
+            sleep: MIN_SLEEP,
+        }
+    }
+
+    /// Add the pauses so far, plus `extra`, to `counter`. Skips the atomic
+    /// when there were none, as on every uncontended lock.
+    #[inline]
+    fn count_into(&self, counter: &AtomicU64, extra: u64) {
+        let n = u64::from(self.pauses) + extra;
+        if n > 0 {
+            counter.fetch_add(n, Ordering::Relaxed);
+        }
+    }
+
+    /// Spin, yield or sleep once, depending on how long this has gone on.
+    #[inline]
+    fn pause(&mut self) -> Waited {
+        self.pauses = self.pauses.saturating_add(1);
+        if self.pauses <= SPIN_LIMIT {
+            std::hint::spin_loop();
+            return Waited::Briefly;
+        }
+        if self.pauses <= SPIN_LIMIT + YIELD_LIMIT {
+            std::thread::yield_now();
+        } else {
+            std::thread::sleep(self.sleep);
+            self.sleep = (self.sleep * 2).min(MAX_SLEEP);
+        }
+
+        let now = Instant::now();
+        let last_check = *self.last_check.get_or_insert(now);
+        if let Some(timeout) = self.timeout {
+            if now >= *self.deadline.get_or_insert(now + timeout) {
+                return Waited::TimedOut;
+            }
+        }
+        if now.duration_since(last_check) >= OWNER_CHECK_INTERVAL {
+            self.last_check = Some(now);
+            return Waited::Long;
+        }
+        Waited::Briefly
+    }
+}
+
+/// The `owner` value this process writes: its PID in the low 32 bits and its
+/// PID namespace in the high ones. Never 0.
+fn owner_id() -> u64 {
+    (u64::from(pid_namespace()) << 32) | u64::from(current_pid()) | 1 << 63
+}
+
+/// Whether the process that wrote `owner` may still be running. Holders in
+/// another PID namespace (a container sharing the cache directory) can't be
+/// checked, since their PIDs mean nothing here, so they count as alive.
+fn owner_alive(owner: u64) -> bool {
+    if owner >> 32 != owner_id() >> 32 {
+        return true;
+    }
+    process_alive(owner as u32)
+}
+
+/// This process's PID, cached: `getpid` is a system call on Linux and the
+/// lock is taken on every write. Reset in fork children.
+#[cfg(unix)]
+pub(super) fn current_pid() -> u32 {
+    static PID: AtomicU32 = AtomicU32::new(0);
+    static AT_FORK: std::sync::Once = std::sync::Once::new();
+    extern "C" fn forget_pid() {
+        PID.store(0, Ordering::Relaxed);
+    }
+    AT_FORK.call_once(|| unsafe {
+        libc::pthread_atfork(None, None, Some(forget_pid));
+    });
+    match PID.load(Ordering::Relaxed) {
+        0 => {
+            let pid = std::process::id();
+            PID.store(pid, Ordering::Relaxed);
+            pid
+        }
+        pid => pid,
+    }
+}
+
+#[cfg(not(unix))]
+pub(super) fn current_pid() -> u32 {
+    std::process::id()
+}
+
+/// An id for this process's PID namespace (its inode number, truncated), or
+/// 0 where there are none.
+#[cfg(target_os = "linux")]
+fn pid_namespace() -> u32 {
+    use std::os::unix::fs::MetadataExt;
+    static NAMESPACE: std::sync::OnceLock<u32> = std::sync::OnceLock::new();
+    // Only the low 31 bits: bit 63 of `owner` keeps it non-zero.
+    *NAMESPACE.get_or_init(|| {
+        std::fs::metadata("/proc/self/ns/pid").map_or(0, |m| m.ino() as u32 & 0x7FFF_FFFF)
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pid_namespace() -> u32 {
+    0
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    // Signal 0 only checks: EPERM means it exists but belongs to someone else.
+    let exists = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+    exists || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+fn process_alive(pid: u32) -> bool {
+    use std::ffi::c_void;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn OpenProcess(access: u32, inherit: i32, pid: u32) -> *mut c_void;
+        fn GetExitCodeProcess(process: *mut c_void, code: *mut u32) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+    const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+    const STILL_ACTIVE: u32 = 259;
+    const ERROR_INVALID_PARAMETER: i32 = 87;
+    // SAFETY: the handle is only used if it is valid, and closed after.
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            // No such process; any other failure (access denied) means it exists.
+            return io::Error::last_os_error().raw_os_error() != Some(ERROR_INVALID_PARAMETER);
+        }
+        let mut code = 0;
+        let ok = GetExitCodeProcess(process, &mut code);
+        CloseHandle(process);
+        ok == 0 || code == STILL_ACTIVE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The PID of a process that has exited and been reaped.
+    #[cfg(unix)]
+    fn dead_pid() -> u32 {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        pid
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn lock_held_by_a_dead_process_is_taken_over() {
+        let mut mem = [0u64; LOCK_SIZE / 8];
+        let lock = unsafe { ShmSeqLock::init(mem.as_mut_ptr() as *mut u8).unwrap() };
+        assert!(process_alive(std::process::id()));
+        assert!(!process_alive(dead_pid()));
+
+        // A writer that died mid-write: lock held, seq odd.
+        lock.write_lock();
+        lock.hand_to(dead_pid());
+        let waits = AtomicU64::new(0);
+        assert_eq!(lock.read_begin(None, &waits), Err(ReadBlocked::DeadWriter));
+        lock.write_lock();
+        assert!(lock.take_repair() && !lock.take_repair());
+        lock.write_unlock();
+        assert!(!lock.is_write_locked());
+        assert_eq!(lock.read_begin(None, &waits), Ok(lock.sequence()));
+
+        // One that died between taking the lock and going odd.
+        lock.write_lock();
+        lock.write_unlock();
+        lock.hand_to(dead_pid());
+        lock.write_lock();
+        assert!(lock.take_repair());
+        lock.write_unlock();
+        assert_eq!(lock.sequence() & 1, 0);
+    }
+
+    #[test]
+    fn waiting_on_a_live_holder_times_out() {
+        let mut mem = [0u64; LOCK_SIZE / 8];
+        let lock = unsafe { ShmSeqLock::init(mem.as_mut_ptr() as *mut u8).unwrap() };
+        lock.write_lock(); // held by this (live) process
+
+        let timeout = Some(Duration::from_millis(50));
+        let (waits, spins) = (AtomicU64::new(0), AtomicU64::new(0));
+        let start = Instant::now();
+        assert_eq!(lock.read_begin(timeout, &waits), Err(ReadBlocked::TimedOut));
+        assert!(!lock.write_lock_within(timeout, &spins));
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        // Both spun through the busy phase before timing out.
+        assert!(waits.load(Ordering::Relaxed) > u64::from(SPIN_LIMIT));
+        assert!(spins.load(Ordering::Relaxed) > u64::from(SPIN_LIMIT));
+
+        lock.write_unlock();
+        let (waits, spins) = (AtomicU64::new(0), AtomicU64::new(0));
+        assert!(lock.write_lock_within(timeout, &spins));
+        lock.write_unlock();
+        assert!(lock.read_begin(timeout, &waits).is_ok());
+        // A free lock is read without a single pause.
+        assert_eq!(waits.into_inner(), 0);
     }
 }
 
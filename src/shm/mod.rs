@@ -7,34 +7,107 @@
 /// Read path uses an optimistic seqlock: lock-free hash lookup + value copy.
 /// On hit, `visited` is set to 1 without a write lock (idempotent store).
 /// All reads are fully lock-free. Stats are updated via atomics (no lock).
+pub mod chain;
 pub mod hashtable;
 pub mod layout;
 pub mod lock;
 pub mod ordering;
+pub mod overflow;
 pub mod region;
 
-use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering as AtomicOrdering};
+use std::time::Duration;
 
-use layout::{Bucket, Header, SlotHeader, BUCKET_EMPTY, SLOT_HEADER_SIZE, SLOT_NONE};
-use lock::ShmSeqLock;
+use parking_lot::Mutex;
+
+use crate::fork::{AfterFork, ForkLock};
+use layout::{
+    Bucket, Header, SlotHeader, BUCKET_EMPTY, SLOT_CONTINUATION, SLOT_HEADER_SIZE, SLOT_NONE,
+    VALUE_SIZE_BUCKETS,
+};
+pub use layout::{
+    CACHE_VERSION_LEN, MAX_TAGS, VALUE_CODEC_LZ4, VALUE_CODEC_NONE, VALUE_CODEC_ZSTD,
+};
+use lock::{ReadBlocked, ShmSeqLock};
 use region::ShmRegion;
+pub use region::{list_regions, remove_orphans, FileOptions, OnMismatch, RegionConfig};
 
 /// Result of a cache get operation.
 pub enum ShmGetResult {
     Hit(Vec<u8>),
-    Miss,
+    /// Pass the token to `insert_with_token` when storing the missing value.
+    Miss(InsertToken),
+}
+
+/// What a lookup that missed learned, so the insert that follows can skip
+/// probing for the key again if nothing was written in between.
+///
+/// Holds the seqlock sequence at which a validated read found the key absent.
+/// The default token knows nothing and makes `insert_with_token` a plain
+/// `insert`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InsertToken {
+    absent_at: Option<u64>,
+}
+
+/// `(key_bytes, value_bytes)` of an entry evicted to make room
+/// (`ShmCache::take_evicted`).
+pub type Evicted = (Vec<u8>, Vec<u8>);
+
+/// Which size limit an entry that wasn't stored ran into
+/// (`ShmCache::record_oversize_skip`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Oversize {
+    /// The key is over `max_key_size`.
+    Key,
+    /// The value is over what a slot, chain or overflow chunk can hold.
+    Value,
+}
+
+/// Why `ShmCache::incr` left an entry unchanged.
+#[derive(Debug, PartialEq, Eq)]
+pub enum IncrError {
+    /// The stored value is not a fast-path-encoded `int`.
+    NotAnInt,
+    /// The result does not fit in an `i64`.
+    Overflow,
 }
 
 /// Result of the optimistic (lock-free) read phase.
 enum OptimisticResult {
-    /// Cache hit — value bytes copied, slot_index for ordering update.
-    Hit { value: Vec<u8>, slot_index: i32 },
+    /// Cache hit — value bytes copied, slot_index for ordering update. `slide`
+    /// is whether a sliding TTL would restart the entry's clock (see
+    /// `SLIDE_GRANULARITY`).
+    Hit {
+        value: Vec<u8>,
+        slot_index: i32,
+        slide: bool,
+    },
     /// Key not found.
     Miss,
-    /// Entry found but TTL expired — slot_index for cleanup.
+    /// Entry past its TTL but within the stale window — value copied, still
+    /// servable while one caller recomputes it.
+    Stale { value: Vec<u8>, slot_index: i32 },
+    /// Entry found but TTL (and any stale window) expired — slot_index for cleanup.
     Expired { slot_index: i32 },
+    /// Entry found but its key and value don't match its checksum. Only
+    /// meaningful once the read section validates (a torn copy fails it too).
+    Corrupt { slot_index: i32 },
 }
 
+/// How many entries `insert` inspects from the eviction list head looking for
+/// an expired slot to reuse before falling back to SIEVE eviction.
+const RECLAIM_SCAN_LIMIT: usize = 8;
+
+/// Most violations `ShmCache::verify` lists before summarizing the rest.
+const MAX_VIOLATIONS: usize = 100;
+
+/// A sliding-TTL hit restarts the entry's clock only once 1/N of its TTL has
+/// passed since the last restart, so a hot key takes the write lock a few
+/// dozen times per TTL instead of on every hit. An idle entry can then expire
+/// up to TTL/N early.
+const SLIDE_GRANULARITY: u64 = 64;
+
 /// The main shared-memory cache handle.
 ///
 /// One instance per decorated function. Multiple processes sharing the
@@ -42,43 +115,810 @@ enum OptimisticResult {
 /// at the same mmap.
 pub struct ShmCache {
     region: ShmRegion,
-    next_unique_id: AtomicU64,
+    /// Minimum time between write-triggered flushes of a durable cache, 0 = off.
+    sync_interval_nanos: u64,
+    /// When this handle last flushed (`current_time_nanos`).
+    last_sync: AtomicU64,
+    /// How long to wait for another writer before giving up, `None` = forever.
+    lock_timeout: Option<Duration>,
+    /// `(key, value)` of the entries this handle evicted, kept only after
+    /// `keep_evicted` until `take_evicted` collects them.
+    evicted: Option<ForkLock<Mutex<Vec<Evicted>>>>,
+    /// This handle's own share of the header's hit/miss/skip counters.
+    local: LocalStats,
+    /// Mapped read-only (`FileOptions::readonly`): every write fails and the
+    /// shared counters and SIEVE bits are left alone.
+    readonly: bool,
+}
+
+/// Counters bumped alongside the header's, but only by one handle, so a
+/// single process's hit rate can be told apart from the fleet's. The seqlock
+/// contention counters (`contention_info`) live only here, so measuring
+/// contention adds no writes to the shared header.
+#[derive(Debug, Default)]
+struct LocalStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    oversize_key_skips: AtomicU64,
+    oversize_value_skips: AtomicU64,
+    read_waits: AtomicU64,
+    read_retries: AtomicU64,
+    write_spins: AtomicU64,
+    write_locks: AtomicU64,
+}
+
+impl LocalStats {
+    fn reset(&self) {
+        for counter in [
+            &self.hits,
+            &self.misses,
+            &self.oversize_key_skips,
+            &self.oversize_value_skips,
+            &self.read_waits,
+            &self.read_retries,
+            &self.write_spins,
+            &self.write_locks,
+        ] {
+            counter.store(0, AtomicOrdering::Relaxed);
+        }
+    }
 }
 
 impl ShmCache {
     /// Create or open a shared cache.
+    ///
+    /// `stale_ttl_secs` enables stale-while-revalidate: for that long past its
+    /// TTL an entry is still served while a single caller recomputes it. With
+    /// `sliding_ttl`, hits restart the entry's TTL clock (at most once per
+    /// 1/`SLIDE_GRANULARITY` of it). With `checksums`, each entry stores an
+    /// XXH32 of its key and value, and a hit that doesn't match it is dropped
+    /// as a miss (`corruption_detected`). `value_codec` (`VALUE_CODEC_*`) and
+    /// `serializer` record how the caller compresses and serializes values,
+    /// so processes that disagree are caught as a mismatch; so does
+    /// `cache_version` (empty = none), the version of the results themselves.
+    /// With `chunk_size`, slots hold `max_key_size + chunk_size` bytes and a
+    /// larger value continues in as many further slots as it needs, so
+    /// `capacity` counts slots rather than entries. `overflow_count` chunks of
+    /// `overflow_size` bytes after the slab hold values over `max_value_size`
+    /// (up to `overflow_size`), one each. `on_mismatch` decides what happens if
+    /// the cache exists with a different configuration.
+    /// `files` sets where the cache files live, their permissions, and whether
+    /// the cache is durable — kept on disk and repaired after a crash on open.
+    #[allow(clippy::too_many_arguments)]
     pub fn create_or_open(
         name: &str,
         capacity: u32,
         max_key_size: u32,
         max_value_size: u32,
         ttl_secs: Option<f64>,
+        stale_ttl_secs: Option<f64>,
+        sliding_ttl: bool,
+        checksums: bool,
+        value_codec: u32,
+        serializer: &str,
+        cache_version: &str,
+        chunk_size: Option<u32>,
+        overflow_count: u32,
+        overflow_size: u32,
+        on_mismatch: OnMismatch,
+        files: &FileOptions,
     ) -> std::io::Result<Self> {
-        let slot_size = SLOT_HEADER_SIZE as u32 + max_key_size + max_value_size;
+        // Slot headers hold u64s and atomics, so every slot must start 8-byte
+        // aligned whatever the key/value limits add up to.
+        let slot_size = (SLOT_HEADER_SIZE as u32)
+            .checked_add(max_key_size)
+            .and_then(|size| size.checked_add(chunk_size.unwrap_or(max_value_size)))
+            .and_then(|size| size.checked_next_multiple_of(8))
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::FileTooLarge,
+                    format!(
+                        "max_key_size + max_value_size (or chunk_size) must add up to under \
+                         {} bytes per slot",
+                        u32::MAX as usize - SLOT_HEADER_SIZE
+                    ),
+                )
+            })?;
         let ttl_nanos = match ttl_secs {
             Some(t) => (t * 1_000_000_000.0) as u64,
             None => 0,
         };
+        let stale_ttl_nanos = match stale_ttl_secs {
+            Some(t) => (t * 1_000_000_000.0) as u64,
+            None => 0,
+        };
 
         let region = ShmRegion::create_or_open(
             name,
-            capacity,
-            slot_size,
-            max_key_size,
-            max_value_size,
-            ttl_nanos,
+            &RegionConfig {
+                capacity,
+                slot_size,
+                max_key_size,
+                max_value_size,
+                ttl_nanos,
+                stale_ttl_nanos,
+                sliding_ttl,
+                durable: files.persistent,
+                checksums,
+                value_codec,
+                serializer: region::text_id(serializer),
+                chunk_size: chunk_size.unwrap_or(0),
+                overflow_count,
+                // Chunks hold their free-list link, so keep them i32-aligned.
+                overflow_size: if overflow_count > 0 {
+                    overflow_size.next_multiple_of(8)
+                } else {
+                    0
+                },
+                cache_version: region::text_id(cache_version),
+            },
+            on_mismatch,
+            files,
         )?;
 
-        Ok(ShmCache {
+        let cache = ShmCache {
             region,
-            next_unique_id: AtomicU64::new(0),
-        })
+            sync_interval_nanos: files
+                .sync_interval
+                .map_or(0, |d| d.as_nanos().min(u64::MAX as u128) as u64),
+            last_sync: AtomicU64::new(current_time_nanos()),
+            lock_timeout: files.lock_timeout,
+            evicted: None,
+            local: LocalStats::default(),
+            readonly: files.readonly,
+        };
+        cache.recover_if_needed()?;
+        cache.reset_if_rebooted()?;
+        Ok(cache)
+    }
+
+    /// Repair a durable cache left inconsistent by a process killed mid-write,
+    /// or by a machine crash that lost part of the file.
+    ///
+    /// Only the first handle to attach checks: with no other handle open, a
+    /// held write lock can only belong to a dead process, so it is taken over.
+    /// The index is then rebuilt from the slots if that writer was interrupted
+    /// or the structures don't add up.
+    fn recover_if_needed(&self) -> std::io::Result<()> {
+        if self.header().durable == 0 || !self.region.opened_alone() {
+            return Ok(());
+        }
+        let mut lock = self.lock();
+        let abandoned = lock.is_write_locked();
+        if abandoned {
+            lock.adopt_abandoned();
+        } else {
+            self.write_lock(&mut lock)?;
+        }
+        unsafe {
+            if abandoned || self.header().pending_slot != SLOT_NONE || !self.is_consistent() {
+                self.rebuild_index();
+            }
+        }
+        lock.write_unlock();
+        Ok(())
+    }
+
+    /// Empty a volatile cache whose file outlived a reboot.
+    ///
+    /// Its entries are stamped with `CLOCK_MONOTONIC`, which restarts at boot,
+    /// so against the new clock they would look too young (or from the future)
+    /// and outlive their TTL. A file on tmpfs never survives a reboot, but one
+    /// under `shm_dir` on disk (the default outside Linux) can. Like
+    /// `recover_if_needed`, only the first handle to attach checks, taking over
+    /// a write lock left held when the machine went down.
+    fn reset_if_rebooted(&self) -> std::io::Result<()> {
+        let current = boot_id();
+        let h = self.header();
+        if h.durable != 0 || current == 0 || h.boot_id == current || !self.region.opened_alone() {
+            return Ok(());
+        }
+        let mut lock = self.lock();
+        if lock.is_write_locked() {
+            lock.adopt_abandoned();
+        } else {
+            self.write_lock(&mut lock)?;
+        }
+        unsafe {
+            self.clear_inner(false);
+            self.header_mut().boot_id = current;
+        }
+        lock.write_unlock();
+        Ok(())
+    }
+
+    /// Whether the eviction list, free list, slot chains, hash table and
+    /// `current_size` agree with each other and with the slots they point at.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn is_consistent(&self) -> bool {
+        self.violations().is_empty()
+    }
+
+    /// Check the region's invariants under the write lock, returning one
+    /// human-readable line per violation (empty = healthy). See `violations`.
+    pub fn verify(&self) -> std::io::Result<Vec<String>> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        let mut violations = unsafe { self.violations() };
+        lock.write_unlock();
+        if violations.len() > MAX_VIOLATIONS {
+            let more = violations.len() - MAX_VIOLATIONS;
+            violations.truncate(MAX_VIOLATIONS);
+            violations.push(format!("... and {more} more"));
+        }
+        Ok(violations)
+    }
+
+    /// Every way the index structures disagree with the slots:
+    ///
+    /// - the eviction list must be a doubly-linked chain of occupied head
+    ///   slots ending at `list_tail`, `current_size` long, each with sane
+    ///   lengths, an intact value chain and a valid overflow chunk;
+    /// - the free list must hold only free slots, and together with the
+    ///   entries and their continuation slots cover every slot exactly once;
+    /// - each live entry must be in exactly one hash-table bucket, holding its
+    ///   `key_hash`, with no empty bucket between that hash's home bucket and
+    ///   it (or linear probing would never reach it), and no bucket may point
+    ///   anywhere else;
+    /// - overflow chunks held by entries and on the free list must partition
+    ///   the arena and match `overflow_used`.
+    ///
+    /// List walks stop at the first bad link, so one corruption is reported
+    /// once rather than for everything after it.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn violations(&self) -> Vec<String> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Owner {
+            None,
+            Entry,
+            Continuation(i32),
+            Free,
+        }
+        let h = self.header();
+        let capacity = h.capacity as usize;
+        let mut out = Vec::new();
+        let mut owner = vec![Owner::None; capacity];
+        let mut chunk_owner = vec![None; h.overflow_count as usize];
+
+        // Eviction list
+        let mut live = 0u32;
+        let mut prev = SLOT_NONE;
+        let mut idx = h.list_head;
+        while idx != SLOT_NONE {
+            let Some(slot) = self.slot_checked(idx) else {
+                out.push(format!(
+                    "eviction list: slot {idx} (after {prev}) is out of range"
+                ));
+                break;
+            };
+            if owner[idx as usize] != Owner::None {
+                out.push(format!("eviction list: slot {idx} appears twice (a cycle)"));
+                break;
+            }
+            owner[idx as usize] = Owner::Entry;
+            live += 1;
+            if slot.occupied != 1 {
+                out.push(format!(
+                    "eviction list: slot {idx} has occupied={}, not 1",
+                    slot.occupied
+                ));
+            }
+            if slot.prev != prev {
+                out.push(format!(
+                    "eviction list: slot {idx} has prev={}, but follows {prev}",
+                    slot.prev
+                ));
+            }
+            if slot.key_len > h.max_key_size || slot.value_len > value_limit(h) {
+                out.push(format!(
+                    "slot {idx}: key_len={} / value_len={} exceed the limits",
+                    slot.key_len, slot.value_len
+                ));
+            } else {
+                match self.chain_slots(idx) {
+                    Some(continuations) => {
+                        for cont in continuations {
+                            if owner[cont as usize] != Owner::None {
+                                out.push(format!(
+                                    "slot {idx}: continuation slot {cont} is already in use"
+                                ));
+                            } else {
+                                owner[cont as usize] = Owner::Continuation(idx);
+                            }
+                        }
+                    }
+                    None => out.push(format!("slot {idx}: value chain is broken")),
+                }
+            }
+            if slot.overflow != SLOT_NONE {
+                match chunk_owner.get_mut(slot.overflow as usize) {
+                    Some(held @ None) => *held = Some(idx),
+                    Some(Some(other)) => out.push(format!(
+                        "slot {idx}: overflow chunk {} is also held by slot {other}",
+                        slot.overflow
+                    )),
+                    None => out.push(format!(
+                        "slot {idx}: overflow chunk {} is out of range",
+                        slot.overflow
+                    )),
+                }
+            }
+            prev = idx;
+            idx = slot.next;
+        }
+        if prev != h.list_tail {
+            out.push(format!(
+                "eviction list ends at {prev}, but list_tail is {}",
+                h.list_tail
+            ));
+        }
+        if live != h.current_size {
+            out.push(format!(
+                "eviction list holds {live} entries, but current_size is {}",
+                h.current_size
+            ));
+        }
+
+        // Free list
+        let mut idx = h.free_head;
+        while idx != SLOT_NONE {
+            let Some(slot) = self.slot_checked(idx) else {
+                out.push(format!("free list: slot {idx} is out of range"));
+                break;
+            };
+            if owner[idx as usize] != Owner::None {
+                out.push(format!(
+                    "free list: slot {idx} is also in use or listed twice"
+                ));
+                break;
+            }
+            owner[idx as usize] = Owner::Free;
+            if slot.occupied != 0 {
+                out.push(format!(
+                    "free list: slot {idx} has occupied={}, not 0",
+                    slot.occupied
+                ));
+            }
+            idx = slot.next;
+        }
+        // Slots past `fresh_start` are free without being on the free list.
+        // Their contents aren't read: faulting in their released pages would
+        // undo `clear_releasing_memory`.
+        if h.fresh_start as usize > capacity {
+            out.push(format!(
+                "fresh_start is {}, past capacity {capacity}",
+                h.fresh_start
+            ));
+        }
+        let fresh = (h.fresh_start as usize).min(capacity);
+        for (idx, slot_owner) in owner.iter_mut().enumerate().skip(fresh) {
+            if *slot_owner != Owner::None {
+                out.push(format!(
+                    "slot {idx} is past fresh_start but also in use or on the free list"
+                ));
+                break;
+            }
+            *slot_owner = Owner::Free;
+        }
+        let orphans: Vec<usize> = (0..capacity).filter(|&i| owner[i] == Owner::None).collect();
+        if !orphans.is_empty() {
+            out.push(format!(
+                "{} slot(s) on neither list nor in a chain: {orphans:?}",
+                orphans.len()
+            ));
+        }
+
+        // Hash table
+        let ht_cap = h.ht_capacity as usize;
+        let mask = h.ht_capacity.wrapping_sub(1) as usize;
+        let bucket = |i: usize| &*(self.ht_base().add(i * Bucket::SIZE) as *const Bucket);
+        let mut indexed = vec![0u32; capacity];
+        for i in 0..ht_cap {
+            let b = bucket(i);
+            if b.slot_index == BUCKET_EMPTY {
+                continue;
+            }
+            let target = b.slot_index;
+            if target < 0 || target as usize >= capacity {
+                out.push(format!("bucket {i}: slot {target} is out of range"));
+                continue;
+            }
+            if owner[target as usize] != Owner::Entry {
+                out.push(format!("bucket {i}: slot {target} is not a live entry"));
+                continue;
+            }
+            indexed[target as usize] += 1;
+            let slot = self.slot(target);
+            if b.hash != slot.key_hash {
+                out.push(format!(
+                    "bucket {i}: hash {:#x} differs from slot {target}'s key_hash {:#x}",
+                    b.hash, slot.key_hash
+                ));
+            }
+            let home = b.hash as usize & mask;
+            let mut j = home;
+            while j != i {
+                if bucket(j).slot_index == BUCKET_EMPTY {
+                    out.push(format!(
+                        "bucket {i}: unreachable, empty bucket {j} lies between it and its \
+                         home bucket {home}"
+                    ));
+                    break;
+                }
+                j = (j + 1) & mask;
+            }
+        }
+        for (i, &count) in indexed.iter().enumerate() {
+            if owner[i] == Owner::Entry && count != 1 {
+                out.push(format!("slot {i}: in {count} hash-table buckets, not 1"));
+            }
+        }
+
+        // Overflow arena
+        let used = chunk_owner.iter().filter(|held| held.is_some()).count() as u32;
+        if used != h.overflow_used {
+            out.push(format!(
+                "{used} overflow chunk(s) held by entries, but overflow_used is {}",
+                h.overflow_used
+            ));
+        }
+        let mut free_chunks = 0u32;
+        let mut chunk = h.overflow_free;
+        while chunk != SLOT_NONE {
+            if chunk < 0 || chunk as u32 >= h.overflow_count || free_chunks >= h.overflow_count {
+                out.push(format!("overflow free list: bad or looping chunk {chunk}"));
+                break;
+            }
+            if let Some(holder) = chunk_owner[chunk as usize] {
+                out.push(format!(
+                    "overflow free list: chunk {chunk} is held by slot {holder}"
+                ));
+            }
+            free_chunks += 1;
+            let arena = self.overflow_base_mut();
+            chunk = *(overflow::chunk(arena, h.overflow_size, chunk) as *const i32);
+        }
+        if used + free_chunks != h.overflow_count {
+            out.push(format!(
+                "{used} held + {free_chunks} free overflow chunks, but there are {}",
+                h.overflow_count
+            ));
+        }
+        out
+    }
+
+    /// The continuation slots of the entry headed at `head_idx`, or `None` if
+    /// its chain is shorter than its length needs or leaves the slab.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn chain_slots(&self, head_idx: i32) -> Option<Vec<i32>> {
+        let h = self.header();
+        let head = self.slot_checked(head_idx)?;
+        if head.overflow != SLOT_NONE {
+            return Some(Vec::new()); // the value is in an overflow chunk
+        }
+        let needed =
+            chain::slots_needed(h.slot_size, head.key_len as usize, head.value_len as usize);
+        let mut slots = Vec::with_capacity(needed - 1);
+        let mut idx = head.chain;
+        for _ in 1..needed {
+            let slot = self.slot_checked(idx)?;
+            if slot.occupied != SLOT_CONTINUATION {
+                return None;
+            }
+            slots.push(idx);
+            idx = slot.chain;
+        }
+        Some(slots)
+    }
+
+    /// Rebuild the hash table, eviction list, free list and size counters from
+    /// the slots alone.
+    ///
+    /// Every entry with sane lengths and an intact chain is kept, except
+    /// `pending_slot`, whose write may be torn; if a key (or a continuation
+    /// slot or overflow chunk) shows up twice the newest entry wins. Orphaned
+    /// continuation slots and overflow chunks are freed.
+    /// The eviction list is rebuilt oldest first, and each slot keeps its
+    /// SIEVE bit.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn rebuild_index(&self) {
+        let h = self.header();
+        let ht_cap = h.ht_capacity;
+        let slot_size = h.slot_size;
+        let capacity = h.capacity;
+
+        // (created_at_nanos, index), newest first
+        let mut candidates: Vec<(u64, i32)> = (0..capacity as i32)
+            .filter(|&idx| idx != h.pending_slot)
+            .filter_map(|idx| {
+                let slot = self.slot(idx);
+                (slot.occupied == 1
+                    && slot.key_len <= h.max_key_size
+                    && slot.value_len <= value_limit(h))
+                .then_some((slot.created_at_nanos, idx))
+            })
+            .collect();
+        candidates.sort_unstable_by(|a, b| b.cmp(a));
+
+        hashtable::ht_clear(self.ht_base_mut(), ht_cap);
+        let mut live = vec![false; capacity as usize];
+        let mut chunk_live = vec![false; h.overflow_count as usize];
+        let mut kept = Vec::with_capacity(candidates.len());
+        for (_, idx) in candidates {
+            let Some(continuations) = self.chain_slots(idx) else {
+                continue;
+            };
+            if continuations.iter().any(|&c| live[c as usize]) {
+                continue;
+            }
+            let chunk = self.slot(idx).overflow;
+            if chunk != SLOT_NONE
+                && (chunk < 0 || chunk as u32 >= h.overflow_count || chunk_live[chunk as usize])
+            {
+                continue;
+            }
+            let key_hash = self.slot(idx).key_hash;
+            let key = self.slot_key(idx);
+            let duplicate = hashtable::ht_lookup(
+                self.ht_base(),
+                ht_cap,
+                self.slab_base(),
+                slot_size,
+                key_hash,
+                key,
+            )
+            .is_some();
+            // The table was just cleared and holds at most `capacity` of its
+            // 2× buckets, so the insert can only fail on a corrupt header.
+            if !duplicate && hashtable::ht_insert(self.ht_base_mut(), ht_cap, key_hash, idx) {
+                kept.push(idx);
+                live[idx as usize] = true;
+                for c in continuations {
+                    live[c as usize] = true;
+                }
+                if chunk != SLOT_NONE {
+                    chunk_live[chunk as usize] = true;
+                }
+            }
+        }
+
+        let header = self.header_mut();
+        header.free_head = SLOT_NONE;
+        header.fresh_start = capacity;
+        for idx in (0..capacity as i32).rev() {
+            if !live[idx as usize] {
+                let slot = &mut *(self.slab_base_mut().add(idx as usize * slot_size as usize)
+                    as *mut SlotHeader);
+                slot.occupied = 0;
+                slot.chain = SLOT_NONE;
+                slot.overflow = SLOT_NONE;
+                slot.prev = SLOT_NONE;
+                slot.next = header.free_head;
+                header.free_head = idx;
+            }
+        }
+
+        header.list_head = SLOT_NONE;
+        header.list_tail = SLOT_NONE;
+        header.sieve_hand = SLOT_NONE;
+        let (mut bytes_used, mut value_bytes_used) = (0u64, 0u64);
+        for &idx in kept.iter().rev() {
+            ordering::list_push_tail(header, self.slab_base_mut(), slot_size, idx);
+            let slot = self.slot(idx);
+            bytes_used += slot.key_len as u64 + slot.value_len as u64;
+            value_bytes_used += slot.value_len as u64;
+        }
+        header.current_size = kept.len() as u32;
+        header.bytes_used = bytes_used;
+        header.value_bytes_used = value_bytes_used;
+        header.pending_slot = SLOT_NONE;
+        overflow::reset_free_list(header, self.overflow_base_mut(), |chunk| {
+            chunk_live[chunk as usize]
+        });
     }
 
+    /// Flush a durable cache's data file to disk, waiting for the write to
+    /// finish. Holds the write lock throughout so the file on disk is a
+    /// consistent snapshot.
+    pub fn sync(&self) -> std::io::Result<()> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        let result = self.region.flush(true);
+        lock.write_unlock();
+        self.last_sync
+            .store(current_time_nanos(), AtomicOrdering::Relaxed);
+        result
+    }
+
+    /// After a write: if `sync_interval` has passed since the last flush,
+    /// schedule one without waiting for it. Errors are left for `sync` to report.
+    fn maybe_sync(&self) {
+        if self.sync_interval_nanos == 0 {
+            return;
+        }
+        let now = current_time_nanos();
+        let last = self.last_sync.load(AtomicOrdering::Relaxed);
+        if now.saturating_sub(last) >= self.sync_interval_nanos
+            && self
+                .last_sync
+                .compare_exchange(last, now, AtomicOrdering::Relaxed, AtomicOrdering::Relaxed)
+                .is_ok()
+        {
+            let _ = self.region.flush(false);
+        }
+    }
+
+    /// Timestamp for `created_at_nanos`: wall-clock time in a durable cache, so
+    /// ages stay meaningful across reboots, and `current_time_nanos` otherwise.
+    #[inline]
+    fn now(&self) -> u64 {
+        if self.header().durable != 0 {
+            wall_clock_nanos()
+        } else {
+            current_time_nanos()
+        }
+    }
+
+    /// The configuration this cache was created with, which differs from the
+    /// requested one after opening with `OnMismatch::OpenExisting`.
+    pub fn config(&self) -> RegionConfig {
+        self.region.sync_generation();
+        RegionConfig::from_header(self.header())
+    }
+
+    /// The seqlock, after picking up any resize done by another handle (and,
+    /// once in a while, any replacement of the files, see `reattach`).
     fn lock(&self) -> ShmSeqLock {
+        self.region.sync_generation();
+        if ShmRegion::replacement_check_due() {
+            self.reattach();
+        }
         self.region.lock()
     }
 
+    /// Switch to the region now at this cache's paths if its files were
+    /// deleted or replaced (say by another process's `OnMismatch::Recreate`)
+    /// since this handle attached; otherwise it would go on with a copy no
+    /// other process sees. A replacement with other size limits or encodings
+    /// is left alone.
+    ///
+    /// The switch happens under the old write lock, so in-flight reads fail
+    /// validation and retry against the new region. If that lock can't be had
+    /// within `lock_timeout`, the next check tries again.
+    #[cold]
+    fn reattach(&self) {
+        let Some(new) = self.region.open_replacement() else {
+            return;
+        };
+        let old = self.region.lock();
+        if self.readonly {
+            self.region.adopt(&old, new);
+        } else if old.write_lock_within(self.lock_timeout, &self.local.write_spins) {
+            self.region.adopt(&old, new);
+            old.write_unlock();
+        }
+    }
+
+    /// Take the write lock and make sure this handle maps the data file that
+    /// lock guards (a resize may have replaced it while we waited). If
+    /// `reattach` switched lock files meanwhile, `lock` is swapped for the
+    /// current one.
+    ///
+    /// If the lock was taken over from a process that died mid-write, the
+    /// index is rebuilt from the slots first, as after a crash on open, and
+    /// `true` returned. Fails with `TimedOut` if `lock_timeout` passes first,
+    /// and with `PermissionDenied` on a read-only handle.
+    ///
+    /// Records this process in the header as the last writer, for
+    /// `debug_info` and the timeout message of whoever waits on it next.
+    fn write_lock(&self, lock: &mut ShmSeqLock) -> std::io::Result<bool> {
+        let repaired = self.acquire_write_lock(lock)?;
+        self.atomic_last_writer_pid()
+            .store(lock::current_pid(), AtomicOrdering::Relaxed);
+        self.atomic_last_write_nanos()
+            .store(wall_clock_nanos(), AtomicOrdering::Relaxed);
+        Ok(repaired)
+    }
+
+    /// `write_lock` without recording this process as the last writer.
+    fn acquire_write_lock(&self, lock: &mut ShmSeqLock) -> std::io::Result<bool> {
+        if self.readonly {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!(
+                    "shared cache {:?} was opened read-only (readonly=True)",
+                    self.region.name()
+                ),
+            ));
+        }
+        loop {
+            if !lock.write_lock_within(self.lock_timeout, &self.local.write_spins) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!(
+                        "timed out after {:?} waiting for another process to finish writing \
+                         to shared cache {:?}{}",
+                        self.lock_timeout.unwrap_or_default(),
+                        self.region.name(),
+                        self.last_writer_note()
+                    ),
+                ));
+            }
+            if self.region.is_current(lock) {
+                break;
+            }
+            lock.write_unlock();
+            *lock = self.region.lock();
+        }
+        self.local.write_locks.fetch_add(1, AtomicOrdering::Relaxed);
+        self.region.sync_generation();
+        let repaired = lock.take_repair();
+        if repaired {
+            unsafe { self.rebuild_index() };
+        }
+        Ok(repaired)
+    }
+
+    /// The tail of a lock timeout message: who last took the write lock (most
+    /// likely the process still holding it) and how long ago.
+    fn last_writer_note(&self) -> String {
+        let pid = self.atomic_last_writer_pid().load(AtomicOrdering::Relaxed);
+        let at = self.atomic_last_write_nanos().load(AtomicOrdering::Relaxed);
+        if pid == 0 {
+            return String::new();
+        }
+        let ago = Duration::from_nanos(wall_clock_nanos().saturating_sub(at));
+        format!(
+            " (write lock held by pid {pid} since {:.3}s ago)",
+            ago.as_secs_f64()
+        )
+    }
+
+    /// `lock.read_begin` bounded by `lock_timeout`, repairing the cache if its
+    /// writer died mid-write. `None` if it timed out, which the caller treats
+    /// as a miss; counted in `lock_timeouts`. Like `write_lock`, swaps `lock`
+    /// for the current one after a `reattach`.
+    fn read_begin(&self, lock: &mut ShmSeqLock) -> Option<u64> {
+        loop {
+            let timed_out = match lock.read_begin(self.lock_timeout, &self.local.read_waits) {
+                Ok(seq) if self.region.is_current(lock) => return Some(seq),
+                Ok(_) => {
+                    *lock = self.region.lock();
+                    continue;
+                }
+                Err(ReadBlocked::DeadWriter) => self.write_lock(lock).is_err(),
+                Err(ReadBlocked::TimedOut) => true,
+            };
+            if timed_out {
+                self.bump(self.atomic_lock_timeouts(), 1);
+                return None;
+            }
+            lock.write_unlock();
+        }
+    }
+
+    /// `lock.read_validate`, also failing if `reattach` switched lock files
+    /// (and so maybe the data read) since `read_begin`. A failure is counted
+    /// in `read_retries`: the caller reads again.
+    #[inline]
+    fn read_validate(&self, lock: &ShmSeqLock, seq: u64) -> bool {
+        let valid = lock.read_validate(seq) && self.region.is_current(lock);
+        if !valid {
+            self.local
+                .read_retries
+                .fetch_add(1, AtomicOrdering::Relaxed);
+        }
+        valid
+    }
+
     fn header(&self) -> &Header {
         self.region.header()
     }
@@ -111,6 +951,12 @@ impl ShmCache {
         unsafe { (self.region.base_ptr() as *mut u8).add(layout::slab_offset(ht_cap)) }
     }
 
+    fn overflow_base_mut(&self) -> *mut u8 {
+        let h = self.header();
+        let offset = layout::overflow_offset(h.capacity, h.ht_capacity, h.slot_size);
+        unsafe { (self.region.base_ptr() as *mut u8).add(offset) }
+    }
+
     // --- Atomic stat accessors (no lock needed) ---
 
     /// Atomic reference to the `hits` field in the header.
@@ -127,79 +973,290 @@ impl ShmCache {
         unsafe { &*(self.base_ptr().add(24) as *const AtomicU64) }
     }
 
-    /// Atomic reference to the `oversize_skips` field in the header.
+    /// Add `n` to a header counter, unless this handle is read-only.
     #[inline]
-    fn atomic_oversize_skips(&self) -> &AtomicU64 {
-        // Header offset of `oversize_skips` = 32
-        unsafe { &*(self.base_ptr().add(32) as *const AtomicU64) }
+    fn bump(&self, counter: &AtomicU64, n: u64) {
+        if !self.readonly {
+            counter.fetch_add(n, AtomicOrdering::Relaxed);
+        }
     }
 
-    /// Bounds-checked hash table lookup for the optimistic read path.
-    ///
-    /// Mirrors `hashtable::ht_lookup` but adds bounds checks to guard against
-    /// torn reads during a concurrent write (the seqlock will detect the tear,
-    /// but we must not segfault before we get to `read_validate`).
-    ///
-    /// Returns `Some((slot_index, value_bytes))` on hit, `None` on miss.
+    /// Count `n` hits, in the header and in this handle's local stats.
     #[inline]
-    #[allow(clippy::too_many_arguments)]
-    unsafe fn ht_lookup_checked(
-        &self,
-        ht_base: *const u8,
-        ht_capacity: u32,
-        slab_base: *const u8,
-        slot_size: u32,
-        capacity: u32,
-        max_data_size: usize,
-        key_hash: u64,
-        key_bytes: &[u8],
-        ttl_nanos: u64,
-    ) -> OptimisticResult {
-        let mask = ht_capacity.wrapping_sub(1);
-        let mut idx = (key_hash as u32) & mask;
+    fn count_hits(&self, n: u64) {
+        self.bump(self.atomic_hits(), n);
+        self.local.hits.fetch_add(n, AtomicOrdering::Relaxed);
+    }
 
-        for _ in 0..ht_capacity {
-            let bucket = &*(ht_base.add(idx as usize * Bucket::SIZE) as *const Bucket);
+    /// Count `n` misses, in the header and in this handle's local stats.
+    #[inline]
+    fn count_misses(&self, n: u64) {
+        self.bump(self.atomic_misses(), n);
+        self.local.misses.fetch_add(n, AtomicOrdering::Relaxed);
+    }
 
-            if bucket.slot_index == BUCKET_EMPTY {
-                return OptimisticResult::Miss;
-            }
+    /// Atomic reference to the skip counter for `kind` in the header.
+    #[inline]
+    fn atomic_oversize_skips(&self, kind: Oversize) -> &AtomicU64 {
+        // Header offsets of `oversize_key_skips` = 32, `oversize_value_skips` = 256
+        let offset = match kind {
+            Oversize::Key => 32,
+            Oversize::Value => 256,
+        };
+        unsafe { &*(self.base_ptr().add(offset) as *const AtomicU64) }
+    }
 
-            if bucket.hash == key_hash {
-                let slot_index = bucket.slot_index;
+    /// Atomic reference to the `lock_timeouts` field in the header.
+    #[inline]
+    fn atomic_lock_timeouts(&self) -> &AtomicU64 {
+        unsafe {
+            &*(self
+                .base_ptr()
+                .add(std::mem::offset_of!(Header, lock_timeouts))
+                as *const AtomicU64)
+        }
+    }
 
-                // Bounds check: slot_index must be in [0, capacity)
-                if slot_index < 0 || slot_index as u32 >= capacity {
-                    return OptimisticResult::Miss; // torn read, will be caught by seqlock
-                }
+    /// Atomic reference to the `corruption_detected` field in the header.
+    #[inline]
+    fn atomic_corruption_detected(&self) -> &AtomicU64 {
+        unsafe {
+            &*(self
+                .base_ptr()
+                .add(std::mem::offset_of!(Header, corruption_detected))
+                as *const AtomicU64)
+        }
+    }
 
-                let slot_ptr = slab_base.add(slot_index as usize * slot_size as usize);
-                let slot = &*(slot_ptr as *const SlotHeader);
+    /// Atomic reference to the `corrupt_values` field in the header.
+    #[inline]
+    fn atomic_corrupt_values(&self) -> &AtomicU64 {
+        unsafe {
+            &*(self
+                .base_ptr()
+                .add(std::mem::offset_of!(Header, corrupt_values))
+                as *const AtomicU64)
+        }
+    }
 
-                if slot.occupied != 0 && slot.key_len == key_bytes.len() as u32 {
-                    let key_len = slot.key_len as usize;
-                    let value_len = slot.value_len as usize;
+    /// Atomic reference to the `skipped_fast` field in the header.
+    #[inline]
+    fn atomic_skipped_fast(&self) -> &AtomicU64 {
+        unsafe {
+            &*(self
+                .base_ptr()
+                .add(std::mem::offset_of!(Header, skipped_fast)) as *const AtomicU64)
+        }
+    }
 
-                    // Bounds check: key + value must fit in slot data area
-                    if key_len + value_len > max_data_size {
-                        return OptimisticResult::Miss; // torn read
-                    }
+    /// Atomic references to the `value_sizes` buckets in the header.
+    #[inline]
+    fn atomic_value_sizes(&self) -> &[AtomicU64; VALUE_SIZE_BUCKETS] {
+        unsafe {
+            &*(self
+                .base_ptr()
+                .add(std::mem::offset_of!(Header, value_sizes))
+                as *const [AtomicU64; VALUE_SIZE_BUCKETS])
+        }
+    }
+
+    /// Atomic reference to the `largest_value` field in the header.
+    #[inline]
+    fn atomic_largest_value(&self) -> &AtomicU64 {
+        unsafe {
+            &*(self
+                .base_ptr()
+                .add(std::mem::offset_of!(Header, largest_value))
+                as *const AtomicU64)
+        }
+    }
+
+    /// Atomic reference to the `last_writer_pid` field in the header.
+    #[inline]
+    fn atomic_last_writer_pid(&self) -> &AtomicU32 {
+        unsafe {
+            &*(self
+                .base_ptr()
+                .add(std::mem::offset_of!(Header, last_writer_pid))
+                as *const AtomicU32)
+        }
+    }
+
+    /// Atomic reference to the `last_write_nanos` field in the header.
+    #[inline]
+    fn atomic_last_write_nanos(&self) -> &AtomicU64 {
+        unsafe {
+            &*(self
+                .base_ptr()
+                .add(std::mem::offset_of!(Header, last_write_nanos))
+                as *const AtomicU64)
+        }
+    }
+
+    /// Atomic reference to the `insert_failures` field in the header.
+    #[inline]
+    fn atomic_insert_failures(&self) -> &AtomicU64 {
+        unsafe {
+            &*(self
+                .base_ptr()
+                .add(std::mem::offset_of!(Header, insert_failures))
+                as *const AtomicU64)
+        }
+    }
+
+    /// Atomic reference to the `serde_format` field in the header.
+    #[inline]
+    fn atomic_serde_format(&self) -> &AtomicU32 {
+        unsafe {
+            &*(self
+                .base_ptr()
+                .add(std::mem::offset_of!(Header, serde_format)) as *const AtomicU32)
+        }
+    }
+
+    /// Atomic reference to the `expired_reclaimed` field in the header.
+    #[inline]
+    fn atomic_expired_reclaimed(&self) -> &AtomicU64 {
+        // Header offset of `expired_reclaimed` = 88 (appended after `sieve_hand`)
+        unsafe { &*(self.base_ptr().add(88) as *const AtomicU64) }
+    }
+
+    /// Atomic reference to the `evictions` field in the header.
+    #[inline]
+    fn atomic_evictions(&self) -> &AtomicU64 {
+        // Header offset of `evictions` = 96
+        unsafe { &*(self.base_ptr().add(96) as *const AtomicU64) }
+    }
+
+    /// Atomic reference to the `expirations` field in the header.
+    #[inline]
+    fn atomic_expirations(&self) -> &AtomicU64 {
+        // Header offset of `expirations` = 104
+        unsafe { &*(self.base_ptr().add(104) as *const AtomicU64) }
+    }
+
+    /// Atomic reference to the `bytes_used` field in the header.
+    ///
+    /// Only written under the write lock; atomic so `info()` can read it lock-free.
+    #[inline]
+    fn atomic_bytes_used(&self) -> &AtomicU64 {
+        // Header offset of `bytes_used` = 112
+        unsafe { &*(self.base_ptr().add(112) as *const AtomicU64) }
+    }
+
+    /// Atomic reference to the `value_bytes_used` field in the header.
+    #[inline]
+    fn atomic_value_bytes_used(&self) -> &AtomicU64 {
+        // Header offset of `value_bytes_used` = 120
+        unsafe { &*(self.base_ptr().add(120) as *const AtomicU64) }
+    }
+
+    /// Bounds-checked hash table lookup for the optimistic read path.
+    ///
+    /// Mirrors `hashtable::ht_lookup` but adds bounds checks to guard against
+    /// torn reads during a concurrent write (the seqlock will detect the tear,
+    /// but we must not segfault before we get to `read_validate`).
+    ///
+    /// Entries past their TTL are reported as `Stale` while within
+    /// `stale_ttl_nanos` of it, and as `Expired` beyond that. With `checksums`,
+    /// a copied value is checked against the slot's checksum.
+    #[inline]
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn ht_lookup_checked(
+        &self,
+        ht_base: *const u8,
+        ht_capacity: u32,
+        slab_base: *const u8,
+        slot_size: u32,
+        capacity: u32,
+        max_data_size: usize,
+        overflow_base: *const u8,
+        overflow_size: u32,
+        overflow_count: u32,
+        key_hash: u64,
+        key_bytes: &[u8],
+        ttl_nanos: u64,
+        stale_ttl_nanos: u64,
+        checksums: bool,
+    ) -> OptimisticResult {
+        let mask = ht_capacity.wrapping_sub(1);
+        let mut idx = (key_hash as u32) & mask;
+
+        for _ in 0..ht_capacity {
+            let bucket = &*(ht_base.add(idx as usize * Bucket::SIZE) as *const Bucket);
+
+            if bucket.slot_index == BUCKET_EMPTY {
+                return OptimisticResult::Miss;
+            }
+
+            if bucket.hash == key_hash {
+                let slot_index = bucket.slot_index;
+
+                // Bounds check: slot_index must be in [0, capacity)
+                if slot_index < 0 || slot_index as u32 >= capacity {
+                    return OptimisticResult::Miss; // torn read, will be caught by seqlock
+                }
+
+                let slot_ptr = slab_base.add(slot_index as usize * slot_size as usize);
+                let slot = &*(slot_ptr as *const SlotHeader);
+
+                if slot.occupied == 1 && slot.key_len == key_bytes.len() as u32 {
+                    let key_len = slot.key_len as usize;
+                    let value_len = slot.value_len as usize;
+
+                    // Bounds check: key + value must fit the configured limits
+                    if key_len + value_len > max_data_size {
+                        return OptimisticResult::Miss; // torn read
+                    }
 
                     let stored_key =
                         std::slice::from_raw_parts(slot_ptr.add(SLOT_HEADER_SIZE), key_len);
                     if stored_key == key_bytes {
-                        // Check TTL
-                        if ttl_nanos > 0 {
-                            let now = current_time_nanos();
-                            if now.saturating_sub(slot.created_at_nanos) > ttl_nanos {
-                                return OptimisticResult::Expired { slot_index };
+                        // Check TTL (per-entry if set, else the cache-wide one)
+                        let ttl = slot_ttl(slot, ttl_nanos);
+                        let mut stale = false;
+                        let mut slide = false;
+                        if ttl > 0 {
+                            let now = self.now();
+                            let age = now.saturating_sub(slot.created_at_nanos);
+                            slide = age >= ttl / SLIDE_GRANULARITY;
+                            if age > ttl {
+                                if age - ttl > stale_ttl_nanos {
+                                    return OptimisticResult::Expired { slot_index };
+                                }
+                                stale = true;
                             }
                         }
 
-                        // Copy value bytes
-                        let value_ptr = slot_ptr.add(SLOT_HEADER_SIZE + key_len);
-                        let value = std::slice::from_raw_parts(value_ptr, value_len).to_vec();
-                        return OptimisticResult::Hit { value, slot_index };
+                        // Copy value bytes, bounds-checking the overflow chunk or
+                        // each slot of a chain
+                        let value = if slot.overflow != SLOT_NONE {
+                            overflow::read_value(
+                                overflow_base,
+                                overflow_size,
+                                overflow_count,
+                                slot.overflow,
+                                value_len,
+                            )
+                        } else {
+                            chain::read_value(
+                                slab_base, slot_size, capacity, slot_ptr, key_len, value_len,
+                            )
+                        };
+                        let Some(value) = value else {
+                            return OptimisticResult::Miss; // torn read
+                        };
+                        if checksums && slot.checksum != entry_checksum(stored_key, &value) {
+                            return OptimisticResult::Corrupt { slot_index };
+                        }
+                        if stale {
+                            return OptimisticResult::Stale { value, slot_index };
+                        }
+                        return OptimisticResult::Hit {
+                            value,
+                            slot_index,
+                            slide,
+                        };
                     }
                 }
             }
@@ -212,37 +1269,26 @@ impl ShmCache {
 
     /// Optimistic lock-free read using the seqlock.
     /// Retries if a writer was active during the read.
+    ///
+    /// Also returns the sequence the result is valid at, or `None` if the
+    /// lock timed out (and the result is a `Miss` that proves nothing).
     unsafe fn get_optimistic(
         &self,
-        lock: &ShmSeqLock,
+        lock: &mut ShmSeqLock,
         key_hash: u64,
         key_bytes: &[u8],
-    ) -> OptimisticResult {
+    ) -> (OptimisticResult, Option<u64>) {
         loop {
-            let seq = lock.read_begin();
-
-            // Read header fields we need (may be torn — that's OK, seqlock catches it)
-            let h = self.header();
-            let ht_capacity = h.ht_capacity;
-            let slot_size = h.slot_size;
-            let capacity = h.capacity;
-            let ttl_nanos = h.ttl_nanos;
-            let max_data_size = (h.max_key_size + h.max_value_size) as usize;
+            let Some(seq) = self.read_begin(lock) else {
+                return (OptimisticResult::Miss, None);
+            };
+            self.region.sync_generation();
 
-            let result = self.ht_lookup_checked(
-                self.ht_base(),
-                ht_capacity,
-                self.slab_base(),
-                slot_size,
-                capacity,
-                max_data_size,
-                key_hash,
-                key_bytes,
-                ttl_nanos,
-            );
+            // Header fields may be torn — that's OK, the seqlock catches it
+            let result = self.lookup_unvalidated(key_hash, key_bytes);
 
-            if lock.read_validate(seq) {
-                return result;
+            if self.read_validate(lock, seq) {
+                return (result, Some(seq));
             }
             // Writer was active — retry
         }
@@ -255,270 +1301,1837 @@ impl ShmCache {
     /// a concurrent writer reusing the slot, so the field is atomic to avoid a data
     /// race (#37). The store is idempotent; if the slot was just evicted/reused, the
     /// worst case is a benign `visited=1` on a fresh entry (one extra SIEVE round).
+    ///
+    /// Entries in their stale window are returned as hits; use
+    /// `get_or_claim_refresh` on paths that can recompute them.
     pub fn get(&self, key_hash: u64, key_bytes: &[u8]) -> ShmGetResult {
-        let lock = self.lock();
+        self.get_inner(key_hash, key_bytes, false)
+    }
+
+    /// Like `get`, but a stale entry is reported as a miss to exactly one
+    /// caller across all processes — the one that wins the slot's
+    /// `refresh_claimed` CAS — and as a hit to everyone else.
+    ///
+    /// A caller handed `Miss` must either insert the key (which clears the
+    /// claim) or call `release_refresh` so another caller can try.
+    pub fn get_or_claim_refresh(&self, key_hash: u64, key_bytes: &[u8]) -> ShmGetResult {
+        self.get_inner(key_hash, key_bytes, true)
+    }
 
-        let result = unsafe { self.get_optimistic(&lock, key_hash, key_bytes) };
+    fn get_inner(&self, key_hash: u64, key_bytes: &[u8], claim: bool) -> ShmGetResult {
+        let mut lock = self.lock();
+
+        let (result, seq) = unsafe { self.get_optimistic(&mut lock, key_hash, key_bytes) };
 
         match result {
-            OptimisticResult::Hit { value, slot_index } => {
-                // SIEVE: mark as visited — lock-free, idempotent atomic store.
-                // ponytail: accept the benign policy skew on a racing reuse; gating the
-                // store on a key re-check would add a racy non-atomic compare for no
-                // correctness gain (returned value was already seqlock-validated).
-                unsafe {
-                    let slot_size = self.header().slot_size;
-                    let slot_ptr = self
-                        .slab_base()
-                        .add(slot_index as usize * slot_size as usize);
-                    let slot = &*(slot_ptr as *const SlotHeader);
-                    slot.visited.store(1, AtomicOrdering::Relaxed);
+            OptimisticResult::Stale { slot_index, .. }
+                if claim
+                    && unsafe {
+                        self.claim_refresh(&mut lock, slot_index, key_hash, key_bytes)
+                    } =>
+            {
+                self.count_misses(1);
+                ShmGetResult::Miss(InsertToken::default())
+            }
+            OptimisticResult::Hit {
+                value,
+                slot_index,
+                slide,
+            } => {
+                unsafe { self.mark_visited(slot_index) };
+
+                // Sliding TTL: lock-free readers compare against created_at_nanos,
+                // so restarting the clock takes the write lock briefly, and only
+                // once in a while for a hot key (`SLIDE_GRANULARITY`).
+                // Both this and removing an expired entry below are skipped if
+                // the lock times out: the lookup itself already succeeded.
+                if slide && self.header().sliding_ttl != 0 && self.write_lock(&mut lock).is_ok() {
+                    unsafe { self.touch_inner(key_hash, key_bytes) };
+                    lock.write_unlock();
                 }
 
                 // Stats: atomic, no lock needed
-                self.atomic_hits().fetch_add(1, AtomicOrdering::Relaxed);
+                self.count_hits(1);
+                ShmGetResult::Hit(value)
+            }
+            OptimisticResult::Stale { value, slot_index } => {
+                unsafe { self.mark_visited(slot_index) };
+                self.count_hits(1);
                 ShmGetResult::Hit(value)
             }
             OptimisticResult::Miss => {
-                self.atomic_misses().fetch_add(1, AtomicOrdering::Relaxed);
-                ShmGetResult::Miss
+                self.count_misses(1);
+                ShmGetResult::Miss(InsertToken { absent_at: seq })
             }
             OptimisticResult::Expired { slot_index } => {
                 // Need write lock to remove the expired entry
-                lock.write_lock();
-                unsafe {
-                    // Re-verify the slot is still the same expired entry
-                    let slot_size = self.header().slot_size;
-                    let slot_ptr = self
-                        .slab_base()
-                        .add(slot_index as usize * slot_size as usize);
-                    let slot = &*(slot_ptr as *const SlotHeader);
-                    if slot.occupied != 0 && slot.key_hash == key_hash {
-                        // Re-read key bytes to pass to remove_slot
-                        let key_len = slot.key_len as usize;
-                        let stored_key =
-                            std::slice::from_raw_parts(slot_ptr.add(SLOT_HEADER_SIZE), key_len);
-                        // Only remove if key actually matches (slot could have been reused)
-                        if stored_key == key_bytes {
-                            self.remove_slot(slot_index, key_bytes);
-                        }
-                    }
+                if self.write_lock(&mut lock).is_ok() {
+                    unsafe { self.remove_expired(slot_index, key_hash, key_bytes) };
+                    lock.write_unlock();
                 }
-                lock.write_unlock();
 
-                self.atomic_misses().fetch_add(1, AtomicOrdering::Relaxed);
-                ShmGetResult::Miss
+                self.count_misses(1);
+                ShmGetResult::Miss(InsertToken::default())
             }
-        }
-    }
+            OptimisticResult::Corrupt { slot_index } => {
+                self.bump(self.atomic_corruption_detected(), 1);
+                if self.write_lock(&mut lock).is_ok() {
+                    unsafe { self.remove_corrupt(slot_index, key_hash, key_bytes) };
+                    lock.write_unlock();
+                }
 
-    /// Insert a key-value pair. Evicts if necessary.
-    pub fn insert(&self, key_hash: u64, key_bytes: &[u8], value_bytes: &[u8]) {
-        let lock = self.lock();
-        lock.write_lock();
-        unsafe { self.insert_inner(key_hash, key_bytes, value_bytes) };
-        lock.write_unlock();
+                self.count_misses(1);
+                ShmGetResult::Miss(InsertToken::default())
+            }
+        }
     }
 
-    unsafe fn insert_inner(&self, key_hash: u64, key_bytes: &[u8], value_bytes: &[u8]) {
-        let h = self.header();
-        let ht_cap = h.ht_capacity;
-        let slot_size = h.slot_size;
-        let capacity = h.capacity;
+    /// Look up many keys in one optimistic seqlock pass.
+    ///
+    /// Results are aligned with `keys`. All lookups share a single
+    /// `read_begin`/`read_validate` pair; if a writer tears that pass, the batch
+    /// falls back to per-key validated reads instead of retrying the whole pass,
+    /// so a busy writer cannot starve a large batch. Expired entries found in the
+    /// pass are removed (and, with a sliding TTL, hits have their clocks
+    /// restarted) under one write-lock acquisition, and stats are bumped once
+    /// per batch.
+    pub fn get_batch(&self, keys: &[(u64, &[u8])]) -> Vec<Option<Vec<u8>>> {
+        let mut lock = self.lock();
 
-        // Check if key already exists — update value in place
-        let existing = hashtable::ht_lookup(
-            self.ht_base(),
-            ht_cap,
-            self.slab_base(),
-            slot_size,
-            key_hash,
-            key_bytes,
-        );
+        let Some(seq) = self.read_begin(&mut lock) else {
+            self.count_misses(keys.len() as u64);
+            return vec![None; keys.len()];
+        };
+        self.region.sync_generation();
+        let mut results: Vec<OptimisticResult> = keys
+            .iter()
+            .map(|&(key_hash, key_bytes)| unsafe { self.lookup_unvalidated(key_hash, key_bytes) })
+            .collect();
+        if !self.read_validate(&lock, seq) {
+            results = keys
+                .iter()
+                .map(|&(key_hash, key_bytes)| unsafe {
+                    self.get_optimistic(&mut lock, key_hash, key_bytes).0
+                })
+                .collect();
+        }
 
-        if let Some(idx) = existing {
-            // Update value in-place
-            let slot_ptr = self.slab_base_mut().add(idx as usize * slot_size as usize);
-            let slot = &mut *(slot_ptr as *mut SlotHeader);
-            slot.value_len = value_bytes.len() as u32;
-            slot.created_at_nanos = current_time_nanos();
-            slot.visited.store(1, AtomicOrdering::Relaxed);
+        let sliding = self.header().sliding_ttl != 0;
+        let mut hits = 0u64;
+        let mut expired = Vec::new();
+        let mut corrupt = Vec::new();
+        let mut slide = Vec::new();
+        let values = results
+            .into_iter()
+            .enumerate()
+            .map(|(i, result)| match result {
+                OptimisticResult::Hit {
+                    value,
+                    slot_index,
+                    slide: due,
+                } => {
+                    unsafe { self.mark_visited(slot_index) };
+                    hits += 1;
+                    if sliding && due {
+                        slide.push(i);
+                    }
+                    Some(value)
+                }
+                OptimisticResult::Stale { value, slot_index } => {
+                    unsafe { self.mark_visited(slot_index) };
+                    hits += 1;
+                    Some(value)
+                }
+                OptimisticResult::Miss => None,
+                OptimisticResult::Expired { slot_index } => {
+                    expired.push((i, slot_index));
+                    None
+                }
+                OptimisticResult::Corrupt { slot_index } => {
+                    corrupt.push((i, slot_index));
+                    None
+                }
+            })
+            .collect();
 
-            let value_dest = slot_ptr.add(SLOT_HEADER_SIZE + slot.key_len as usize);
-            std::ptr::copy_nonoverlapping(value_bytes.as_ptr(), value_dest, value_bytes.len());
-            return;
+        self.bump(self.atomic_corruption_detected(), corrupt.len() as u64);
+        if (!expired.is_empty() || !corrupt.is_empty() || !slide.is_empty())
+            && self.write_lock(&mut lock).is_ok()
+        {
+            for (i, slot_index) in expired {
+                let (key_hash, key_bytes) = keys[i];
+                unsafe { self.remove_expired(slot_index, key_hash, key_bytes) };
+            }
+            for (i, slot_index) in corrupt {
+                let (key_hash, key_bytes) = keys[i];
+                unsafe { self.remove_corrupt(slot_index, key_hash, key_bytes) };
+            }
+            for i in slide {
+                let (key_hash, key_bytes) = keys[i];
+                unsafe { self.touch_inner(key_hash, key_bytes) };
+            }
+            lock.write_unlock();
         }
 
-        // Allocate a slot
-        let header = self.header_mut();
-        let slot_idx = if header.free_head != SLOT_NONE {
-            // Pop from free list
-            let idx = header.free_head;
-            let free_slot =
-                &*(self.slab_base().add(idx as usize * slot_size as usize) as *const SlotHeader);
-            header.free_head = free_slot.next;
-            idx
-        } else if header.current_size >= capacity {
-            // Need to evict — SIEVE picks the victim
-            let evict_idx = ordering::sieve_evict(header, self.slab_base_mut(), slot_size);
-            if evict_idx == SLOT_NONE {
-                return; // shouldn't happen
-            }
-
-            // Remove evicted entry from hash table
-            let evict_slot_ptr = self
-                .slab_base()
-                .add(evict_idx as usize * slot_size as usize);
-            let evict_slot = &*(evict_slot_ptr as *const SlotHeader);
-            let evict_key = std::slice::from_raw_parts(
-                evict_slot_ptr.add(SLOT_HEADER_SIZE),
-                evict_slot.key_len as usize,
-            );
-
-            hashtable::ht_remove(
-                self.ht_base_mut(),
-                ht_cap,
-                self.slab_base(),
-                slot_size,
-                evict_slot.key_hash,
-                evict_key,
-            );
+        self.count_hits(hits);
+        self.count_misses(keys.len() as u64 - hits);
+        values
+    }
 
-            ordering::list_remove(header, self.slab_base_mut(), slot_size, evict_idx);
-            header.current_size -= 1;
+    /// One bounds-checked lookup against the current header, without seqlock
+    /// validation. Caller must validate the enclosing read section.
+    ///
+    /// Every pointer is derived from one snapshot of the mapping base, so a
+    /// concurrent remap can't pair one file's header with another's slab.
+    unsafe fn lookup_unvalidated(&self, key_hash: u64, key_bytes: &[u8]) -> OptimisticResult {
+        let base = self.base_ptr();
+        let h = &*(base as *const Header);
+        self.ht_lookup_checked(
+            base.add(layout::ht_offset()),
+            h.ht_capacity,
+            base.add(layout::slab_offset(h.ht_capacity)),
+            h.slot_size,
+            h.capacity,
+            (h.max_key_size + value_limit(h)) as usize,
+            base.add(layout::overflow_offset(
+                h.capacity,
+                h.ht_capacity,
+                h.slot_size,
+            )),
+            h.overflow_size,
+            h.overflow_count,
+            key_hash,
+            key_bytes,
+            h.ttl_nanos,
+            h.stale_ttl_nanos,
+            h.checksums != 0,
+        )
+    }
 
-            evict_idx
-        } else {
-            // This shouldn't happen if free list is properly maintained
+    /// SIEVE: mark a hit slot as visited — lock-free, idempotent atomic store.
+    /// A slot already marked is only loaded, so repeated hits on a hot key
+    /// don't keep pulling its cache line away from other cores and processes.
+    /// A read-only handle leaves eviction order to the processes that write.
+    ///
+    /// ponytail: accept the benign policy skew on a racing reuse; gating the
+    /// store on a key re-check would add a racy non-atomic compare for no
+    /// correctness gain (returned value was already seqlock-validated).
+    unsafe fn mark_visited(&self, slot_index: i32) {
+        if self.readonly {
             return;
-        };
+        }
+        if let Some(slot) = self.slot_checked(slot_index) {
+            if slot.visited.load(AtomicOrdering::Relaxed) == 0 {
+                slot.visited.store(1, AtomicOrdering::Relaxed);
+            }
+        }
+    }
 
-        // Write the new entry into the slot
-        let slot_ptr = self
-            .slab_base_mut()
-            .add(slot_idx as usize * slot_size as usize);
-        let slot = &mut *(slot_ptr as *mut SlotHeader);
-        slot.occupied = 1;
-        slot.key_hash = key_hash;
-        slot.key_len = key_bytes.len() as u32;
-        slot.value_len = value_bytes.len() as u32;
-        slot.created_at_nanos = current_time_nanos();
-        slot.visited.store(0, AtomicOrdering::Relaxed);
-        slot.prev = SLOT_NONE;
-        slot.next = SLOT_NONE;
-        slot.unique_id = self.next_unique_id.fetch_add(1, AtomicOrdering::Relaxed);
+    /// Try to claim the recompute of a stale entry. Returns true for exactly one
+    /// caller per stale period.
+    ///
+    /// Readers that see the flag already set bail out lock-free; the CAS itself
+    /// runs under the write lock, after re-checking that the slot still holds
+    /// this key and is still stale — the slot may have been refreshed or reused
+    /// since the optimistic read.
+    unsafe fn claim_refresh(
+        &self,
+        lock: &mut ShmSeqLock,
+        slot_index: i32,
+        key_hash: u64,
+        key_bytes: &[u8],
+    ) -> bool {
+        match self.slot_checked(slot_index) {
+            Some(slot) if slot.refresh_claimed.load(AtomicOrdering::Relaxed) == 0 => {}
+            _ => return false,
+        }
 
-        // Copy key bytes
-        let key_dest = slot_ptr.add(SLOT_HEADER_SIZE);
-        std::ptr::copy_nonoverlapping(key_bytes.as_ptr(), key_dest, key_bytes.len());
+        // Not claimed if the lock times out: the caller serves the stale value.
+        if self.write_lock(lock).is_err() {
+            return false;
+        }
+        let Some(slot) = self.slot_checked(slot_index) else {
+            lock.write_unlock();
+            return false;
+        };
+        let h = self.header();
+        let still_stale = slot.occupied == 1
+            && slot.key_hash == key_hash
+            && self.slot_key(slot_index) == key_bytes
+            && {
+                let ttl = slot_ttl(slot, h.ttl_nanos);
+                ttl > 0 && self.now().saturating_sub(slot.created_at_nanos) > ttl
+            };
+        let claimed = still_stale
+            && slot
+                .refresh_claimed
+                .compare_exchange(0, 1, AtomicOrdering::Relaxed, AtomicOrdering::Relaxed)
+                .is_ok();
+        lock.write_unlock();
+        claimed
+    }
 
-        // Copy value bytes
-        let value_dest = key_dest.add(key_bytes.len());
-        std::ptr::copy_nonoverlapping(value_bytes.as_ptr(), value_dest, value_bytes.len());
+    /// Give up a refresh claimed via `get_or_claim_refresh` without inserting
+    /// (e.g. the recompute raised), so the next stale read can claim it.
+    pub fn release_refresh(&self, key_hash: u64, key_bytes: &[u8]) -> std::io::Result<()> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        unsafe {
+            let h = self.header();
+            if let Some(idx) = hashtable::ht_lookup(
+                self.ht_base(),
+                h.ht_capacity,
+                self.slab_base(),
+                h.slot_size,
+                key_hash,
+                key_bytes,
+            ) {
+                self.slot(idx)
+                    .refresh_claimed
+                    .store(0, AtomicOrdering::Relaxed);
+            }
+        }
+        lock.write_unlock();
+        Ok(())
+    }
 
-        // Insert into hash table
-        hashtable::ht_insert(self.ht_base_mut(), ht_cap, key_hash, slot_idx);
+    /// Slot header at `slot_index`.
+    unsafe fn slot(&self, slot_index: i32) -> &SlotHeader {
+        let slot_size = self.header().slot_size;
+        &*(self
+            .slab_base()
+            .add(slot_index as usize * slot_size as usize) as *const SlotHeader)
+    }
 
-        // Add to eviction list (SIEVE: new entries go to tail, unvisited)
-        let header = self.header_mut();
-        ordering::list_push_tail(header, self.slab_base_mut(), slot_size, slot_idx);
-        header.current_size += 1;
+    /// Slot header at `slot_index`, or `None` if the index is out of range for
+    /// the current mapping — an index read before a resize may no longer be.
+    /// Safe to call without the lock: header and slab come from one base.
+    unsafe fn slot_checked(&self, slot_index: i32) -> Option<&SlotHeader> {
+        let base = self.base_ptr();
+        let h = &*(base as *const Header);
+        if slot_index < 0 || slot_index as u32 >= h.capacity {
+            return None;
+        }
+        let slab = base.add(layout::slab_offset(h.ht_capacity));
+        Some(&*(slab.add(slot_index as usize * h.slot_size as usize) as *const SlotHeader))
     }
 
-    /// Remove a specific slot.
-    unsafe fn remove_slot(&self, slot_idx: i32, key_bytes: &[u8]) {
-        let h = self.header();
-        let ht_cap = h.ht_capacity;
-        let slot_size = h.slot_size;
+    /// Stored key bytes of the slot at `slot_index`.
+    unsafe fn slot_key(&self, slot_index: i32) -> &[u8] {
+        let slot = self.slot(slot_index);
+        let key_ptr = (slot as *const SlotHeader as *const u8).add(SLOT_HEADER_SIZE);
+        std::slice::from_raw_parts(key_ptr, slot.key_len as usize)
+    }
 
-        let slot_ptr = self.slab_base().add(slot_idx as usize * slot_size as usize);
+    /// Remove an entry the optimistic read found expired, if the slot still
+    /// holds that key (it may have been reused since the read).
+    ///
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn remove_expired(&self, slot_index: i32, key_hash: u64, key_bytes: &[u8]) {
+        // Re-verify the slot is still the same expired entry
+        if slot_index as u32 >= self.header().capacity {
+            return; // read before a resize shrank the cache
+        }
+        let slot_size = self.header().slot_size;
+        let slot_ptr = self
+            .slab_base()
+            .add(slot_index as usize * slot_size as usize);
         let slot = &*(slot_ptr as *const SlotHeader);
-        let key_hash = slot.key_hash;
-
-        // Remove from hash table
-        hashtable::ht_remove(
-            self.ht_base_mut(),
-            ht_cap,
-            self.slab_base(),
-            slot_size,
-            key_hash,
-            key_bytes,
-        );
-
-        // Remove from eviction list
-        let header = self.header_mut();
-        ordering::list_remove(header, self.slab_base_mut(), slot_size, slot_idx);
+        if slot.occupied == 1 && slot.key_hash == key_hash {
+            // Re-read key bytes to confirm the slot still holds this key
+            let key_len = slot.key_len as usize;
+            let stored_key = std::slice::from_raw_parts(slot_ptr.add(SLOT_HEADER_SIZE), key_len);
+            // Only remove if key actually matches (slot could have been reused)
+            if stored_key == key_bytes {
+                self.remove_slot(slot_index);
+                self.atomic_expirations()
+                    .fetch_add(1, AtomicOrdering::Relaxed);
+            }
+        }
+    }
 
-        // Mark slot as free and push to free list
-        let slot = &mut *(self
-            .slab_base_mut()
-            .add(slot_idx as usize * slot_size as usize)
-            as *mut SlotHeader);
-        slot.occupied = 0;
-        slot.next = header.free_head;
-        slot.prev = SLOT_NONE;
-        header.free_head = slot_idx;
-        header.current_size -= 1;
+    /// Remove an entry the optimistic read found corrupt, if the slot still
+    /// holds that key and still fails its checksum (it may have been
+    /// rewritten since the read).
+    ///
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn remove_corrupt(&self, slot_index: i32, key_hash: u64, key_bytes: &[u8]) {
+        if slot_index as u32 >= self.header().capacity {
+            return; // read before a resize shrank the cache
+        }
+        let slot = self.slot(slot_index);
+        let slot_ptr = slot as *const SlotHeader as *const u8;
+        if slot.occupied != 1 || slot.key_hash != key_hash || self.slot_key(slot_index) != key_bytes
+        {
+            return;
+        }
+        // A chain or overflow index scribbled out of range reads as `None`:
+        // that is corrupt too.
+        let intact = self
+            .read_entry_value(slot_ptr)
+            .is_some_and(|value| slot.checksum == entry_checksum(key_bytes, &value));
+        if !intact {
+            self.remove_slot(slot_index);
+        }
     }
 
-    /// Clear the entire cache.
-    pub fn clear(&self) {
-        let lock = self.lock();
-        lock.write_lock();
-        unsafe { self.clear_inner() };
-        lock.write_unlock();
+    /// Keep a copy of every live entry this handle evicts to make room, for
+    /// `take_evicted`. Evictions by other processes aren't seen.
+    pub fn keep_evicted(&mut self) {
+        self.evicted = Some(ForkLock::new(Mutex::new(Vec::new())));
     }
 
-    unsafe fn clear_inner(&self) {
-        let h = self.header();
-        let ht_cap = h.ht_capacity;
-        let slot_size = h.slot_size;
-        let capacity = h.capacity;
+    /// The `(key_bytes, value_bytes)` of the entries evicted since the last
+    /// call, oldest first; always empty without `keep_evicted`. Call it after
+    /// the write that evicted them, once the lock is released.
+    pub fn take_evicted(&self) -> Vec<Evicted> {
+        self.evicted
+            .as_ref()
+            .map_or_else(Vec::new, |evicted| std::mem::take(&mut *evicted.lock()))
+    }
 
-        // Clear hash table
-        hashtable::ht_clear(self.ht_base_mut(), ht_cap);
+    /// Insert a key-value pair. Evicts if necessary.
+    ///
+    /// `ttl_nanos` overrides the cache-wide TTL for this entry; `None` (or
+    /// `Some(0)`) falls back to it.
+    pub fn insert(
+        &self,
+        key_hash: u64,
+        key_bytes: &[u8],
+        value_bytes: &[u8],
+        ttl_nanos: Option<u64>,
+    ) -> std::io::Result<()> {
+        self.insert_with_token(
+            InsertToken::default(),
+            key_hash,
+            key_bytes,
+            value_bytes,
+            ttl_nanos,
+            &[],
+        )
+    }
 
-        // Reset all slots to free list
-        for i in 0..capacity as usize {
-            let slot_ptr = self.slab_base_mut().add(i * slot_size as usize);
-            let slot = &mut *(slot_ptr as *mut SlotHeader);
-            slot.occupied = 0;
-            slot.prev = SLOT_NONE;
-            slot.next = if i + 1 < capacity as usize {
-                (i + 1) as i32
+    /// `insert` after a lookup that missed and handed out `token`.
+    ///
+    /// If no write has happened since, the key is known to be absent and the
+    /// hash table isn't probed for it again. Otherwise another process may
+    /// have inserted it meanwhile, so this falls back to `insert`, updating
+    /// that entry in place rather than adding a second one.
+    ///
+    /// `tags` (at most `MAX_TAGS` hashes, none 0) replace the entry's tags.
+    pub fn insert_with_token(
+        &self,
+        token: InsertToken,
+        key_hash: u64,
+        key_bytes: &[u8],
+        value_bytes: &[u8],
+        ttl_nanos: Option<u64>,
+        tags: &[u64],
+    ) -> std::io::Result<()> {
+        let mut lock = self.lock();
+        let repaired = self.write_lock(&mut lock)?;
+        // Taking the lock made the sequence odd: one past the read's, unless
+        // some write (or a resize) came in between. A repair can leave it
+        // there too, after finishing a dead writer's insert.
+        let absent = !repaired
+            && token
+                .absent_at
+                .is_some_and(|seq| lock.sequence() == seq + 1);
+        let ttl_nanos = ttl_nanos.unwrap_or(0);
+        unsafe {
+            if absent {
+                self.insert_entry(
+                    None,
+                    key_hash,
+                    key_bytes,
+                    value_bytes,
+                    ttl_nanos,
+                    Some(tags),
+                );
             } else {
-                SLOT_NONE
-            };
+                self.insert_inner(key_hash, key_bytes, value_bytes, ttl_nanos, Some(tags));
+            }
+        }
+        lock.write_unlock();
+        self.maybe_sync();
+        Ok(())
+    }
+
+    /// Return the live value for a key, or insert `value_bytes` if it is absent
+    /// or expired — in one write-lock critical section, so two processes racing
+    /// on the same key agree on a single winner.
+    ///
+    /// Returns `(value, inserted)`: the existing value and `false`, or a copy of
+    /// `value_bytes` and `true`. A found entry counts as a hit (and is marked
+    /// visited); an insert counts as a miss.
+    pub fn get_or_insert(
+        &self,
+        key_hash: u64,
+        key_bytes: &[u8],
+        value_bytes: &[u8],
+    ) -> std::io::Result<(Vec<u8>, bool)> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        let existing = unsafe { self.get_live_locked(key_hash, key_bytes) };
+        if existing.is_none() {
+            unsafe { self.insert_inner(key_hash, key_bytes, value_bytes, 0, None) };
+        } else if self.header().sliding_ttl != 0 {
+            unsafe { self.touch_inner(key_hash, key_bytes) };
+        }
+        lock.write_unlock();
+        self.maybe_sync();
+
+        Ok(match existing {
+            Some(value) => {
+                self.count_hits(1);
+                (value, false)
+            }
+            None => {
+                self.count_misses(1);
+                (value_bytes.to_vec(), true)
+            }
+        })
+    }
+
+    /// Overwrite the value for a key only if it currently equals `expected`,
+    /// comparing the stored bytes and writing in one write-lock critical
+    /// section. `expected = None` means "only if absent": the value is inserted
+    /// when there is no live entry. Returns whether the write happened.
+    ///
+    /// An expired entry counts as absent. A successful swap restarts the
+    /// entry's TTL, as `insert` does. Hit/miss stats are not affected.
+    pub fn compare_and_swap(
+        &self,
+        key_hash: u64,
+        key_bytes: &[u8],
+        expected: Option<&[u8]>,
+        new_bytes: &[u8],
+    ) -> std::io::Result<bool> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        let swapped = unsafe {
+            let current = self.get_live_locked(key_hash, key_bytes);
+            let matches = current.as_deref() == expected;
+            if matches {
+                self.insert_inner(key_hash, key_bytes, new_bytes, 0, None);
+            }
+            matches
+        };
+        lock.write_unlock();
+        if swapped {
+            self.maybe_sync();
+        }
+        Ok(swapped)
+    }
+
+    /// Copy out the value of a live (present, unexpired) entry and mark it
+    /// visited. Expired entries are left in place for the caller to overwrite.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn get_live_locked(&self, key_hash: u64, key_bytes: &[u8]) -> Option<Vec<u8>> {
+        let h = self.header();
+        let slot_size = h.slot_size;
+        let ttl_nanos = h.ttl_nanos;
+        let idx = hashtable::ht_lookup(
+            self.ht_base(),
+            h.ht_capacity,
+            self.slab_base(),
+            slot_size,
+            key_hash,
+            key_bytes,
+        )?;
+
+        let slot_ptr = self.slab_base().add(idx as usize * slot_size as usize);
+        let slot = &*(slot_ptr as *const SlotHeader);
+        let ttl = slot_ttl(slot, ttl_nanos);
+        if ttl > 0 && self.now().saturating_sub(slot.created_at_nanos) > ttl {
+            return None;
+        }
+        slot.visited.store(1, AtomicOrdering::Relaxed);
+        self.read_entry_value(slot_ptr)
+    }
+
+    /// Reset a live entry's age to now, extending its TTL without touching its
+    /// value, SIEVE state, or stats. Returns false if the key is absent or
+    /// already past its TTL (including entries only being served stale).
+    pub fn touch(&self, key_hash: u64, key_bytes: &[u8]) -> std::io::Result<bool> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        let touched = unsafe { self.touch_inner(key_hash, key_bytes) };
+        lock.write_unlock();
+        Ok(touched)
+    }
+
+    unsafe fn touch_inner(&self, key_hash: u64, key_bytes: &[u8]) -> bool {
+        let h = self.header();
+        let slot_size = h.slot_size;
+        let Some(idx) = hashtable::ht_lookup(
+            self.ht_base(),
+            h.ht_capacity,
+            self.slab_base(),
+            slot_size,
+            key_hash,
+            key_bytes,
+        ) else {
+            return false;
+        };
+
+        let slot =
+            &mut *(self.slab_base_mut().add(idx as usize * slot_size as usize) as *mut SlotHeader);
+        let now = self.now();
+        let ttl = slot_ttl(slot, h.ttl_nanos);
+        if ttl > 0 && now.saturating_sub(slot.created_at_nanos) > ttl {
+            return false;
         }
+        slot.created_at_nanos = now;
+        true
+    }
+
+    /// Remove the entry for a key. Returns false if there was none. An entry
+    /// removed this way counts as neither an eviction nor an expiration.
+    pub fn remove(&self, key_hash: u64, key_bytes: &[u8]) -> std::io::Result<bool> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        let removed = unsafe {
+            let h = self.header();
+            let idx = hashtable::ht_lookup(
+                self.ht_base(),
+                h.ht_capacity,
+                self.slab_base(),
+                h.slot_size,
+                key_hash,
+                key_bytes,
+            );
+            if let Some(idx) = idx {
+                self.remove_slot(idx);
+            }
+            idx.is_some()
+        };
+        lock.write_unlock();
+        self.maybe_sync();
+        Ok(removed)
+    }
 
+    /// Remove every entry tagged with `tag_hash` (see `insert_with_token`), in
+    /// one sweep of the slab under the write lock. Returns how many were
+    /// removed; like `remove`, they count as neither evictions nor expirations.
+    pub fn invalidate_tag(&self, tag_hash: u64) -> std::io::Result<usize> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        let removed = unsafe {
+            let mut removed = 0;
+            let mut idx = self.header().list_head;
+            while idx != SLOT_NONE {
+                let slot = self.slot(idx);
+                let next = slot.next;
+                if slot.tags.contains(&tag_hash) {
+                    self.remove_slot(idx);
+                    removed += 1;
+                }
+                idx = next;
+            }
+            removed
+        };
+        lock.write_unlock();
+        if removed > 0 {
+            self.maybe_sync();
+        }
+        Ok(removed)
+    }
+
+    /// Add `delta` to the integer stored under a key and return the result,
+    /// in one write-lock critical section so concurrent callers in any process
+    /// never lose an update.
+    ///
+    /// A missing or expired entry is created as `initial + delta`. A live one is
+    /// updated in place: the encoded length never changes, so the hash table,
+    /// eviction order and the entry's TTL are left alone (with `sliding_ttl` the
+    /// age is reset, as on a hit). Overflow is an error rather than saturating,
+    /// and leaves the entry untouched. Hit/miss stats are not affected.
+    pub fn incr(
+        &self,
+        key_hash: u64,
+        key_bytes: &[u8],
+        delta: i64,
+        initial: i64,
+    ) -> std::io::Result<Result<i64, IncrError>> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        let result = unsafe { self.incr_inner(key_hash, key_bytes, delta, initial) };
+        lock.write_unlock();
+        self.maybe_sync();
+        Ok(result)
+    }
+
+    unsafe fn incr_inner(
+        &self,
+        key_hash: u64,
+        key_bytes: &[u8],
+        delta: i64,
+        initial: i64,
+    ) -> Result<i64, IncrError> {
+        let h = self.header();
+        let slot_size = h.slot_size;
+        let idx = hashtable::ht_lookup(
+            self.ht_base(),
+            h.ht_capacity,
+            self.slab_base(),
+            slot_size,
+            key_hash,
+            key_bytes,
+        );
+
+        if let Some(idx) = idx {
+            let slot_ptr = self.slab_base_mut().add(idx as usize * slot_size as usize);
+            let slot = &mut *(slot_ptr as *mut SlotHeader);
+            let now = self.now();
+            let ttl = slot_ttl(slot, h.ttl_nanos);
+            if ttl == 0 || now.saturating_sub(slot.created_at_nanos) <= ttl {
+                let value = self.read_entry_value(slot_ptr).unwrap_or_default();
+                let current = crate::serde::decode_i64(&value).ok_or(IncrError::NotAnInt)?;
+                let updated = current.checked_add(delta).ok_or(IncrError::Overflow)?;
+                let encoded = crate::serde::encode_i64(updated);
+                if encoded.len() != value.len() {
+                    // Crossed between the 2- and 9-byte forms: store it like
+                    // any resized value, keeping its TTL and creation time.
+                    let (created_at, ttl_nanos) = (slot.created_at_nanos, slot.ttl_nanos);
+                    self.insert_entry(Some(idx), key_hash, key_bytes, &encoded, ttl_nanos, None);
+                    if h.sliding_ttl == 0 {
+                        if let Some(idx) = hashtable::ht_lookup(
+                            self.ht_base(),
+                            h.ht_capacity,
+                            self.slab_base(),
+                            slot_size,
+                            key_hash,
+                            key_bytes,
+                        ) {
+                            let slot_ptr =
+                                self.slab_base_mut().add(idx as usize * slot_size as usize);
+                            (*(slot_ptr as *mut SlotHeader)).created_at_nanos = created_at;
+                        }
+                    }
+                    return Ok(updated);
+                }
+
+                // Same encoded length, so the value fits the storage it already has.
+                self.header_mut().pending_slot = idx;
+                self.write_entry_value(slot_ptr, &encoded);
+                if h.sliding_ttl != 0 {
+                    slot.created_at_nanos = now;
+                }
+                slot.visited.store(1, AtomicOrdering::Relaxed);
+                self.header_mut().pending_slot = SLOT_NONE;
+                return Ok(updated);
+            }
+        }
+
+        let created = initial.checked_add(delta).ok_or(IncrError::Overflow)?;
+        self.insert_inner(
+            key_hash,
+            key_bytes,
+            &crate::serde::encode_i64(created),
+            0,
+            None,
+        );
+        Ok(created)
+    }
+
+    /// Insert many key-value pairs under a single write-lock acquisition.
+    ///
+    /// Each pair goes through the same path as `insert` (in-place update,
+    /// free-list pop, expired-slot reclaim, or SIEVE eviction), in order, so a
+    /// later pair with the same key wins.
+    pub fn insert_batch(&self, entries: &[(u64, &[u8], &[u8])]) -> std::io::Result<()> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        for &(key_hash, key_bytes, value_bytes) in entries {
+            unsafe { self.insert_inner(key_hash, key_bytes, value_bytes, 0, Some(&[])) };
+        }
+        lock.write_unlock();
+        self.maybe_sync();
+        Ok(())
+    }
+
+    /// `tags` replace the entry's tags; `None` keeps those of an existing
+    /// entry (and gives a new one none).
+    unsafe fn insert_inner(
+        &self,
+        key_hash: u64,
+        key_bytes: &[u8],
+        value_bytes: &[u8],
+        ttl_nanos: u64,
+        tags: Option<&[u64]>,
+    ) {
+        // Check if key already exists — update value in place
+        let h = self.header();
+        let existing = hashtable::ht_lookup(
+            self.ht_base(),
+            h.ht_capacity,
+            self.slab_base(),
+            h.slot_size,
+            key_hash,
+            key_bytes,
+        );
+        self.insert_entry(existing, key_hash, key_bytes, value_bytes, ttl_nanos, tags);
+    }
+
+    /// `insert_inner` with the key already looked up: `existing` is its head
+    /// slot, or `None` if it is absent.
+    unsafe fn insert_entry(
+        &self,
+        existing: Option<i32>,
+        key_hash: u64,
+        key_bytes: &[u8],
+        value_bytes: &[u8],
+        ttl_nanos: u64,
+        tags: Option<&[u64]>,
+    ) {
+        let h = self.header();
+        let ht_cap = h.ht_capacity;
+        let slot_size = h.slot_size;
+        let (in_overflow, needed) = entry_storage(h, key_bytes.len(), value_bytes.len());
+        if needed > h.capacity as usize
+            || (in_overflow && value_bytes.len() > h.overflow_size as usize)
+        {
+            // Even an empty cache has no room for this entry
+            self.record_oversize_skip(Oversize::Value);
+            return;
+        }
+
+        let mut entry_tags = [0; MAX_TAGS];
+        if let Some(tags) = tags {
+            let n = tags.len().min(MAX_TAGS);
+            entry_tags[..n].copy_from_slice(&tags[..n]);
+        }
+
+        if let Some(idx) = existing {
+            let slot_ptr = self.slab_base_mut().add(idx as usize * slot_size as usize);
+            let slot = &mut *(slot_ptr as *mut SlotHeader);
+            if tags.is_none() {
+                entry_tags = slot.tags;
+            }
+            if entry_storage(h, key_bytes.len(), slot.value_len as usize) == (in_overflow, needed) {
+                // Journal the slot: if we die mid-copy, recovery drops the entry
+                // rather than serve a torn value.
+                self.header_mut().pending_slot = idx;
+                let old_len = slot.value_len as u64;
+                let new_len = value_bytes.len() as u64;
+                self.atomic_bytes_used()
+                    .fetch_sub(old_len, AtomicOrdering::Relaxed);
+                self.atomic_bytes_used()
+                    .fetch_add(new_len, AtomicOrdering::Relaxed);
+                self.atomic_value_bytes_used()
+                    .fetch_sub(old_len, AtomicOrdering::Relaxed);
+                self.atomic_value_bytes_used()
+                    .fetch_add(new_len, AtomicOrdering::Relaxed);
+                slot.value_len = value_bytes.len() as u32;
+                slot.created_at_nanos = self.now();
+                slot.ttl_nanos = ttl_nanos;
+                slot.tags = entry_tags;
+                slot.refresh_claimed.store(0, AtomicOrdering::Relaxed);
+                slot.visited.store(1, AtomicOrdering::Relaxed);
+
+                self.write_entry_value(slot_ptr, value_bytes);
+                self.header_mut().pending_slot = SLOT_NONE;
+                return;
+            }
+            // The value needs different storage: replace the entry
+            self.remove_slot(idx);
+        }
+
+        // Take the overflow chunk first, since getting one may evict an entry
+        let chunk = if in_overflow {
+            let chunk = self.alloc_overflow();
+            if chunk == SLOT_NONE {
+                return; // shouldn't happen: every chunk in use belongs to an entry
+            }
+            chunk
+        } else {
+            SLOT_NONE
+        };
+
+        // Allocate the head and any continuation slots
+        let mut slots = Vec::with_capacity(needed);
+        while slots.len() < needed {
+            let idx = self.alloc_slot();
+            if idx == SLOT_NONE {
+                // shouldn't happen: every slot not in `slots` is free or evictable
+                for &idx in &slots {
+                    self.free_slot(idx);
+                }
+                if chunk != SLOT_NONE {
+                    overflow::push_free(self.header_mut(), self.overflow_base_mut(), chunk);
+                }
+                return;
+            }
+            slots.push(idx);
+        }
+        let slot_idx = slots[0];
+
+        // Write the new entry into the slots, journaled as for the in-place update
+        self.header_mut().pending_slot = slot_idx;
+        for (i, &idx) in slots.iter().enumerate().skip(1) {
+            let cont = &mut *(self.slab_base_mut().add(idx as usize * slot_size as usize)
+                as *mut SlotHeader);
+            cont.occupied = SLOT_CONTINUATION;
+            cont.chain = slots.get(i + 1).copied().unwrap_or(SLOT_NONE);
+            cont.overflow = SLOT_NONE;
+            cont.prev = SLOT_NONE;
+            cont.next = SLOT_NONE;
+        }
+        let slot_ptr = self
+            .slab_base_mut()
+            .add(slot_idx as usize * slot_size as usize);
+        let slot = &mut *(slot_ptr as *mut SlotHeader);
+        slot.occupied = 1;
+        slot.key_hash = key_hash;
+        slot.key_len = key_bytes.len() as u32;
+        slot.value_len = value_bytes.len() as u32;
+        slot.created_at_nanos = self.now();
+        slot.ttl_nanos = ttl_nanos;
+        slot.tags = entry_tags;
+        slot.refresh_claimed.store(0, AtomicOrdering::Relaxed);
+        slot.visited.store(0, AtomicOrdering::Relaxed);
+        slot.prev = SLOT_NONE;
+        slot.next = SLOT_NONE;
+        slot.chain = slots.get(1).copied().unwrap_or(SLOT_NONE);
+        slot.overflow = chunk;
+
+        // Copy key bytes, then the value into its chunk or across the chain
+        let key_dest = slot_ptr.add(SLOT_HEADER_SIZE);
+        std::ptr::copy_nonoverlapping(key_bytes.as_ptr(), key_dest, key_bytes.len());
+        self.write_entry_value(slot_ptr, value_bytes);
+
+        // Insert into hash table. It has 2× as many buckets as slots, so it
+        // is only full if corrupt; give the slots back rather than leak them.
+        if !hashtable::ht_insert(self.ht_base_mut(), ht_cap, key_hash, slot_idx) {
+            for &idx in &slots {
+                self.free_slot(idx);
+            }
+            if chunk != SLOT_NONE {
+                overflow::push_free(self.header_mut(), self.overflow_base_mut(), chunk);
+            }
+            self.header_mut().pending_slot = SLOT_NONE;
+            self.atomic_insert_failures()
+                .fetch_add(1, AtomicOrdering::Relaxed);
+            return;
+        }
+
+        // Add to eviction list (SIEVE: new entries go to tail, unvisited)
+        let header = self.header_mut();
+        ordering::list_push_tail(header, self.slab_base_mut(), slot_size, slot_idx);
+        header.current_size += 1;
+        self.atomic_bytes_used().fetch_add(
+            (key_bytes.len() + value_bytes.len()) as u64,
+            AtomicOrdering::Relaxed,
+        );
+        self.atomic_value_bytes_used()
+            .fetch_add(value_bytes.len() as u64, AtomicOrdering::Relaxed);
+        header.pending_slot = SLOT_NONE;
+    }
+
+    /// Copy out the value of the entry whose head slot is at `slot_ptr`, from
+    /// its overflow chunk or its chain of slots.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn read_entry_value(&self, slot_ptr: *const u8) -> Option<Vec<u8>> {
+        let h = self.header();
+        let slot = &*(slot_ptr as *const SlotHeader);
+        if slot.overflow != SLOT_NONE {
+            overflow::read_value(
+                self.overflow_base_mut(),
+                h.overflow_size,
+                h.overflow_count,
+                slot.overflow,
+                slot.value_len as usize,
+            )
+        } else {
+            chain::read_value(
+                self.slab_base(),
+                h.slot_size,
+                h.capacity,
+                slot_ptr,
+                slot.key_len as usize,
+                slot.value_len as usize,
+            )
+        }
+    }
+
+    /// Write `value` into the storage already set up for the entry whose head
+    /// slot is at `slot_ptr`: its overflow chunk, or its chain of slots.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock; `value` must need the same storage
+    /// the entry has.
+    unsafe fn write_entry_value(&self, slot_ptr: *mut u8, value: &[u8]) {
+        let h = self.header();
+        let slot = &mut *(slot_ptr as *mut SlotHeader);
+        if h.checksums != 0 {
+            let key =
+                std::slice::from_raw_parts(slot_ptr.add(SLOT_HEADER_SIZE), slot.key_len as usize);
+            slot.checksum = entry_checksum(key, value);
+        }
+        if slot.overflow != SLOT_NONE {
+            overflow::write_value(
+                self.overflow_base_mut(),
+                h.overflow_size,
+                slot.overflow,
+                value,
+            );
+        } else {
+            let key_len = slot.key_len as usize;
+            chain::write_value(self.slab_base_mut(), h.slot_size, slot_ptr, key_len, value);
+        }
+    }
+
+    /// Take an overflow chunk for a new entry, evicting the oldest entry that
+    /// holds one if they are all in use. Returns `SLOT_NONE` only if the cache
+    /// has no overflow arena.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn alloc_overflow(&self) -> i32 {
+        let chunk = overflow::pop_free(self.header_mut(), self.overflow_base_mut());
+        if chunk != SLOT_NONE {
+            return chunk;
+        }
+        let mut idx = self.header().list_head;
+        while idx != SLOT_NONE {
+            let slot = self.slot(idx);
+            if slot.overflow != SLOT_NONE {
+                self.note_evicted(idx);
+                self.remove_slot(idx);
+                self.atomic_evictions()
+                    .fetch_add(1, AtomicOrdering::Relaxed);
+                return overflow::pop_free(self.header_mut(), self.overflow_base_mut());
+            }
+            idx = slot.next;
+        }
+        SLOT_NONE
+    }
+
+    /// Take one slot for a new entry: from the free list if it has any, else by
+    /// reclaiming an expired entry, else by evicting one with SIEVE. Returns
+    /// `SLOT_NONE` only if the cache holds no entries to give up.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn alloc_slot(&self) -> i32 {
+        let slot_size = self.header().slot_size;
+        let header = self.header_mut();
+        if header.free_head != SLOT_NONE {
+            // Pop from free list
+            let idx = header.free_head;
+            let free_slot =
+                &*(self.slab_base().add(idx as usize * slot_size as usize) as *const SlotHeader);
+            header.free_head = free_slot.next;
+            return idx;
+        }
+        if header.fresh_start < header.capacity {
+            // Untouched since `clear_releasing_memory`: zeroed, on no list
+            let idx = header.fresh_start as i32;
+            header.fresh_start += 1;
+            return idx;
+        }
+        if header.current_size == 0 {
+            return SLOT_NONE;
+        }
+
+        // Prefer reusing an expired slot over evicting a live entry
+        let reclaimed = self.reclaim_expired(header.ttl_nanos);
+        if reclaimed != SLOT_NONE {
+            self.atomic_expired_reclaimed()
+                .fetch_add(1, AtomicOrdering::Relaxed);
+            self.atomic_expirations()
+                .fetch_add(1, AtomicOrdering::Relaxed);
+            return reclaimed;
+        }
+
+        // Need to evict — SIEVE picks the victim
+        let evict_idx = ordering::sieve_evict(header, self.slab_base_mut(), slot_size);
+        if evict_idx != SLOT_NONE {
+            self.note_evicted(evict_idx);
+            self.detach_slot(evict_idx);
+            self.atomic_evictions()
+                .fetch_add(1, AtomicOrdering::Relaxed);
+        }
+        evict_idx
+    }
+
+    /// Copy out the entry at head slot `idx`, about to be evicted, if this
+    /// handle keeps evicted entries.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock; `idx` must still be linked in.
+    unsafe fn note_evicted(&self, idx: i32) {
+        let Some(evicted) = &self.evicted else {
+            return;
+        };
+        let slot_ptr = self
+            .slab_base()
+            .add(idx as usize * self.header().slot_size as usize);
+        let slot = &*(slot_ptr as *const SlotHeader);
+        let key = std::slice::from_raw_parts(slot_ptr.add(SLOT_HEADER_SIZE), slot.key_len as usize);
+        if let Some(value) = self.read_entry_value(slot_ptr) {
+            let mut evicted = evicted.lock();
+            evicted.push((key.to_vec(), value));
+        }
+    }
+
+    /// Mark a detached slot free and push it onto the free list.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn free_slot(&self, slot_idx: i32) {
+        let slot_size = self.header().slot_size;
+        let header = self.header_mut();
+        let slot = &mut *(self
+            .slab_base_mut()
+            .add(slot_idx as usize * slot_size as usize)
+            as *mut SlotHeader);
+        slot.occupied = 0;
+        slot.chain = SLOT_NONE;
+        slot.overflow = SLOT_NONE;
+        slot.next = header.free_head;
+        slot.prev = SLOT_NONE;
+        header.free_head = slot_idx;
+    }
+
+    /// Scan up to `RECLAIM_SCAN_LIMIT` entries from the eviction list head for
+    /// one whose TTL has expired. If found, it is detached (hash table + list)
+    /// and its index returned for reuse; otherwise `SLOT_NONE`.
+    ///
+    /// The head holds the oldest insertions, so it is where expired entries
+    /// collect. The scan is bounded so insert latency stays predictable. It runs
+    /// even without a cache-wide TTL, since entries may carry their own.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn reclaim_expired(&self, ttl_nanos: u64) -> i32 {
+        let slot_size = self.header().slot_size;
+        // Entries in their stale window are still being served — leave them.
+        let stale_ttl_nanos = self.header().stale_ttl_nanos;
+        let now = self.now();
+
+        let mut idx = self.header().list_head;
+        for _ in 0..RECLAIM_SCAN_LIMIT {
+            if idx == SLOT_NONE {
+                break;
+            }
+            let slot =
+                &*(self.slab_base().add(idx as usize * slot_size as usize) as *const SlotHeader);
+            let ttl = slot_ttl(slot, ttl_nanos);
+            if ttl > 0
+                && now.saturating_sub(slot.created_at_nanos) > ttl.saturating_add(stale_ttl_nanos)
+            {
+                self.detach_slot(idx);
+                return idx;
+            }
+            idx = slot.next;
+        }
+        SLOT_NONE
+    }
+
+    /// Unlink an occupied slot from the hash table and the eviction list, leaving
+    /// it ready to be overwritten in place (it is not pushed to the free list).
+    /// Its continuation slots and overflow chunk, if any, are freed.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn detach_slot(&self, slot_idx: i32) {
+        let h = self.header();
+        let ht_cap = h.ht_capacity;
+        let slot_size = h.slot_size;
+
+        let slot_ptr = self
+            .slab_base_mut()
+            .add(slot_idx as usize * slot_size as usize);
+        let slot = &mut *(slot_ptr as *mut SlotHeader);
+        let key = std::slice::from_raw_parts(slot_ptr.add(SLOT_HEADER_SIZE), slot.key_len as usize);
+
+        hashtable::ht_remove(
+            self.ht_base_mut(),
+            ht_cap,
+            self.slab_base(),
+            slot_size,
+            slot.key_hash,
+            key,
+        );
+
+        self.atomic_bytes_used().fetch_sub(
+            slot.key_len as u64 + slot.value_len as u64,
+            AtomicOrdering::Relaxed,
+        );
+        self.atomic_value_bytes_used()
+            .fetch_sub(slot.value_len as u64, AtomicOrdering::Relaxed);
+
+        let header = self.header_mut();
+        ordering::list_remove(header, self.slab_base_mut(), slot_size, slot_idx);
+        header.current_size -= 1;
+
+        // The continuation slots go straight back to the free list
+        let mut next = slot.chain;
+        while next != SLOT_NONE {
+            let cont =
+                &*(self.slab_base().add(next as usize * slot_size as usize) as *const SlotHeader);
+            let after = cont.chain;
+            self.free_slot(next);
+            next = after;
+        }
+        slot.chain = SLOT_NONE;
+
+        // So does its overflow chunk
+        if slot.overflow != SLOT_NONE {
+            overflow::push_free(self.header_mut(), self.overflow_base_mut(), slot.overflow);
+            slot.overflow = SLOT_NONE;
+        }
+    }
+
+    /// Remove a specific slot and push it onto the free list.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn remove_slot(&self, slot_idx: i32) {
+        self.detach_slot(slot_idx);
+        self.free_slot(slot_idx);
+    }
+
+    /// Snapshot the serialized key bytes of every occupied slot, in eviction
+    /// order (list head first).
+    ///
+    /// Takes the write lock so the walk never sees a half-written slot; does not
+    /// touch `visited`, the SIEVE hand, or any stats.
+    pub fn keys(&self) -> std::io::Result<Vec<Vec<u8>>> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        let keys = unsafe { self.keys_inner() };
+        lock.write_unlock();
+        Ok(keys)
+    }
+
+    unsafe fn keys_inner(&self) -> Vec<Vec<u8>> {
+        let h = self.header();
+        let slot_size = h.slot_size;
+        let mut keys = Vec::with_capacity(h.current_size as usize);
+
+        let mut idx = h.list_head;
+        while idx != SLOT_NONE {
+            let slot_ptr = self.slab_base().add(idx as usize * slot_size as usize);
+            let slot = &*(slot_ptr as *const SlotHeader);
+            if slot.occupied != 0 {
+                let key = std::slice::from_raw_parts(
+                    slot_ptr.add(SLOT_HEADER_SIZE),
+                    slot.key_len as usize,
+                );
+                keys.push(key.to_vec());
+            }
+            idx = slot.next;
+        }
+        keys
+    }
+
+    /// Snapshot `(key_bytes, value_bytes)` of every live entry, in eviction order.
+    ///
+    /// Entries whose TTL has lapsed are skipped but not removed. Like `keys`, this
+    /// copies out under the write lock and leaves ordering and stats untouched.
+    pub fn items(&self) -> std::io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .map(|(key, value, _)| (key, value))
+            .collect())
+    }
+
+    /// Snapshot `(key, value, remaining_ttl_nanos)` for every live (unexpired)
+    /// entry, in eviction order. `remaining_ttl_nanos` is 0 for entries that
+    /// never expire.
+    ///
+    /// Takes the write lock, like `keys`.
+    #[allow(clippy::type_complexity)]
+    pub fn entries(&self) -> std::io::Result<Vec<(Vec<u8>, Vec<u8>, u64)>> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        let entries = unsafe { self.entries_inner() };
+        lock.write_unlock();
+        Ok(entries)
+    }
+
+    unsafe fn entries_inner(&self) -> Vec<(Vec<u8>, Vec<u8>, u64)> {
+        let h = self.header();
+        let slot_size = h.slot_size;
+        let ttl_nanos = h.ttl_nanos;
+        let now = self.now();
+        let mut entries = Vec::with_capacity(h.current_size as usize);
+
+        let mut idx = h.list_head;
+        while idx != SLOT_NONE {
+            let slot_ptr = self.slab_base().add(idx as usize * slot_size as usize);
+            let slot = &*(slot_ptr as *const SlotHeader);
+            let ttl = slot_ttl(slot, ttl_nanos);
+            let age = now.saturating_sub(slot.created_at_nanos);
+            let expired = ttl > 0 && age > ttl;
+            if slot.occupied != 0 && !expired {
+                let key_len = slot.key_len as usize;
+                let key = std::slice::from_raw_parts(slot_ptr.add(SLOT_HEADER_SIZE), key_len);
+                let value = self.read_entry_value(slot_ptr).unwrap_or_default();
+                // An entry exactly at its TTL keeps 1ns rather than "never".
+                let remaining = if ttl > 0 { (ttl - age).max(1) } else { 0 };
+                entries.push((key.to_vec(), value, remaining));
+            }
+            idx = slot.next;
+        }
+        entries
+    }
+
+    /// Clear the entire cache.
+    pub fn clear(&self) -> std::io::Result<()> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        unsafe { self.clear_inner(false) };
+        lock.write_unlock();
+        self.local.reset();
+        Ok(())
+    }
+
+    /// `clear`, also handing the slab's pages back to the filesystem so an
+    /// empty cache stops holding tmpfs memory (Linux only; elsewhere, or if
+    /// the filesystem or an `mlock` refuses, it is a plain `clear`). Slots are
+    /// then handed out in order from `fresh_start`, faulting their pages back
+    /// in as the cache refills.
+    pub fn clear_releasing_memory(&self) -> std::io::Result<()> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        unsafe { self.clear_inner(true) };
+        lock.write_unlock();
+        self.local.reset();
+        Ok(())
+    }
+
+    /// Zero the hit/miss and event counters, in the header (for every
+    /// process) and in this handle's local stats, leaving the entries alone.
+    pub fn reset_stats(&self) -> std::io::Result<()> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        unsafe { self.reset_counters() };
+        lock.write_unlock();
+        self.local.reset();
+        Ok(())
+    }
+
+    /// Zero this handle's local stats only (`local_info`).
+    pub fn reset_local_stats(&self) {
+        self.local.reset();
+    }
+
+    /// # Safety
+    /// Caller must hold the write lock.
+    unsafe fn reset_counters(&self) {
         let header = self.header_mut();
-        header.current_size = 0;
         header.hits = 0;
         header.misses = 0;
-        header.oversize_skips = 0;
+        header.oversize_key_skips = 0;
+        header.oversize_value_skips = 0;
+        header.lock_timeouts = 0;
+        header.corruption_detected = 0;
+        header.corrupt_values = 0;
+        header.skipped_fast = 0;
+        header.value_sizes = [0; VALUE_SIZE_BUCKETS];
+        header.largest_value = 0;
+        header.insert_failures = 0;
+        header.expired_reclaimed = 0;
+        header.evictions = 0;
+        header.expirations = 0;
+    }
+
+    unsafe fn clear_inner(&self, release_memory: bool) {
+        let h = self.header();
+        let ht_cap = h.ht_capacity;
+        let slot_size = h.slot_size;
+        let capacity = h.capacity;
+
+        // Clear hash table
+        hashtable::ht_clear(self.ht_base_mut(), ht_cap);
+
+        // Either zero the slab and leave every slot to `fresh_start`, or reset
+        // all slots to the free list (which touches every one of them)
+        let released = release_memory
+            && self
+                .region
+                .release_range(
+                    layout::slab_offset(ht_cap),
+                    capacity as usize * slot_size as usize,
+                )
+                .is_ok();
+        if !released {
+            for i in 0..capacity as usize {
+                let slot_ptr = self.slab_base_mut().add(i * slot_size as usize);
+                let slot = &mut *(slot_ptr as *mut SlotHeader);
+                slot.occupied = 0;
+                slot.chain = SLOT_NONE;
+                slot.overflow = SLOT_NONE;
+                slot.prev = SLOT_NONE;
+                slot.next = if i + 1 < capacity as usize {
+                    (i + 1) as i32
+                } else {
+                    SLOT_NONE
+                };
+            }
+        }
+
+        self.reset_counters();
+        let header = self.header_mut();
+        header.current_size = 0;
+        header.bytes_used = 0;
+        header.value_bytes_used = 0;
         header.list_head = SLOT_NONE;
         header.list_tail = SLOT_NONE;
-        header.free_head = 0;
+        (header.free_head, header.fresh_start) = if released {
+            (SLOT_NONE, 0)
+        } else {
+            (0, capacity)
+        };
         header.sieve_hand = SLOT_NONE;
+        header.pending_slot = SLOT_NONE;
+        overflow::reset_free_list(header, self.overflow_base_mut(), |_| false);
     }
 
-    /// Increment oversize skip counter. Lock-free via atomic.
-    pub fn record_oversize_skip(&self) {
-        self.atomic_oversize_skips()
-            .fetch_add(1, AtomicOrdering::Relaxed);
+    /// Rebuild the cache with room for `new_capacity` entries, keeping every
+    /// entry that is still servable along with its age, TTL and SIEVE bit. When
+    /// shrinking, entries nearest the eviction list tail survive.
+    ///
+    /// The new data file is built beside the old one and renamed over it, all
+    /// under the write lock; other handles see the lock file's generation move
+    /// and remap on their next operation. Stats carry over, and entries dropped
+    /// by a shrink are not counted as evictions.
+    pub fn resize(&self, new_capacity: u32) -> std::io::Result<()> {
+        let mut lock = self.lock();
+        self.write_lock(&mut lock)?;
+        let result = unsafe { self.resize_inner(new_capacity) };
+        lock.write_unlock();
+        result
     }
 
-    /// Get cache statistics. Lock-free via atomic loads.
+    unsafe fn resize_inner(&self, new_capacity: u32) -> std::io::Result<()> {
+        let h = self.header();
+        let slot_size = h.slot_size;
+        let now = self.now();
+
+        // (hash, key, value, created_at_nanos, ttl_nanos, visited, tags), head first
+        let mut entries = Vec::with_capacity(h.current_size as usize);
+        let mut idx = h.list_head;
+        while idx != SLOT_NONE {
+            let slot_ptr = self.slab_base().add(idx as usize * slot_size as usize);
+            let slot = &*(slot_ptr as *const SlotHeader);
+            let ttl = slot_ttl(slot, h.ttl_nanos);
+            let gone = ttl > 0
+                && now.saturating_sub(slot.created_at_nanos)
+                    > ttl.saturating_add(h.stale_ttl_nanos);
+            if slot.occupied != 0 && !gone {
+                let key_len = slot.key_len as usize;
+                let key = std::slice::from_raw_parts(slot_ptr.add(SLOT_HEADER_SIZE), key_len);
+                let key = key.to_vec();
+                let value = self.read_entry_value(slot_ptr).unwrap_or_default();
+                entries.push((
+                    slot.key_hash,
+                    key,
+                    value,
+                    slot.created_at_nanos,
+                    slot.ttl_nanos,
+                    slot.visited.load(AtomicOrdering::Relaxed),
+                    slot.tags,
+                ));
+            }
+            idx = slot.next;
+        }
+        // Keep the entries nearest the tail that fit in `new_capacity` slots.
+        let mut kept = 0;
+        let mut slots_left = new_capacity as usize;
+        for (_, key, value, ..) in entries.iter().rev() {
+            let (_, needed) = entry_storage(h, key.len(), value.len());
+            if needed > slots_left {
+                break;
+            }
+            slots_left -= needed;
+            kept += 1;
+        }
+        let dropped = entries.len() - kept;
+
+        let config = RegionConfig {
+            capacity: new_capacity,
+            ..RegionConfig::from_header(h)
+        };
+        let stats = [
+            self.atomic_hits().load(AtomicOrdering::Relaxed),
+            self.atomic_misses().load(AtomicOrdering::Relaxed),
+            self.atomic_oversize_skips(Oversize::Key)
+                .load(AtomicOrdering::Relaxed),
+            self.atomic_oversize_skips(Oversize::Value)
+                .load(AtomicOrdering::Relaxed),
+            self.atomic_lock_timeouts().load(AtomicOrdering::Relaxed),
+            self.atomic_corruption_detected()
+                .load(AtomicOrdering::Relaxed),
+            self.atomic_corrupt_values().load(AtomicOrdering::Relaxed),
+            self.atomic_skipped_fast().load(AtomicOrdering::Relaxed),
+            self.atomic_insert_failures().load(AtomicOrdering::Relaxed),
+            self.atomic_expired_reclaimed()
+                .load(AtomicOrdering::Relaxed),
+            self.atomic_evictions().load(AtomicOrdering::Relaxed),
+            self.atomic_expirations().load(AtomicOrdering::Relaxed),
+        ];
+        let value_sizes = self
+            .atomic_value_sizes()
+            .each_ref()
+            .map(|count| count.load(AtomicOrdering::Relaxed));
+        let largest_value = self.atomic_largest_value().load(AtomicOrdering::Relaxed);
+        let serde_format = self.atomic_serde_format().load(AtomicOrdering::Relaxed);
+        let (creator_pid, created_at_wallclock) = (h.creator_pid, h.created_at_wallclock);
+
+        self.region.begin_resize(&config)?;
+        // From here on, header/slab accessors point into the new file.
+        let header = self.header_mut();
+        [
+            header.hits,
+            header.misses,
+            header.oversize_key_skips,
+            header.oversize_value_skips,
+            header.lock_timeouts,
+            header.corruption_detected,
+            header.corrupt_values,
+            header.skipped_fast,
+            header.insert_failures,
+            header.expired_reclaimed,
+            header.evictions,
+            header.expirations,
+        ] = stats;
+        header.value_sizes = value_sizes;
+        header.largest_value = largest_value;
+        header.serde_format = serde_format;
+        header.creator_pid = creator_pid;
+        header.created_at_wallclock = created_at_wallclock;
+        header.last_writer_pid = lock::current_pid();
+        header.last_write_nanos = wall_clock_nanos();
+
+        for (key_hash, key, value, created_at, ttl_nanos, visited, tags) in
+            entries.into_iter().skip(dropped)
+        {
+            self.insert_inner(key_hash, &key, &value, ttl_nanos, Some(&tags));
+            // insert_inner appends at the tail and stamps "now"; restore the
+            // entry's original age and SIEVE bit.
+            let tail = self.header().list_tail;
+            let slot = &mut *(self.slab_base_mut().add(tail as usize * slot_size as usize)
+                as *mut SlotHeader);
+            slot.created_at_nanos = created_at;
+            slot.visited.store(visited, AtomicOrdering::Relaxed);
+        }
+
+        if let Err(e) = self.region.publish_resized() {
+            self.region.abandon_resized();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Whether a writer holds the lock right now, so a read or write started
+    /// now would have to wait for it. Lock-free; the answer can be stale by the
+    /// time the caller acts on it.
+    pub fn is_busy(&self) -> bool {
+        self.region.lock().is_write_locked()
+    }
+
+    /// The cache's name, as passed to `create_or_open`.
+    pub fn name(&self) -> String {
+        self.region.name()
+    }
+
+    /// Whether this handle was opened with `FileOptions::readonly`.
+    pub fn is_readonly(&self) -> bool {
+        self.readonly
+    }
+
+    /// Count a lookup the caller answered as a miss without reading the
+    /// cache (a key too large to ever be stored). Lock-free via atomic.
+    pub fn record_miss(&self) {
+        self.count_misses(1);
+    }
+
+    /// Count a hit whose bytes the caller couldn't deserialize, in
+    /// `corrupt_values`. Dropping the entry is up to the caller. Lock-free via
+    /// atomic.
+    pub fn record_corrupt_value(&self) {
+        self.bump(self.atomic_corrupt_values(), 1);
+    }
+
+    /// Count a miss the caller didn't store because it computed in under
+    /// `min_compute_time`, in `skipped_fast`. Lock-free via atomic.
+    pub fn record_skipped_fast(&self) {
+        self.bump(self.atomic_skipped_fast(), 1);
+    }
+
+    /// Count a serialized value of `len` bytes offered for storage, stored or
+    /// not, in the value size histogram. Lock-free via atomics.
+    pub fn record_value_size(&self, len: usize) {
+        let len = len as u64;
+        let bucket = value_size_bucket(self.header(), len);
+        self.bump(&self.atomic_value_sizes()[bucket], 1);
+        if !self.readonly {
+            self.atomic_largest_value()
+                .fetch_max(len, AtomicOrdering::Relaxed);
+        }
+    }
+
+    /// The value size histogram: each bucket's inclusive upper bound in bytes
+    /// (`None` for the last, which has none) with its count, and the largest
+    /// value recorded.
+    pub fn value_size_histogram(&self) -> (Vec<(Option<u64>, u64)>, u64) {
+        let bounds = value_size_bounds(self.header());
+        let buckets = self
+            .atomic_value_sizes()
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (bounds.get(i).copied(), count.load(AtomicOrdering::Relaxed)))
+            .collect();
+        let largest = self.atomic_largest_value().load(AtomicOrdering::Relaxed);
+        (buckets, largest)
+    }
+
+    /// Increment the oversize skip counter for `kind`. Lock-free via atomic.
+    pub fn record_oversize_skip(&self, kind: Oversize) {
+        self.bump(self.atomic_oversize_skips(kind), 1);
+        let local = match kind {
+            Oversize::Key => &self.local.oversize_key_skips,
+            Oversize::Value => &self.local.oversize_value_skips,
+        };
+        local.fetch_add(1, AtomicOrdering::Relaxed);
+    }
+
+    /// Record that this handle writes values in serde format `format`, and
+    /// return the newest format any handle recorded before it (0 = none). The
+    /// shared backend only keeps the highest id; what it means is up to the
+    /// caller (`crate::serde::FORMAT`). A read-only handle records nothing.
+    /// Lock-free via atomic.
+    pub fn record_serde_format(&self, format: u32) -> u32 {
+        let field = self.atomic_serde_format();
+        if self.readonly {
+            field.load(AtomicOrdering::Relaxed)
+        } else {
+            field.fetch_max(format, AtomicOrdering::Relaxed)
+        }
+    }
+
+    /// Get cache statistics. Lock-free via atomic loads, after checking that
+    /// the files are still in place (see `reattach`).
     pub fn info(&self) -> ShmCacheInfo {
+        self.region.sync_generation();
+        self.reattach();
         let h = self.header();
+        // Value bytes each slot holds: a chunk when chaining, else a whole value
+        let value_area = if h.chunk_size != 0 {
+            h.chunk_size
+        } else {
+            h.max_value_size
+        };
         ShmCacheInfo {
             hits: self.atomic_hits().load(AtomicOrdering::Relaxed),
             misses: self.atomic_misses().load(AtomicOrdering::Relaxed),
             max_size: h.capacity as usize,
             current_size: h.current_size as usize,
-            oversize_skips: self.atomic_oversize_skips().load(AtomicOrdering::Relaxed),
+            oversize_key_skips: self
+                .atomic_oversize_skips(Oversize::Key)
+                .load(AtomicOrdering::Relaxed),
+            oversize_value_skips: self
+                .atomic_oversize_skips(Oversize::Value)
+                .load(AtomicOrdering::Relaxed),
+            lock_timeouts: self.atomic_lock_timeouts().load(AtomicOrdering::Relaxed),
+            corruption_detected: self
+                .atomic_corruption_detected()
+                .load(AtomicOrdering::Relaxed),
+            corrupt_values: self.atomic_corrupt_values().load(AtomicOrdering::Relaxed),
+            skipped_fast: self.atomic_skipped_fast().load(AtomicOrdering::Relaxed),
+            insert_failures: self.atomic_insert_failures().load(AtomicOrdering::Relaxed),
+            expired_reclaimed: self
+                .atomic_expired_reclaimed()
+                .load(AtomicOrdering::Relaxed),
+            evictions: self.atomic_evictions().load(AtomicOrdering::Relaxed),
+            expirations: self.atomic_expirations().load(AtomicOrdering::Relaxed),
+            bytes_used: self.atomic_bytes_used().load(AtomicOrdering::Relaxed),
+            value_bytes_used: self.atomic_value_bytes_used().load(AtomicOrdering::Relaxed),
+            bytes_capacity: h.capacity as u64 * (h.max_key_size as u64 + value_area as u64)
+                + h.overflow_count as u64 * h.overflow_size as u64,
+            overflow_used: h.overflow_used as usize,
+            memory_locked: self.region.memory_locked(),
+            allocated_bytes: self.region.allocated_bytes(),
+            cache_version: Some(region::id_text(&h.cache_version))
+                .filter(|version| !version.is_empty()),
+            remaps: self.region.remaps(),
+        }
+    }
+
+    /// Raw state of the cache for debugging, copied out under the write lock:
+    /// header fields, the first `max_slots` slots (up to `fresh_start`), and up to `max_slots`
+    /// indices of the eviction list (head first) and free list. List walks
+    /// stop at an out-of-range index or after `capacity` steps, so a corrupt
+    /// list can't hang them.
+    /// Doesn't count as a write in `last_writer_pid`, so the dump names the
+    /// process that last changed the cache rather than this call.
+    pub fn debug_info(&self, max_slots: usize) -> std::io::Result<DebugInfo> {
+        let mut lock = self.lock();
+        self.acquire_write_lock(&mut lock)?;
+        let info = unsafe { self.debug_info_inner(max_slots) };
+        lock.write_unlock();
+        Ok(info)
+    }
+
+    unsafe fn debug_info_inner(&self, max_slots: usize) -> DebugInfo {
+        let h = self.header();
+        let now = self.now();
+        let header = vec![
+            ("version", h.version as i64),
+            ("capacity", h.capacity as i64),
+            ("ht_capacity", h.ht_capacity as i64),
+            ("slot_size", h.slot_size as i64),
+            ("max_key_size", h.max_key_size as i64),
+            ("max_value_size", h.max_value_size as i64),
+            ("current_size", h.current_size as i64),
+            ("list_head", h.list_head as i64),
+            ("list_tail", h.list_tail as i64),
+            ("free_head", h.free_head as i64),
+            ("fresh_start", h.fresh_start as i64),
+            ("serde_format", h.serde_format as i64),
+            ("sieve_hand", h.sieve_hand as i64),
+            ("pending_slot", h.pending_slot as i64),
+            ("ttl_nanos", h.ttl_nanos as i64),
+            ("stale_ttl_nanos", h.stale_ttl_nanos as i64),
+            ("sliding_ttl", h.sliding_ttl as i64),
+            ("durable", h.durable as i64),
+            ("checksums", h.checksums as i64),
+            ("chunk_size", h.chunk_size as i64),
+            ("overflow_count", h.overflow_count as i64),
+            ("overflow_size", h.overflow_size as i64),
+            ("overflow_free", h.overflow_free as i64),
+            ("overflow_used", h.overflow_used as i64),
+            ("bytes_used", h.bytes_used as i64),
+            ("value_bytes_used", h.value_bytes_used as i64),
+            ("creator_pid", h.creator_pid as i64),
+            ("created_at_wallclock", h.created_at_wallclock as i64),
+            ("last_writer_pid", h.last_writer_pid as i64),
+            ("last_write_nanos", h.last_write_nanos as i64),
+        ];
+        // Slots past `fresh_start` are zero, and reading them would fault
+        // released pages back in
+        let slots = (0..h.capacity.min(h.fresh_start) as usize)
+            .take(max_slots)
+            .map(|idx| {
+                let slot = self.slot(idx as i32);
+                SlotDebug {
+                    index: idx as i32,
+                    occupied: slot.occupied,
+                    key_hash: slot.key_hash,
+                    key_len: slot.key_len,
+                    value_len: slot.value_len,
+                    age_nanos: now.saturating_sub(slot.created_at_nanos),
+                    ttl_nanos: slot.ttl_nanos,
+                    visited: slot.visited.load(AtomicOrdering::Relaxed) != 0,
+                    prev: slot.prev,
+                    next: slot.next,
+                    chain: slot.chain,
+                    overflow: slot.overflow,
+                }
+            })
+            .collect();
+        let walk = |start: i32| {
+            let limit = max_slots.min(h.capacity as usize);
+            let mut order = Vec::new();
+            let mut idx = start;
+            while idx != SLOT_NONE && order.len() < limit {
+                let Some(slot) = self.slot_checked(idx) else {
+                    break;
+                };
+                order.push(idx);
+                idx = slot.next;
+            }
+            order
+        };
+        DebugInfo {
+            header,
+            slots,
+            eviction_order: walk(h.list_head),
+            free_list: walk(h.free_head),
+        }
+    }
+
+    /// `info`, with the hit, miss and oversize counts this handle made itself
+    /// (since it opened the cache, or its last `clear` or stats reset) in
+    /// place of everyone's. The other fields describe the shared cache.
+    pub fn local_info(&self) -> ShmCacheInfo {
+        let load = |counter: &AtomicU64| counter.load(AtomicOrdering::Relaxed);
+        ShmCacheInfo {
+            hits: load(&self.local.hits),
+            misses: load(&self.local.misses),
+            oversize_key_skips: load(&self.local.oversize_key_skips),
+            oversize_value_skips: load(&self.local.oversize_value_skips),
+            ..self.info()
         }
     }
+
+    /// How much this handle has waited on the seqlock, since it was opened or
+    /// its stats were last reset.
+    pub fn contention_info(&self) -> ContentionInfo {
+        let load = |counter: &AtomicU64| counter.load(AtomicOrdering::Relaxed);
+        ContentionInfo {
+            read_waits: load(&self.local.read_waits),
+            read_retries: load(&self.local.read_retries),
+            write_spins: load(&self.local.write_spins),
+            write_locks: load(&self.local.write_locks),
+        }
+    }
+}
+
+/// Snapshot returned by `ShmCache::contention_info`.
+pub struct ContentionInfo {
+    /// Pauses (spins, yields, sleeps) of reads waiting for a writer to finish.
+    pub read_waits: u64,
+    /// Optimistic reads a writer invalidated, each then read again.
+    pub read_retries: u64,
+    /// Pauses and lost compare-and-swaps of writers waiting for the lock.
+    pub write_spins: u64,
+    /// Write lock acquisitions.
+    pub write_locks: u64,
+}
+
+/// Snapshot returned by `ShmCache::debug_info`.
+pub struct DebugInfo {
+    /// `(name, value)` of the header's layout, list and config fields.
+    pub header: Vec<(&'static str, i64)>,
+    pub slots: Vec<SlotDebug>,
+    /// Slot indices from the eviction list head (oldest) towards the tail.
+    pub eviction_order: Vec<i32>,
+    /// Slot indices on the free list, in the order they'd be handed out.
+    pub free_list: Vec<i32>,
+}
+
+/// One slot's header fields, as `ShmCache::debug_info` saw them.
+pub struct SlotDebug {
+    pub index: i32,
+    /// 1 = an entry's head slot, `SLOT_CONTINUATION` = part of a chained
+    /// value, 0 = free.
+    pub occupied: u32,
+    pub key_hash: u64,
+    pub key_len: u32,
+    pub value_len: u32,
+    /// Time since the entry was written (or its TTL last restarted).
+    pub age_nanos: u64,
+    /// The entry's own TTL, 0 = the cache-wide one.
+    pub ttl_nanos: u64,
+    /// SIEVE bit: hit since the hand last passed.
+    pub visited: bool,
+    pub prev: i32,
+    pub next: i32,
+    pub chain: i32,
+    pub overflow: i32,
 }
 
 pub struct ShmCacheInfo {
@@ -526,7 +3139,108 @@ pub struct ShmCacheInfo {
     pub misses: u64,
     pub max_size: usize,
     pub current_size: usize,
-    pub oversize_skips: u64,
+    /// Keys over `max_key_size` that weren't stored.
+    pub oversize_key_skips: u64,
+    /// Values too big for a slot (or chain, or overflow chunk) that weren't stored.
+    pub oversize_value_skips: u64,
+    /// Lookups given up as misses because a writer held the lock past
+    /// `lock_timeout`.
+    pub lock_timeouts: u64,
+    /// Hits dropped because the entry failed its checksum (`checksums` only).
+    pub corruption_detected: u64,
+    /// Hits whose bytes the caller couldn't deserialize (`record_corrupt_value`).
+    pub corrupt_values: u64,
+    /// Misses the caller didn't store for computing too quickly
+    /// (`record_skipped_fast`).
+    pub skipped_fast: u64,
+    /// Inserts dropped because the hash table had no free bucket.
+    pub insert_failures: u64,
+    pub expired_reclaimed: u64,
+    pub evictions: u64,
+    pub expirations: u64,
+    pub bytes_used: u64,
+    pub value_bytes_used: u64,
+    pub bytes_capacity: u64,
+    /// Overflow chunks holding a value.
+    pub overflow_used: usize,
+    /// This handle's mapping is `mlock`ed (`FileOptions::lock_memory`).
+    pub memory_locked: bool,
+    /// Storage the data file occupies (tmpfs memory, or disk when durable),
+    /// which `clear_releasing_memory` shrinks.
+    pub allocated_bytes: u64,
+    /// The `cache_version` the cache was created with, if any.
+    pub cache_version: Option<String>,
+    /// Times this handle switched to a replacement of its files.
+    pub remaps: u64,
+}
+
+/// Largest value the cache can hold: `max_value_size`, or the overflow chunk
+/// size if that is larger.
+fn value_limit(h: &Header) -> u32 {
+    if h.overflow_count > 0 {
+        h.max_value_size.max(h.overflow_size)
+    } else {
+        h.max_value_size
+    }
+}
+
+/// log2 of the first `value_size_bounds` bound. The bounds double from there
+/// to the last one, the power of two at or above 4× `value_limit`, but start
+/// no lower than 1 byte.
+fn value_size_shift(h: &Header) -> u32 {
+    let top = (4 * value_limit(h) as u64).next_power_of_two();
+    top.trailing_zeros()
+        .saturating_sub(VALUE_SIZE_BUCKETS as u32 - 2)
+}
+
+/// Inclusive upper bounds of every value size bucket but the last.
+fn value_size_bounds(h: &Header) -> Vec<u64> {
+    let shift = value_size_shift(h);
+    (0..VALUE_SIZE_BUCKETS as u32 - 1)
+        .map(|i| 1u64 << (shift + i))
+        .collect()
+}
+
+/// The `value_size_bounds` bucket a value of `len` bytes counts in.
+fn value_size_bucket(h: &Header, len: u64) -> usize {
+    let shift = value_size_shift(h);
+    if len <= 1 << shift {
+        return 0;
+    }
+    // ceil(log2(len)), for len >= 2
+    let ceil_log2 = u64::BITS - (len - 1).leading_zeros();
+    ((ceil_log2 - shift) as usize).min(VALUE_SIZE_BUCKETS - 1)
+}
+
+/// How an entry with this key and value is stored: whether its value goes to
+/// an overflow chunk (it is over `max_value_size` and the cache has an arena),
+/// and how many slots it takes, head included.
+fn entry_storage(h: &Header, key_len: usize, value_len: usize) -> (bool, usize) {
+    if value_len > h.max_value_size as usize && h.overflow_count > 0 {
+        (true, 1)
+    } else {
+        (false, chain::slots_needed(h.slot_size, key_len, value_len))
+    }
+}
+
+/// The TTL governing a slot: its own `ttl_nanos` when set, otherwise the
+/// cache-wide `default_ttl` from the header. 0 means the entry never expires.
+#[inline]
+fn slot_ttl(slot: &SlotHeader, default_ttl: u64) -> u64 {
+    if slot.ttl_nanos != 0 {
+        slot.ttl_nanos
+    } else {
+        default_ttl
+    }
+}
+
+/// Checksum stored in `SlotHeader::checksum` when the cache has `checksums`.
+#[inline]
+fn entry_checksum(key: &[u8], value: &[u8]) -> u32 {
+    let mut hasher = xxhash_rust::xxh32::Xxh32::new(0);
+    hasher.update(key);
+    hasher.update(value);
+    hasher.digest()
 }
 
 /// Get current monotonic time in nanoseconds.
@@ -536,8 +3250,9 @@ pub struct ShmCacheInfo {
 /// is written into shared memory by one process and compared against `now` in another
 /// (#32). `std::time::Instant` must NOT be used here — its epoch is per-process, so the
 /// subtraction would cross two unrelated time bases and silently break cross-process TTL.
-/// The shm module only compiles on non-Windows targets (see `lib.rs`), so `libc` and
-/// `CLOCK_MONOTONIC` are always available.
+/// On Windows the same role is played by `QueryPerformanceCounter`, whose ticks are
+/// consistent across processes on one machine.
+#[cfg(unix)]
 fn current_time_nanos() -> u64 {
     let mut ts = libc::timespec {
         tv_sec: 0,
@@ -549,6 +3264,1530 @@ fn current_time_nanos() -> u64 {
     (ts.tv_sec as u64) * 1_000_000_000 + (ts.tv_nsec as u64)
 }
 
+#[cfg(windows)]
+fn current_time_nanos() -> u64 {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn QueryPerformanceCounter(count: *mut i64) -> i32;
+        fn QueryPerformanceFrequency(frequency: *mut i64) -> i32;
+    }
+    let (mut count, mut frequency) = (0i64, 0i64);
+    // SAFETY: both calls only write through the valid pointers passed; they
+    // cannot fail on Windows XP and later.
+    unsafe {
+        QueryPerformanceCounter(&mut count);
+        QueryPerformanceFrequency(&mut frequency);
+    }
+    (count as u128 * 1_000_000_000 / frequency.max(1) as u128) as u64
+}
+
+/// An id for the current boot of this machine, or 0 if it can't be told.
+///
+/// Stored in the header so a volatile cache can tell its `current_time_nanos`
+/// stamps came from an earlier boot (see `ShmCache::reset_if_rebooted`).
+#[cfg(target_os = "linux")]
+fn boot_id() -> u64 {
+    std::fs::read("/proc/sys/kernel/random/boot_id")
+        .map_or(0, |id| xxhash_rust::xxh64::xxh64(&id, 0))
+}
+
+/// The boot time (`kern.boottime`) in microseconds, which is fixed for a boot.
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+fn boot_id() -> u64 {
+    let mut tv = libc::timeval {
+        tv_sec: 0,
+        tv_usec: 0,
+    };
+    let mut len = std::mem::size_of::<libc::timeval>();
+    // SAFETY: the name is NUL-terminated and `tv`/`len` describe a valid buffer.
+    let rc = unsafe {
+        libc::sysctlbyname(
+            c"kern.boottime".as_ptr(),
+            &mut tv as *mut libc::timeval as *mut libc::c_void,
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if rc != 0 {
+        return 0;
+    }
+    (tv.tv_sec as u64) * 1_000_000 + tv.tv_usec as u64
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd"
+)))]
+fn boot_id() -> u64 {
+    0
+}
+
+/// Get the current wall-clock time in nanoseconds since the Unix epoch.
+///
+/// Durable caches stamp entries with this instead of `current_time_nanos`:
+/// `CLOCK_MONOTONIC` restarts at boot, so a monotonic stamp read back after a
+/// reboot would make every entry look brand new (or from the future). The
+/// cost is that a clock step changes entry ages; ages are clamped at 0.
+fn wall_clock_nanos() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// What a thread the child of a fork doesn't have can leave locked: the
+/// evicted entries waiting for `take_evicted`, and the region's mapping lists.
+/// The seqlock needs nothing, as the process that held it is still alive.
+impl AfterFork for ShmCache {
+    fn after_fork_in_child(&self) {
+        // SAFETY: neither lock is held while Python code runs, so the forking
+        // thread holds neither.
+        unsafe {
+            if let Some(evicted) = &self.evicted {
+                evicted.release_in_child();
+            }
+            self.region.after_fork_in_child();
+        }
+    }
+}
+
+impl Drop for ShmCache {
+    fn drop(&mut self) {
+        if self.header().durable != 0 {
+            let _ = self.region.flush(true);
+        }
+    }
+}
+
 // ShmCache is Send+Sync because all mutations go through the shm seqlock
 unsafe impl Send for ShmCache {}
 unsafe impl Sync for ShmCache {}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    /// Open a cache under a per-test name, cleared so leftovers from a
+    /// previous run don't leak in.
+    fn open(name: &str, capacity: u32) -> ShmCache {
+        let cache = ShmCache::create_or_open(
+            &format!("rust_test_{name}"),
+            capacity,
+            64,
+            64,
+            None,
+            None,
+            false,
+            false,
+            VALUE_CODEC_NONE,
+            "pickle",
+            "",
+            None,
+            0,
+            0,
+            OnMismatch::Recreate,
+            &FileOptions::default(),
+        )
+        .unwrap();
+        cache.clear().unwrap();
+        cache
+    }
+
+    #[test]
+    fn insert_batch_takes_the_write_lock_once() {
+        let cache = open("insert_batch", 16);
+        let keys: Vec<Vec<u8>> = (0u8..32).map(|i| vec![i; 4]).collect();
+        let entries: Vec<(u64, &[u8], &[u8])> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, k)| (i as u64, k.as_slice(), b"value".as_slice()))
+            .collect();
+
+        let before = cache.lock().sequence();
+        cache.insert_batch(&entries).unwrap();
+        // One write section = one odd bump + one even bump.
+        assert_eq!(cache.lock().sequence() - before, 2);
+
+        let info = cache.info();
+        assert_eq!(info.current_size, 16);
+        assert_eq!(info.evictions, 16);
+        // The last 16 pairs survive eviction (all unvisited, FIFO order).
+        for (i, k) in keys.iter().enumerate().skip(16) {
+            assert!(matches!(cache.get(i as u64, k), ShmGetResult::Hit(_)));
+        }
+    }
+
+    #[test]
+    fn per_entry_ttl_overrides_the_header_ttl() {
+        let cache = open("per_entry_ttl", 16);
+        cache.insert(1, b"short", b"v", Some(1)).unwrap();
+        cache.insert(2, b"default", b"v", None).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+
+        assert!(matches!(cache.get(1, b"short"), ShmGetResult::Miss(_)));
+        assert!(matches!(cache.get(2, b"default"), ShmGetResult::Hit(_)));
+
+        // Updating in place without a TTL drops the override.
+        cache.insert(1, b"short", b"v", None).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        assert!(matches!(cache.get(1, b"short"), ShmGetResult::Hit(_)));
+    }
+
+    #[test]
+    fn stale_entry_is_claimed_once() {
+        let cache = ShmCache::create_or_open(
+            "rust_test_stale_claim",
+            16,
+            64,
+            64,
+            Some(1e-6),
+            Some(60.0),
+            false,
+            false,
+            VALUE_CODEC_NONE,
+            "pickle",
+            "",
+            None,
+            0,
+            0,
+            OnMismatch::Recreate,
+            &FileOptions::default(),
+        )
+        .unwrap();
+        cache.clear().unwrap();
+        cache.insert(1, b"key", b"old", None).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+
+        assert!(matches!(
+            cache.get_or_claim_refresh(1, b"key"),
+            ShmGetResult::Miss(_)
+        ));
+        assert!(matches!(
+            cache.get_or_claim_refresh(1, b"key"),
+            ShmGetResult::Hit(_)
+        ));
+
+        cache.release_refresh(1, b"key").unwrap();
+        assert!(matches!(
+            cache.get_or_claim_refresh(1, b"key"),
+            ShmGetResult::Miss(_)
+        ));
+
+        // Inserting clears the claim along with the staleness.
+        cache
+            .insert(1, b"key", b"new", Some(60_000_000_000))
+            .unwrap();
+        assert!(
+            matches!(cache.get_or_claim_refresh(1, b"key"), ShmGetResult::Hit(v) if v == b"new")
+        );
+    }
+
+    #[test]
+    fn touch_only_extends_live_entries() {
+        let cache = open("touch", 16);
+        assert!(!cache.touch(1, b"key").unwrap());
+
+        cache.insert(1, b"key", b"v", Some(60_000_000_000)).unwrap();
+        let before = cache.info();
+        assert!(cache.touch(1, b"key").unwrap());
+        let after = cache.info();
+        assert_eq!((after.hits, after.misses), (before.hits, before.misses));
+
+        cache.insert(2, b"short", b"v", Some(1)).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        assert!(!cache.touch(2, b"short").unwrap());
+    }
+
+    #[test]
+    fn incr_creates_updates_and_rejects() {
+        let cache = open("incr", 4);
+        assert_eq!(cache.incr(1, b"n", 5, 10).unwrap(), Ok(15));
+        assert_eq!(cache.incr(1, b"n", -20, 10).unwrap(), Ok(-5));
+        match cache.get(1, b"n") {
+            ShmGetResult::Hit(v) => assert_eq!(crate::serde::decode_i64(&v), Some(-5)),
+            ShmGetResult::Miss(_) => panic!("counter missing"),
+        }
+        // Across the 2- and 9-byte encodings and back.
+        assert_eq!(cache.incr(1, b"n", 1000, 0).unwrap(), Ok(995));
+        assert_eq!(cache.incr(1, b"n", -1000, 0).unwrap(), Ok(-5));
+        assert_eq!(cache.info().current_size, 1);
+
+        cache
+            .insert(1, b"n", &crate::serde::encode_i64(i64::MAX), None)
+            .unwrap();
+        assert_eq!(cache.incr(1, b"n", 1, 0).unwrap(), Err(IncrError::Overflow));
+        assert_eq!(cache.incr(1, b"n", -1, 0).unwrap(), Ok(i64::MAX - 1));
+
+        cache.insert(2, b"s", b"\x06abc", None).unwrap();
+        assert_eq!(cache.incr(2, b"s", 1, 0).unwrap(), Err(IncrError::NotAnInt));
+        assert_eq!(cache.info().current_size, 2);
+    }
+
+    #[test]
+    fn compare_and_swap_has_one_winner_per_round() {
+        let a = open("cas", 8);
+        let b = ShmCache::create_or_open(
+            "rust_test_cas",
+            8,
+            64,
+            64,
+            None,
+            None,
+            false,
+            false,
+            VALUE_CODEC_NONE,
+            "pickle",
+            "",
+            None,
+            0,
+            0,
+            OnMismatch::Error,
+            &FileOptions::default(),
+        )
+        .unwrap();
+
+        assert!(!a.compare_and_swap(1, b"k", Some(b"x"), b"y").unwrap());
+        assert!(a.compare_and_swap(1, b"k", None, b"0").unwrap());
+        assert!(!b.compare_and_swap(1, b"k", None, b"other").unwrap());
+
+        // Both handles bump the counter by CAS-ing from the value they read;
+        // every lost race retries, so no increment goes missing.
+        let bump = |cache: &ShmCache| {
+            let mut wins = 0;
+            while wins < 500 {
+                let ShmGetResult::Hit(current) = cache.get(1, b"k") else {
+                    panic!("counter missing");
+                };
+                let next = (std::str::from_utf8(&current)
+                    .unwrap()
+                    .parse::<u32>()
+                    .unwrap()
+                    + 1)
+                .to_string();
+                if cache
+                    .compare_and_swap(1, b"k", Some(&current), next.as_bytes())
+                    .unwrap()
+                {
+                    wins += 1;
+                }
+            }
+        };
+        std::thread::scope(|s| {
+            s.spawn(|| bump(&a));
+            s.spawn(|| bump(&b));
+        });
+        assert!(matches!(a.get(1, b"k"), ShmGetResult::Hit(v) if v == b"1000"));
+    }
+
+    #[test]
+    fn list_regions_reads_headers_and_flags_foreign_files() {
+        let dir = std::env::temp_dir().join(format!("rust_test_list_{}", std::process::id()));
+        let cache = ShmCache::create_or_open(
+            "counters",
+            8,
+            64,
+            64,
+            Some(2.0),
+            None,
+            false,
+            false,
+            VALUE_CODEC_NONE,
+            "pickle",
+            "",
+            None,
+            0,
+            0,
+            OnMismatch::Error,
+            &FileOptions {
+                base_dir: Some(&dir),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        cache.insert(1, b"a", b"1", None).unwrap();
+        cache.get(1, b"a");
+        cache.get(2, b"b");
+        let shm = region::shm_dir(Some(&dir));
+        std::fs::write(shm.join("junk.data"), b"not a cache").unwrap();
+        std::fs::write(shm.join("old.data"), vec![0u8; 4096]).unwrap();
+
+        let listings = list_regions(Some(&dir)).unwrap();
+        let names: Vec<_> = listings.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["counters", "junk", "old"]);
+        let stats = listings[0].stats.as_ref().unwrap();
+        assert_eq!((stats.capacity, stats.current_size), (8, 1));
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(stats.ttl_nanos, 2_000_000_000);
+        assert!(listings[1].stats.is_err() && listings[2].stats.is_err());
+
+        drop(cache);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn truncated_or_corrupt_file_is_replaced_on_open() {
+        let dir = std::env::temp_dir().join(format!("rust_test_corrupt_{}", std::process::id()));
+        let files = FileOptions {
+            base_dir: Some(&dir),
+            ..Default::default()
+        };
+        let open_in_dir = || {
+            ShmCache::create_or_open(
+                "corrupt",
+                8,
+                64,
+                64,
+                None,
+                None,
+                false,
+                false,
+                VALUE_CODEC_NONE,
+                "pickle",
+                "",
+                None,
+                0,
+                0,
+                OnMismatch::Error,
+                &files,
+            )
+            .unwrap()
+        };
+        let cache = open_in_dir();
+        let data_path = region::shm_dir(Some(&dir)).join("corrupt.data");
+        let full_size = std::fs::metadata(&data_path).unwrap().len();
+
+        // Cut off the end of the slab, then break the hash table's geometry:
+        // either would send lookups past the end of the mapping.
+        type Corrupt = fn(&std::path::Path, u64);
+        let corruptions: [Corrupt; 2] = [
+            |path, size| {
+                let file = std::fs::OpenOptions::new().write(true).open(path).unwrap();
+                file.set_len(size / 2).unwrap();
+            },
+            |path, _| {
+                let mut bytes = std::fs::read(path).unwrap();
+                let at = std::mem::offset_of!(Header, ht_capacity);
+                bytes[at..at + 4].copy_from_slice(&1_000_000u32.to_ne_bytes());
+                std::fs::write(path, bytes).unwrap();
+            },
+        ];
+        drop(cache);
+        for corrupt in corruptions {
+            let cache = open_in_dir();
+            cache.insert(1, b"a", b"1", None).unwrap();
+            drop(cache);
+            corrupt(&data_path, full_size);
+
+            let cache = open_in_dir();
+            assert!(matches!(cache.get(1, b"a"), ShmGetResult::Miss(_)));
+            cache.insert(2, b"b", b"2", None).unwrap();
+            assert!(matches!(cache.get(2, b"b"), ShmGetResult::Hit(_)));
+            assert_eq!(std::fs::metadata(&data_path).unwrap().len(), full_size);
+        }
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn other_layout_versions_are_refused_or_replaced() {
+        let dir = std::env::temp_dir().join(format!("rust_test_version_{}", std::process::id()));
+        let files = FileOptions {
+            base_dir: Some(&dir),
+            ..Default::default()
+        };
+        let open_in_dir = |on_mismatch| {
+            ShmCache::create_or_open(
+                "version",
+                8,
+                64,
+                64,
+                None,
+                None,
+                false,
+                false,
+                VALUE_CODEC_NONE,
+                "pickle",
+                "",
+                None,
+                0,
+                0,
+                on_mismatch,
+                &files,
+            )
+        };
+        let data_path = region::shm_dir(Some(&dir)).join("version.data");
+        let set_version = |version: u32| {
+            let mut bytes = std::fs::read(&data_path).unwrap();
+            let at = std::mem::offset_of!(Header, version);
+            bytes[at..at + 4].copy_from_slice(&version.to_ne_bytes());
+            std::fs::write(&data_path, bytes).unwrap();
+        };
+
+        drop(open_in_dir(OnMismatch::Error).unwrap());
+        set_version(layout::VERSION + 1);
+        for on_mismatch in [
+            OnMismatch::Error,
+            OnMismatch::OpenExisting,
+            OnMismatch::Recreate,
+        ] {
+            let err = open_in_dir(on_mismatch).err().unwrap();
+            assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+            assert!(err.to_string().contains("created by a newer warp_cache"));
+        }
+
+        set_version(layout::VERSION - 1);
+        for on_mismatch in [OnMismatch::Error, OnMismatch::OpenExisting] {
+            let err = open_in_dir(on_mismatch).err().unwrap();
+            assert!(err.to_string().contains("created by an older warp_cache"));
+        }
+        let cache = open_in_dir(OnMismatch::Recreate).unwrap();
+        assert_eq!(cache.header().version, layout::VERSION);
+        drop(cache);
+        assert!(open_in_dir(OnMismatch::Error).is_ok());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn remove_frees_the_slot() {
+        let cache = open("remove", 2);
+        cache.insert(1, b"a", b"1", None).unwrap();
+        cache.insert(2, b"b", b"2", None).unwrap();
+        assert!(cache.remove(1, b"a").unwrap());
+        assert!(!cache.remove(1, b"a").unwrap());
+        assert!(matches!(cache.get(1, b"a"), ShmGetResult::Miss(_)));
+
+        cache.insert(3, b"c", b"3", None).unwrap();
+        let info = cache.info();
+        assert_eq!((info.current_size, info.evictions), (2, 0));
+        assert_eq!(info.bytes_used, 4);
+    }
+
+    #[test]
+    fn invalidate_tag_removes_only_tagged_entries() {
+        let cache = open("invalidate_tag", 8);
+        let tagged = |hash: u64, key: &[u8], tags: &[u64]| {
+            cache
+                .insert_with_token(InsertToken::default(), hash, key, b"v", None, tags)
+                .unwrap()
+        };
+        tagged(1, b"a", &[7]);
+        tagged(2, b"b", &[9, 7]);
+        tagged(3, b"c", &[7]);
+        tagged(4, b"d", &[7]);
+        // Overwriting an entry replaces its tags.
+        cache.insert(3, b"c", b"v", None).unwrap();
+        cache.compare_and_swap(4, b"d", Some(b"v"), b"w").unwrap();
+
+        // ... but a compare-and-swap keeps them.
+        assert_eq!(cache.invalidate_tag(7).unwrap(), 3);
+        assert_eq!(cache.invalidate_tag(7).unwrap(), 0);
+        assert!(matches!(cache.get(3, b"c"), ShmGetResult::Hit(_)));
+        assert_eq!(cache.invalidate_tag(9).unwrap(), 0);
+        assert_eq!(cache.info().current_size, 1);
+        assert!(cache.verify().unwrap().is_empty());
+    }
+
+    #[test]
+    fn chained_entries_free_their_whole_chain() {
+        // 8 + 16 data bytes per slot: a 60-byte value takes 3 slots, 40 takes 2.
+        let cache = ShmCache::create_or_open(
+            "rust_test_chained",
+            5,
+            8,
+            64,
+            None,
+            None,
+            false,
+            false,
+            VALUE_CODEC_NONE,
+            "pickle",
+            "",
+            Some(16),
+            0,
+            0,
+            OnMismatch::Recreate,
+            &FileOptions::default(),
+        )
+        .unwrap();
+        cache.clear().unwrap();
+        let big: Vec<u8> = (0..60).collect();
+        let mid: Vec<u8> = (100..140).collect();
+
+        cache.insert(1, b"a", &big, None).unwrap();
+        cache.insert(2, b"b", &mid, None).unwrap();
+        assert!(matches!(cache.get(1, b"a"), ShmGetResult::Hit(v) if v == big));
+        // Full: the next insert evicts one chained entry and reuses all its slots.
+        cache.insert(3, b"c", b"3", None).unwrap();
+        cache.insert(4, b"d", b"4", None).unwrap();
+        let info = cache.info();
+        assert_eq!((info.current_size, info.evictions), (3, 1));
+        assert!(unsafe { cache.is_consistent() });
+
+        // `a` was visited, so SIEVE evicted `b`. Growing `a` moves it to a
+        // longer chain.
+        assert!(matches!(cache.get(2, b"b"), ShmGetResult::Miss(_)));
+        cache.remove(3, b"c").unwrap();
+        cache.remove(4, b"d").unwrap();
+        cache.insert(1, b"a", &[7; 64], None).unwrap();
+        assert!(matches!(cache.get(1, b"a"), ShmGetResult::Hit(v) if v == [7; 64]));
+        assert!(unsafe { cache.is_consistent() });
+
+        // Removing it frees every slot of its chain.
+        assert!(cache.remove(1, b"a").unwrap());
+        for i in 0u8..5 {
+            cache.insert(10 + i as u64, &[i], b"x", None).unwrap();
+        }
+        let info = cache.info();
+        assert_eq!((info.current_size, info.evictions), (5, 1));
+        assert!(unsafe { cache.is_consistent() });
+    }
+
+    #[test]
+    fn overflow_chunks_hold_large_values() {
+        let cache = ShmCache::create_or_open(
+            "rust_test_overflow",
+            8,
+            64,
+            64,
+            None,
+            None,
+            false,
+            false,
+            VALUE_CODEC_NONE,
+            "pickle",
+            "",
+            None,
+            2,
+            256,
+            OnMismatch::Recreate,
+            &FileOptions::default(),
+        )
+        .unwrap();
+        cache.clear().unwrap();
+        let big = |b: u8| vec![b; 200];
+
+        cache.insert(1, b"a", &big(1), None).unwrap();
+        cache.insert(2, b"b", &big(2), None).unwrap();
+        assert!(matches!(cache.get(2, b"b"), ShmGetResult::Hit(v) if v == big(2)));
+        assert_eq!(cache.info().overflow_used, 2);
+
+        // Both chunks taken: the oldest entry holding one gives it up.
+        cache.insert(3, b"c", &big(3), None).unwrap();
+        assert!(matches!(cache.get(1, b"a"), ShmGetResult::Miss(_)));
+        let info = cache.info();
+        assert_eq!(
+            (info.current_size, info.evictions, info.overflow_used),
+            (2, 1, 2)
+        );
+
+        // Shrinking a value moves it back inline and frees its chunk.
+        cache.insert(2, b"b", b"small", None).unwrap();
+        assert!(matches!(cache.get(2, b"b"), ShmGetResult::Hit(v) if v == b"small"));
+        assert!(cache.remove(3, b"c").unwrap());
+        assert_eq!(cache.info().overflow_used, 0);
+
+        // Over the chunk size is still too big.
+        cache.insert(4, b"d", &[0; 300], None).unwrap();
+        assert!(matches!(cache.get(4, b"d"), ShmGetResult::Miss(_)));
+        assert_eq!(cache.info().oversize_value_skips, 1);
+        assert!(unsafe { cache.is_consistent() });
+    }
+
+    #[test]
+    fn get_or_insert_keeps_the_first_value() {
+        let cache = open("get_or_insert", 16);
+
+        let before = cache.lock().sequence();
+        assert_eq!(
+            cache.get_or_insert(7, b"key", b"first").unwrap(),
+            (b"first".to_vec(), true)
+        );
+        assert_eq!(cache.lock().sequence() - before, 2);
+
+        assert_eq!(
+            cache.get_or_insert(7, b"key", b"second").unwrap(),
+            (b"first".to_vec(), false)
+        );
+        let info = cache.info();
+        assert_eq!(info.current_size, 1);
+        assert_eq!((info.hits, info.misses), (1, 1));
+    }
+
+    #[test]
+    fn resize_is_seen_by_other_handles() {
+        let cache = open("resize", 8);
+        let other = ShmCache::create_or_open(
+            "rust_test_resize",
+            8,
+            64,
+            64,
+            None,
+            None,
+            false,
+            false,
+            VALUE_CODEC_NONE,
+            "pickle",
+            "",
+            None,
+            0,
+            0,
+            OnMismatch::Error,
+            &FileOptions::default(),
+        )
+        .unwrap();
+        for i in 0u8..8 {
+            cache.insert(i as u64, &[i], &[i], None).unwrap();
+        }
+        let serde_format = cache.record_serde_format(1).max(1);
+
+        cache.resize(4).unwrap();
+        assert_eq!(other.info().max_size, 4);
+        assert_eq!(other.record_serde_format(0), serde_format);
+        assert_eq!(
+            other.keys().unwrap(),
+            vec![vec![4], vec![5], vec![6], vec![7]]
+        );
+        assert!(matches!(other.get(3, &[3]), ShmGetResult::Miss(_)));
+
+        other.resize(16).unwrap();
+        for i in 8u8..16 {
+            cache.insert(i as u64, &[i], &[i], None).unwrap();
+        }
+        let info = other.info();
+        assert_eq!((info.max_size, info.current_size), (16, 12));
+        assert_eq!(info.evictions, 0);
+        assert!(matches!(other.get(15, &[15]), ShmGetResult::Hit(v) if v == [15]));
+    }
+
+    #[test]
+    fn mismatched_open_leaves_the_region_alone() {
+        let cache = open("mismatch", 8);
+        cache.insert(1, b"key", b"value", None).unwrap();
+        let reopen = |capacity, on_mismatch| {
+            ShmCache::create_or_open(
+                "rust_test_mismatch",
+                capacity,
+                64,
+                64,
+                None,
+                None,
+                false,
+                false,
+                VALUE_CODEC_NONE,
+                "pickle",
+                "",
+                None,
+                0,
+                0,
+                on_mismatch,
+                &FileOptions::default(),
+            )
+        };
+
+        let err = reopen(16, OnMismatch::Error).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(err
+            .to_string()
+            .contains("capacity: existing 8, requested 16"));
+        assert!(matches!(cache.get(1, b"key"), ShmGetResult::Hit(_)));
+
+        let adopted = reopen(16, OnMismatch::OpenExisting).unwrap();
+        assert_eq!(adopted.config().capacity, 8);
+        assert!(matches!(adopted.get(1, b"key"), ShmGetResult::Hit(_)));
+
+        let recreated = reopen(16, OnMismatch::Recreate).unwrap();
+        assert_eq!(recreated.info().max_size, 16);
+        assert!(matches!(recreated.get(1, b"key"), ShmGetResult::Miss(_)));
+        // The new files replaced the old ones rather than overwriting them, so
+        // handles already open never saw their region truncated, and they
+        // switch over to the new one once they notice.
+        if cfg!(unix) {
+            let info = cache.info();
+            assert_eq!((info.max_size, info.remaps), (16, 1));
+            cache.insert(2, b"other", b"value", None).unwrap();
+            assert!(matches!(recreated.get(2, b"other"), ShmGetResult::Hit(_)));
+            assert_eq!(cache.info().remaps, 1);
+        }
+    }
+
+    #[test]
+    fn same_name_in_different_dirs_does_not_collide() {
+        let base = std::env::temp_dir().join(format!("rust_test_shm_dir_{}", std::process::id()));
+        let (dir_a, dir_b) = (base.join("a"), base.join("b"));
+        let open_in = |dir: &Path| {
+            ShmCache::create_or_open(
+                "rust_test_dirs",
+                8,
+                64,
+                64,
+                None,
+                None,
+                false,
+                false,
+                VALUE_CODEC_NONE,
+                "pickle",
+                "",
+                None,
+                0,
+                0,
+                OnMismatch::Error,
+                &FileOptions {
+                    base_dir: Some(dir),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let a = open_in(&dir_a);
+        let b = open_in(&dir_b);
+        a.insert(1, b"key", b"a", None).unwrap();
+        assert!(matches!(b.get(1, b"key"), ShmGetResult::Miss(_)));
+        assert!(matches!(a.get(1, b"key"), ShmGetResult::Hit(v) if v == b"a"));
+
+        drop((a, b));
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn remove_orphans_skips_open_caches() {
+        let base = std::env::temp_dir().join(format!("rust_test_gc_{}", std::process::id()));
+        let files = FileOptions {
+            base_dir: Some(&base),
+            ..Default::default()
+        };
+        let open_named = |name: &str| {
+            ShmCache::create_or_open(
+                name,
+                8,
+                64,
+                64,
+                None,
+                None,
+                false,
+                false,
+                VALUE_CODEC_NONE,
+                "pickle",
+                "",
+                None,
+                0,
+                0,
+                OnMismatch::Error,
+                &files,
+            )
+            .unwrap()
+        };
+
+        let live = open_named("live");
+        drop(open_named("orphan"));
+
+        let zero = std::time::Duration::ZERO;
+        assert_eq!(remove_orphans(Some(&base), zero, true).unwrap(), ["orphan"]);
+        assert_eq!(
+            remove_orphans(Some(&base), zero, false).unwrap(),
+            ["orphan"]
+        );
+        assert!(remove_orphans(Some(&base), zero, false).unwrap().is_empty());
+        live.insert(1, b"key", b"value", None).unwrap();
+        assert!(matches!(live.get(1, b"key"), ShmGetResult::Hit(_)));
+
+        drop(live);
+        assert_eq!(remove_orphans(Some(&base), zero, false).unwrap(), ["live"]);
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn durable_cache_recovers_from_a_writer_killed_mid_insert() {
+        let base = std::env::temp_dir().join(format!("rust_test_durable_{}", std::process::id()));
+        let files = FileOptions {
+            base_dir: Some(&base),
+            persistent: true,
+            ..Default::default()
+        };
+        let open_durable = || {
+            ShmCache::create_or_open(
+                "rust_test_durable",
+                4,
+                64,
+                64,
+                Some(60.0),
+                None,
+                false,
+                false,
+                VALUE_CODEC_NONE,
+                "pickle",
+                "",
+                None,
+                0,
+                0,
+                OnMismatch::Error,
+                &files,
+            )
+            .unwrap()
+        };
+
+        let cache = open_durable();
+        for i in 0u8..3 {
+            cache.insert(i as u64, &[i], b"value", None).unwrap();
+        }
+        // Die halfway through overwriting key 1: write lock held, slot
+        // journaled, value torn, size already bumped.
+        let lock = cache.lock();
+        lock.write_lock();
+        unsafe {
+            let idx = hashtable::ht_lookup(
+                cache.ht_base(),
+                cache.header().ht_capacity,
+                cache.slab_base(),
+                cache.header().slot_size,
+                1,
+                &[1],
+            )
+            .unwrap();
+            let header = cache.header_mut();
+            header.pending_slot = idx;
+            header.current_size += 1;
+        }
+        drop(cache);
+
+        let cache = open_durable();
+        assert!(matches!(cache.get(0, &[0]), ShmGetResult::Hit(v) if v == b"value"));
+        assert!(matches!(cache.get(1, &[1]), ShmGetResult::Miss(_)));
+        assert!(matches!(cache.get(2, &[2]), ShmGetResult::Hit(v) if v == b"value"));
+        assert_eq!(cache.info().current_size, 2);
+        assert_eq!(cache.info().bytes_used, 2 * 6);
+        // The dead writer's lock was released, and the freed slot is reusable.
+        for i in 3u8..6 {
+            cache.insert(i as u64, &[i], b"value", None).unwrap();
+        }
+        assert_eq!(cache.info().current_size, 4);
+        assert!(unsafe { cache.is_consistent() });
+
+        drop(cache);
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writer_that_died_holding_the_lock_is_recovered_from() {
+        let cache = open("dead_writer", 4);
+        for i in 0u8..3 {
+            cache.insert(i as u64, &[i], b"value", None).unwrap();
+        }
+        // Another process dies halfway through overwriting key 1.
+        let lock = cache.lock();
+        lock.write_lock();
+        unsafe {
+            let idx = hashtable::ht_lookup(
+                cache.ht_base(),
+                cache.header().ht_capacity,
+                cache.slab_base(),
+                cache.header().slot_size,
+                1,
+                &[1],
+            )
+            .unwrap();
+            let header = cache.header_mut();
+            header.pending_slot = idx;
+            header.current_size += 1;
+        }
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        lock.hand_to(child.id());
+        child.wait().unwrap();
+
+        // A reader notices the dead writer, takes the lock over and repairs.
+        assert!(matches!(cache.get(0, &[0]), ShmGetResult::Hit(v) if v == b"value"));
+        assert!(!lock.is_write_locked());
+        assert!(matches!(cache.get(1, &[1]), ShmGetResult::Miss(_)));
+        assert_eq!(cache.info().current_size, 2);
+        assert!(unsafe { cache.is_consistent() });
+        cache.insert(3, &[3], b"value", None).unwrap();
+        assert!(matches!(cache.get(3, &[3]), ShmGetResult::Hit(_)));
+    }
+
+    #[test]
+    fn insert_after_a_miss_reprobes_only_if_something_was_written() {
+        let cache = open("insert_token", 8);
+        cache.clear().unwrap();
+        let ShmGetResult::Miss(token) = cache.get(1, &[1]) else {
+            panic!("hit in an empty cache");
+        };
+        assert!(token.absent_at.is_some());
+        cache
+            .insert_with_token(token, 1, &[1], b"first", None, &[])
+            .unwrap();
+
+        // Another process inserts the key between the miss and our insert:
+        // that entry is updated, not joined by a second one.
+        let ShmGetResult::Miss(token) = cache.get(2, &[2]) else {
+            panic!("hit on an absent key");
+        };
+        cache.insert(2, &[2], b"theirs", None).unwrap();
+        cache
+            .insert_with_token(token, 2, &[2], b"ours", None, &[])
+            .unwrap();
+        assert!(matches!(cache.get(2, &[2]), ShmGetResult::Hit(v) if v == b"ours"));
+        assert!(matches!(cache.get(1, &[1]), ShmGetResult::Hit(v) if v == b"first"));
+        assert_eq!(cache.info().current_size, 2);
+        assert!(unsafe { cache.is_consistent() });
+    }
+
+    #[test]
+    fn checksums_turn_a_scribbled_value_into_a_miss() {
+        let cache = ShmCache::create_or_open(
+            "rust_test_checksums",
+            8,
+            64,
+            64,
+            None,
+            None,
+            false,
+            true,
+            VALUE_CODEC_NONE,
+            "pickle",
+            "",
+            None,
+            0,
+            0,
+            OnMismatch::Recreate,
+            &FileOptions::default(),
+        )
+        .unwrap();
+        cache.clear().unwrap();
+        for k in 1u8..=3 {
+            cache.insert(k as u64, &[k], b"value", None).unwrap();
+        }
+        // Another process scribbles over the values of keys 1 and 2.
+        let flip = |k: u8| unsafe {
+            let h = cache.header();
+            let idx = hashtable::ht_lookup(
+                cache.ht_base(),
+                h.ht_capacity,
+                cache.slab_base(),
+                h.slot_size,
+                k as u64,
+                &[k],
+            )
+            .unwrap();
+            let slot_ptr = cache
+                .slab_base_mut()
+                .add(idx as usize * h.slot_size as usize);
+            *slot_ptr.add(SLOT_HEADER_SIZE + 1) ^= 0x20;
+        };
+        flip(1);
+        flip(2);
+
+        assert!(matches!(cache.get(1, &[1]), ShmGetResult::Miss(_)));
+        assert_eq!(
+            cache.get_batch(&[(2, &[2]), (3, &[3])]),
+            vec![None, Some(b"value".to_vec())]
+        );
+        let info = cache.info();
+        assert_eq!((info.corruption_detected, info.current_size), (2, 1));
+        assert!(unsafe { cache.is_consistent() });
+
+        // Rewritten entries get a fresh checksum.
+        cache.insert(1, &[1], b"value", None).unwrap();
+        assert!(matches!(cache.get(1, &[1]), ShmGetResult::Hit(v) if v == b"value"));
+    }
+
+    #[test]
+    fn key_hot_during_warm_up_is_evicted_once_its_traffic_stops() {
+        let cache = open("hot_then_cold", 4);
+        cache.insert(0, &[0], b"warm-up", None).unwrap();
+        for _ in 0..1000 {
+            assert!(matches!(cache.get(0, &[0]), ShmGetResult::Hit(_)));
+        }
+
+        // New keys that are each hit once: SIEVE keeps one visited bit, not
+        // a count, so a thousand old hits buy one pass of the hand at most.
+        for k in 1u8..=4 {
+            cache.insert(k as u64, &[k], b"new", None).unwrap();
+            assert!(matches!(cache.get(k as u64, &[k]), ShmGetResult::Hit(_)));
+        }
+        assert!(matches!(cache.get(0, &[0]), ShmGetResult::Miss(_)));
+        assert_eq!(cache.info().current_size, 4);
+        assert!(unsafe { cache.is_consistent() });
+    }
+
+    #[test]
+    fn verify_stays_clean_under_random_operations() {
+        let cache = ShmCache::create_or_open(
+            "rust_test_verify_fuzz",
+            16,
+            16,
+            64,
+            None,
+            None,
+            false,
+            false,
+            VALUE_CODEC_NONE,
+            "pickle",
+            "",
+            Some(24),
+            2,
+            256,
+            OnMismatch::Recreate,
+            &FileOptions::default(),
+        )
+        .unwrap();
+        cache.clear().unwrap();
+
+        // xorshift64: deterministic, so a failure replays.
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let mut next = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+        for step in 0..5000 {
+            let key = [next(24) as u8];
+            let op = next(100);
+            if op < 60 {
+                // Mostly inline values, some chained, a few in the overflow arena
+                let len = match next(10) {
+                    0 => 64 + next(192),
+                    1..=3 => 24 + next(40),
+                    _ => next(24),
+                } as usize;
+                cache
+                    .insert(key[0] as u64, &key, &vec![7; len], None)
+                    .unwrap();
+            } else if op < 85 {
+                cache.remove(key[0] as u64, &key).unwrap();
+            } else if op < 99 {
+                let _ = cache.get(key[0] as u64, &key);
+            } else {
+                cache.clear().unwrap();
+            }
+            assert_eq!(cache.verify().unwrap(), Vec::<String>::new(), "step {step}");
+        }
+    }
+
+    #[test]
+    fn verify_reports_a_corrupted_index() {
+        let cache = open("verify_corrupt", 4);
+        cache.insert(1, b"a", b"one", None).unwrap();
+        cache.insert(2, b"b", b"two", None).unwrap();
+        assert!(cache.verify().unwrap().is_empty());
+
+        unsafe { cache.header_mut().current_size = 3 };
+        let violations = cache.verify().unwrap();
+        assert_eq!(violations.len(), 1);
+        assert!(
+            violations[0].contains("current_size is 3"),
+            "{violations:?}"
+        );
+
+        unsafe { cache.header_mut().current_size = 2 };
+        let head = cache.header().list_head;
+        unsafe { (*(cache.slot(head) as *const SlotHeader as *mut SlotHeader)).key_hash ^= 1 };
+        let violations = cache.verify().unwrap();
+        assert!(
+            violations.iter().any(|v| v.contains("differs from slot")),
+            "{violations:?}"
+        );
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn clear_releasing_memory_refills_from_fresh_slots() {
+        let cache = open("release_memory", 256);
+        for i in 0..256u64 {
+            cache.insert(i, &i.to_le_bytes(), b"value", None).unwrap();
+        }
+        let before = cache.info().allocated_bytes;
+        cache.clear_releasing_memory().unwrap();
+        // verify() must not fault the released pages back in
+        assert!(cache.verify().unwrap().is_empty());
+        if cfg!(target_os = "linux") {
+            assert_eq!(cache.header().fresh_start, 0);
+            assert!(cache.info().allocated_bytes < before);
+        }
+
+        // Refill past capacity, so fresh slots, evictions and then the free
+        // list (after a remove) all hand out slots
+        for i in 0..300u64 {
+            cache.insert(i, &i.to_le_bytes(), b"value", None).unwrap();
+        }
+        assert_eq!(cache.header().fresh_start, 256);
+        assert!(cache.remove(299, &299u64.to_le_bytes()).unwrap());
+        cache.insert(1000, b"k", b"v", None).unwrap();
+        assert!(cache.verify().unwrap().is_empty());
+        assert!(
+            matches!(cache.get(298, &298u64.to_le_bytes()), ShmGetResult::Hit(v) if v == b"value")
+        );
+        assert_eq!(cache.info().current_size, 256);
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn readonly_handle_reads_without_writing() {
+        let open_readonly = |name: &str, capacity: u32| {
+            ShmCache::create_or_open(
+                &format!("rust_test_{name}"),
+                capacity,
+                64,
+                64,
+                None,
+                None,
+                false,
+                false,
+                VALUE_CODEC_NONE,
+                "pickle",
+                "",
+                None,
+                0,
+                0,
+                OnMismatch::Error,
+                &FileOptions {
+                    readonly: true,
+                    ..Default::default()
+                },
+            )
+        };
+        let writer = open("readonly", 16);
+        writer.insert(1, b"a", b"one", None).unwrap();
+        let reader = open_readonly("readonly", 16).unwrap();
+        assert!(reader.is_readonly());
+
+        // Hits and misses count only locally, and the SIEVE bit stays clear
+        assert!(matches!(reader.get(1, b"a"), ShmGetResult::Hit(v) if v == b"one"));
+        assert!(matches!(reader.get(2, b"b"), ShmGetResult::Miss(_)));
+        let local = reader.local_info();
+        assert_eq!((local.hits, local.misses), (1, 1));
+        let shared = writer.info();
+        assert_eq!((shared.hits, shared.misses), (0, 0));
+        assert!(!writer.debug_info(16).unwrap().slots[0].visited);
+
+        // The newest serde format is kept, and a reader only reads it
+        let newest = writer.record_serde_format(3).max(3);
+        assert_eq!(reader.record_serde_format(newest + 1), newest);
+        assert_eq!(writer.record_serde_format(1), newest);
+
+        // Every write is refused; the writer's later ones are seen
+        let refused = reader.insert(2, b"b", b"two", None).unwrap_err();
+        assert_eq!(refused.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(reader.remove(1, b"a").is_err());
+        assert!(reader.clear().is_err());
+        writer.insert(2, b"b", b"two", None).unwrap();
+        assert!(matches!(reader.get(2, b"b"), ShmGetResult::Hit(v) if v == b"two"));
+
+        // A mismatch or a missing cache is an error, never a recreation
+        let mismatch = open_readonly("readonly", 32).err().unwrap();
+        assert_eq!(mismatch.kind(), std::io::ErrorKind::Unsupported);
+        assert_eq!(writer.info().current_size, 2);
+        let missing = open_readonly("readonly_never_created", 16).err().unwrap();
+        assert_eq!(missing.kind(), std::io::ErrorKind::NotFound);
+        writer.clear().unwrap();
+    }
+
+    #[test]
+    fn insert_into_a_full_hash_table_gives_its_slots_back() {
+        let cache = open("ht_full", 4);
+        // Occupy every bucket with hashes that aren't ours
+        let ht_cap = cache.header().ht_capacity as usize;
+        for i in 0..ht_cap {
+            let bucket =
+                unsafe { &mut *(cache.ht_base_mut().add(i * Bucket::SIZE) as *mut Bucket) };
+            bucket.hash = 100 + i as u64;
+            bucket.slot_index = 0;
+        }
+
+        cache.insert(1, b"a", b"one", None).unwrap();
+        let info = cache.info();
+        assert_eq!((info.insert_failures, info.current_size), (1, 0));
+        let debug = cache.debug_info(100).unwrap();
+        assert_eq!(debug.free_list.len(), 4);
+        assert_eq!(
+            debug
+                .header
+                .iter()
+                .find(|(k, _)| *k == "pending_slot")
+                .unwrap()
+                .1,
+            -1
+        );
+
+        cache.clear().unwrap();
+        cache.insert(1, b"a", b"one", None).unwrap();
+        assert_eq!(cache.info().current_size, 1);
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn debug_info_walks_stop_on_a_cyclic_list() {
+        let cache = open("debug_info_cycle", 4);
+        cache.insert(1, b"a", b"one", None).unwrap();
+        cache.insert(2, b"b", b"two", None).unwrap();
+        let info = cache.debug_info(100).unwrap();
+        assert_eq!(info.eviction_order.len(), 2);
+        assert_eq!((info.slots.len(), info.free_list.len()), (4, 2));
+
+        // Point the tail back at the head: the walk gives up after `capacity`.
+        let (head, tail) = (info.eviction_order[0], info.eviction_order[1]);
+        unsafe { (*(cache.slot(tail) as *const SlotHeader as *mut SlotHeader)).next = head };
+        assert_eq!(cache.debug_info(100).unwrap().eviction_order.len(), 4);
+        assert_eq!(cache.debug_info(3).unwrap().eviction_order.len(), 3);
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn evicted_entries_are_kept_for_the_evicting_handle_only() {
+        let mut cache = open("keep_evicted", 2);
+        cache.keep_evicted();
+        let other = open("keep_evicted", 2);
+        cache.insert(1, b"a", b"one", None).unwrap();
+        cache.insert(2, b"b", b"two", None).unwrap();
+        assert!(cache.take_evicted().is_empty());
+
+        cache.insert(3, b"c", b"three", None).unwrap();
+        assert_eq!(cache.take_evicted(), vec![(b"a".to_vec(), b"one".to_vec())]);
+        assert!(cache.take_evicted().is_empty());
+
+        // Another handle's eviction isn't reported here.
+        other.insert(4, b"d", b"four", None).unwrap();
+        assert!(cache.take_evicted().is_empty());
+    }
+
+    #[test]
+    fn sliding_hits_take_the_write_lock_only_once_the_entry_has_aged() {
+        let cache = ShmCache::create_or_open(
+            "rust_test_sliding_hits",
+            8,
+            64,
+            64,
+            Some(6.4),
+            None,
+            true,
+            false,
+            VALUE_CODEC_NONE,
+            "pickle",
+            "",
+            None,
+            0,
+            0,
+            OnMismatch::Recreate,
+            &FileOptions::default(),
+        )
+        .unwrap();
+        cache.clear().unwrap();
+        cache.insert(1, &[1], b"value", None).unwrap();
+
+        // Well under ttl / SLIDE_GRANULARITY old: hits are read-only.
+        let before = cache.lock().sequence();
+        for _ in 0..100 {
+            assert!(matches!(cache.get(1, &[1]), ShmGetResult::Hit(_)));
+        }
+        assert_eq!(cache.get_batch(&[(1, &[1])]), vec![Some(b"value".to_vec())]);
+        assert_eq!(cache.lock().sequence(), before);
+
+        // Once it has aged past that, the next hit restarts the clock.
+        std::thread::sleep(Duration::from_millis(120));
+        assert!(matches!(cache.get(1, &[1]), ShmGetResult::Hit(_)));
+        assert_eq!(cache.lock().sequence(), before + 2);
+        assert!(matches!(cache.get(1, &[1]), ShmGetResult::Hit(_)));
+        assert_eq!(cache.lock().sequence(), before + 2);
+    }
+
+    #[test]
+    fn lock_memory_and_prefault_pin_the_mapping() {
+        let open_with = |files: &FileOptions| {
+            ShmCache::create_or_open(
+                "rust_test_pinned",
+                8,
+                64,
+                64,
+                None,
+                None,
+                false,
+                false,
+                VALUE_CODEC_NONE,
+                "pickle",
+                "",
+                None,
+                0,
+                0,
+                OnMismatch::Recreate,
+                files,
+            )
+        };
+        let cache = open_with(&FileOptions {
+            prefault: true,
+            ..Default::default()
+        })
+        .unwrap();
+        cache.insert(1, &[1], b"value", None).unwrap();
+        assert!(!cache.info().memory_locked);
+
+        // A few KiB fits under any RLIMIT_MEMLOCK but zero; if mlock is refused
+        // anyway, the error must say which limit to raise.
+        match open_with(&FileOptions {
+            lock_memory: true,
+            ..Default::default()
+        }) {
+            Ok(cache) => {
+                assert!(cache.info().memory_locked);
+                assert!(matches!(cache.get(1, &[1]), ShmGetResult::Hit(_)));
+            }
+            Err(e) => assert!(
+                cfg!(windows) || e.to_string().contains("RLIMIT_MEMLOCK"),
+                "{e}"
+            ),
+        }
+    }
+
+    #[test]
+    fn lock_held_past_the_timeout_fails_writes_and_misses_reads() {
+        let mut cache = open("lock_timeout", 4);
+        cache.insert(1, &[1], b"value", None).unwrap();
+        cache.lock_timeout = Some(Duration::from_millis(20));
+
+        // A live writer (this process) holds the lock and never lets go.
+        let lock = cache.lock();
+        lock.write_lock();
+        assert!(matches!(cache.get(1, &[1]), ShmGetResult::Miss(_)));
+        assert_eq!(cache.get_batch(&[(1, &[1])]), vec![None]);
+        let err = cache.insert(2, &[2], b"value", None).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert!(err.to_string().contains("rust_test_lock_timeout"));
+        let holder = format!("write lock held by pid {} since", std::process::id());
+        assert!(err.to_string().contains(&holder), "{err}");
+        let info = cache.info();
+        assert_eq!((info.lock_timeouts, info.misses), (2, 2));
+
+        lock.write_unlock();
+        assert!(matches!(cache.get(1, &[1]), ShmGetResult::Hit(_)));
+        cache.insert(2, &[2], b"value", None).unwrap();
+        cache.clear().unwrap();
+        assert_eq!(cache.info().lock_timeouts, 0);
+    }
+
+    #[test]
+    fn volatile_cache_is_emptied_after_a_reboot() {
+        if boot_id() == 0 {
+            return; // no boot id on this platform
+        }
+        let base = std::env::temp_dir().join(format!("rust_test_reboot_{}", std::process::id()));
+        let files = FileOptions {
+            base_dir: Some(&base),
+            ..Default::default()
+        };
+        let open = || {
+            ShmCache::create_or_open(
+                "rust_test_reboot",
+                4,
+                64,
+                64,
+                Some(60.0),
+                None,
+                false,
+                false,
+                VALUE_CODEC_NONE,
+                "pickle",
+                "",
+                None,
+                0,
+                0,
+                OnMismatch::Error,
+                &files,
+            )
+            .unwrap()
+        };
+
+        let cache = open();
+        cache.insert(1, &[1], b"value", None).unwrap();
+        drop(cache);
+        // Same boot: the entry is kept.
+        let cache = open();
+        assert!(matches!(cache.get(1, &[1]), ShmGetResult::Hit(_)));
+
+        // Pretend the file was written in an earlier boot by a process that
+        // died holding the write lock.
+        cache.lock().write_lock();
+        unsafe { cache.header_mut().boot_id ^= 1 };
+        drop(cache);
+
+        let cache = open();
+        assert!(matches!(cache.get(1, &[1]), ShmGetResult::Miss(_)));
+        assert_eq!(cache.info().current_size, 0);
+        assert_eq!(cache.header().boot_id, boot_id());
+        cache.insert(2, &[2], b"value", None).unwrap();
+        assert!(matches!(cache.get(2, &[2]), ShmGetResult::Hit(_)));
+
+        drop(cache);
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn value_sizes_land_in_doubling_buckets() {
+        // max_value_size 64 reaches 4× in few buckets, so the bounds start at 1 byte.
+        let cache = open("value_sizes", 4);
+        cache.reset_stats().unwrap();
+        for len in [1, 2, 3, 64, 65, 16384, 16385] {
+            cache.record_value_size(len);
+        }
+        let (buckets, largest) = cache.value_size_histogram();
+        assert_eq!(buckets.len(), VALUE_SIZE_BUCKETS);
+        assert_eq!(buckets[0], (Some(1), 1));
+        assert_eq!(buckets[1], (Some(2), 1));
+        assert_eq!(buckets[2], (Some(4), 1));
+        assert_eq!(buckets[6], (Some(64), 1));
+        assert_eq!(buckets[7], (Some(128), 1));
+        assert_eq!(buckets[14], (Some(16384), 1));
+        assert_eq!(buckets[15], (None, 1));
+        assert_eq!(largest, 16385);
+    }
+
+    #[test]
+    fn contention_is_counted_per_handle() {
+        let cache = open("contention", 64);
+        cache.reset_stats().unwrap();
+        for i in 0..32u64 {
+            cache.insert(i, &i.to_le_bytes(), b"value", None).unwrap();
+            cache.get(i, &i.to_le_bytes());
+        }
+        // Alone, nothing waits; every insert took the lock once.
+        let info = cache.contention_info();
+        assert_eq!(
+            (info.read_waits, info.read_retries, info.write_spins),
+            (0, 0, 0)
+        );
+        assert_eq!(info.write_locks, 32);
+
+        // Writers and readers racing until both sides have had to wait (on a
+        // single core, only once a writer is preempted holding the lock).
+        let deadline = std::time::Instant::now() + Duration::from_secs(20);
+        let busy = || {
+            let info = cache.contention_info();
+            info.write_spins == 0 || info.read_waits + info.read_retries == 0
+        };
+        std::thread::scope(|s| {
+            for t in 0..4u64 {
+                let cache = &cache;
+                s.spawn(move || {
+                    let value = vec![t as u8; 48];
+                    let mut i = 0u64;
+                    while busy() && std::time::Instant::now() < deadline {
+                        let key = (i % 64).to_le_bytes();
+                        cache.insert(i % 64, &key, &value, None).unwrap();
+                        cache.get((i + 7) % 64, &((i + 7) % 64).to_le_bytes());
+                        i += 1;
+                    }
+                });
+            }
+        });
+        let info = cache.contention_info();
+        assert!(info.write_spins > 0);
+        assert!(info.read_waits + info.read_retries > 0);
+
+        cache.reset_local_stats();
+        assert_eq!(cache.contention_info().write_locks, 0);
+    }
+}
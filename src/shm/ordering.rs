@@ -27,6 +27,12 @@ pub unsafe fn list_remove(header: &mut Header, slab_base: *mut u8, slot_size: u3
     let prev = s.prev;
     let next = s.next;
 
+    // Keep the SIEVE hand on a live entry: a hand left on a freed slot would
+    // walk into the free list on the next eviction.
+    if header.sieve_hand == index {
+        header.sieve_hand = next;
+    }
+
     if prev != SLOT_NONE {
         slot_mut(slab_base, slot_size, prev).next = next;
     } else {
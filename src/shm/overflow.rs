@@ -0,0 +1,120 @@
+/// The overflow arena: a few large chunks after the slab for the occasional
+/// value over `max_value_size`.
+///
+/// An entry whose value lives here keeps only its key in its slot, with
+/// `SlotHeader::overflow` pointing at the chunk. Free chunks form their own
+/// list, linked through the first four bytes of each chunk.
+use super::layout::{Header, SLOT_NONE};
+
+/// Pointer to chunk `index` of the arena at `arena`.
+///
+/// # Safety
+/// `arena` must point to an arena of chunks of `chunk_size` bytes, and
+/// `index` must be one of them.
+#[inline]
+pub unsafe fn chunk(arena: *const u8, chunk_size: u32, index: i32) -> *const u8 {
+    arena.add(index as usize * chunk_size as usize)
+}
+
+/// Copy out a `value_len`-byte value from chunk `index`, or `None` if either
+/// is out of bounds (a torn optimistic read).
+///
+/// # Safety
+/// `arena` must point to `count` chunks of `chunk_size` bytes.
+pub unsafe fn read_value(
+    arena: *const u8,
+    chunk_size: u32,
+    count: u32,
+    index: i32,
+    value_len: usize,
+) -> Option<Vec<u8>> {
+    if index < 0 || index as u32 >= count || value_len > chunk_size as usize {
+        return None;
+    }
+    let src = chunk(arena, chunk_size, index);
+    Some(std::slice::from_raw_parts(src, value_len).to_vec())
+}
+
+/// Write `value` into chunk `index`.
+///
+/// # Safety
+/// Caller must hold the write lock; `value` must fit in a chunk.
+pub unsafe fn write_value(arena: *mut u8, chunk_size: u32, index: i32, value: &[u8]) {
+    let dest = chunk(arena, chunk_size, index) as *mut u8;
+    std::ptr::copy_nonoverlapping(value.as_ptr(), dest, value.len());
+}
+
+/// Take a chunk off the free list, or `SLOT_NONE` if every chunk is in use.
+///
+/// # Safety
+/// Caller must hold the write lock.
+pub unsafe fn pop_free(header: &mut Header, arena: *mut u8) -> i32 {
+    let index = header.overflow_free;
+    if index != SLOT_NONE {
+        header.overflow_free = *(chunk(arena, header.overflow_size, index) as *const i32);
+        header.overflow_used += 1;
+    }
+    index
+}
+
+/// Give chunk `index` back to the free list.
+///
+/// # Safety
+/// Caller must hold the write lock; the chunk must be in use.
+pub unsafe fn push_free(header: &mut Header, arena: *mut u8, index: i32) {
+    *(chunk(arena, header.overflow_size, index) as *mut i32) = header.overflow_free;
+    header.overflow_free = index;
+    header.overflow_used -= 1;
+}
+
+/// Rebuild the free list from every chunk for which `in_use` is false, and
+/// recount `overflow_used`.
+///
+/// # Safety
+/// Caller must hold the write lock (or own the region exclusively).
+pub unsafe fn reset_free_list(header: &mut Header, arena: *mut u8, in_use: impl Fn(i32) -> bool) {
+    header.overflow_free = SLOT_NONE;
+    let mut used = header.overflow_count;
+    for index in (0..header.overflow_count as i32).rev() {
+        if !in_use(index) {
+            *(chunk(arena, header.overflow_size, index) as *mut i32) = header.overflow_free;
+            header.overflow_free = index;
+            used -= 1;
+        }
+    }
+    header.overflow_used = used;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shm::layout::HEADER_SIZE;
+
+    #[test]
+    fn free_list_hands_out_every_chunk_once() {
+        let mut raw = vec![0u64; HEADER_SIZE / 8];
+        let header = unsafe { &mut *(raw.as_mut_ptr() as *mut Header) };
+        header.overflow_count = 3;
+        header.overflow_size = 16;
+        let mut arena = vec![0u8; 3 * 16];
+        let base = arena.as_mut_ptr();
+        unsafe {
+            reset_free_list(header, base, |index| index == 1);
+            assert_eq!(header.overflow_used, 1);
+            assert_eq!((pop_free(header, base), pop_free(header, base)), (0, 2));
+            assert_eq!(pop_free(header, base), SLOT_NONE);
+            assert_eq!(header.overflow_used, 3);
+
+            write_value(base, 16, 2, b"overflowing");
+            assert_eq!(
+                read_value(base, 16, 3, 2, 11).as_deref(),
+                Some(&b"overflowing"[..])
+            );
+            assert_eq!(read_value(base, 16, 3, 3, 11), None);
+            assert_eq!(read_value(base, 16, 3, 2, 17), None);
+
+            push_free(header, base, 2);
+            assert_eq!((header.overflow_used, pop_free(header, base)), (2, 2));
+        }
+    }
+}
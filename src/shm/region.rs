@@ -2,46 +2,311 @@
 ///
 /// Creates or opens a named memory-mapped file that holds the entire
 /// cache: header + lock + hash table + slab arena.
+///
+/// The same file layout is used on every platform. Unix-only protections —
+/// file modes, ownership checks, the uid-scoped directory — have no Windows
+/// counterpart here: there the files live under the user's profile
+/// (`%TEMP%`, `%LOCALAPPDATA%`), whose ACLs already keep other users out.
+use std::cell::Cell;
+use std::collections::BTreeMap;
+#[cfg(unix)]
+use std::ffi::CString;
 use std::fs;
 use std::io;
+use std::mem::offset_of;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(unix)]
 use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
 
-use memmap2::MmapMut;
+use memmap2::{Mmap, MmapMut, MmapOptions, MmapRaw};
+use parking_lot::Mutex;
 
-use super::layout::{self, Bucket, Header, SlotHeader, BUCKET_EMPTY, MAGIC, SLOT_NONE, VERSION};
+use super::layout::{
+    self, Bucket, Header, SlotHeader, BUCKET_EMPTY, CACHE_VERSION_LEN, MAGIC, SERIALIZER_ID_LEN,
+    SLOT_NONE, VALUE_CODEC_LZ4, VALUE_CODEC_NONE, VALUE_CODEC_ZSTD, VALUE_SIZE_BUCKETS, VERSION,
+};
 use super::lock::{ShmSeqLock, LOCK_SIZE};
+use super::overflow;
+use crate::fork::ForkLock;
 
-/// File mode for the cache files: owner read/write only. They hold serialized
-/// (possibly pickled) return values, so no group/other access (#39).
+/// Default file mode for the cache files: owner read/write only. They hold
+/// serialized (possibly pickled) return values, so no group/other access (#39).
 const FILE_MODE: u32 = 0o600;
 
+/// Where the cache files live and how they are protected.
+#[derive(Clone, Copy, Default)]
+pub struct FileOptions<'a> {
+    /// Base directory for the per-user directory (see `shm_dir`).
+    pub base_dir: Option<&'a Path>,
+    /// Mode for the data and lock files this handle creates; `None` means
+    /// `FILE_MODE`. Applied with `fchmod`, so the umask can't change it.
+    /// Ignored on Windows.
+    pub file_mode: Option<u32>,
+    /// Open existing files even if they are world-writable or owned by
+    /// another user. Ignored on Windows.
+    pub allow_insecure: bool,
+    /// Keep the cache on a real filesystem so it survives reboots (see
+    /// `persistent_base_dir`), with wall-clock timestamps and crash recovery.
+    pub persistent: bool,
+    /// With `persistent`, how often writes schedule a flush of the data file
+    /// to disk; `None` flushes only on `sync` and drop.
+    pub sync_interval: Option<Duration>,
+    /// How long this handle waits for another process's write section before
+    /// giving up; `None` waits as long as it takes.
+    pub lock_timeout: Option<Duration>,
+    /// `mlock` the mappings so they are never paged out. Opening fails if
+    /// `RLIMIT_MEMLOCK` doesn't allow it. Unsupported on Windows.
+    pub lock_memory: bool,
+    /// Fault in every page of the data file right after mapping it, so the
+    /// first access to each doesn't pay for it.
+    pub prefault: bool,
+    /// Map an existing cache read-only and never create, truncate or replace
+    /// it; opening fails instead wherever that would be needed.
+    pub readonly: bool,
+}
+
+/// Apply `lock_memory` and `prefault` to a data or lock file mapping.
+fn pin_mapping(map: &MmapRaw, lock_memory: bool, prefault: bool) -> io::Result<()> {
+    if lock_memory {
+        lock_mapping(map)?;
+    } else if prefault {
+        #[cfg(unix)]
+        let _ = map.advise(memmap2::Advice::WillNeed);
+        // madvise only starts the reads; touching every page also maps them
+        // into this process.
+        for offset in (0..map.len()).step_by(PAGE_SIZE) {
+            unsafe { std::ptr::read_volatile(map.as_ptr().add(offset)) };
+        }
+    }
+    Ok(())
+}
+
+/// Fail with `StorageFull` if the filesystem holding `dir` has less than
+/// `size` bytes free. A failed `statvfs` is not an error: the write that
+/// follows reports the real problem.
+#[cfg(unix)]
+fn check_free_space(dir: &Path, size: usize) -> io::Result<()> {
+    let Ok(c_dir) = CString::new(dir.as_os_str().as_bytes()) else {
+        return Ok(());
+    };
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_dir.as_ptr(), &mut stat) } != 0 {
+        return Ok(());
+    }
+    let free = stat.f_bavail as u64 * stat.f_frsize as u64;
+    if (size as u64) > free {
+        return Err(io::Error::new(
+            io::ErrorKind::StorageFull,
+            format!(
+                "the cache file needs {size} bytes but only {free} are free in {}; \
+                 lower max_size or max_value_size, or free up space there",
+                dir.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_free_space(_dir: &Path, _size: usize) -> io::Result<()> {
+    Ok(())
+}
+
+/// Smallest page size on the platforms we run on; touching every
+/// `PAGE_SIZE` bytes reaches every page whatever the real size is.
+const PAGE_SIZE: usize = 4096;
+
+/// `mlock` a mapping (which also faults it in), explaining a refusal by the
+/// `RLIMIT_MEMLOCK` it ran into.
+#[cfg(unix)]
+fn lock_mapping(map: &MmapRaw) -> io::Result<()> {
+    map.lock().map_err(|e| {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        let limit = if unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) } == 0 {
+            match limit.rlim_cur {
+                libc::RLIM_INFINITY => "unlimited".to_string(),
+                bytes => format!("{bytes} bytes"),
+            }
+        } else {
+            "unknown".to_string()
+        };
+        io::Error::new(
+            e.kind(),
+            format!(
+                "lock_memory: mlock of {} bytes failed ({e}); RLIMIT_MEMLOCK is {limit}, \
+                 raise it (ulimit -l) or drop lock_memory",
+                map.len()
+            ),
+        )
+    })
+}
+
+#[cfg(windows)]
+fn lock_mapping(_map: &MmapRaw) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "lock_memory is not supported on Windows",
+    ))
+}
+
+/// Refuse an existing cache file another local user could tamper with: one
+/// owned by someone else, or writable by everyone. Unpickling values from such
+/// a file would run whatever they wrote.
+#[cfg(unix)]
+fn check_file_security(file: &fs::File, path: &Path) -> io::Result<()> {
+    let meta = file.metadata()?;
+    if meta.uid() != current_uid() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "refusing to open shm file {} owned by uid {} (pass allow_insecure to override)",
+                path.display(),
+                meta.uid()
+            ),
+        ));
+    }
+    let mode = meta.permissions().mode() & 0o777;
+    if mode & 0o002 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "refusing to open world-writable shm file {} (mode {mode:#o}; pass \
+                 allow_insecure to override)",
+                path.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn check_file_security(_file: &fs::File, _path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
 /// Current real user id.
+#[cfg(unix)]
 fn current_uid() -> u32 {
     // SAFETY: getuid() always succeeds and is thread-safe.
     unsafe { libc::getuid() }
 }
 
-/// Per-user directory holding the mmap cache files.
+/// Options to open (or create) a cache file for reading and writing, created
+/// with permissions `mode` where the platform has them.
+fn file_options(mode: u32) -> fs::OpenOptions {
+    let mut options = fs::OpenOptions::new();
+    options.read(true).write(true);
+    #[cfg(unix)]
+    options.mode(mode);
+    #[cfg(windows)]
+    let _ = mode;
+    options
+}
+
+/// Map all of an existing cache file, shared, and read-only if `readonly`.
+fn map_file(file: &fs::File, readonly: bool) -> io::Result<MmapRaw> {
+    if readonly {
+        MmapOptions::new().map_raw_read_only(file)
+    } else {
+        MmapOptions::new().map_raw(file)
+    }
+}
+
+/// Set `mode` on a file we created, whatever the umask did to it.
+fn set_file_mode(file: &fs::File, mode: u32) -> io::Result<()> {
+    #[cfg(unix)]
+    file.set_permissions(fs::Permissions::from_mode(mode))?;
+    #[cfg(windows)]
+    let _ = (file, mode);
+    Ok(())
+}
+
+/// Environment variable that overrides the default base directory for the
+/// cache files when no directory is passed explicitly.
+pub const SHM_DIR_ENV: &str = "WARP_CACHE_SHM_DIR";
+
+/// Per-user directory holding the mmap cache files, under `base` if given,
+/// else under `$WARP_CACHE_SHM_DIR`, else under the platform default.
 ///
 /// The files contain serialized (and possibly pickled) return values, so they
 /// must not be readable or pre-creatable by other local users (#39). On Linux the
 /// parent is the world-writable, sticky `/dev/shm`, so the directory name is scoped
 /// to the uid and created `0o700` (see `ensure_secure_dir`); on macOS/other `$TMPDIR`
-/// is already per-user, but we keep the same layout for defense in depth.
-fn shm_dir() -> PathBuf {
-    let base = if cfg!(target_os = "linux") {
-        PathBuf::from("/dev/shm")
-    } else {
-        std::env::temp_dir()
+/// is already per-user, but we keep the same layout for defense in depth. Windows
+/// has no uid to scope by, so the directory is plain `warp_cache`.
+pub(super) fn shm_dir(base: Option<&Path>) -> PathBuf {
+    let base = match (base, std::env::var_os(SHM_DIR_ENV)) {
+        (Some(base), _) => base.to_path_buf(),
+        (None, Some(env)) if !env.is_empty() => PathBuf::from(env),
+        _ if cfg!(target_os = "linux") => PathBuf::from("/dev/shm"),
+        _ => std::env::temp_dir(),
     };
-    base.join(format!("warp_cache-{}", current_uid()))
+    #[cfg(unix)]
+    return base.join(format!("warp_cache-{}", current_uid()));
+    #[cfg(windows)]
+    return base.join("warp_cache");
+}
+
+/// Default base directory for persistent caches: `$XDG_CACHE_HOME/warp_cache`,
+/// else `~/.cache/warp_cache` (`%LOCALAPPDATA%\\warp_cache` on Windows).
+/// `WARP_CACHE_SHM_DIR` is not consulted — it usually points at a tmpfs, which
+/// would defeat the point.
+#[cfg(unix)]
+fn persistent_base_dir() -> PathBuf {
+    let cache_home = match (std::env::var_os("XDG_CACHE_HOME"), std::env::var_os("HOME")) {
+        (Some(xdg), _) if !xdg.is_empty() => PathBuf::from(xdg),
+        (_, Some(home)) if !home.is_empty() => Path::new(&home).join(".cache"),
+        _ => std::env::temp_dir(),
+    };
+    cache_home.join("warp_cache")
+}
+
+#[cfg(windows)]
+fn persistent_base_dir() -> PathBuf {
+    match std::env::var_os("LOCALAPPDATA") {
+        Some(local) if !local.is_empty() => PathBuf::from(local),
+        _ => std::env::temp_dir(),
+    }
+    .join("warp_cache")
+}
+
+/// Create the base directory (the parent of the per-user dir) if it is missing
+/// and check that we can create files in it, so a bad `shm_dir` fails up front
+/// with the offending path in the message.
+fn ensure_base_dir(base: &Path) -> io::Result<()> {
+    fs::create_dir_all(base).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("cannot create shm directory {}: {e}", base.display()),
+        )
+    })?;
+    #[cfg(unix)]
+    {
+        let path = CString::new(base.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: `path` is a valid NUL-terminated string for the duration of the call.
+        if unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) } != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("shm directory {} is not writable", base.display()),
+            ));
+        }
+    }
+    Ok(())
 }
 
 /// Create the per-user shm directory as `0o700`, or — if it already exists — verify
 /// it is a directory we own with no group/other access. This refuses to use a
 /// directory a hostile local user pre-created on a shared parent like `/dev/shm`,
 /// which would otherwise let them read or tamper with the cache files (#39).
+#[cfg(unix)]
 fn ensure_secure_dir(dir: &Path) -> io::Result<()> {
     match fs::DirBuilder::new().mode(0o700).create(dir) {
         Ok(()) => return Ok(()),
@@ -69,23 +334,601 @@ fn ensure_secure_dir(dir: &Path) -> io::Result<()> {
     Ok(())
 }
 
-/// The full shared-memory region, owning the mmap handle and providing
+/// Create the per-user directory if missing. It inherits the ACL of its
+/// parent, which under the user's profile is already private.
+#[cfg(windows)]
+fn ensure_secure_dir(dir: &Path) -> io::Result<()> {
+    match fs::create_dir(dir) {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists && dir.is_dir() => Ok(()),
+        result => result,
+    }
+}
+
+/// Open `path` and take an exclusive advisory lock on it.
+///
+/// `remove_orphans` unlinks `.init` files while holding their lock, so a caller
+/// that was blocked on one may end up holding a lock on a deleted file. Retry
+/// until the locked file is the one at `path`. (On Windows `.init` files are
+/// never removed, so the first lock is always the right one.)
+fn lock_init_file(path: &Path) -> io::Result<fs::File> {
+    loop {
+        let file = file_options(FILE_MODE) // owner-only (#39)
+            .create(true)
+            .truncate(false) // never truncate — it's purely an advisory lock file
+            .open(path)?;
+        file.lock()?;
+        if still_at_path(&file, path)? {
+            return Ok(file);
+        }
+    }
+}
+
+/// Whether `file` is still the file at `path` (it hasn't been unlinked or
+/// replaced since it was opened).
+#[cfg(unix)]
+fn still_at_path(file: &fs::File, path: &Path) -> io::Result<bool> {
+    let opened = file.metadata()?;
+    match fs::metadata(path) {
+        Ok(current) => Ok(current.ino() == opened.ino() && current.dev() == opened.dev()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(windows)]
+fn still_at_path(_file: &fs::File, _path: &Path) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// File suffixes making up one cache, longest first so `.data.resize` isn't
+/// mistaken for `.data`.
+const CACHE_FILE_SUFFIXES: [&str; 6] = [
+    ".data.resize",
+    ".data.create",
+    ".lock.create",
+    ".data",
+    ".lock",
+    ".init",
+];
+
+/// Remove the files of caches that no process has open and that were last
+/// written more than `max_age` ago, from the per-user directory under
+/// `base_dir`. Returns the cache names removed (or, with `dry_run`, that would
+/// be), sorted.
+///
+/// Every `ShmRegion` holds a shared lock on its `.lock` file for as long as it
+/// is alive, so a cache counts as open while that lock is held — however old
+/// its files are. The `.init` lock is held while deciding, so no process can
+/// attach in between.
+pub fn remove_orphans(
+    base_dir: Option<&Path>,
+    max_age: Duration,
+    dry_run: bool,
+) -> io::Result<Vec<String>> {
+    let dir = shm_dir(base_dir);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    ensure_secure_dir(&dir)?;
+
+    let mut caches: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+        if let Some(name) = CACHE_FILE_SUFFIXES
+            .iter()
+            .find_map(|suffix| file_name.strip_suffix(suffix))
+        {
+            caches
+                .entry(name.to_owned())
+                .or_default()
+                .push(entry.path());
+        }
+    }
+
+    let now = SystemTime::now();
+    let mut removed = Vec::new();
+    for (name, paths) in caches {
+        let init_path = dir.join(format!("{name}.init"));
+        let lock_path = dir.join(format!("{name}.lock"));
+
+        // On Windows `.init` files stay (see `lock_init_file`), so one alone
+        // is not a cache left to collect.
+        if cfg!(windows) && paths.iter().all(|p| *p == init_path) {
+            continue;
+        }
+
+        let init_file = file_options(FILE_MODE)
+            .create(true)
+            .truncate(false)
+            .open(&init_path)?;
+        if init_file.try_lock().is_err() {
+            continue; // a process is opening or creating it right now
+        }
+        let lock_file = match fs::OpenOptions::new().read(true).open(&lock_path) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        if let Some(lock_file) = &lock_file {
+            if lock_file.try_lock().is_err() {
+                continue; // mapped by at least one live handle
+            }
+        }
+
+        // Age by the newest write to any of its files; the .init file only
+        // counts when nothing else is left.
+        let newest = paths
+            .iter()
+            .filter(|p| **p != init_path || paths.len() == 1)
+            .filter_map(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+            .max();
+        let old_enough = newest
+            .map(|t| now.duration_since(t).unwrap_or_default() >= max_age)
+            .unwrap_or(true);
+        if !old_enough {
+            continue;
+        }
+
+        if !dry_run {
+            for path in paths.iter().filter(|p| **p != init_path) {
+                match fs::remove_file(path) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            // Last, while still holding its lock (see `lock_init_file`).
+            #[cfg(unix)]
+            let _ = fs::remove_file(&init_path);
+        }
+        removed.push(name);
+    }
+    Ok(removed)
+}
+
+/// One cache found by `list_regions`.
+#[derive(Debug)]
+pub struct RegionListing {
+    pub name: String,
+    /// Size of the `.data` file in bytes.
+    pub file_size: u64,
+    /// Its configuration and counters, or why the file could not be read as a
+    /// cache of this version.
+    pub stats: Result<RegionStats, String>,
+}
+
+/// Header fields reported by `list_regions`.
+#[derive(Debug)]
+pub struct RegionStats {
+    pub capacity: u32,
+    pub current_size: u32,
+    pub hits: u64,
+    pub misses: u64,
+    /// Oversize key and value skips together.
+    pub oversize_skips: u64,
+    pub ttl_nanos: u64,
+    /// `None` for a cache created without a `cache_version`.
+    pub cache_version: Option<String>,
+}
+
+/// Describe every cache in the per-user directory under `base_dir`, sorted by
+/// name.
+///
+/// Each `.data` file is mapped read-only and its header read with atomic
+/// loads, without the seqlock — the counters are a moment-in-time view and
+/// neither block nor disturb live handles. Files that aren't caches of this
+/// layout version (foreign, truncated, or left by an older release) are listed
+/// with the reason instead of failing the scan.
+pub fn list_regions(base_dir: Option<&Path>) -> io::Result<Vec<RegionListing>> {
+    let dir = shm_dir(base_dir);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    ensure_secure_dir(&dir)?;
+
+    let mut listings = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(name) = file_name.to_str().and_then(|n| n.strip_suffix(".data")) else {
+            continue;
+        };
+        let metadata = entry.metadata()?;
+        let stats = if metadata.is_file() {
+            read_region_stats(&entry.path(), metadata.len())
+        } else {
+            Err("not a regular file".to_owned())
+        };
+        listings.push(RegionListing {
+            name: name.to_owned(),
+            file_size: metadata.len(),
+            stats,
+        });
+    }
+    listings.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(listings)
+}
+
+/// Why a data file of `file_size` bytes with this header can't be used, if
+/// it can't. A truncated file or a corrupt geometry would otherwise send hash
+/// probes and slot accesses past the end of the mapping.
+fn check_geometry(h: &Header, file_size: usize) -> Result<(), String> {
+    if h.capacity == 0 {
+        return Err("capacity is 0".to_owned());
+    }
+    if !h.ht_capacity.is_power_of_two() || (h.ht_capacity as u64) < 2 * h.capacity as u64 {
+        return Err(format!(
+            "hash table of {} buckets for capacity {}",
+            h.ht_capacity, h.capacity
+        ));
+    }
+    let data = if h.chunk_size != 0 {
+        h.chunk_size
+    } else {
+        h.max_value_size
+    };
+    let min_slot = layout::SLOT_HEADER_SIZE as u64 + h.max_key_size as u64 + data as u64;
+    if !h.slot_size.is_multiple_of(8) || (h.slot_size as u64) < min_slot {
+        return Err(format!(
+            "slot size {} for keys of {} and values of {} bytes",
+            h.slot_size, h.max_key_size, data
+        ));
+    }
+    if h.overflow_count != 0 && (h.overflow_size == 0 || !h.overflow_size.is_multiple_of(8)) {
+        return Err(format!("overflow chunk size {}", h.overflow_size));
+    }
+    let expected = layout::region_size(
+        h.capacity,
+        h.ht_capacity,
+        h.slot_size,
+        h.overflow_count,
+        h.overflow_size,
+    );
+    if expected != file_size {
+        return Err(format!(
+            "file is {file_size} bytes, header describes {expected}"
+        ));
+    }
+    Ok(())
+}
+
+fn read_region_stats(path: &Path, file_size: u64) -> Result<RegionStats, String> {
+    if file_size < layout::HEADER_SIZE as u64 {
+        return Err("file is smaller than a cache header".to_owned());
+    }
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mmap = unsafe { Mmap::map(&file) }.map_err(|e| e.to_string())?;
+    let base = mmap.as_ptr();
+    let u32_at = |offset: usize| unsafe {
+        (*(base.add(offset) as *const AtomicU32)).load(Ordering::Relaxed)
+    };
+    let u64_at = |offset: usize| unsafe {
+        (*(base.add(offset) as *const AtomicU64)).load(Ordering::Relaxed)
+    };
+
+    if mmap[..MAGIC.len()] != MAGIC {
+        return Err("bad magic".to_owned());
+    }
+    let version = u32_at(offset_of!(Header, version));
+    if version != VERSION {
+        return Err(format!(
+            "layout version {version} (this release reads {VERSION})"
+        ));
+    }
+    let capacity = u32_at(offset_of!(Header, capacity));
+    let expected = layout::region_size(
+        capacity,
+        u32_at(offset_of!(Header, ht_capacity)),
+        u32_at(offset_of!(Header, slot_size)),
+        u32_at(offset_of!(Header, overflow_count)),
+        u32_at(offset_of!(Header, overflow_size)),
+    );
+    if expected as u64 != file_size {
+        return Err(format!(
+            "file is {file_size} bytes, header describes {expected}"
+        ));
+    }
+    Ok(RegionStats {
+        capacity,
+        current_size: u32_at(offset_of!(Header, current_size)),
+        hits: u64_at(offset_of!(Header, hits)),
+        misses: u64_at(offset_of!(Header, misses)),
+        oversize_skips: u64_at(offset_of!(Header, oversize_key_skips))
+            + u64_at(offset_of!(Header, oversize_value_skips)),
+        ttl_nanos: u64_at(offset_of!(Header, ttl_nanos)),
+        cache_version: Some(id_text(
+            &mmap[offset_of!(Header, cache_version)..][..CACHE_VERSION_LEN],
+        ))
+        .filter(|version| !version.is_empty()),
+    })
+}
+
+/// Operations between two checks that a handle's files are still in place,
+/// each a `stat` of the lock file.
+const REPLACED_CHECK_INTERVAL: u32 = 1024;
+
+thread_local! {
+    /// Shared-cache operations this thread has started, for
+    /// `ShmRegion::replacement_check_due`.
+    static OPS: Cell<u32> = const { Cell::new(0) };
+}
+
+/// The full shared-memory region, owning the mmap handles and providing
 /// raw accessors to the structures within.
+///
+/// The data file can be replaced by `resize` (in this or another process). Each
+/// handle tracks the lock file's `generation` and remaps when it moves; the
+/// previous mappings are kept alive until the handle drops, so a thread still
+/// holding a pre-resize pointer never touches unmapped memory.
+///
+/// Both files can also be replaced outright: deleted by hand, or rebuilt by
+/// another process's `OnMismatch::Recreate`. That doesn't move the old lock
+/// file's generation, so every `REPLACED_CHECK_INTERVAL` operations the handle
+/// checks whether its lock file is still the one at its path, and if not
+/// attaches to the new pair (see `open_replacement` and `adopt`).
 pub struct ShmRegion {
-    pub mmap: MmapMut,
-    pub lock_mmap: MmapMut,
+    /// Base of the current data mapping (one of `maps`).
+    base: AtomicPtr<u8>,
+    /// Every data mapping this handle has used, kept until drop.
+    maps: ForkLock<Mutex<Vec<MmapRaw>>>,
+    /// Data-file generation `base` belongs to.
+    generation: AtomicU64,
+    /// Base of the current lock mapping (one of `lock_maps`).
+    lock_base: AtomicPtr<u8>,
+    /// Every lock mapping this handle has used, kept until drop.
+    lock_maps: ForkLock<Mutex<Vec<MmapRaw>>>,
+    /// The current lock file, held with a shared advisory lock so
+    /// `remove_orphans` can tell the cache is in use.
+    lock_file: ForkLock<Mutex<fs::File>>,
+    data_path: PathBuf,
+    lock_path: PathBuf,
+    /// Times this handle attached to a replacement of its files.
+    remaps: AtomicU64,
+    /// Mode for data files this handle creates (on resize).
+    file_mode: u32,
+    /// Skip `check_file_security` when remapping.
+    allow_insecure: bool,
+    /// Both files are mapped read-only (`FileOptions::readonly`).
+    readonly: bool,
+    /// No other handle had the cache open when this one attached.
+    opened_alone: bool,
+    /// `FileOptions::lock_memory` and `prefault`, applied again to the data
+    /// file after a resize replaces it.
+    lock_memory: bool,
+    prefault: bool,
+    /// The current data mapping is `mlock`ed.
+    memory_locked: AtomicBool,
+}
+
+/// Parameters stored in a region header, needed to build a data file.
+#[derive(Clone, Copy)]
+pub struct RegionConfig {
+    pub capacity: u32,
+    pub slot_size: u32,
+    pub max_key_size: u32,
+    pub max_value_size: u32,
+    pub ttl_nanos: u64,
+    pub stale_ttl_nanos: u64,
+    pub sliding_ttl: bool,
+    pub durable: bool,
+    /// Slots carry a checksum of their key and value, verified on every hit.
+    pub checksums: bool,
+    /// `VALUE_CODEC_*` id the values are compressed with.
+    pub value_codec: u32,
+    /// Name of the serializer non-fast-path keys and values go through.
+    pub serializer: [u8; SERIALIZER_ID_LEN],
+    /// Value bytes per slot when entries may span several slots, 0 = never.
+    pub chunk_size: u32,
+    /// Chunks in the overflow arena, 0 = no arena.
+    pub overflow_count: u32,
+    /// Bytes per overflow chunk.
+    pub overflow_size: u32,
+    /// Version of the cached function's results, all zeros = none. Entries
+    /// written under one version are never served under another.
+    pub cache_version: [u8; CACHE_VERSION_LEN],
+}
+
+/// What `create_or_open` does when the existing region was created with a
+/// different configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnMismatch {
+    /// Fail with `ErrorKind::InvalidInput`, leaving the existing region alone.
+    Error,
+    /// Replace the existing region with an empty one built from the request.
+    Recreate,
+    /// Attach to the existing region and adopt its configuration.
+    OpenExisting,
+}
+
+impl RegionConfig {
+    /// The configuration `header` was created with.
+    pub fn from_header(header: &Header) -> Self {
+        RegionConfig {
+            capacity: header.capacity,
+            slot_size: header.slot_size,
+            max_key_size: header.max_key_size,
+            max_value_size: header.max_value_size,
+            ttl_nanos: header.ttl_nanos,
+            stale_ttl_nanos: header.stale_ttl_nanos,
+            sliding_ttl: header.sliding_ttl != 0,
+            durable: header.durable != 0,
+            checksums: header.checksums != 0,
+            value_codec: header.value_codec,
+            serializer: header.serializer,
+            chunk_size: header.chunk_size,
+            overflow_count: header.overflow_count,
+            overflow_size: header.overflow_size,
+            cache_version: header.cache_version,
+        }
+    }
+
+    /// Largest value the cache can hold: `max_value_size`, or the overflow
+    /// chunk size if that is larger.
+    pub fn value_limit(&self) -> u32 {
+        if self.overflow_count > 0 {
+            self.max_value_size.max(self.overflow_size)
+        } else {
+            self.max_value_size
+        }
+    }
+
+    /// Whether a handle opened on a region with this configuration can carry
+    /// on against one created with `other`: entries are checked against the
+    /// same size limits and encoded the same way. The rest (capacity, TTLs,
+    /// slot layout) is read from the header as it goes.
+    pub fn adoptable(&self, other: &RegionConfig) -> bool {
+        self.max_key_size == other.max_key_size
+            && self.value_limit() == other.value_limit()
+            && self.value_codec == other.value_codec
+            && self.serializer == other.serializer
+            && self.cache_version == other.cache_version
+    }
+
+    /// Human-readable list of the fields where `existing` differs from `self`,
+    /// or `None` if they match.
+    fn mismatch(&self, existing: &RegionConfig) -> Option<String> {
+        let mut diffs = Vec::new();
+        let mut check = |field: &str, have: String, want: String| {
+            if have != want {
+                diffs.push(format!("{field}: existing {have}, requested {want}"));
+            }
+        };
+        check(
+            "capacity",
+            existing.capacity.to_string(),
+            self.capacity.to_string(),
+        );
+        check(
+            "max_key_size",
+            existing.max_key_size.to_string(),
+            self.max_key_size.to_string(),
+        );
+        check(
+            "max_value_size",
+            existing.max_value_size.to_string(),
+            self.max_value_size.to_string(),
+        );
+        check(
+            "ttl_nanos",
+            existing.ttl_nanos.to_string(),
+            self.ttl_nanos.to_string(),
+        );
+        check(
+            "stale_ttl_nanos",
+            existing.stale_ttl_nanos.to_string(),
+            self.stale_ttl_nanos.to_string(),
+        );
+        check(
+            "sliding_ttl",
+            existing.sliding_ttl.to_string(),
+            self.sliding_ttl.to_string(),
+        );
+        check(
+            "durable",
+            existing.durable.to_string(),
+            self.durable.to_string(),
+        );
+        check(
+            "checksums",
+            existing.checksums.to_string(),
+            self.checksums.to_string(),
+        );
+        check(
+            "compress",
+            codec_name(existing.value_codec),
+            codec_name(self.value_codec),
+        );
+        check(
+            "serializer",
+            id_text(&existing.serializer),
+            id_text(&self.serializer),
+        );
+        check(
+            "cache_version",
+            cache_version_name(&existing.cache_version),
+            cache_version_name(&self.cache_version),
+        );
+        let chunk = |size: u32| match size {
+            0 => "None".to_string(),
+            size => size.to_string(),
+        };
+        check(
+            "chunk_size",
+            chunk(existing.chunk_size),
+            chunk(self.chunk_size),
+        );
+        check(
+            "overflow_count",
+            existing.overflow_count.to_string(),
+            self.overflow_count.to_string(),
+        );
+        check(
+            "overflow_size",
+            existing.overflow_size.to_string(),
+            self.overflow_size.to_string(),
+        );
+        (!diffs.is_empty()).then(|| diffs.join(", "))
+    }
+}
+
+/// Name of a `VALUE_CODEC_*` id, as passed to `compress=`.
+fn codec_name(id: u32) -> String {
+    match id {
+        VALUE_CODEC_NONE => "None".to_string(),
+        VALUE_CODEC_LZ4 => "lz4".to_string(),
+        VALUE_CODEC_ZSTD => "zstd".to_string(),
+        other => format!("unknown({other})"),
+    }
+}
+
+/// Header form of a serializer name or cache version: NUL-padded, truncated
+/// to fit.
+pub fn text_id<const N: usize>(name: &str) -> [u8; N] {
+    let mut id = [0u8; N];
+    let len = name.len().min(N);
+    id[..len].copy_from_slice(&name.as_bytes()[..len]);
+    id
+}
+
+/// Inverse of `text_id` (up to the truncation).
+pub fn id_text(id: &[u8]) -> String {
+    let len = id.iter().position(|&b| b == 0).unwrap_or(id.len());
+    String::from_utf8_lossy(&id[..len]).into_owned()
+}
+
+/// Refuse to adopt (`OnMismatch::OpenExisting`) a region holding another
+/// `cache_version`'s entries: serving them is what the version rules out.
+fn check_same_version(name: &str, config: &RegionConfig, header: &Header) -> io::Result<()> {
+    if header.cache_version == config.cache_version {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "shared cache {name:?} holds entries of cache_version {}, not {}; \
+             on_mismatch='open_existing' can't adopt them, use 'recreate' to replace it",
+            cache_version_name(&header.cache_version),
+            cache_version_name(&config.cache_version)
+        ),
+    ))
+}
+
+/// A stored cache version as `on_mismatch` errors show it.
+fn cache_version_name(id: &[u8; CACHE_VERSION_LEN]) -> String {
+    match id_text(id) {
+        version if version.is_empty() => "None".to_string(),
+        version => format!("{version:?}"),
+    }
 }
 
 impl ShmRegion {
-    /// Create a new shared memory region, initializing all structures.
-    pub fn create(
-        name: &str,
-        capacity: u32,
-        slot_size: u32,
-        max_key_size: u32,
-        max_value_size: u32,
-        ttl_nanos: u64,
-    ) -> io::Result<Self> {
+    /// Create and initialize a data file at `path` (header, empty hash table,
+    /// slab free list) with permissions `mode`, truncating anything already there.
+    fn create_data_file(path: &Path, config: &RegionConfig, mode: u32) -> io::Result<MmapMut> {
+        let capacity = config.capacity;
         // ponytail: capacity==0 makes a zero-slot slab while free_head stays 0,
         // so the first insert dereferences a slot one-past-the-end of the mmap
         // (OOB read+write). Reject at the unsafe boundary so the region can never
@@ -97,9 +940,6 @@ impl ShmRegion {
             ));
         }
 
-        let dir = shm_dir();
-        ensure_secure_dir(&dir)?;
-
         // Hash table must be power-of-2 for bitmask probing. `capacity * 2` and its
         // next-power-of-two must both fit in u32, or the table is silently mis-sized
         // (overflow panics in debug; in release `capacity * 2` wraps and rounds down to
@@ -114,38 +954,37 @@ impl ShmRegion {
                     "shared cache capacity (max_size) too large: 2x hash table exceeds u32",
                 )
             })?;
-        let total_size = layout::region_size(capacity, ht_capacity, slot_size);
-
-        let data_path = dir.join(format!("{name}.data"));
-        let lock_path = dir.join(format!("{name}.lock"));
+        let slot_size = config.slot_size;
+        let total_size = layout::checked_region_size(
+            capacity,
+            ht_capacity,
+            slot_size,
+            config.overflow_count,
+            config.overflow_size,
+        )
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::FileTooLarge,
+                "shared cache would be larger than this platform can map; \
+                 lower max_size, max_value_size or overflow_count",
+            )
+        })?;
+        // Touching the pages of a file the filesystem has no room for raises
+        // SIGBUS, so check first.
+        if let Some(dir) = path.parent() {
+            check_free_space(dir, total_size)?;
+        }
 
-        // Create or truncate the data file (owner-only, #39)
-        let data_file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .mode(FILE_MODE)
-            .open(&data_path)?;
+        // Create or truncate the data file (owner-only by default, #39)
+        let data_file = file_options(mode).create(true).truncate(true).open(path)?;
+        set_file_mode(&data_file, mode)?;
         data_file.set_len(total_size as u64)?;
 
-        // Create or truncate the lock file (owner-only, #39)
-        let lock_file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .mode(FILE_MODE)
-            .open(&lock_path)?;
-        lock_file.set_len(LOCK_SIZE as u64)?;
-
-        // Safety: we just created these files and own them exclusively at this point.
+        // Safety: we just created this file and own it exclusively at this point.
         let mut mmap = unsafe { MmapMut::map_mut(&data_file)? };
-        let mut lock_mmap = unsafe { MmapMut::map_mut(&lock_file)? };
 
         // Zero the entire region
         mmap.fill(0);
-        lock_mmap.fill(0);
 
         // Initialize header
         let header = unsafe { &mut *(mmap.as_mut_ptr() as *mut Header) };
@@ -155,16 +994,46 @@ impl ShmRegion {
         header.capacity = capacity;
         header.ht_capacity = ht_capacity;
         header.slot_size = slot_size;
-        header.max_key_size = max_key_size;
-        header.max_value_size = max_value_size;
-        header.ttl_nanos = ttl_nanos;
+        header.max_key_size = config.max_key_size;
+        header.max_value_size = config.max_value_size;
+        header.ttl_nanos = config.ttl_nanos;
+        header.stale_ttl_nanos = config.stale_ttl_nanos;
+        header.sliding_ttl = config.sliding_ttl as u64;
+        header.durable = config.durable as u64;
+        header.checksums = config.checksums as u32;
+        header.value_codec = config.value_codec;
+        header.serializer = config.serializer;
+        header.cache_version = config.cache_version;
+        header.chunk_size = config.chunk_size;
+        header.overflow_count = config.overflow_count;
+        header.overflow_size = config.overflow_size;
+        header.boot_id = super::boot_id();
+        header.creator_pid = super::lock::current_pid();
+        header.created_at_wallclock = super::wall_clock_nanos();
+        header.last_writer_pid = 0;
+        header.last_write_nanos = 0;
+        header.pending_slot = SLOT_NONE;
         header.hits = 0;
         header.misses = 0;
-        header.oversize_skips = 0;
+        header.oversize_key_skips = 0;
+        header.oversize_value_skips = 0;
+        header.insert_failures = 0;
+        header.lock_timeouts = 0;
+        header.corruption_detected = 0;
+        header.corrupt_values = 0;
+        header.skipped_fast = 0;
+        header.value_sizes = [0; VALUE_SIZE_BUCKETS];
+        header.largest_value = 0;
+        header.expired_reclaimed = 0;
+        header.evictions = 0;
+        header.expirations = 0;
+        header.bytes_used = 0;
+        header.value_bytes_used = 0;
         header.current_size = 0;
         header.list_head = SLOT_NONE;
         header.list_tail = SLOT_NONE;
         header.free_head = 0; // first slot is start of free list
+        header.fresh_start = capacity;
         header.sieve_hand = SLOT_NONE;
 
         // Initialize hash table buckets to empty
@@ -182,6 +1051,8 @@ impl ShmRegion {
             let offset = slab_base + i * slot_size as usize;
             let slot = unsafe { &mut *(mmap.as_mut_ptr().add(offset) as *mut SlotHeader) };
             slot.occupied = 0;
+            slot.chain = SLOT_NONE;
+            slot.overflow = SLOT_NONE;
             slot.prev = SLOT_NONE;
             slot.next = if i + 1 < capacity as usize {
                 (i + 1) as i32
@@ -190,6 +1061,118 @@ impl ShmRegion {
             };
         }
 
+        // Initialize the overflow arena's free list: every chunk is free
+        unsafe {
+            let arena =
+                mmap.as_mut_ptr()
+                    .add(layout::overflow_offset(capacity, ht_capacity, slot_size));
+            let header = &mut *(mmap.as_mut_ptr() as *mut Header);
+            overflow::reset_free_list(header, arena, |_| false);
+        }
+
+        Ok(mmap)
+    }
+
+    fn from_parts(
+        data: MmapRaw,
+        lock_mmap: MmapRaw,
+        lock_file: fs::File,
+        data_path: PathBuf,
+        files: &FileOptions,
+    ) -> io::Result<Self> {
+        // Under the .init lock nobody can attach meanwhile, so if the exclusive
+        // lock is free, no live handle exists. Then downgrade to shared.
+        // A read-only handle never repairs anything, so it never counts as alone.
+        let opened_alone = !files.readonly && lock_file.try_lock().is_ok();
+        lock_file.lock_shared()?;
+        pin_mapping(&data, files.lock_memory, files.prefault)?;
+        pin_mapping(&lock_mmap, files.lock_memory, false)?;
+        let base = data.as_mut_ptr();
+        let generation =
+            unsafe { ShmSeqLock::from_existing(lock_mmap.as_ptr() as *mut u8) }.generation();
+        Ok(ShmRegion {
+            base: AtomicPtr::new(base),
+            maps: ForkLock::new(Mutex::new(vec![data])),
+            generation: AtomicU64::new(generation),
+            lock_base: AtomicPtr::new(lock_mmap.as_mut_ptr()),
+            lock_maps: ForkLock::new(Mutex::new(vec![lock_mmap])),
+            lock_file: ForkLock::new(Mutex::new(lock_file)),
+            lock_path: data_path.with_extension("lock"),
+            data_path,
+            remaps: AtomicU64::new(0),
+            file_mode: files.file_mode.unwrap_or(FILE_MODE),
+            allow_insecure: files.allow_insecure,
+            readonly: files.readonly,
+            opened_alone,
+            lock_memory: files.lock_memory,
+            prefault: files.prefault,
+            memory_locked: AtomicBool::new(files.lock_memory),
+        })
+    }
+
+    /// Create a new shared memory region in `dir`, initializing all structures.
+    fn create(
+        dir: &Path,
+        name: &str,
+        config: &RegionConfig,
+        files: &FileOptions,
+    ) -> io::Result<Self> {
+        let data_path = dir.join(format!("{name}.data"));
+        let lock_path = dir.join(format!("{name}.lock"));
+        let mode = files.file_mode.unwrap_or(FILE_MODE);
+
+        // Build both files under temporary names and rename them into place.
+        // Replacing a stale file, or one with other parameters under
+        // OnMismatch::Recreate, must not truncate it under a handle that still
+        // maps it (SIGBUS there): that handle keeps the old, now unlinked, pair.
+        // Windows can't rename over a mapped file, but there truncating one
+        // fails instead, so the files are built in place.
+        let (data_tmp, lock_tmp) = if cfg!(windows) {
+            (data_path.clone(), lock_path.clone())
+        } else {
+            (
+                dir.join(format!("{name}.data.create")),
+                dir.join(format!("{name}.lock.create")),
+            )
+        };
+        let built = Self::create_files(&data_tmp, &lock_tmp, config, mode).and_then(|parts| {
+            if !cfg!(windows) {
+                fs::rename(&data_tmp, &data_path)?;
+                fs::rename(&lock_tmp, &lock_path)?;
+            }
+            Ok(parts)
+        });
+        let (mmap, lock_mmap, lock_file) = built.inspect_err(|_| {
+            if !cfg!(windows) {
+                let _ = fs::remove_file(&data_tmp);
+                let _ = fs::remove_file(&lock_tmp);
+            }
+        })?;
+
+        Self::from_parts(mmap.into(), lock_mmap.into(), lock_file, data_path, files)
+    }
+
+    /// Create and initialize a data file and its lock file at these paths.
+    fn create_files(
+        data_path: &Path,
+        lock_path: &Path,
+        config: &RegionConfig,
+        mode: u32,
+    ) -> io::Result<(MmapMut, MmapMut, fs::File)> {
+        let mmap = Self::create_data_file(data_path, config, mode)?;
+
+        // Create or truncate the lock file (owner-only by default, #39)
+        let lock_file = file_options(mode)
+            .create(true)
+            .truncate(true)
+            .open(lock_path)?;
+        set_file_mode(&lock_file, mode)?;
+        lock_file.set_len(LOCK_SIZE as u64)?;
+
+        // Safety: we just created this file and own it exclusively at this point.
+        let mut lock_mmap = unsafe { MmapMut::map_mut(&lock_file)? };
+        lock_mmap.fill(0);
+
         // Initialize the seqlock in the lock region
         unsafe {
             ShmSeqLock::init(lock_mmap.as_mut_ptr())?;
@@ -198,45 +1181,92 @@ impl ShmRegion {
         mmap.flush()?;
         lock_mmap.flush()?;
 
-        Ok(ShmRegion { mmap, lock_mmap })
+        Ok((mmap, lock_mmap, lock_file))
     }
 
-    fn open_paths(data_path: &Path, lock_path: &Path) -> io::Result<ShmRegion> {
+    /// Map an existing data file and check its magic (and, unless
+    /// `allow_insecure`, its ownership and mode).
+    fn open_data_file(
+        data_path: &Path,
+        allow_insecure: bool,
+        readonly: bool,
+    ) -> io::Result<MmapRaw> {
         let data_file = fs::OpenOptions::new()
             .read(true)
-            .write(true)
+            .write(!readonly)
             .open(data_path)?;
-
-        let lock_file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(lock_path)?;
-
-        let mmap = unsafe { MmapMut::map_mut(&data_file)? };
-        let lock_mmap = unsafe { MmapMut::map_mut(&lock_file)? };
+        if !allow_insecure {
+            check_file_security(&data_file, data_path)?;
+        }
+        let mmap = map_file(&data_file, readonly)?;
+        let invalid = |why: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid shared cache file: {why}"),
+            )
+        };
+        if mmap.len() < layout::HEADER_SIZE {
+            return Err(invalid("smaller than a cache header".to_owned()));
+        }
 
         // Validate magic
         let header = unsafe { &*(mmap.as_ptr() as *const Header) };
         if header.magic != MAGIC {
+            return Err(invalid("bad magic".to_owned()));
+        }
+        // Older layouts are reported as a version mismatch by the caller.
+        if header.version == VERSION {
+            check_geometry(header, mmap.len()).map_err(invalid)?;
+        }
+        Ok(mmap)
+    }
+
+    fn open_paths(
+        data_path: &Path,
+        lock_path: &Path,
+        files: &FileOptions,
+    ) -> io::Result<ShmRegion> {
+        let mmap = Self::open_data_file(data_path, files.allow_insecure, files.readonly)?;
+
+        let lock_file = fs::OpenOptions::new()
+            .read(true)
+            .write(!files.readonly)
+            .open(lock_path)?;
+        if !files.allow_insecure {
+            check_file_security(&lock_file, lock_path)?;
+        }
+        let lock_mmap = map_file(&lock_file, files.readonly)?;
+        if lock_mmap.len() < LOCK_SIZE {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
-                "Invalid shared cache file: bad magic",
+                "Invalid shared cache lock file: truncated",
             ));
         }
 
-        Ok(ShmRegion { mmap, lock_mmap })
+        Self::from_parts(mmap, lock_mmap, lock_file, data_path.to_path_buf(), files)
     }
 
-    /// Create if doesn't exist, otherwise open.
+    /// Create if doesn't exist, otherwise open, with files placed and protected
+    /// per `files`.
+    ///
+    /// An existing region whose configuration differs from `config` is handled
+    /// per `on_mismatch`. A region written by an incompatible layout `VERSION`
+    /// can't be adopted, so `OpenExisting` fails on it like `Error` does.
     pub fn create_or_open(
         name: &str,
-        capacity: u32,
-        slot_size: u32,
-        max_key_size: u32,
-        max_value_size: u32,
-        ttl_nanos: u64,
+        config: &RegionConfig,
+        on_mismatch: OnMismatch,
+        files: &FileOptions,
     ) -> io::Result<Self> {
-        let dir = shm_dir();
+        let persistent_base =
+            (files.persistent && files.base_dir.is_none()).then(persistent_base_dir);
+        let dir = shm_dir(files.base_dir.or(persistent_base.as_deref()));
+        if files.readonly {
+            return Self::open_readonly(&dir, name, config, on_mismatch, files);
+        }
+        if let Some(base) = dir.parent() {
+            ensure_base_dir(base)?;
+        }
         ensure_secure_dir(&dir)?;
         let data_path = dir.join(format!("{name}.data"));
         let lock_path = dir.join(format!("{name}.lock"));
@@ -249,62 +1279,424 @@ impl ShmRegion {
         // lock on a dedicated .init file (never truncated) for the whole
         // open-or-create decision; concurrent starters block here, then open the
         // already-initialized file instead of racing into create().
-        let init_file = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(false) // never truncate — it's purely an advisory lock file
-            .mode(FILE_MODE) // owner-only (#39)
-            .open(&init_path)?;
-        init_file.lock()?;
+        let _init_file = lock_init_file(&init_path)?;
 
         if data_path.exists() && lock_path.exists() {
-            match Self::open_paths(&data_path, &lock_path) {
+            match Self::open_paths(&data_path, &lock_path, files) {
                 Ok(region) => {
                     // Validate parameters match. ttl_nanos is included (#42): TTL
                     // lives in the shared header and governs expiry for every process
                     // (shm/mod.rs reads h.ttl_nanos at lookup time), so a process
-                    // opening with a different TTL must recreate rather than silently
-                    // inherit the creator's TTL — same last-writer-wins recreate as the
-                    // other config params.
+                    // opening with a different TTL must not silently inherit the
+                    // creator's TTL. stale_ttl_nanos and sliding_ttl follow the same
+                    // rule. On a mismatch, recreating is opt-in: one misconfigured
+                    // process would otherwise wipe the cache for every other one.
                     let header = region.header();
-                    if header.version == VERSION
-                        && header.capacity == capacity
-                        && header.max_key_size == max_key_size
-                        && header.max_value_size == max_value_size
-                        && header.ttl_nanos == ttl_nanos
-                    {
+                    // A file in another layout can't be read at all. One from a
+                    // newer release is never replaced, even with Recreate: its
+                    // processes would replace it right back.
+                    if header.version > VERSION {
+                        return Err(io::Error::new(
+                            io::ErrorKind::Unsupported,
+                            format!(
+                                "shared cache {name:?} was created by a newer warp_cache \
+                                 (layout version {}, this release supports {VERSION}); \
+                                 delete it or upgrade",
+                                header.version
+                            ),
+                        ));
+                    }
+                    if header.version < VERSION && on_mismatch != OnMismatch::Recreate {
+                        return Err(io::Error::new(
+                            io::ErrorKind::Unsupported,
+                            format!(
+                                "shared cache {name:?} was created by an older warp_cache \
+                                 (layout version {}, this release uses {VERSION}); delete \
+                                 it or pass on_mismatch='recreate' to replace it",
+                                header.version
+                            ),
+                        ));
+                    }
+                    let diff = if header.version != VERSION {
+                        Some(format!(
+                            "layout version: existing {}, requested {VERSION}",
+                            header.version
+                        ))
+                    } else {
+                        config.mismatch(&RegionConfig::from_header(header))
+                    };
+                    let Some(diff) = diff else {
                         return Ok(region);
+                    };
+                    match on_mismatch {
+                        OnMismatch::OpenExisting => {
+                            check_same_version(name, config, header)?;
+                            return Ok(region);
+                        }
+                        OnMismatch::Error => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                format!(
+                                    "shared cache {name:?} already exists with different \
+                                     parameters ({diff})"
+                                ),
+                            ));
+                        }
+                        // Recreate below (still under the init lock).
+                        OnMismatch::Recreate => drop(region),
                     }
-                    // Parameters don't match — recreate (still under the init lock).
-                    drop(region);
                 }
+                // Someone else's or world-writable file: refuse rather than
+                // truncate and reuse it.
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => return Err(e),
                 Err(_) => {
                     // Stale or corrupted file — recreate (still under the init lock).
                 }
             }
         }
 
-        Self::create(
-            name,
-            capacity,
-            slot_size,
-            max_key_size,
-            max_value_size,
-            ttl_nanos,
+        Self::create(&dir, name, config, files)
+        // _init_file's advisory lock is released when it drops at end of scope.
+    }
+
+    /// `create_or_open` for `FileOptions::readonly`: attach to the region in
+    /// `dir` as it is, or fail. The files appear by rename, so without the
+    /// .init lock (which would mean writing) they are either complete or absent.
+    /// A region from another layout version, or a configuration mismatch under
+    /// anything but `OpenExisting`, is an error rather than a recreation.
+    fn open_readonly(
+        dir: &Path,
+        name: &str,
+        config: &RegionConfig,
+        on_mismatch: OnMismatch,
+        files: &FileOptions,
+    ) -> io::Result<Self> {
+        let data_path = dir.join(format!("{name}.data"));
+        let lock_path = dir.join(format!("{name}.lock"));
+        if !data_path.exists() || !lock_path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "shared cache {name:?} does not exist in {}; readonly=True only \
+                     opens a cache another process created",
+                    dir.display()
+                ),
+            ));
+        }
+        let region = Self::open_paths(&data_path, &lock_path, files)?;
+        let header = region.header();
+        if header.version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "shared cache {name:?} has layout version {}, this release reads \
+                     {VERSION}",
+                    header.version
+                ),
+            ));
+        }
+        if on_mismatch == OnMismatch::OpenExisting {
+            check_same_version(name, config, header)?;
+        } else if let Some(diff) = config.mismatch(&RegionConfig::from_header(header)) {
+            // Not InvalidInput: recreating is no way out for a reader.
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "shared cache {name:?} exists with different parameters ({diff}); \
+                     pass on_mismatch='open_existing' to read it as is"
+                ),
+            ));
+        }
+        Ok(region)
+    }
+
+    /// Pick up a data file replaced by another handle's `resize`, if any.
+    ///
+    /// Cheap when nothing changed (one atomic load). Must be called at the start
+    /// of every operation and again once the write lock or a read section is
+    /// entered, since a resize can land in between.
+    #[inline]
+    pub fn sync_generation(&self) {
+        let shared = self.lock().generation();
+        if shared != self.generation.load(Ordering::Acquire) {
+            self.remap(shared);
+        }
+    }
+
+    #[cold]
+    fn remap(&self, shared: u64) {
+        let mut maps = self.maps.lock();
+        // Another thread got here first, or `adopt` switched lock files since
+        // `shared` was read.
+        if self.generation.load(Ordering::Acquire) == shared || self.lock().generation() != shared {
+            return;
+        }
+        // On failure keep the old mapping; the next operation retries.
+        if let Ok(mmap) = Self::open_data_file(&self.data_path, self.allow_insecure, self.readonly)
+        {
+            self.pin(&mmap);
+            self.base.store(mmap.as_mut_ptr(), Ordering::Release);
+            maps.push(mmap);
+            self.generation.store(shared, Ordering::Release);
+        }
+    }
+
+    /// Count one operation; true once every `REPLACED_CHECK_INTERVAL`, when
+    /// the caller should look for a replacement (`open_replacement`).
+    ///
+    /// Counted per thread across all handles rather than per handle, so that
+    /// hits don't all write one shared cache line. Any run of that many
+    /// operations on one handle still includes a check.
+    #[inline]
+    pub fn replacement_check_due() -> bool {
+        OPS.with(|ops| {
+            let count = ops.get().wrapping_add(1);
+            ops.set(count);
+            count.is_multiple_of(REPLACED_CHECK_INTERVAL)
+        })
+    }
+
+    /// The region now at this handle's paths, if its lock file was deleted or
+    /// replaced since it attached and a region it can carry on against took
+    /// its place (the same layout `VERSION`, and see `RegionConfig::adoptable`).
+    ///
+    /// `None` when nothing changed, on Windows (where files are never replaced
+    /// under a handle), or when the files are missing or unusable; the next
+    /// check tries again.
+    pub fn open_replacement(&self) -> Option<ShmRegion> {
+        if still_at_path(&self.lock_file.lock(), &self.lock_path).unwrap_or(true) {
+            return None;
+        }
+        let files = FileOptions {
+            file_mode: Some(self.file_mode),
+            allow_insecure: self.allow_insecure,
+            lock_memory: self.lock_memory,
+            prefault: self.prefault,
+            readonly: self.readonly,
+            ..Default::default()
+        };
+        let new = Self::open_paths(&self.data_path, &self.lock_path, &files).ok()?;
+        let ours = RegionConfig::from_header(self.header());
+        let theirs = RegionConfig::from_header(new.header());
+        (new.header().version == VERSION && ours.adoptable(&theirs)).then_some(new)
+    }
+
+    /// Switch this handle to `new` (from `open_replacement`), unless `old` is
+    /// no longer its lock because another thread already did. The old
+    /// mappings stay alive until drop, as after a resize.
+    ///
+    /// Caller must hold `old`'s write lock, so that no write of this handle
+    /// straddles the switch, except on a read-only handle, which never writes.
+    /// The lock is switched before the data on a read-only handle and after it
+    /// otherwise: a reader that saw the new data then always fails
+    /// `is_current` or its validation of `old`, and a writer that took the new
+    /// lock always writes the new data.
+    pub fn adopt(&self, old: &ShmSeqLock, new: ShmRegion) {
+        let mut maps = self.maps.lock();
+        let mut lock_maps = self.lock_maps.lock();
+        if !self.is_current(old) {
+            return;
+        }
+        let base = new.base.load(Ordering::Acquire);
+        let lock_base = new.lock_base.load(Ordering::Acquire);
+        if self.readonly {
+            self.lock_base.store(lock_base, Ordering::Release);
+        }
+        self.base.store(base, Ordering::Release);
+        self.lock_base.store(lock_base, Ordering::Release);
+        maps.extend(new.maps.into_inner());
+        lock_maps.extend(new.lock_maps.into_inner());
+        self.generation
+            .store(new.generation.into_inner(), Ordering::Release);
+        self.memory_locked
+            .store(new.memory_locked.into_inner(), Ordering::Relaxed);
+        // Dropping the old lock file releases its shared lock.
+        *self.lock_file.lock() = new.lock_file.into_inner();
+        self.remaps.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Times this handle switched to a replacement of its files (`adopt`).
+    pub fn remaps(&self) -> u64 {
+        self.remaps.load(Ordering::Relaxed)
+    }
+
+    /// Release this handle's mapping lists and lock file in the child of a
+    /// fork, if a thread the child doesn't have was remapping.
+    ///
+    /// # Safety
+    ///
+    /// As for `ForkLock::release_in_child`.
+    pub unsafe fn after_fork_in_child(&self) {
+        self.maps.release_in_child();
+        self.lock_maps.release_in_child();
+        self.lock_file.release_in_child();
+    }
+
+    /// Build a fresh, empty data file with `config` next to the live one and
+    /// switch this handle to it. The file is not yet visible to other
+    /// processes: call `publish_resized` (or `abandon_resized`) next.
+    ///
+    /// Caller must hold the write lock.
+    ///
+    /// Not supported on Windows, where the rename in `publish_resized` can't
+    /// replace a file other handles still have mapped.
+    pub fn begin_resize(&self, config: &RegionConfig) -> io::Result<()> {
+        if cfg!(windows) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "resizing a shared cache is not supported on Windows",
+            ));
+        }
+        let tmp_path = self.resize_tmp_path();
+        let mmap: MmapRaw = Self::create_data_file(&tmp_path, config, self.file_mode)
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&tmp_path);
+            })?
+            .into();
+        self.pin(&mmap);
+        let mut maps = self.maps.lock();
+        self.base.store(mmap.as_mut_ptr(), Ordering::Release);
+        maps.push(mmap);
+        Ok(())
+    }
+
+    /// Rename the file built by `begin_resize` over the live one and bump the
+    /// generation so other handles remap. Caller must hold the write lock.
+    pub fn publish_resized(&self) -> io::Result<()> {
+        fs::rename(self.resize_tmp_path(), &self.data_path)?;
+        let generation = self.lock().bump_generation();
+        self.generation.store(generation, Ordering::Release);
+        Ok(())
+    }
+
+    /// Switch back to the previous data file after a failed resize and remove
+    /// the temporary one. Caller must hold the write lock.
+    pub fn abandon_resized(&self) {
+        let maps = self.maps.lock();
+        if let Some(prev) = maps.len().checked_sub(2).map(|i| &maps[i]) {
+            self.base.store(prev.as_ptr() as *mut u8, Ordering::Release);
+        }
+        let _ = fs::remove_file(self.resize_tmp_path());
+    }
+
+    /// Write the current data file back to disk. With `wait`, block until it
+    /// is written (`msync(MS_SYNC)`); otherwise only schedule the writeback.
+    pub fn flush(&self, wait: bool) -> io::Result<()> {
+        let maps = self.maps.lock();
+        let base = self.base_ptr();
+        match maps.iter().find(|m| m.as_ptr() == base) {
+            Some(map) if wait => map.flush(),
+            Some(map) => map.flush_async(),
+            None => Ok(()),
+        }
+    }
+
+    /// `pin_mapping` for a data file replacing the current one. A resize
+    /// can't be refused at this point, so an `mlock` failure (the new file may
+    /// be larger) only clears `memory_locked`.
+    fn pin(&self, map: &MmapRaw) {
+        let locked = pin_mapping(map, self.lock_memory, self.prefault).is_ok();
+        self.memory_locked
+            .store(self.lock_memory && locked, Ordering::Relaxed);
+    }
+
+    /// Zero `len` bytes of the data file from `offset`, giving the whole
+    /// pages among them back to the filesystem (`MADV_REMOVE`) so that a
+    /// tmpfs file stops holding their memory. Every process mapping the file
+    /// then reads zeros there, and touching a page again allocates it anew.
+    /// On an error nothing has been released, though the partial pages at
+    /// either end may already be zeroed.
+    ///
+    /// # Safety
+    /// Caller must hold the write lock, with no reference into the range alive.
+    #[cfg(target_os = "linux")]
+    pub unsafe fn release_range(&self, offset: usize, len: usize) -> io::Result<()> {
+        let page = libc::sysconf(libc::_SC_PAGESIZE).max(1) as usize;
+        let end = offset + len;
+        let (inner_start, inner_end) = (offset.next_multiple_of(page), end / page * page);
+        let maps = self.maps.lock();
+        let base = self.base_ptr();
+        let Some(map) = maps.iter().find(|m| m.as_ptr() == base) else {
+            return Err(io::Error::other("data file is not mapped"));
+        };
+        if inner_start >= inner_end {
+            std::ptr::write_bytes(base.add(offset) as *mut u8, 0, len);
+            return Ok(());
+        }
+        std::ptr::write_bytes(base.add(offset) as *mut u8, 0, inner_start - offset);
+        std::ptr::write_bytes(base.add(inner_end) as *mut u8, 0, end - inner_end);
+        map.unchecked_advise_range(
+            memmap2::UncheckedAdvice::Remove,
+            inner_start,
+            inner_end - inner_start,
         )
-        // init_file's advisory lock is released when it drops at end of scope.
+    }
+
+    /// `MADV_REMOVE` is Linux-only; elsewhere nothing is released.
+    ///
+    /// # Safety
+    /// As on Linux.
+    #[cfg(not(target_os = "linux"))]
+    pub unsafe fn release_range(&self, _offset: usize, _len: usize) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "releasing memory needs MADV_REMOVE (Linux only)",
+        ))
+    }
+
+    /// Bytes of storage the data file occupies: tmpfs memory, or disk blocks
+    /// for a persistent cache. 0 if it can't be determined.
+    pub fn allocated_bytes(&self) -> u64 {
+        #[cfg(unix)]
+        {
+            fs::metadata(&self.data_path).map_or(0, |m| m.blocks() * 512)
+        }
+        #[cfg(not(unix))]
+        {
+            fs::metadata(&self.data_path).map_or(0, |m| m.len())
+        }
+    }
+
+    /// Whether the current data file is `mlock`ed (see `FileOptions::lock_memory`).
+    pub fn memory_locked(&self) -> bool {
+        self.memory_locked.load(Ordering::Relaxed)
+    }
+
+    /// Whether no other handle, in any process, had the cache open when this
+    /// one attached — so a held write lock can only belong to a dead process.
+    pub fn opened_alone(&self) -> bool {
+        self.opened_alone
+    }
+
+    /// The cache's name, as passed to `create_or_open`.
+    pub fn name(&self) -> String {
+        self.data_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    fn resize_tmp_path(&self) -> PathBuf {
+        self.data_path.with_extension("data.resize")
     }
 
     pub fn header(&self) -> &Header {
-        unsafe { &*(self.mmap.as_ptr() as *const Header) }
+        unsafe { &*(self.base_ptr() as *const Header) }
     }
 
     pub fn lock(&self) -> ShmSeqLock {
-        unsafe { ShmSeqLock::from_existing(self.lock_mmap.as_ptr() as *mut u8) }
+        unsafe { ShmSeqLock::from_existing(self.lock_base.load(Ordering::Acquire)) }
+    }
+
+    /// Whether `lock` is still this handle's lock, i.e. no `adopt` has
+    /// switched it to a replacement since `lock` was taken.
+    #[inline]
+    pub fn is_current(&self, lock: &ShmSeqLock) -> bool {
+        std::ptr::eq(lock.as_ptr(), self.lock_base.load(Ordering::Acquire))
     }
 
+    #[inline]
     pub fn base_ptr(&self) -> *const u8 {
-        self.mmap.as_ptr()
+        self.base.load(Ordering::Acquire)
     }
 }
@@ -0,0 +1,202 @@
+/// Snapshot files: a cache's live entries written out by `save()` and read back
+/// by `load()`.
+///
+/// The format knows nothing about the backend that wrote it. Keys and values
+/// are the `serde` byte encoding (fast path or wrapped pickle) and TTLs are
+/// stored as time left, so any cache able to decode those bytes can load it.
+///
+/// Layout (little-endian):
+///   [magic: 8][version: u32][reserved: u32][saved_at_unix_nanos: u64][count: u64]
+///   then `count` entries of
+///   [key_len: u32][value_len: u32][remaining_ttl_nanos: u64][key][value]
+///
+/// `remaining_ttl_nanos` is 0 for entries that never expire.
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Magic bytes at the start of every snapshot file.
+const MAGIC: [u8; 8] = *b"WCSNAP01";
+
+/// Format version — bumped when the layout changes.
+const VERSION: u32 = 1;
+
+/// One cache entry as stored in a snapshot.
+pub struct SnapshotEntry {
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+    /// Time the entry had left when saved, 0 = no expiry.
+    pub remaining_ttl_nanos: u64,
+}
+
+/// Write `entries` to `path`. The file is written beside it first and renamed
+/// into place, so a reader never sees a half-written snapshot.
+pub fn write(path: &Path, entries: &[SnapshotEntry]) -> io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = Path::new(&tmp_name);
+
+    let result = (|| {
+        let mut out = BufWriter::new(fs::File::create(tmp_path)?);
+        out.write_all(&MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(&unix_nanos().to_le_bytes())?;
+        out.write_all(&(entries.len() as u64).to_le_bytes())?;
+        for entry in entries {
+            out.write_all(&(entry.key.len() as u32).to_le_bytes())?;
+            out.write_all(&(entry.value.len() as u32).to_le_bytes())?;
+            out.write_all(&entry.remaining_ttl_nanos.to_le_bytes())?;
+            out.write_all(&entry.key)?;
+            out.write_all(&entry.value)?;
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()
+    })();
+    match result {
+        Ok(()) => fs::rename(tmp_path, path),
+        Err(e) => {
+            let _ = fs::remove_file(tmp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Read the entries saved in `path`, in the order they were written.
+///
+/// Each remaining TTL is reduced by the wall-clock time since the snapshot was
+/// taken; entries whose time ran out meanwhile are dropped, and their count is
+/// returned alongside the rest.
+pub fn read(path: &Path) -> io::Result<(Vec<SnapshotEntry>, usize)> {
+    let mut input = BufReader::new(fs::File::open(path)?);
+
+    let mut magic = [0u8; 8];
+    input.read_exact(&mut magic).map_err(truncated)?;
+    if magic != MAGIC {
+        return Err(invalid(format!(
+            "{} is not a warp_cache snapshot",
+            path.display()
+        )));
+    }
+    let version = read_u32(&mut input)?;
+    if version != VERSION {
+        return Err(invalid(format!(
+            "unsupported snapshot version {version} (expected {VERSION})"
+        )));
+    }
+    let _reserved = read_u32(&mut input)?;
+    let saved_at = read_u64(&mut input)?;
+    let count = read_u64(&mut input)?;
+    let elapsed = unix_nanos().saturating_sub(saved_at);
+
+    let mut entries = Vec::new();
+    let mut expired = 0;
+    for _ in 0..count {
+        let key_len = read_u32(&mut input)? as usize;
+        let value_len = read_u32(&mut input)? as usize;
+        let remaining_ttl_nanos = read_u64(&mut input)?;
+        let key = read_bytes(&mut input, key_len)?;
+        let value = read_bytes(&mut input, value_len)?;
+        if remaining_ttl_nanos == 0 {
+            entries.push(SnapshotEntry {
+                key,
+                value,
+                remaining_ttl_nanos,
+            });
+        } else if remaining_ttl_nanos > elapsed {
+            entries.push(SnapshotEntry {
+                key,
+                value,
+                remaining_ttl_nanos: remaining_ttl_nanos - elapsed,
+            });
+        } else {
+            expired += 1;
+        }
+    }
+    Ok((entries, expired))
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    input.read_exact(&mut buf).map_err(truncated)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    input.read_exact(&mut buf).map_err(truncated)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_bytes(input: &mut impl Read, len: usize) -> io::Result<Vec<u8>> {
+    // Grow as data arrives rather than trusting a (possibly corrupt) length.
+    let mut buf = Vec::new();
+    input.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(invalid("snapshot file is truncated".to_string()));
+    }
+    Ok(buf)
+}
+
+fn truncated(e: io::Error) -> io::Error {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        invalid("snapshot file is truncated".to_string())
+    } else {
+        e
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Wall-clock nanoseconds since the Unix epoch; snapshots outlive the
+/// monotonic clock's boot-relative epoch.
+fn unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_keeps_order_and_ttls() {
+        let path = std::env::temp_dir().join(format!("rust_test_snapshot_{}", std::process::id()));
+        let entries = vec![
+            SnapshotEntry {
+                key: b"a".to_vec(),
+                value: b"1".to_vec(),
+                remaining_ttl_nanos: 0,
+            },
+            SnapshotEntry {
+                key: b"b".to_vec(),
+                value: vec![7; 1000],
+                remaining_ttl_nanos: 3_600_000_000_000,
+            },
+            SnapshotEntry {
+                key: b"c".to_vec(),
+                value: b"3".to_vec(),
+                remaining_ttl_nanos: 1,
+            },
+        ];
+        write(&path, &entries).unwrap();
+
+        let (read_back, expired) = read(&path).unwrap();
+        assert_eq!(expired, 1);
+        assert_eq!(read_back.len(), 2);
+        assert_eq!(
+            (&read_back[0].key[..], read_back[0].remaining_ttl_nanos),
+            (&b"a"[..], 0)
+        );
+        assert_eq!(read_back[1].value, vec![7; 1000]);
+        assert!(read_back[1].remaining_ttl_nanos <= 3_600_000_000_000);
+
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(matches!(read(&path), Err(e) if e.kind() == io::ErrorKind::InvalidData));
+        let _ = fs::remove_file(path);
+    }
+}
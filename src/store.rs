@@ -3,15 +3,24 @@ use std::collections::VecDeque;
 use std::hash::{BuildHasher, Hasher};
 use std::ops::{Deref, DerefMut};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(Py_GIL_DISABLED)]
+use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyTuple};
+use pyo3::types::{PyDict, PyString, PyTuple};
 use pyo3::{ffi, Bound, PyErr};
 
+use crate::config;
 use crate::entry::SieveEntry;
-use crate::key::{BorrowedArgs, CacheKey};
+#[cfg(Py_GIL_DISABLED)]
+use crate::fork::{self, AfterFork, ForkLock};
+use crate::key::{
+    args_contain_nan, hashable_key, kwargs_key, take_compare_error, BorrowedArgs, CacheKey,
+};
+use crate::latency::Latencies;
+use crate::shared_store::{call_tags_func, check_tags_func, parse_cache_version, parse_tags};
 
 const MAX_SHARDS: usize = 16;
 const MIN_SHARD_SIZE: usize = 8;
@@ -62,7 +71,13 @@ impl BuildHasher for PassthroughBuildHasher {
 // ---------------------------------------------------------------------------
 // GIL-conditional lock: under GIL-enabled Python the GIL already serialises
 // all #[pymethods] access, so the per-shard RwLock is pure overhead (~8ns).
-// Under free-threaded Python (3.13t+) we need real locking.
+// Under free-threaded Python (3.14t+) we need real locking.
+//
+// Shards are locked through pyo3's `RwLockExt`, which detaches from the
+// interpreter while it blocks. A thread parked on a shard lock while still
+// attached would hold up a stop-the-world pause (the free-threaded GC) that
+// the lock's holder, running a key's `__eq__` or a value's `__del__`, may be
+// waiting on: a deadlock. An uncontended lock never detaches.
 // ---------------------------------------------------------------------------
 
 #[cfg(Py_GIL_DISABLED)]
@@ -71,6 +86,57 @@ type ShardLock = parking_lot::RwLock<Shard>;
 #[cfg(not(Py_GIL_DISABLED))]
 type ShardLock = GilCell<Shard>;
 
+#[cfg(Py_GIL_DISABLED)]
+use pyo3::sync::RwLockExt;
+
+/// The shards of a free-threaded build, shared with `fork::register` so that
+/// one a thread held when the process forked is released in the child.
+#[cfg(Py_GIL_DISABLED)]
+struct ShardSet {
+    locks: Box<[ForkLock<ShardLock>]>,
+    per_shard: usize,
+}
+
+#[cfg(Py_GIL_DISABLED)]
+impl Deref for ShardSet {
+    type Target = [ForkLock<ShardLock>];
+    #[inline(always)]
+    fn deref(&self) -> &[ForkLock<ShardLock>] {
+        &self.locks
+    }
+}
+
+/// A shard read-locked by a lost thread keeps its entries; one that was
+/// being written starts over empty. Skipped if the forking thread was itself
+/// inside this cache (forked from a key's `__eq__`), as it may hold a shard.
+#[cfg(Py_GIL_DISABLED)]
+impl AfterFork for ShardSet {
+    fn after_fork_in_child(&self) {
+        let id = self as *const Self as usize;
+        if ACTIVE.with(|a| a.borrow().contains(&id)) {
+            return;
+        }
+        for lock in self.locks.iter() {
+            // SAFETY: this thread holds no shard of this cache (checked above).
+            unsafe { lock.release_in_child(|| Shard::new(self.per_shard)) };
+        }
+    }
+}
+
+#[cfg(Py_GIL_DISABLED)]
+type Shards = Arc<ShardSet>;
+
+// GIL builds have no lock to lose: a fork happens with the GIL, so no other
+// thread is inside a shard. One may be inside a key's `__eq__` with the
+// reentrancy flag set, which then stays set in the child and leaves that
+// function uncached there. Clearing it is not safe: the forking thread may be
+// the one inside the `__eq__`, with its own borrow still live.
+#[cfg(not(Py_GIL_DISABLED))]
+type Shards = Box<[ShardLock]>;
+
+/// `(hash, key_ptr, key_owner)` as returned by `hash_args`.
+type HashedArgs = (isize, *mut ffi::PyObject, Option<Py<PyAny>>);
+
 /// Zero-cost lock substitute for GIL-enabled builds.
 ///
 /// SAFETY: All access is through `#[pymethods]` which hold the GIL.
@@ -89,13 +155,15 @@ impl<T> GilCell<T> {
         GilCell(UnsafeCell::new(val))
     }
 
+    /// Named after `RwLockExt::read_py_attached`, so shard access reads
+    /// the same on both builds.
     #[inline(always)]
-    fn read(&self) -> GilReadGuard<'_, T> {
+    fn read_py_attached(&self, _py: Python<'_>) -> GilReadGuard<'_, T> {
         GilReadGuard(&self.0)
     }
 
     #[inline(always)]
-    fn write(&self) -> GilWriteGuard<'_, T> {
+    fn write_py_attached(&self, _py: Python<'_>) -> GilWriteGuard<'_, T> {
         GilWriteGuard(&self.0)
     }
 }
@@ -215,6 +283,54 @@ struct Shard {
     order: VecDeque<CacheKey>,
     hand: usize,
     capacity: usize,
+    /// Keys of the entries carrying each tag. Kept in step with `map` by
+    /// `insert` and `remove`; empty unless entries are tagged.
+    tags: HashMap<String, HashSet<CacheKey, PassthroughBuildHasher>>,
+}
+
+impl Shard {
+    fn new(capacity: usize) -> Self {
+        Shard {
+            map: HashMap::with_capacity_and_hasher(capacity, PassthroughBuildHasher),
+            order: VecDeque::with_capacity(capacity),
+            hand: 0,
+            capacity,
+            tags: HashMap::new(),
+        }
+    }
+
+    /// Store an entry for a key not in `map`, indexing its tags. The caller
+    /// handles `order`.
+    fn insert(&mut self, py: Python<'_>, key: CacheKey, entry: SieveEntry) {
+        for tag in &entry.tags {
+            match self.tags.get_mut(tag) {
+                Some(keys) => {
+                    keys.insert(key.clone_with(py));
+                }
+                None => {
+                    let mut keys = HashSet::with_hasher(PassthroughBuildHasher);
+                    keys.insert(key.clone_with(py));
+                    self.tags.insert(tag.clone(), keys);
+                }
+            }
+        }
+        self.map.insert(key, entry);
+    }
+
+    /// Remove a key's entry from `map` and the tag index. Its `order` slot
+    /// is cleaned up lazily by `evict_one`.
+    fn remove(&mut self, key: &CacheKey) -> Option<SieveEntry> {
+        let entry = self.map.remove(key)?;
+        for tag in &entry.tags {
+            if let Some(keys) = self.tags.get_mut(tag) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.tags.remove(tag);
+                }
+            }
+        }
+        Some(entry)
+    }
 }
 
 #[pyclass(frozen)]
@@ -227,35 +343,204 @@ pub struct CacheInfo {
     pub max_size: usize,
     #[pyo3(get)]
     pub current_size: usize,
+    /// "memory", or which stand-in a shared cache that couldn't be set up
+    /// fell back to ("local-fallback", "nocache-fallback").
+    #[pyo3(get)]
+    pub backend: String,
+    /// Calls run uncached because their arguments don't hash, with
+    /// `unhashable="bypass"`.
+    #[pyo3(get)]
+    pub uncacheable_calls: u64,
+    /// Misses not stored because the call took less than
+    /// `min_compute_time`.
+    #[pyo3(get)]
+    pub skipped_fast: u64,
 }
 
 #[pymethods]
 impl CacheInfo {
+    /// Built from Python only by the decorator's `fallback="nocache"` stand-in.
+    #[new]
+    #[pyo3(signature = (hits, misses, max_size, current_size, backend="memory".to_string(), uncacheable_calls=0, skipped_fast=0))]
+    fn new(
+        hits: u64,
+        misses: u64,
+        max_size: usize,
+        current_size: usize,
+        backend: String,
+        uncacheable_calls: u64,
+        skipped_fast: u64,
+    ) -> Self {
+        CacheInfo {
+            hits,
+            misses,
+            max_size,
+            current_size,
+            backend,
+            uncacheable_calls,
+            skipped_fast,
+        }
+    }
+
     fn __repr__(&self) -> String {
+        let backend = if self.backend == "memory" {
+            String::new()
+        } else {
+            format!(", backend={:?}", self.backend)
+        };
+        let uncacheable = if self.uncacheable_calls == 0 {
+            String::new()
+        } else {
+            format!(", uncacheable_calls={}", self.uncacheable_calls)
+        };
+        let skipped_fast = if self.skipped_fast == 0 {
+            String::new()
+        } else {
+            format!(", skipped_fast={}", self.skipped_fast)
+        };
         format!(
-            "CacheInfo(hits={}, misses={}, max_size={}, current_size={})",
+            "CacheInfo(hits={}, misses={}, max_size={}, current_size={}{backend}{uncacheable}{skipped_fast})",
             self.hits, self.misses, self.max_size, self.current_size
         )
     }
 }
 
+impl CacheInfo {
+    /// What `CachedFunction::metrics` returns.
+    pub(crate) fn metrics<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let metrics = PyDict::new(py);
+        metrics.set_item("hits", self.hits)?;
+        metrics.set_item("misses", self.misses)?;
+        metrics.set_item("hit_rate", hit_rate(self.hits, self.misses))?;
+        metrics.set_item("current_size", self.current_size)?;
+        metrics.set_item("max_size", self.max_size)?;
+        metrics.set_item("uncacheable_calls", self.uncacheable_calls)?;
+        metrics.set_item("skipped_fast", self.skipped_fast)?;
+        Ok(metrics)
+    }
+}
+
+/// Hits over lookups, 0.0 before the first lookup.
+pub(crate) fn hit_rate(hits: u64, misses: u64) -> f64 {
+    match hits + misses {
+        0 => 0.0,
+        lookups => hits as f64 / lookups as f64,
+    }
+}
+
 // `dict` gives instances a __dict__ so the Python decorator can apply
 // functools.wraps (copies __name__/__qualname__/__module__/__doc__/__wrapped__)
 // for introspection (#43). Only touched at decoration time, never on the hot path.
-#[pyclass(frozen, dict)]
+// `weakref` lets the decorator's registry of live caches (`all_caches`) hold
+// them without keeping them alive.
+#[pyclass(frozen, dict, weakref)]
 pub struct CachedFunction {
     fn_obj: Py<PyAny>,
-    shards: Box<[ShardLock]>,
+    shards: Shards,
     shard_mask: usize,
     ttl: Option<Duration>,
+    sliding_ttl: bool,
     max_size: usize,
+    /// Stands in for a shared cache that couldn't be set up (`fallback="local"`).
+    fallback: bool,
+    /// `cache_version` as a str, prepended to every key so entries from
+    /// another version never match.
+    cache_version: Option<Py<PyString>>,
+    /// Called as `tags_func(args, kwargs, result)` for the tags of each
+    /// stored result.
+    tags_func: Option<Py<PyAny>>,
+    unhashable: Unhashable,
+    /// Store keys `CacheKey::stored` takes as bytes (`compact_keys=True`).
+    /// Off by default: a hit on such a key serializes the lookup's arguments
+    /// to compare them, which costs more than `__eq__` on the stored tuple.
+    compact_keys: bool,
     hits: AtomicU64,
     misses: AtomicU64,
+    uncacheable_calls: AtomicU64,
+    /// Misses shorter than this are returned but not stored.
+    min_compute_time: Option<Duration>,
+    skipped_fast: AtomicU64,
+    /// Hit and miss timings of `__call__`, with `record_latencies`.
+    latencies: Option<Latencies>,
     #[cfg(not(Py_GIL_DISABLED))]
     reentry: ReentryCell,
 }
 
+/// What to do with call arguments that don't hash (`unhashable=`).
+#[derive(Clone, Copy, PartialEq)]
+enum Unhashable {
+    /// Raise the `TypeError`, as `functools.lru_cache` does.
+    Error,
+    /// Call the function uncached, counted in `uncacheable_calls`.
+    Bypass,
+    /// Key the call on a hashable stand-in (`key::hashable_key`).
+    Serialize,
+}
+
+/// The argument of a call `f(x)`, which the call is keyed on in place of
+/// its args tuple, as `functools.lru_cache` does for ints and strs: hashing
+/// and comparing it skips the tuple's own pass over its one item. A tuple
+/// argument keeps its wrapper, or `f((1, 2))` would share the key of
+/// `f(1, 2)`; no other key is a bare argument, so nothing else can alias.
+#[inline(always)]
+fn lone_arg<'a, 'py>(args: &'a Bound<'py, PyTuple>) -> Option<Borrowed<'a, 'py, PyAny>> {
+    if args.len() != 1 {
+        return None;
+    }
+    let arg = args.get_borrowed_item(0).ok()?;
+    (!arg.is_instance_of::<PyTuple>()).then_some(arg)
+}
+
+/// The hash to key a lone argument on. An int hashes to itself, so small
+/// ones leave the top bits, which hashbrown tells a group's entries apart
+/// by, all zero; multiplying by an odd constant spreads them there without
+/// making two hashes equal. A tuple's hash is well mixed already.
+#[inline(always)]
+fn spread(hash: isize) -> isize {
+    (hash as usize).wrapping_mul(0x9E37_79B9_7F4A_7C15_u64 as usize) as isize
+}
+
+fn parse_unhashable(unhashable: &str) -> PyResult<Unhashable> {
+    match unhashable {
+        "error" => Ok(Unhashable::Error),
+        "bypass" => Ok(Unhashable::Bypass),
+        "serialize" => Ok(Unhashable::Serialize),
+        other => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "unhashable must be 'error', 'bypass' or 'serialize' (got {other:?})"
+        ))),
+    }
+}
+
+/// A duration argument (`ttl=`, `min_compute_time=`) in seconds as a
+/// `Duration`, which must be positive.
+pub(crate) fn parse_seconds(name: &str, seconds: Option<f64>) -> PyResult<Option<Duration>> {
+    let Some(t) = seconds else {
+        return Ok(None);
+    };
+    match Duration::try_from_secs_f64(t) {
+        Ok(duration) if !duration.is_zero() => Ok(Some(duration)),
+        _ => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "{name} must be a positive number of seconds (got {t})"
+        ))),
+    }
+}
+
+/// Whether a miss that took `elapsed` is too quick to store.
+#[inline(always)]
+pub(crate) fn too_fast(min_compute_time: Option<Duration>, elapsed: Option<Duration>) -> bool {
+    matches!((min_compute_time, elapsed), (Some(min), Some(elapsed)) if elapsed < min)
+}
+
 impl CachedFunction {
+    /// `CacheInfo::backend` for this cache.
+    fn backend_name(&self) -> String {
+        if self.fallback {
+            "local-fallback".to_string()
+        } else {
+            "memory".to_string()
+        }
+    }
+
     /// Evict one entry using the SIEVE algorithm.
     /// Called with the shard write-locked (caller passes `&mut Shard`).
     fn evict_one(shard: &mut Shard) {
@@ -273,27 +558,27 @@ impl CachedFunction {
                 shard.hand = 0;
             }
 
-            let key = shard.order[shard.hand].clone();
-
             // Read visited status (ends immutable borrow before mutations)
             let status = shard
                 .map
-                .get(&key)
+                .get(&shard.order[shard.hand])
                 .map(|e| e.visited.load(Ordering::Relaxed));
 
             match status {
                 Some(true) => {
                     // Second chance: clear visited bit, advance hand
-                    if let Some(entry) = shard.map.get(&key) {
+                    if let Some(entry) = shard.map.get(&shard.order[shard.hand]) {
                         entry.visited.store(false, Ordering::Relaxed);
                     }
                     shard.hand += 1;
                     scanned += 1;
                 }
                 Some(false) => {
-                    // Evict this entry
-                    shard.map.remove(&key);
-                    shard.order.remove(shard.hand);
+                    // Evict this entry. Taking the key out of `order` first
+                    // saves copying it (and so a refcount bump) per scan.
+                    if let Some(key) = shard.order.remove(shard.hand) {
+                        shard.remove(&key);
+                    }
                     if shard.hand >= shard.order.len() && !shard.order.is_empty() {
                         shard.hand = 0;
                     }
@@ -311,158 +596,144 @@ impl CachedFunction {
         }
     }
 
+    /// The object to key the cache on when the args tuple alone won't do:
+    /// `(args, sorted kwargs items)` with keyword arguments, and either key
+    /// behind the `cache_version`. `None` on the fast path (plain args).
     #[inline(always)]
-    fn make_key<'py>(
+    fn composite_key<'py>(
+        &self,
         py: Python<'py>,
         args: &Bound<'py, PyTuple>,
         kwargs: &Option<Bound<'py, PyDict>>,
-    ) -> PyResult<CacheKey> {
-        let key_obj: Py<PyAny> = match kwargs {
-            Some(ref kw) if !kw.is_empty() => {
-                let builtins = py.import("builtins")?;
-                let items = kw.call_method0("items")?;
-                let sorted_items = builtins.call_method1("sorted", (items,))?;
-                let kw_tup = builtins.getattr("tuple")?.call1((sorted_items,))?;
-                let combined = PyTuple::new(py, [args.as_any().clone(), kw_tup])?;
-                combined.unbind().into()
+    ) -> PyResult<Option<Bound<'py, PyAny>>> {
+        let with_kwargs = match kwargs {
+            Some(ref kw) if !kw.is_empty() => Some(kwargs_key(py, args, kw)?),
+            _ => None,
+        };
+        match &self.cache_version {
+            None => Ok(with_kwargs),
+            Some(version) => {
+                let inner = with_kwargs.unwrap_or_else(|| args.as_any().clone());
+                let versioned = PyTuple::new(py, [version.bind(py).as_any().clone(), inner])?;
+                Ok(Some(versioned.into_any()))
             }
-            _ => args.clone().unbind().into(),
+        }
+    }
+
+    /// The key to store for `obj`: with `compact_keys`, its bytes if
+    /// `CacheKey::stored` takes it; otherwise `obj` itself.
+    #[inline(always)]
+    fn stored_key(&self, py: Python<'_>, hash: isize, obj: Py<PyAny>) -> CacheKey {
+        if self.compact_keys {
+            CacheKey::stored(py, hash, obj)
+        } else {
+            CacheKey::with_hash(hash, obj)
+        }
+    }
+
+    /// The key for a call, or `None` if its arguments don't hash and
+    /// `unhashable="bypass"`.
+    #[inline(always)]
+    fn make_key<'py>(
+        &self,
+        py: Python<'py>,
+        args: &Bound<'py, PyTuple>,
+        kwargs: &Option<Bound<'py, PyDict>>,
+    ) -> PyResult<Option<CacheKey>> {
+        let Some((hash, key_ptr, key_owner)) = self.hash_args(py, args, kwargs)? else {
+            return Ok(None);
         };
-        CacheKey::new(py, key_obj)
+        // SAFETY: without an owner, `key_ptr` is `args` or one of its items.
+        let key_obj = key_owner.unwrap_or_else(|| {
+            unsafe { Borrowed::from_ptr(py, key_ptr) }
+                .to_owned()
+                .unbind()
+        });
+        Ok(Some(self.stored_key(py, hash, key_obj)))
     }
 
     /// Compute hash + key pointer for the common no-kwargs fast path, or fall
-    /// back to building a composite key object when kwargs are present.
+    /// back to building a composite key object when kwargs (or a
+    /// `cache_version`) are present. On the fast path the key is the args
+    /// tuple, or for `f(x)` the lone argument itself (see `lone_arg`).
     /// Returns `(hash, key_ptr)` where key_ptr is the raw PyObject* to compare.
-    /// On the kwargs path, also returns an owned Py<PyAny> to keep the
-    /// composite key alive; on the no-kwargs path this is None.
+    /// On the composite path, also returns an owned Py<PyAny> to keep the
+    /// key alive; on the fast path this is None. `None` in place of all
+    /// three if the key doesn't hash and `unhashable="bypass"`.
     #[inline(always)]
     fn hash_args<'py>(
+        &self,
         py: Python<'py>,
         args: &Bound<'py, PyTuple>,
         kwargs: &Option<Bound<'py, PyDict>>,
-    ) -> PyResult<(isize, *mut ffi::PyObject, Option<Py<PyAny>>)> {
-        match kwargs {
-            Some(ref kw) if !kw.is_empty() => {
-                // Rare path: build composite key, hash it
-                let builtins = py.import("builtins")?;
-                let items = kw.call_method0("items")?;
-                let sorted_items = builtins.call_method1("sorted", (items,))?;
-                let kw_tup = builtins.getattr("tuple")?.call1((sorted_items,))?;
-                let combined = PyTuple::new(py, [args.as_any().clone(), kw_tup])?;
-                let key_obj: Py<PyAny> = combined.unbind().into();
+    ) -> PyResult<Option<HashedArgs>> {
+        let composite = self.composite_key(py, args, kwargs)?;
+        let (hashed, lone) = match composite {
+            Some(key) => {
+                // Rare path: hash the composite key
+                let key_obj = key.unbind();
                 let ptr = key_obj.as_ptr();
                 let hash = unsafe { ffi::PyObject_Hash(ptr) };
-                if hash == -1 {
-                    return Err(PyErr::fetch(py));
+                if hash != -1 {
+                    return Ok(Some((hash, ptr, Some(key_obj))));
                 }
-                Ok((hash, ptr, Some(key_obj)))
+                (key_obj.into_bound(py), false)
             }
-            _ => {
-                // Fast path: hash the args tuple directly via raw FFI
-                let ptr = args.as_ptr();
+            None => {
+                // Fast path: hash the lone argument or the args tuple
+                // directly via raw FFI
+                let lone = lone_arg(args);
+                let ptr = lone.as_ref().map_or(args.as_ptr(), |arg| arg.as_ptr());
                 let hash = unsafe { ffi::PyObject_Hash(ptr) };
-                if hash == -1 {
-                    return Err(PyErr::fetch(py));
+                if hash != -1 {
+                    let hash = if lone.is_some() { spread(hash) } else { hash };
+                    return Ok(Some((hash, ptr, None)));
+                }
+                match lone {
+                    Some(arg) => (arg.to_owned(), true),
+                    None => (args.clone().into_any(), false),
                 }
-                Ok((hash, ptr, None))
             }
-        }
-    }
+        };
 
-    /// Mark this function active for one borrow region. Returns `None` if it is
-    /// already active (reentrant call / GIL handed off mid-`__eq__`), in which
-    /// case the caller must bypass the cache. The returned guard clears the
-    /// marker on drop. See issue #30.
-    #[cfg(not(Py_GIL_DISABLED))]
-    #[inline(always)]
-    fn try_enter(&self) -> Option<EnterGuard<'_>> {
-        let p = self.reentry.0.get();
-        // SAFETY: GIL held; access serialized. No reference is held across any
-        // Python call — we read then write a bool by value.
-        unsafe {
-            if *p {
-                None
-            } else {
-                *p = true;
-                Some(EnterGuard(&self.reentry.0))
-            }
+        // Arguments that don't hash raise TypeError, which `unhashable` decides
+        // about; anything else a __hash__ raises goes to the caller.
+        let err = PyErr::fetch(py);
+        if !err.is_instance_of::<pyo3::exceptions::PyTypeError>(py) {
+            return Err(err);
         }
-    }
-
-    #[cfg(Py_GIL_DISABLED)]
-    #[inline]
-    fn try_enter(&self) -> Option<EnterGuard> {
-        let id = self as *const Self as usize;
-        ACTIVE.with(|a| {
-            let mut v = a.borrow_mut();
-            if v.contains(&id) {
-                None
-            } else {
-                v.push(id);
-                Some(EnterGuard { id })
+        match self.unhashable {
+            Unhashable::Error => Err(err),
+            Unhashable::Bypass => Ok(None),
+            Unhashable::Serialize => {
+                // A lone argument's stand-in is keyed as a hashable lone
+                // argument is, so that `{1, 2}` shares `frozenset({1, 2})`'s.
+                let key_obj = hashable_key(&hashed)?;
+                let hash = key_obj.hash()?;
+                let hash = if lone { spread(hash) } else { hash };
+                let key_obj = key_obj.unbind();
+                Ok(Some((hash, key_obj.as_ptr(), Some(key_obj))))
             }
-        })
-    }
-}
-
-#[pymethods]
-impl CachedFunction {
-    #[new]
-    #[pyo3(signature = (fn_obj, max_size, ttl=None))]
-    fn new(fn_obj: Py<PyAny>, max_size: usize, ttl: Option<f64>) -> Self {
-        let n_shards = (max_size / MIN_SHARD_SIZE)
-            .clamp(1, MAX_SHARDS)
-            .next_power_of_two()
-            .min(MAX_SHARDS);
-        let per_shard = max_size.div_ceil(n_shards);
-
-        #[cfg(Py_GIL_DISABLED)]
-        let shards: Vec<ShardLock> = (0..n_shards)
-            .map(|_| {
-                parking_lot::RwLock::new(Shard {
-                    map: HashMap::with_capacity_and_hasher(per_shard, PassthroughBuildHasher),
-                    order: VecDeque::with_capacity(per_shard),
-                    hand: 0,
-                    capacity: per_shard,
-                })
-            })
-            .collect();
-
-        #[cfg(not(Py_GIL_DISABLED))]
-        let shards: Vec<ShardLock> = (0..n_shards)
-            .map(|_| {
-                GilCell::new(Shard {
-                    map: HashMap::with_capacity_and_hasher(per_shard, PassthroughBuildHasher),
-                    order: VecDeque::with_capacity(per_shard),
-                    hand: 0,
-                    capacity: per_shard,
-                })
-            })
-            .collect();
-
-        CachedFunction {
-            fn_obj,
-            shards: shards.into_boxed_slice(),
-            shard_mask: n_shards - 1,
-            ttl: ttl.map(Duration::from_secs_f64),
-            max_size,
-            hits: AtomicU64::new(0),
-            misses: AtomicU64::new(0),
-            #[cfg(not(Py_GIL_DISABLED))]
-            reentry: ReentryCell::new(),
         }
     }
 
-    #[pyo3(signature = (*args, **kwargs))]
-    fn __call__<'py>(
+    /// The body of `__call__`: the result, and whether it came from the cache.
+    #[inline(always)]
+    fn call_cached<'py>(
         &self,
         py: Python<'py>,
         args: Bound<'py, PyTuple>,
         kwargs: Option<Bound<'py, PyDict>>,
-    ) -> PyResult<Py<PyAny>> {
+    ) -> PyResult<(Py<PyAny>, bool)> {
         // Step 1: compute hash + pointer without creating a CacheKey
-        let (hash, key_ptr, _key_owner) = Self::hash_args(py, &args, &kwargs)?;
+        let Some((hash, key_ptr, _key_owner)) = self.hash_args(py, &args, &kwargs)? else {
+            self.uncacheable_calls.fetch_add(1, Ordering::Relaxed);
+            return self
+                .fn_obj
+                .bind(py)
+                .call(&args, kwargs.as_ref())
+                .map(|result| (result.unbind(), false));
+        };
         let borrowed = BorrowedArgs { hash, ptr: key_ptr };
         let shard_idx = hash as usize & self.shard_mask;
 
@@ -472,23 +743,26 @@ impl CachedFunction {
         // mid-comparison). Such calls bypass the cache and recompute. See #30.
         let entered = match self.try_enter() {
             Some(_enter) => {
-                let shard = self.shards[shard_idx].read();
+                let shard = self.shards[shard_idx].read_py_attached(py);
                 if let Some(entry) = shard.map.get(&borrowed) {
                     if let Some(ttl) = self.ttl {
-                        if entry.created_at.elapsed() <= ttl {
-                            entry.visited.store(true, Ordering::Relaxed);
+                        if entry.age() <= ttl {
+                            entry.mark_visited();
+                            if self.sliding_ttl {
+                                entry.refresh_age();
+                            }
                             let val = entry.value.clone_ref(py);
                             drop(shard);
                             self.hits.fetch_add(1, Ordering::Relaxed);
-                            return Ok(val);
+                            return Ok((val, true));
                         }
                         // Expired — fall through to miss path
                     } else {
-                        entry.visited.store(true, Ordering::Relaxed);
+                        entry.mark_visited();
                         let val = entry.value.clone_ref(py);
                         drop(shard);
                         self.hits.fetch_add(1, Ordering::Relaxed);
-                        return Ok(val);
+                        return Ok((val, true));
                     }
                 }
                 true
@@ -499,25 +773,37 @@ impl CachedFunction {
         // A key's __eq__ may have raised during the probe above (RichCompareBool
         // returned -1, leaving the exception set and the probe reported as a miss).
         // Surface it instead of recomputing with an exception pending (#36).
-        if let Some(err) = PyErr::take(py) {
-            return Err(err);
+        take_compare_error(py)?;
+
+        // Cache miss (or reentrant bypass): call the wrapped function (no lock
+        // held), timed if quick results aren't to be stored.
+        let start = self.min_compute_time.map(|_| Instant::now());
+        let result = self.fn_obj.bind(py).call(&args, kwargs.as_ref())?.unbind();
+        let fast = too_fast(self.min_compute_time, start.map(|s| s.elapsed()));
+        if fast && entered {
+            self.skipped_fast.fetch_add(1, Ordering::Relaxed);
         }
 
-        // Cache miss (or reentrant bypass): call the wrapped function (no lock held)
-        let result = self.fn_obj.bind(py).call(args, kwargs.as_ref())?.unbind();
+        // Only populate the cache for non-reentrant misses that took long
+        // enough, and never under a key with a NaN in it, which no later call
+        // could match.
+        if entered && !fast && !args_contain_nan(&args, &kwargs)? {
+            let tags = match &self.tags_func {
+                Some(tags_func) => call_tags_func(py, tags_func, &args, &kwargs, result.bind(py))?,
+                None => Vec::new(),
+            };
 
-        // Only populate the cache for non-reentrant misses.
-        if entered {
             // NOW create a CacheKey since we need to store it.
             let cache_key = match _key_owner {
-                Some(obj) => CacheKey::with_hash(hash, obj),
+                Some(obj) => self.stored_key(py, hash, obj),
                 None => {
-                    // No-kwargs path: incref the args tuple for storage
+                    // No-kwargs path: incref the args tuple (or its lone
+                    // item) for storage
                     let obj: Py<PyAny> = unsafe {
                         ffi::Py_IncRef(key_ptr);
                         Bound::from_owned_ptr(py, key_ptr).unbind()
                     };
-                    CacheKey::with_hash(hash, obj)
+                    self.stored_key(py, hash, obj)
                 }
             };
 
@@ -526,13 +812,13 @@ impl CachedFunction {
             // call returns from the `None` arm above), so try_enter succeeds;
             // the `if let` is a defensive guard.
             if let Some(_enter) = self.try_enter() {
-                let mut shard = self.shards[shard_idx].write();
+                let mut shard = self.shards[shard_idx].write_py_attached(py);
 
                 // Double-check: another thread may have inserted while we were computing
                 let needs_insert = match shard.map.get(&cache_key) {
                     Some(entry) => {
                         if let Some(ttl) = self.ttl {
-                            entry.created_at.elapsed() > ttl
+                            entry.age() > ttl
                         } else {
                             false
                         }
@@ -542,13 +828,11 @@ impl CachedFunction {
 
                 // The double-check lookup runs __eq__ too; if it raised, propagate
                 // rather than inserting and returning with an exception pending (#36).
-                if let Some(err) = PyErr::take(py) {
-                    return Err(err);
-                }
+                take_compare_error(py)?;
 
                 if needs_insert {
                     // Remove expired entry from map if present (order cleaned lazily)
-                    shard.map.remove(&cache_key);
+                    shard.remove(&cache_key);
 
                     // Evict if at capacity
                     while shard.map.len() >= shard.capacity {
@@ -561,16 +845,166 @@ impl CachedFunction {
                     let entry = SieveEntry {
                         value: result.clone_ref(py),
                         created_at: Instant::now(),
+                        refreshed_nanos: AtomicU64::new(0),
                         visited: AtomicBool::new(false),
+                        tags,
                     };
-                    shard.map.insert(cache_key.clone(), entry);
+                    shard.insert(py, cache_key.clone_with(py), entry);
                     shard.order.push_back(cache_key);
                 }
+                // Removing, evicting and inserting compare keys as well.
+                take_compare_error(py)?;
             }
         }
 
         self.misses.fetch_add(1, Ordering::Relaxed);
-        Ok(result)
+        Ok((result, false))
+    }
+
+    /// Mark this function active for one borrow region. Returns `None` if it is
+    /// already active (reentrant call / GIL handed off mid-`__eq__`), in which
+    /// case the caller must bypass the cache. The returned guard clears the
+    /// marker on drop. See issue #30.
+    #[cfg(not(Py_GIL_DISABLED))]
+    #[inline(always)]
+    fn try_enter(&self) -> Option<EnterGuard<'_>> {
+        let p = self.reentry.0.get();
+        // SAFETY: GIL held; access serialized. No reference is held across any
+        // Python call — we read then write a bool by value.
+        unsafe {
+            if *p {
+                None
+            } else {
+                *p = true;
+                Some(EnterGuard(&self.reentry.0))
+            }
+        }
+    }
+
+    #[cfg(Py_GIL_DISABLED)]
+    #[inline]
+    fn try_enter(&self) -> Option<EnterGuard> {
+        // The shards' address, which `ShardSet::after_fork_in_child` looks for.
+        let id = Arc::as_ptr(&self.shards) as usize;
+        ACTIVE.with(|a| {
+            let mut v = a.borrow_mut();
+            if v.contains(&id) {
+                None
+            } else {
+                v.push(id);
+                Some(EnterGuard { id })
+            }
+        })
+    }
+}
+
+#[pymethods]
+impl CachedFunction {
+    #[new]
+    // Arguments left out or None take their defaults from `config`.
+    #[pyo3(signature = (fn_obj, max_size=None, ttl=None, sliding_ttl=None, fallback=false, cache_version=None, tags_func=None, unhashable=None, record_latencies=None, min_compute_time=None, compact_keys=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        fn_obj: Py<PyAny>,
+        max_size: Option<usize>,
+        ttl: Option<f64>,
+        sliding_ttl: Option<bool>,
+        fallback: bool,
+        cache_version: Option<Bound<'_, PyAny>>,
+        tags_func: Option<Py<PyAny>>,
+        unhashable: Option<String>,
+        record_latencies: Option<bool>,
+        min_compute_time: Option<f64>,
+        compact_keys: Option<bool>,
+    ) -> PyResult<Self> {
+        let max_size: usize = config::resolved(py, "max_size", max_size)?;
+        if max_size == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "max_size must be >= 1",
+            ));
+        }
+        let ttl = parse_seconds("ttl", config::configured(py, "ttl", ttl)?)?;
+        let sliding_ttl = config::resolved(py, "sliding_ttl", sliding_ttl)?;
+        let min_compute_time = parse_seconds(
+            "min_compute_time",
+            config::configured(py, "min_compute_time", min_compute_time)?,
+        )?;
+        let cache_version = config::configured(py, "cache_version", cache_version)?;
+        let cache_version = parse_cache_version(cache_version.as_ref())?
+            .map(|version| PyString::new(py, &version).unbind());
+        let tags_func = config::configured(py, "tags_func", tags_func)?;
+        check_tags_func(py, tags_func.as_ref())?;
+        // A shared cache's in-process stand-in keys unhashable arguments as
+        // the shared cache would have, unless told otherwise.
+        let unhashable = config::configured(py, "unhashable", unhashable)?;
+        let unhashable = parse_unhashable(unhashable.as_deref().unwrap_or(if fallback {
+            "serialize"
+        } else {
+            "error"
+        }))?;
+        let record_latencies: bool = config::resolved(py, "record_latencies", record_latencies)?;
+        let compact_keys = config::resolved(py, "compact_keys", compact_keys)?;
+        let n_shards = (max_size / MIN_SHARD_SIZE)
+            .clamp(1, MAX_SHARDS)
+            .next_power_of_two()
+            .min(MAX_SHARDS);
+        let per_shard = max_size.div_ceil(n_shards);
+
+        #[cfg(Py_GIL_DISABLED)]
+        let shards = {
+            let set = Arc::new(ShardSet {
+                locks: (0..n_shards)
+                    .map(|_| ForkLock::new(parking_lot::RwLock::new(Shard::new(per_shard))))
+                    .collect(),
+                per_shard,
+            });
+            fork::register(py, Arc::downgrade(&set) as Weak<dyn AfterFork>)?;
+            set
+        };
+
+        #[cfg(not(Py_GIL_DISABLED))]
+        let shards: Shards = (0..n_shards)
+            .map(|_| GilCell::new(Shard::new(per_shard)))
+            .collect();
+
+        Ok(CachedFunction {
+            fn_obj,
+            shards,
+            shard_mask: n_shards - 1,
+            ttl,
+            sliding_ttl,
+            max_size,
+            fallback,
+            cache_version,
+            tags_func,
+            unhashable,
+            compact_keys,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            uncacheable_calls: AtomicU64::new(0),
+            min_compute_time,
+            skipped_fast: AtomicU64::new(0),
+            latencies: record_latencies.then(Latencies::new),
+            #[cfg(not(Py_GIL_DISABLED))]
+            reentry: ReentryCell::new(),
+        })
+    }
+
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__<'py>(
+        &self,
+        py: Python<'py>,
+        args: Bound<'py, PyTuple>,
+        kwargs: Option<Bound<'py, PyDict>>,
+    ) -> PyResult<Py<PyAny>> {
+        let Some(latencies) = &self.latencies else {
+            return self.call_cached(py, args, kwargs).map(|(value, _)| value);
+        };
+        let start = Instant::now();
+        let (value, hit) = self.call_cached(py, args, kwargs)?;
+        latencies.record(hit, start.elapsed());
+        Ok(value)
     }
 
     /// Cache lookup only. Returns the cached value or None on miss.
@@ -581,22 +1015,27 @@ impl CachedFunction {
         args: Bound<'py, PyTuple>,
         kwargs: Option<Bound<'py, PyDict>>,
     ) -> PyResult<Option<Py<PyAny>>> {
-        let (hash, key_ptr, _key_owner) = Self::hash_args(py, &args, &kwargs)?;
+        let Some((hash, key_ptr, _key_owner)) = self.hash_args(py, &args, &kwargs)? else {
+            return Ok(None);
+        };
         let borrowed = BorrowedArgs { hash, ptr: key_ptr };
         let shard_idx = hash as usize & self.shard_mask;
 
         // Reentrant calls bypass (report miss) rather than take a second guard.
         if let Some(_enter) = self.try_enter() {
-            let shard = self.shards[shard_idx].read();
+            let shard = self.shards[shard_idx].read_py_attached(py);
             if let Some(entry) = shard.map.get(&borrowed) {
                 if let Some(ttl) = self.ttl {
-                    if entry.created_at.elapsed() > ttl {
+                    if entry.age() > ttl {
                         drop(shard);
                         self.misses.fetch_add(1, Ordering::Relaxed);
                         return Ok(None);
                     }
+                    if self.sliding_ttl {
+                        entry.refresh_age();
+                    }
                 }
-                entry.visited.store(true, Ordering::Relaxed);
+                entry.mark_visited();
                 let val = entry.value.clone_ref(py);
                 drop(shard);
                 self.hits.fetch_add(1, Ordering::Relaxed);
@@ -605,9 +1044,7 @@ impl CachedFunction {
         }
 
         // A raising __eq__ during the probe leaves the exception set (#36).
-        if let Some(err) = PyErr::take(py) {
-            return Err(err);
-        }
+        take_compare_error(py)?;
 
         self.misses.fetch_add(1, Ordering::Relaxed);
         Ok(None)
@@ -621,22 +1058,29 @@ impl CachedFunction {
         args: Bound<'py, PyTuple>,
         kwargs: Option<Bound<'py, PyDict>>,
     ) -> PyResult<(bool, Py<PyAny>)> {
-        let (hash, key_ptr, _key_owner) = Self::hash_args(py, &args, &kwargs)?;
+        // The async wrapper's lookup, so an uncacheable call counts here.
+        let Some((hash, key_ptr, _key_owner)) = self.hash_args(py, &args, &kwargs)? else {
+            self.uncacheable_calls.fetch_add(1, Ordering::Relaxed);
+            return Ok((false, py.None()));
+        };
         let borrowed = BorrowedArgs { hash, ptr: key_ptr };
         let shard_idx = hash as usize & self.shard_mask;
 
         // Reentrant calls bypass (report miss).
         if let Some(_enter) = self.try_enter() {
-            let shard = self.shards[shard_idx].read();
+            let shard = self.shards[shard_idx].read_py_attached(py);
             if let Some(entry) = shard.map.get(&borrowed) {
                 if let Some(ttl) = self.ttl {
-                    if entry.created_at.elapsed() > ttl {
+                    if entry.age() > ttl {
                         drop(shard);
                         self.misses.fetch_add(1, Ordering::Relaxed);
                         return Ok((false, py.None()));
                     }
+                    if self.sliding_ttl {
+                        entry.refresh_age();
+                    }
                 }
-                entry.visited.store(true, Ordering::Relaxed);
+                entry.mark_visited();
                 let val = entry.value.clone_ref(py);
                 drop(shard);
                 self.hits.fetch_add(1, Ordering::Relaxed);
@@ -645,31 +1089,62 @@ impl CachedFunction {
         }
 
         // A raising __eq__ during the probe leaves the exception set (#36).
-        if let Some(err) = PyErr::take(py) {
-            return Err(err);
-        }
+        take_compare_error(py)?;
 
         self.misses.fetch_add(1, Ordering::Relaxed);
         Ok((false, py.None()))
     }
 
     /// Store a value in the cache for the given arguments.
-    #[pyo3(signature = (value, *args, **kwargs))]
+    ///
+    /// `_warp_tags` tags the entry for `invalidate_tag`, in place of what
+    /// `tags_func` would return; it is not part of the key.
+    ///
+    /// `_warp_compute_time` is how many seconds `value` took to compute, for
+    /// a caller that ran the function itself (the async wrapper): under
+    /// `min_compute_time` it is counted in `skipped_fast` and not stored. An
+    /// explicit `set()` without it always stores.
+    #[pyo3(signature = (value, *args, _warp_tags=None, _warp_compute_time=None, **kwargs))]
     fn set<'py>(
         &self,
         py: Python<'py>,
         value: Py<PyAny>,
         args: Bound<'py, PyTuple>,
+        _warp_tags: Option<Bound<'py, PyAny>>,
+        _warp_compute_time: Option<f64>,
         kwargs: Option<Bound<'py, PyDict>>,
     ) -> PyResult<()> {
-        let cache_key = Self::make_key(py, &args, &kwargs)?;
+        let elapsed = _warp_compute_time.and_then(|t| Duration::try_from_secs_f64(t).ok());
+        if too_fast(self.min_compute_time, elapsed) {
+            self.skipped_fast.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+        let tags = match (&_warp_tags, &self.tags_func) {
+            (Some(tags), _) => parse_tags(tags)?,
+            (None, Some(tags_func)) => {
+                call_tags_func(py, tags_func, &args, &kwargs, value.bind(py))?
+            }
+            (None, None) => Vec::new(),
+        };
+        // Not stored under a key with a NaN in it, as for a call.
+        if args_contain_nan(&args, &kwargs)? {
+            return Ok(());
+        }
+        let Some(cache_key) = self.make_key(py, &args, &kwargs)? else {
+            return Ok(());
+        };
         let shard_idx = cache_key.shard_index(self.shard_mask);
 
         // Reentrant calls skip the store rather than take a second guard.
         if let Some(_enter) = self.try_enter() {
-            let mut shard = self.shards[shard_idx].write();
+            let mut shard = self.shards[shard_idx].write_py_attached(py);
+
+            // Decide between insert and update only on a lookup that didn't
+            // raise, or a raising __eq__ would look like a new key (#36).
+            let exists = shard.map.get(&cache_key).is_some();
+            take_compare_error(py)?;
 
-            if shard.map.get(&cache_key).is_none() {
+            if !exists {
                 // New key: evict if needed, then insert
                 while shard.map.len() >= shard.capacity {
                     Self::evict_one(&mut shard);
@@ -680,25 +1155,56 @@ impl CachedFunction {
                 let entry = SieveEntry {
                     value: value.clone_ref(py),
                     created_at: Instant::now(),
+                    refreshed_nanos: AtomicU64::new(0),
                     visited: AtomicBool::new(false),
+                    tags,
                 };
-                shard.map.insert(cache_key.clone(), entry);
+                shard.insert(py, cache_key.clone_with(py), entry);
                 shard.order.push_back(cache_key);
             } else {
                 // Existing key: update value in place
                 let entry = SieveEntry {
                     value: value.clone_ref(py),
                     created_at: Instant::now(),
+                    refreshed_nanos: AtomicU64::new(0),
                     visited: AtomicBool::new(false),
+                    tags,
                 };
-                shard.map.insert(cache_key, entry);
+                shard.remove(&cache_key);
+                shard.insert(py, cache_key, entry);
             }
+            take_compare_error(py)?;
         }
 
         Ok(())
     }
 
-    fn cache_info(&self) -> CacheInfo {
+    /// Remove every entry tagged `tag`, by `_warp_tags` or `tags_func`.
+    /// Returns how many were removed.
+    fn invalidate_tag(&self, py: Python<'_>, tag: &str) -> PyResult<usize> {
+        // If reentrant, skip rather than alias the outer borrow.
+        let Some(_enter) = self.try_enter() else {
+            return Ok(0);
+        };
+
+        let mut removed = 0;
+        for shard in self.shards.iter() {
+            let mut s = shard.write_py_attached(py);
+            if let Some(keys) = s.tags.remove(tag) {
+                for key in &keys {
+                    if s.remove(key).is_some() {
+                        removed += 1;
+                    }
+                }
+            }
+        }
+
+        // A raising __eq__ while matching keys leaves the exception set (#36).
+        take_compare_error(py)?;
+        Ok(removed)
+    }
+
+    fn cache_info(&self, py: Python<'_>) -> CacheInfo {
         // If reentrant, we cannot safely read the shards; report counters only.
         let _enter = match self.try_enter() {
             Some(g) => g,
@@ -708,23 +1214,41 @@ impl CachedFunction {
                     misses: self.misses.load(Ordering::Relaxed),
                     max_size: self.max_size,
                     current_size: 0,
+                    backend: self.backend_name(),
+                    uncacheable_calls: self.uncacheable_calls.load(Ordering::Relaxed),
+                    skipped_fast: self.skipped_fast.load(Ordering::Relaxed),
                 };
             }
         };
 
         let mut current_size = 0;
         for shard in self.shards.iter() {
-            current_size += shard.read().map.len();
+            current_size += shard.read_py_attached(py).map.len();
         }
         CacheInfo {
             hits: self.hits.load(Ordering::Relaxed),
             misses: self.misses.load(Ordering::Relaxed),
             max_size: self.max_size,
             current_size,
+            backend: self.backend_name(),
+            uncacheable_calls: self.uncacheable_calls.load(Ordering::Relaxed),
+            skipped_fast: self.skipped_fast.load(Ordering::Relaxed),
         }
     }
 
-    fn cache_clear(&self) {
+    /// `cache_info()` as a flat dict of metric names to numbers, for exporters
+    /// such as Prometheus: `hits`, `misses`, `hit_rate`, `current_size`,
+    /// `max_size`, `uncacheable_calls` and `skipped_fast`. The names are
+    /// stable.
+    fn metrics<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.cache_info(py).metrics(py)
+    }
+
+    /// `release_memory` is for the shared backend; the entries dropped here
+    /// are freed either way.
+    #[pyo3(signature = (release_memory=false))]
+    fn cache_clear(&self, py: Python<'_>, release_memory: bool) {
+        let _ = release_memory;
         // If reentrant, skip rather than alias the outer borrow.
         let _enter = match self.try_enter() {
             Some(g) => g,
@@ -732,12 +1256,44 @@ impl CachedFunction {
         };
 
         for shard in self.shards.iter() {
-            let mut s = shard.write();
+            let mut s = shard.write_py_attached(py);
             s.map.clear();
             s.order.clear();
+            s.tags.clear();
             s.hand = 0;
         }
+        self.reset_stats(false);
+    }
+
+    /// Zero the counters in `cache_info()` and the latency histograms, keeping
+    /// the cached entries. `local_only` is for the shared backend; every
+    /// counter here is local.
+    #[pyo3(signature = (local_only=false))]
+    fn reset_stats(&self, local_only: bool) {
+        let _ = local_only;
         self.hits.store(0, Ordering::Relaxed);
         self.misses.store(0, Ordering::Relaxed);
+        self.uncacheable_calls.store(0, Ordering::Relaxed);
+        self.skipped_fast.store(0, Ordering::Relaxed);
+        if let Some(latencies) = &self.latencies {
+            latencies.reset();
+        }
+    }
+
+    /// With `record_latencies`, the hit and miss latency histograms of calls
+    /// (see `Latencies::to_dict`); otherwise None.
+    fn latency_histogram<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        self.latencies
+            .as_ref()
+            .map(|latencies| latencies.to_dict(py))
+            .transpose()
+    }
+
+    /// Record one call's latency from outside `__call__` (the async wrapper,
+    /// which times its own calls). A no-op without `record_latencies`.
+    fn _record_latency(&self, hit: bool, nanos: u64) {
+        if let Some(latencies) = &self.latencies {
+            latencies.record(hit, Duration::from_nanos(nanos));
+        }
     }
 }
@@ -1,11 +1,10 @@
 import asyncio
-import sys
 import threading
 import time
 
 import pytest
 
-from warp_cache import cache
+from warp_cache import cache, configure
 from warp_cache._decorator import AsyncCachedFunction
 
 # ── Basic hit/miss ────────────────────────────────────────────────────────
@@ -144,7 +143,6 @@ async def test_async_eviction():
 # ── Shared backend ───────────────────────────────────────────────────────
 
 
-@pytest.mark.skipif(sys.platform == "win32", reason="shared memory is Unix-only")
 @pytest.mark.asyncio
 async def test_async_shared_backend():
     call_count = 0
@@ -164,6 +162,31 @@ async def test_async_shared_backend():
     assert call_count == 1
 
 
+@pytest.mark.asyncio
+async def test_async_unhashable_arguments():
+    """Arguments that don't hash skip single-flight but are still cached
+    wherever the backend can key them."""
+    calls = []
+
+    async def total(xs):
+        calls.append(xs)
+        return sum(xs)
+
+    shared = cache(max_size=16, backend="shared")(total)
+    shared.cache_clear()
+    assert await shared([1, 2]) == await shared([1, 2]) == 3
+    assert len(calls) == 1
+
+    serialize = cache(max_size=16, unhashable="serialize")(total)
+    assert await serialize([3]) == await serialize([3]) == 3
+    assert len(calls) == 2
+
+    bypass = cache(max_size=16, unhashable="bypass")(total)
+    assert await bypass([4]) == await bypass([4]) == 4
+    assert len(calls) == 4
+    assert bypass.cache_info().uncacheable_calls == 2
+
+
 # ── Repr / attributes ────────────────────────────────────────────────────
 
 
@@ -224,7 +247,6 @@ async def test_async_none_return_value():
     assert info.misses == 1
 
 
-@pytest.mark.skipif(sys.platform == "win32", reason="shared memory is Unix-only")
 @pytest.mark.asyncio
 async def test_async_none_return_value_shared():
     """Verify that async functions returning None are cached with shared backend."""
@@ -387,7 +409,39 @@ def test_async_single_flight_across_event_loops():
     assert results == {"A": [10, 10, 10], "B": [10, 10, 10]}
 
 
-@pytest.mark.skipif(sys.platform == "win32", reason="shared memory is Unix-only")
+@pytest.mark.asyncio
+async def test_async_min_compute_time():
+    calls = []
+
+    @cache(max_size=16, min_compute_time=0.02)
+    async def compute(x):
+        calls.append(x)
+        if x == "slow":
+            await asyncio.sleep(0.03)
+        return x
+
+    assert await compute("fast") == "fast" and await compute("fast") == "fast"
+    assert await compute("slow") == "slow" and await compute("slow") == "slow"
+    assert calls == ["fast", "fast", "slow"]
+    info = compute.cache_info()
+    assert (info.hits, info.misses, info.skipped_fast) == (1, 3, 2)
+
+
+@pytest.mark.asyncio
+async def test_async_configured_min_compute_time():
+    try:
+        configure(min_compute_time=0.02)
+
+        @cache(max_size=16)
+        async def compute(x):
+            return x
+
+    finally:
+        configure(min_compute_time=None)
+    assert await compute("fast") == "fast" and await compute("fast") == "fast"
+    assert compute.cache_info().skipped_fast == 2
+
+
 @pytest.mark.asyncio
 async def test_async_single_flight_shared():
     """Single-flight works with the shared backend too."""
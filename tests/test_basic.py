@@ -1,4 +1,11 @@
-from warp_cache import cache
+import inspect
+import time
+import warnings
+
+import pytest
+
+from warp_cache import cache, configure, get_config
+from warp_cache._warp_cache_rs import CachedFunction
 
 
 def test_basic_hit_miss():
@@ -80,6 +87,38 @@ def test_kwargs():
     assert call_count == 2
 
 
+def test_kwargs_key_does_not_collide_with_positional():
+    """f(x=1) must not share an entry with the positional call that passes
+    what used to be its key, (args, sorted kwargs items)."""
+
+    @cache(max_size=128)
+    def f(*args, **kwargs):
+        return (args, kwargs)
+
+    assert f(x=1) == ((), {"x": 1})
+    assert f((), (("x", 1),)) == (((), (("x", 1),)), {})
+    assert f.cache_info().misses == 2
+
+
+def test_lone_argument_key_does_not_collide_with_a_tuple():
+    """f(x) is keyed on x itself, which must not share an entry with a call
+    whose args tuple equals it, nor stop equal arguments sharing one."""
+
+    @cache(max_size=128)
+    def f(*args):
+        return args
+
+    assert f(1) == (1,)
+    assert f((1,)) == ((1,),)
+    assert f((1, 2)) == ((1, 2),)
+    assert f(1, 2) == (1, 2)
+    assert f.cache_info().misses == 4
+
+    assert f(1.0) == (1,)
+    assert f((1, 2)) == ((1, 2),)
+    assert f.cache_info().hits == 2
+
+
 def test_eviction_at_capacity():
     @cache(max_size=3)
     def identity(x):
@@ -95,3 +134,233 @@ def test_eviction_at_capacity():
     identity(4)
     info = identity.cache_info()
     assert info.current_size == 3
+
+
+def test_invalid_max_size_and_ttl_raise():
+    import pytest
+
+    with pytest.raises(ValueError, match="max_size must be >= 1"):
+        cache(max_size=0)(lambda x: x)
+    for ttl in (0, -5, float("nan"), float("inf"), 1e30):
+        with pytest.raises(ValueError, match="ttl must be a positive number"):
+            cache(max_size=16, ttl=ttl)(lambda x: x)
+
+
+def test_cache_version_bump_misses():
+    import pytest
+
+    calls = []
+
+    def fn(x, y=0):
+        calls.append(x)
+        return x + y
+
+    v1 = cache(max_size=16, cache_version=1)(fn)
+    v2 = cache(max_size=16, cache_version=2)(fn)
+    assert v1(1) == v2(1) == 1
+    assert v1(1, y=2) == v2(1, y=2) == 3
+    assert calls == [1, 1, 1, 1]
+    assert v2.cache_info().hits == 0
+    assert v1(1) == 1 and v1.cache_info().hits == 1
+    with pytest.raises(TypeError, match="cache_version"):
+        cache(max_size=16, cache_version=1.5)(fn)
+
+
+def test_invalidate_tag():
+    import pytest
+
+    calls = []
+
+    @cache(max_size=64, tags_func=lambda args, kwargs, result: [f"user:{args[0]}"])
+    def order(user, n):
+        calls.append((user, n))
+        return user * 100 + n
+
+    for n in range(3):
+        order(42, n)
+    order(7, 0)
+    order.set(-1, 7, 1, _warp_tags=["user:42"])
+    assert order.invalidate_tag("user:42") == 4
+    assert order.invalidate_tag("user:42") == 0
+    assert order.cache_info().current_size == 1
+    assert order(7, 0) == 700 and order.get(7, 1) is None
+    assert order(42, 0) == 4200 and len(calls) == 5
+
+    # Overwriting an entry replaces its tags.
+    order.set(1, 7, 0, _warp_tags=[])
+    assert order.invalidate_tag("user:7") == 0
+    with pytest.raises(TypeError, match="not a str"):
+        order.set(1, 7, 0, _warp_tags="user:7")
+
+
+
+def test_unhashable_arguments():
+    import pytest
+
+    calls = []
+
+    def total(xs, opts=None):
+        calls.append(xs)
+        return sum(xs)
+
+    strict = cache(max_size=16)(total)
+    with pytest.raises(TypeError, match="unhashable"):
+        strict([1, 2])
+
+    bypass = cache(max_size=16, unhashable="bypass")(total)
+    assert bypass([1, 2]) == bypass([1, 2]) == 3
+    assert bypass({1, 2}) == 3
+    assert bypass((1, 2), opts={"a": 1}) == 3
+    assert bypass((1, 2)) == bypass((1, 2)) == 3
+    assert len(calls) == 5
+    info = bypass.cache_info()
+    assert (info.hits, info.misses, info.uncacheable_calls) == (1, 1, 4)
+    assert "uncacheable_calls=4" in repr(info)
+    assert bypass.get([1, 2]) is None
+    bypass.set(0, [1, 2])
+    assert bypass.cache_info().current_size == 1
+
+    calls.clear()
+    serialize = cache(max_size=16, unhashable="serialize")(total)
+    assert serialize([1, 2]) == serialize([1, 2]) == 3
+    assert serialize((1, 2)) == 3  # a tuple isn't equal to the list
+    assert serialize({2, 1}) == serialize(frozenset({1, 2})) == 3  # but a set is
+    assert serialize([3], opts={"b": [1], "a": {2}}) == 3
+    assert serialize([3], opts={"a": {2}, "b": [1]}) == 3
+    assert serialize([3], opts={"a": {2}, "b": (1,)}) == 3
+    assert len(calls) == 5
+    assert serialize.cache_info().uncacheable_calls == 0
+
+    serialize.set(10, [4, 5])
+    assert serialize.get([4, 5]) == 10
+    assert serialize.get((4, 5)) is None
+
+    with pytest.raises(ValueError, match="unhashable"):
+        cache(max_size=16, unhashable="sometimes")
+
+
+def test_shared_only_arguments_warn_with_the_memory_backend():
+    with warnings.catch_warnings(record=True) as caught:
+        warnings.simplefilter("always")
+        cache(max_key_size=64, persistent=False, checksums=True)(lambda x: x)
+        cache(allow_insecure=True, sync_interval=1.0, compress_threshold=64)(lambda x: x)
+    assert [str(w.message) for w in caught] == [
+        f"{name} has no effect with the memory backend"
+        for name in (
+            "max_key_size",
+            "checksums",
+            "allow_insecure",
+            "sync_interval",
+            "compress_threshold",
+        )
+    ]
+
+
+def test_configure_sets_defaults_for_later_caches():
+    def f(x):
+        return x
+
+    try:
+        configure(max_size=2, ttl=60)
+        assert get_config()["max_size"] == 2
+        configured = cache()(f)
+        explicit = cache(max_size=5)(f)
+        positional = cache(7)(f)
+        assert configured.cache_info().max_size == 2
+        assert explicit.cache_info().max_size == 5
+        assert positional.cache_info().max_size == 7
+
+        # Existing caches keep what they were made with.
+        configure(max_size=3)
+        assert configured.cache_info().max_size == 2
+        assert cache()(f).cache_info().max_size == 3
+        assert get_config()["ttl"] == 60
+
+        with pytest.raises(TypeError, match="max_sise"):
+            configure(max_sise=4)
+    finally:
+        configure(max_size=None, ttl=None)
+    assert get_config()["max_size"] == 128 and get_config()["ttl"] is None
+    assert cache()(f).cache_info().max_size == 128
+
+
+def test_configure_takes_default_prefixed_names():
+    def f(x):
+        return x
+
+    assert list(get_config()) == list(inspect.signature(cache).parameters)
+    try:
+        configure(default_strategy="memory", default_max_size=4, default_ttl=60)
+        config = get_config()
+        assert (config["backend"], config["max_size"], config["ttl"]) == ("memory", 4, 60)
+        assert cache()(f).cache_info().max_size == 4
+        assert cache(max_size=6)(f).cache_info().max_size == 6
+
+        with pytest.raises(TypeError, match="default_max_sise"):
+            configure(default_max_sise=4)
+    finally:
+        configure(default_strategy=None, default_max_size=None, default_ttl=None)
+    assert get_config()["max_size"] == 128 and get_config()["ttl"] is None
+
+
+def test_cached_function_reads_the_configured_defaults():
+    def f(x):
+        return x
+
+    try:
+        configure(max_size=3, unhashable="bypass")
+        configured = CachedFunction(f)
+        assert configured.cache_info().max_size == 3
+        assert configured([1]) == [1]
+        assert configured.cache_info().uncacheable_calls == 1
+
+        # Arguments passed explicitly win.
+        explicit = CachedFunction(f, 9, unhashable="error")
+        assert explicit.cache_info().max_size == 9
+        with pytest.raises(TypeError):
+            explicit([1])
+
+        configure(max_size="many")
+        with pytest.raises(TypeError, match="default for max_size"):
+            CachedFunction(f)
+    finally:
+        configure(max_size=None, unhashable=None)
+    assert CachedFunction(f).cache_info().max_size == 128
+
+
+def test_configured_shared_only_defaults_dont_warn_on_the_memory_backend():
+    try:
+        configure(oversize="warn", fallback="local", max_value_size=1 << 16)
+        with warnings.catch_warnings():
+            warnings.simplefilter("error")
+            assert cache()(lambda x: x)(1) == 1
+    finally:
+        configure(oversize=None, fallback=None, max_value_size=None)
+
+
+def test_min_compute_time_stores_only_slow_results():
+    calls = []
+
+    @cache(max_size=16, min_compute_time=0.02)
+    def compute(x):
+        calls.append(x)
+        if x == "slow":
+            time.sleep(0.03)
+        return x
+
+    assert compute("fast") == "fast" and compute("fast") == "fast"
+    assert compute("slow") == "slow" and compute("slow") == "slow"
+    assert calls == ["fast", "fast", "slow"]
+    info = compute.cache_info()
+    assert (info.hits, info.misses, info.skipped_fast, info.current_size) == (1, 3, 2, 1)
+    assert "skipped_fast=2" in repr(info)
+    assert compute.metrics()["skipped_fast"] == 2
+
+    # An explicit set() stores however quick the value was to get.
+    compute.set("stored", "fast")
+    assert compute("fast") == "stored" and calls == ["fast", "fast", "slow"]
+
+    compute.reset_stats()
+    assert compute.cache_info().skipped_fast == 0
+    with pytest.raises(ValueError, match="min_compute_time"):
+        cache(min_compute_time=0)(lambda: None)
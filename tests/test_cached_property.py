@@ -0,0 +1,136 @@
+import gc
+import threading
+import time
+import weakref
+
+import pytest
+
+from warp_cache import CachedProperty, cached_property
+
+
+class Value:
+    pass
+
+
+def test_computed_once_per_instance():
+    calls = []
+
+    class Point:
+        def __init__(self, x):
+            self.x = x
+
+        @cached_property
+        def norm(self):
+            """The point's distance from 0."""
+            calls.append(self.x)
+            return abs(self.x)
+
+    a, b = Point(3), Point(-4)
+    assert (a.norm, a.norm, b.norm, b.norm) == (3, 3, 4, 4)
+    assert calls == [3, -4]
+
+    prop = Point.norm
+    assert isinstance(prop, CachedProperty)
+    assert prop.attrname == "norm"
+    assert prop.__name__ == "norm" and prop.__doc__ == "The point's distance from 0."
+    info = prop.cache_info()
+    assert (info.hits, info.misses, info.current_size, info.max_size) == (2, 2, 2, 0)
+    assert prop.metrics()["hits"] == 2 and prop.metrics()["current_size"] == 2
+
+    prop.reset_stats()
+    info = prop.cache_info()
+    assert (info.hits, info.misses, info.current_size) == (0, 0, 2)
+
+    prop.cache_clear()
+    assert prop.cache_info().current_size == 0
+    assert a.norm == 3 and calls == [3, -4, 3]
+
+
+def test_instance_going_out_of_scope_releases_its_value():
+    class Holder:
+        @cached_property
+        def value(self):
+            return Value()
+
+    holder = Holder()
+    value = weakref.ref(holder.value)
+    assert Holder.value.cache_info().current_size == 1
+
+    del holder
+    gc.collect()
+    assert value() is None
+    assert Holder.value.cache_info().current_size == 0
+
+    # A new instance, perhaps at the same address, starts empty.
+    assert isinstance(Holder().value, Value)
+    assert Holder.value.cache_info().misses == 2
+
+
+def test_ttl_expiry():
+    calls = 0
+
+    class Clock:
+        @cached_property(ttl=0.05)
+        def reading(self):
+            nonlocal calls
+            calls += 1
+            return calls
+
+    clock = Clock()
+    assert clock.reading == 1 and clock.reading == 1
+    time.sleep(0.1)
+    assert clock.reading == 2 and clock.reading == 2
+
+
+def test_max_size_bounds_the_instances_kept():
+    class Item:
+        @cached_property(max_size=2)
+        def value(self):
+            return Value()
+
+    items = [Item() for _ in range(5)]
+    assert all(isinstance(item.value, Value) for item in items)
+    info = Item.value.cache_info()
+    assert (info.current_size, info.max_size, info.misses) == (2, 2, 5)
+
+    with pytest.raises(ValueError, match="max_size"):
+        cached_property(max_size=0)(lambda self: None)
+
+
+def test_threads_racing_on_first_access_get_one_value():
+    barrier = threading.Barrier(8)
+
+    class Slow:
+        @cached_property
+        def value(self):
+            time.sleep(0.01)
+            return Value()
+
+    slow = Slow()
+    results = []
+
+    def worker():
+        barrier.wait()
+        results.append(slow.value)
+
+    threads = [threading.Thread(target=worker) for _ in range(8)]
+    for t in threads:
+        t.start()
+    for t in threads:
+        t.join()
+    assert len(results) == 8
+    assert all(result is results[0] for result in results)
+    assert slow.value is results[0]
+    assert Slow.value.cache_info().current_size == 1
+
+
+def test_instances_without_weak_references_raise():
+    class Slotted:
+        __slots__ = ()
+
+        @cached_property
+        def value(self):
+            return 1
+
+    with pytest.raises(TypeError, match="__weakref__"):
+        _ = Slotted().value
@@ -2,6 +2,7 @@
 
 import contextlib
 import glob
+import math
 import os
 import sys
 import tempfile
@@ -11,7 +12,6 @@ import pytest
 
 from warp_cache import cache
 
-_skip_on_windows = pytest.mark.skipif(sys.platform == "win32", reason="shared memory is Unix-only")
 
 # ---------------------------------------------------------------------------
 # Helpers
@@ -34,7 +34,8 @@ class User:
 
 def _cleanup_shm():
     tmpdir = tempfile.gettempdir()
-    shm_dir = os.path.join(tmpdir, f"warp_cache-{os.getuid()}")
+    user_dir = "warp_cache" if sys.platform == "win32" else f"warp_cache-{os.getuid()}"
+    shm_dir = os.path.join(tmpdir, user_dir)
     if os.path.isdir(shm_dir):
         for f in glob.glob(os.path.join(shm_dir, "*")):
             with contextlib.suppress(OSError):
@@ -249,7 +250,6 @@ class TestMemoryComplexKeys:
 # ===========================================================================
 
 
-@_skip_on_windows
 class TestSharedComplexValues:
     def setup_method(self):
         _cleanup_shm()
@@ -337,7 +337,6 @@ class TestSharedComplexValues:
         assert call_count == 1
 
 
-@_skip_on_windows
 class TestSharedComplexKeys:
     def setup_method(self):
         _cleanup_shm()
@@ -432,3 +431,139 @@ class TestSharedComplexKeys:
         assert f(key) == key
         assert f(key) == key
         assert call_count == 1
+
+
+# ===========================================================================
+# Float edge cases in keys — the same rule on both backends
+# ===========================================================================
+
+
+class TestFloatKeys:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    @pytest.mark.parametrize("backend", ["memory", "shared"])
+    def test_signed_zeros_share_an_entry(self, backend):
+        calls = []
+
+        @cache(max_size=16, backend=backend)
+        def f(x):
+            calls.append(x)
+            return "zero"
+
+        assert f(0.0) == "zero"
+        assert f(-0.0) == "zero"
+        assert f((1, -0.0)) == f((1, 0.0)) == "zero"
+        assert f(complex(1, -0.0)) == f(complex(1, 0.0)) == "zero"
+        assert len(calls) == 3
+        assert f.cache_info().hits == 3
+
+    @pytest.mark.parametrize("backend", ["memory", "shared"])
+    def test_nan_key_is_never_cached(self, backend):
+        """NaN never equals itself, so neither backend stores a key holding
+        one, whatever NaN object or bit pattern each call passes."""
+        calls = []
+
+        @cache(max_size=16, backend=backend)
+        def f(*args, **kwargs):
+            calls.append(args)
+            return "computed"
+
+        for _ in range(2):
+            assert f(float("nan")) == "computed"
+            assert f(math.nan) == "computed"
+            assert f(("a", (1, math.nan))) == "computed"
+            assert f(complex(1, math.nan)) == "computed"
+            assert f(1, scale=math.nan) == "computed"
+        assert len(calls) == 10
+        info = f.cache_info()
+        assert (info.hits, info.misses, info.current_size) == (0, 10, 0)
+
+        f.set("stored", math.nan)
+        assert f.cache_info().current_size == 0
+        assert f(1.5) == "computed"
+        assert f(1.5) == "computed"
+        assert len(calls) == 11
+
+
+class TestCompactKeys:
+    """With compact_keys, keys of ints, strs, bytes, None and tuples of them
+    are kept as bytes on the memory backend; lookups must match exactly as
+    they do on objects."""
+
+    @pytest.mark.parametrize("compact_keys", [True, False])
+    def test_equal_keys_of_other_types_still_hit(self, compact_keys):
+        calls = []
+
+        @cache(max_size=16, compact_keys=compact_keys)
+        def f(*args):
+            calls.append(args)
+            return len(calls)
+
+        assert f(1, "a") == 1
+        assert f(1.0, "a") == 1
+        assert f(True, "a") == 1
+        assert f(1, "b") == 2
+        assert f(1, b"a") == 3
+        assert f(2**70, None) == 4
+        assert f(2**70, None) == 4
+
+        # And the other way round: an object key found by a compact lookup.
+        assert f(2.0) == 5
+        assert f(2) == 5
+        assert len(calls) == 5
+
+    def test_str_subclass_is_not_compact(self):
+        class Loose(str):
+            def __eq__(self, other):
+                return str.__eq__(self.lower(), other.lower())
+
+            __hash__ = str.__hash__
+
+        @cache(max_size=16, compact_keys=True)
+        def f(x):
+            return x
+
+        assert f("abc") == "abc"
+        assert f(Loose("abc")) == "abc"
+
+    def test_kwargs_and_tags_on_compact_keys(self):
+        @cache(
+            max_size=16,
+            compact_keys=True,
+            tags_func=lambda args, kwargs, result: [f"user:{args[0]}"],
+        )
+        def f(user, *, page=0):
+            return (user, page)
+
+        assert f(1, page=2) == (1, 2)
+        assert f(1, page=2) == (1, 2)
+        assert f.cache_info().hits == 1
+        assert f.invalidate_tag("user:1") == 1
+        assert f.cache_info().current_size == 0
+
+    def test_million_small_keys(self):
+        """A million int keys keep no Python objects alive: without
+        compact_keys, every entry holds its args tuple (about 80 MB for this
+        loop)."""
+        tracemalloc = pytest.importorskip("tracemalloc")
+
+        @cache(max_size=1_000_000, compact_keys=True)
+        def f(x):
+            return None
+
+        tracemalloc.start()
+        try:
+            for i in range(1_000_000):
+                f(i)
+            current, _ = tracemalloc.get_traced_memory()
+        finally:
+            tracemalloc.stop()
+        assert current < 5_000_000
+        assert f.cache_info().misses == 1_000_000
+        assert f(999_999) is None
+        assert f(999_998) is None
+        assert f.cache_info().hits == 2
@@ -11,37 +11,36 @@ pytest-asyncio) treated it as sync and dropped the returned coroutine.
 
 import asyncio
 import contextlib
+import gc
 import glob
 import inspect
 import os
 import sys
 import tempfile
+import time
 
 import pytest
 
-from warp_cache import cache
+from warp_cache import (
+    aggregate_info,
+    all_caches,
+    cache,
+    cached_property,
+    clear_all,
+    collect_all_metrics,
+)
 
 
 def _cleanup_shm():
-    if not hasattr(os, "getuid"):  # Windows: no shared backend / shm files
-        return
-    shm_dir = os.path.join(tempfile.gettempdir(), f"warp_cache-{os.getuid()}")
+    user_dir = "warp_cache" if sys.platform == "win32" else f"warp_cache-{os.getuid()}"
+    shm_dir = os.path.join(tempfile.gettempdir(), user_dir)
     if os.path.isdir(shm_dir):
         for f in glob.glob(os.path.join(shm_dir, "*")):
             with contextlib.suppress(OSError):
                 os.unlink(f)
 
 
-@pytest.mark.parametrize(
-    "backend",
-    [
-        "memory",
-        pytest.param(
-            "shared",
-            marks=pytest.mark.skipif(sys.platform == "win32", reason="shared memory is Unix-only"),
-        ),
-    ],
-)
+@pytest.mark.parametrize("backend", ["memory", "shared"])
 def test_sync_preserves_introspection(backend):
     """#43: name/qualname/module/doc/__wrapped__ and a resolvable signature."""
     _cleanup_shm()
@@ -95,3 +94,218 @@ def test_async_is_detected_as_coroutine_function():
 
     # And it still returns an awaitable yielding the right value.
     assert asyncio.run(fetch(7)) == 7
+
+
+MEMORY_METRICS = {
+    "hits",
+    "misses",
+    "hit_rate",
+    "current_size",
+    "max_size",
+    "uncacheable_calls",
+    "skipped_fast",
+}
+SHARED_METRICS = {
+    "hits",
+    "misses",
+    "hit_rate",
+    "evictions",
+    "expirations",
+    "current_size",
+    "max_size",
+    "oversize_skips",
+    "lock_timeouts",
+    "skipped_fast",
+    "bytes_used",
+    "bytes_capacity",
+}
+
+
+@pytest.mark.parametrize("backend", ["memory", "shared"])
+def test_metrics_names_and_values(backend):
+    _cleanup_shm()
+    try:
+
+        @cache(max_size=16, backend=backend)
+        def square(x):
+            return x * x
+
+        assert square.metrics()["hit_rate"] == 0.0
+        square(3)
+        square(3)
+        square(3)
+        square(4)
+        metrics = square.metrics()
+        assert set(metrics) == (MEMORY_METRICS if backend == "memory" else SHARED_METRICS)
+        assert (metrics["hits"], metrics["misses"], metrics["current_size"]) == (2, 2, 2)
+        assert metrics["hit_rate"] == 0.5
+        assert metrics["max_size"] == square.cache_info().max_size
+    finally:
+        _cleanup_shm()
+
+
+def test_async_and_fallback_metrics():
+    @cache(max_size=16)
+    async def fetch(x):
+        return x
+
+    asyncio.run(fetch(1))
+    assert set(fetch.metrics()) == MEMORY_METRICS
+    assert fetch.metrics()["misses"] == 1
+
+    def identity(x):
+        return x
+
+    with tempfile.NamedTemporaryFile() as f, pytest.warns(UserWarning):
+        # A directory under a regular file can never be created.
+        bad_dir = os.path.join(f.name, "sub")
+        uncached = cache(backend="shared", shm_dir=bad_dir, fallback="nocache")(identity)
+    uncached(1)
+    assert set(uncached.metrics()) == MEMORY_METRICS
+    assert uncached.metrics()["misses"] == 1
+
+
+def test_collect_all_metrics_tracks_live_caches():
+    @cache(max_size=16)
+    def tracked(x):
+        return x
+
+    @cache(max_size=16)
+    async def tracked_async(x):
+        return x
+
+    tracked(1)
+    name = f"{__name__}.{tracked.__qualname__}"
+    async_name = f"{__name__}.{tracked_async.__qualname__}"
+    collected = collect_all_metrics()
+    assert collected[name] == tracked.metrics()
+    assert collected[name]["misses"] == 1
+    assert async_name in collected
+
+    del tracked, tracked_async
+    gc.collect()
+    collected = collect_all_metrics()
+    assert name not in collected
+    assert async_name not in collected
+
+
+def test_clear_all_resets_every_live_cache():
+    _cleanup_shm()
+    try:
+
+        @cache(max_size=16)
+        def first(x):
+            return x
+
+        earlier = first
+
+        @cache(max_size=16)
+        def first(x):  # noqa: F811 — unlike collect_all_metrics, both are kept
+            return -x
+
+        @cache(max_size=16, backend="shared")
+        def shared(x):
+            return x
+
+        @cache(max_size=16)
+        async def tracked_async(x):
+            return x
+
+        class Point:
+            @cached_property
+            def norm(self):
+                return 1
+
+        for fn in (earlier, first, shared):
+            fn(1)
+            fn(1)
+        asyncio.run(tracked_async(1))
+        point = Point()
+        assert point.norm == point.norm == 1
+        caches = [earlier, first, shared, tracked_async, Point.norm]
+        assert all_caches()[-5:] == caches
+        assert collect_all_metrics()[f"{__name__}.{Point.norm.__qualname__}"]["hits"] == 1
+        totals = aggregate_info()
+        assert totals["caches"] >= 5
+        assert totals["hits"] >= 4 and totals["misses"] >= 5
+        assert totals["hit_rate"] == totals["hits"] / (totals["hits"] + totals["misses"])
+
+        clear_all()
+        for fn in caches:
+            info = fn.cache_info()
+            assert (info.hits, info.misses, info.current_size) == (0, 0, 0)
+
+        @cache(max_size=16)
+        def later(x):
+            return x
+
+        later(1)
+        assert later.cache_info().misses == 1
+        assert all_caches()[-1] is later
+
+        count = len(all_caches())
+        del earlier, first, caches, fn, Point, point
+        gc.collect()
+        assert len(all_caches()) == count - 3
+    finally:
+        _cleanup_shm()
+
+
+def _bucket(histogram, nanos):
+    """Index of the bucket a latency of `nanos` lands in."""
+    return next(i for i, bound in enumerate(histogram["bounds_ns"]) if nanos < bound)
+
+
+@pytest.mark.parametrize("backend", ["memory", "shared"])
+def test_latency_histogram(backend):
+    _cleanup_shm()
+    try:
+
+        @cache(max_size=16, backend=backend, record_latencies=True)
+        def slow(x):
+            time.sleep(0.01)
+            return x
+
+        histogram = slow.latency_histogram()
+        assert len(histogram["bounds_ns"]) == len(histogram["hit"]) == len(histogram["miss"])
+        assert histogram["bounds_ns"][-1] == float("inf")
+        assert sum(histogram["hit"]) == sum(histogram["miss"]) == 0
+
+        slow(1)
+        for _ in range(100):
+            slow(1)
+        histogram = slow.latency_histogram()
+        assert sum(histogram["miss"]) == 1
+        assert sum(histogram["hit"]) == 100
+        # The miss slept 10 ms; it took at least that and (generously) under 1 s.
+        miss_bucket = histogram["miss"].index(1)
+        assert _bucket(histogram, 10_000_000) <= miss_bucket <= _bucket(histogram, 10**9)
+        # Hits never call the function, so every one is well under the sleep.
+        assert sum(histogram["hit"][: _bucket(histogram, 10_000_000)]) == 100
+
+        slow.reset_stats()
+        histogram = slow.latency_histogram()
+        assert sum(histogram["hit"]) == sum(histogram["miss"]) == 0
+        assert slow.cache_info().hits == 0
+    finally:
+        _cleanup_shm()
+
+
+def test_latency_histogram_off_and_async():
+    @cache(max_size=16)
+    def plain(x):
+        return x
+
+    plain(1)
+    assert plain.latency_histogram() is None
+
+    @cache(max_size=16, record_latencies=True)
+    async def fetch(x):
+        await asyncio.sleep(0.01)
+        return x
+
+    asyncio.run(fetch(1))
+    asyncio.run(fetch(1))
+    histogram = fetch.latency_histogram()
+    assert (sum(histogram["hit"]), sum(histogram["miss"])) == (1, 1)
+    assert histogram["miss"].index(1) >= _bucket(histogram, 10_000_000)
@@ -101,3 +101,45 @@ def test_non_raising_collision_still_caches():
     assert f(a) == 1  # hit
     assert f(b) == 2  # hit
     assert calls["n"] == 2
+
+
+def test_raising_eq_propagates_on_set():
+    """set() must not take a raising lookup for a new key and insert a
+    duplicate entry with the exception still pending."""
+
+    @cache(max_size=128)
+    def f(key):
+        return 1
+
+    f(RaisingEq())  # prime
+    with pytest.raises(RuntimeError, match="boom from __eq__"):
+        f.set(2, RaisingEq())
+    assert f.cache_info().current_size == 1
+    assert f("ok") == 1
+
+
+def test_raising_eq_while_storing_propagates():
+    """An __eq__ that compares cleanly in the lookup and the double-check but
+    raises in the insert after them. That error must reach the caller, not be
+    left pending behind a returned result."""
+
+    class RaisesLater:
+        compares = 0
+
+        def __hash__(self):
+            return 0
+
+        def __eq__(self, other):
+            RaisesLater.compares += 1
+            if RaisesLater.compares > 2:
+                raise RuntimeError("boom from __eq__")
+            return False
+
+    @cache(max_size=128)
+    def f(key):
+        return 1
+
+    f(RaisesLater())  # prime: nothing to compare against
+    with pytest.raises(RuntimeError, match="boom from __eq__"):
+        f(RaisesLater())
+    assert f("ok") == 1
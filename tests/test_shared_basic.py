@@ -1,21 +1,136 @@
 """Basic functionality tests for the shared memory backend."""
 
+import collections
 import contextlib
+import dataclasses
+import datetime
+import decimal
+import enum
 import glob
+import math
+import mmap
 import os
+import pickle
 import stat
+import struct
 import sys
 import tempfile
+import uuid
 
 import pytest
 
-from warp_cache import SharedCacheInfo, cache
+from warp_cache import SharedCacheInfo, SharedDict, cache, configure
+
+
+# Per-user directory name under the base dir; must match src/shm/region.rs::shm_dir.
+_USER_DIR = "warp_cache" if sys.platform == "win32" else f"warp_cache-{os.getuid()}"
+
+_skip_on_windows = pytest.mark.skipif(sys.platform == "win32", reason="Unix-only behavior")
+
+
+class _TaggedList(list):
+    """A list subclass, which the serde fast path must leave to pickle."""
+
+
+_Point = collections.namedtuple("_Point", "x y")
+
+
+@dataclasses.dataclass(frozen=True)
+class _Span:
+    start: datetime.date
+    end: datetime.date
+
+
+class _Color(enum.Enum):
+    RED = 1
+    BLUE = (0, 0, 255)
+
+
+class _Level(enum.IntEnum):
+    LOW = 1
+    HIGH = 2
+
+
+if sys.version_info >= (3, 11):
+
+    class _Mode(enum.StrEnum):
+        FAST = "fast"
+        SLOW = "slow"
+
+else:
+
+    class _Mode(str, enum.Enum):
+        FAST = "fast"
+        SLOW = "slow"
+
+
+class _Perm(enum.Flag):
+    R = 4
+    W = 2
+
+
+@dataclasses.dataclass
+class _Mutable:
+    x: int
+
+
+@dataclasses.dataclass(frozen=True)
+class _Report:
+    name: str
+    span: _Span
+    points: list
+    total: int = dataclasses.field(default=0, init=False)
+
+    def __post_init__(self):
+        object.__setattr__(self, "total", len(self.points))
+
+
+_pwned_calls = []
+
+
+def _pwned(*args):
+    """Not a class: a crafted entry naming it in place of one must never
+    get it called."""
+    _pwned_calls.append(args)
+
+
+# Names as long as those of the classes above, to patch over them in place.
+_Pwned = _Pwnd = _pwned
+
+
+class _Money:
+    """A toy type for `register_serializer`: registered by the tests below."""
+
+    def __init__(self, cents, currency):
+        self.cents = cents
+        self.currency = currency
+
+    def __eq__(self, other):
+        return (self.cents, self.currency) == (other.cents, other.currency)
+
+    def __hash__(self):
+        return hash((self.cents, self.currency))
+
+
+class _Celsius:
+    """Registered only once a cache that stores it exists."""
+
+    def __init__(self, degrees):
+        self.degrees = degrees
+
+    def __eq__(self, other):
+        return self.degrees == other.degrees
+
+    def __hash__(self):
+        return hash(self.degrees)
 
 
 def _cleanup_shm():
     """Remove any leftover shared memory files."""
-    tmpdir = tempfile.gettempdir()
-    shm_dir = os.path.join(tmpdir, f"warp_cache-{os.getuid()}")
+    # Must match src/shm/region.rs::shm_dir. Leftovers matter: reopening a name
+    # with different parameters raises unless on_mismatch is given.
+    base = "/dev/shm" if sys.platform.startswith("linux") else tempfile.gettempdir()
+    shm_dir = os.path.join(base, _USER_DIR)
     if os.path.isdir(shm_dir):
         for f in glob.glob(os.path.join(shm_dir, "*")):
             with contextlib.suppress(OSError):
@@ -92,6 +207,16 @@ class TestSharedBasicHitMiss:
         assert fn(b=2, a=1) == 3  # same key regardless of kwarg order
         assert fn.cache_info().hits == 1
 
+    def test_kwargs_key_does_not_collide_with_positional(self):
+        @cache(max_size=128, backend="shared")
+        def fn(*args, **kwargs):
+            return [args, kwargs]
+
+        assert fn(x=1) == [(), {"x": 1}]
+        assert fn((), (("x", 1),)) == [((), (("x", 1),)), {}]
+        assert fn(x=1) == [(), {"x": 1}]
+        assert fn.cache_info().misses == 2
+
     def test_eviction_at_capacity(self):
         @cache(max_size=4, backend="shared")
         def fn(x):
@@ -150,6 +275,666 @@ class TestSharedBasicHitMiss:
         assert fn(1, 2) == 3  # hit
         assert fn.cache_info().hits == 1
 
+    def test_fast_path_lists(self):
+        """Lists of primitives skip pickle; one unsupported element anywhere
+        sends the whole value to pickle instead."""
+        pickled = []
+
+        def dumps(value):
+            pickled.append(value)
+            return pickle.dumps(value)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(x):
+            return x
+
+        values = [[], [1, 2.5, "three", b"four", None, True], [[1, [2, []]], (3, [4])]]
+        for value in values:
+            assert fn(value) == value  # miss
+            result = fn(value)  # hit
+            assert result == value
+            assert type(result) is list
+        assert fn.cache_info().hits == len(values)
+        assert pickled == []
+
+        mixed = [1, [2, range(3)], "x"]
+        assert fn(mixed) == mixed
+        assert fn(mixed) == mixed
+        assert fn.cache_info().hits == len(values) + 1
+        # Pickled whole: the args on each call, the value once.
+        assert pickled == [(mixed,), mixed, (mixed,)]
+
+        fn(_TaggedList([1]))
+        assert type(fn(_TaggedList([1]))) is _TaggedList
+
+    def test_fast_path_buffers(self):
+        """bytearrays and memoryviews skip pickle, as keys and values, and come
+        back as bytes; equal contents make one key, as they compare equal."""
+        pickled = []
+
+        def dumps(value):
+            pickled.append(value)
+            return pickle.dumps(value)
+
+        calls = []
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(x):
+            calls.append(x)
+            return x
+
+        packet = bytearray(b"header:payload:trailer")
+        assert fn(packet) == b"header:payload:trailer"
+        assert type(fn(packet)) is bytes
+        assert fn(memoryview(packet)[7:14]) == b"payload"
+        assert fn(b"payload") == b"payload"
+        assert fn(memoryview(packet)[::2]) == bytes(packet[::2])
+        assert fn([memoryview(b"ab"), bytearray(b"cd")]) == [b"ab", b"cd"]
+        assert len(calls) == 4
+        assert pickled == []
+
+    def test_memoryview_of_ints_is_not_its_bytes(self):
+        """A view of anything but unsigned bytes doesn't equal the bytes it
+        holds, so it goes to the serializer rather than sharing their key."""
+        import array
+
+        def dumps(value):
+            if isinstance(value, tuple):
+                value = tuple(
+                    (v.format, v.tolist()) if isinstance(v, memoryview) else v for v in value
+                )
+            return pickle.dumps(value)
+
+        calls = []
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(x):
+            calls.append(x)
+            return len(calls)
+
+        ints = memoryview(array.array("i", [1]))
+        assert ints != ints.tobytes()
+        assert fn(ints.tobytes()) == 1
+        assert fn(ints) == 2
+        assert fn(ints) == 2
+        assert fn(ints.tobytes()) == 1
+
+    def test_fast_path_dicts(self):
+        """Dicts of primitives skip pickle and keep their insertion order."""
+        pickled = []
+
+        def dumps(value):
+            pickled.append(value)
+            return pickle.dumps(value)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(x):
+            return x
+
+        values = [{}, {"b": 1, "a": [2.5, None]}, {"n": {"m": {1: b"x", (2, 3): True}}}]
+        for value in values:
+            fn(value)
+            result = fn(value)
+            assert result == value
+            assert list(result) == list(value)
+        assert fn.cache_info().hits == len(values)
+        assert pickled == []
+
+        mixed = {"a": 1, "b": {"c": range(3)}}
+        fn(mixed)
+        assert fn(mixed) == mixed
+        assert pickled == [(mixed,), mixed, (mixed,)]
+
+    def test_dict_keys_ignore_insertion_order(self):
+        calls = 0
+
+        @cache(max_size=16, backend="shared")
+        def fn(options):
+            nonlocal calls
+            calls += 1
+            return sorted(options)
+
+        assert fn({"a": 1, "b": {"x": 1, "y": 2}}) == ["a", "b"]
+        assert fn({"b": {"y": 2, "x": 1}, "a": 1}) == ["a", "b"]
+        assert calls == 1
+        assert fn({"a": 1, "b": {"x": 1, "y": 3}}) == ["a", "b"]
+        assert calls == 2
+
+        shared = SharedDict("test_dict_keys", 16)
+        shared.set({"x": 1, "y": 2}, "v")
+        assert shared.get({"y": 2, "x": 1}) == "v"
+
+    def test_fast_path_sets(self):
+        pickled = []
+
+        def dumps(value):
+            pickled.append(value)
+            return pickle.dumps(value)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(x):
+            return x
+
+        values = [set(), {1, "a", b"b", (2, 3)}, frozenset({frozenset({1.5}), None})]
+        for value in values:
+            fn(value)
+            result = fn(value)
+            assert result == value
+            assert type(result) is type(value)
+        assert fn.cache_info().hits == len(values)
+        assert pickled == []
+
+    def test_equal_sets_make_the_same_key(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        # hash(-1) == hash(-2), so insertion order decides iteration order.
+        first, second = frozenset([-1, -2]), frozenset([-2, -1])
+        assert list(first) != list(second)
+
+        fn = SharedCachedFunction(lambda s: sorted(s), 16, shm_name="test_set_keys")
+        assert fn(first) == [-2, -1]
+        assert fn(second) == [-2, -1]
+        assert fn.cache_info().hits == 1
+        # One entry: both serialized to the same key bytes.
+        assert fn.cache_info().current_size == 1
+        assert fn.keys() == [(first,)]
+
+    @pytest.mark.parametrize("value", [0, 2**63, -(2**63), 2**63 - 1, 2**200, -(2**200)])
+    def test_fast_path_ints_of_any_size(self, value):
+        pickled = []
+
+        def dumps(obj):
+            pickled.append(obj)
+            return pickle.dumps(obj)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(x):
+            return x
+
+        fn(value)
+        assert fn(value) == value
+        assert fn.cache_info().hits == 1
+        assert pickled == []
+
+    def test_big_int_key_bytes_skip_pickle(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            fn = SharedCachedFunction(lambda *a: 0, 16, shm_name="test_bigint_key", shm_dir=tmp)
+            fn(2**70, "id")
+            # TAG_TUPLE, 2 elements, then TAG_BIGINT: positive, 9 magnitude bytes.
+            magnitude = (2**70).to_bytes(9, "little")
+            key = bytes([8, 2, 13, 0]) + struct.pack("<I", 9) + magnitude
+            key += bytes([28, 2]) + b"id"
+            path = os.path.join(tmp, _USER_DIR, "test_bigint_key.data")
+            with open(path, "rb") as f, mmap.mmap(f.fileno(), 0, access=mmap.ACCESS_READ) as data:
+                assert data.find(key) >= 0
+
+    @pytest.mark.parametrize(
+        "value",
+        [
+            datetime.date(2024, 1, 1),
+            datetime.time(23, 59, 59, 999999),
+            datetime.time(1, 30, fold=1),
+            datetime.timedelta(days=-3, seconds=5, microseconds=7),
+            datetime.datetime(2024, 1, 1, 12, 30, 15, 250),
+            datetime.datetime(1, 1, 1, fold=1),
+            datetime.datetime(2024, 1, 1, 12, tzinfo=datetime.timezone.utc),
+            datetime.datetime(
+                9999, 12, 31, 23, tzinfo=datetime.timezone(datetime.timedelta(hours=5))
+            ),
+            datetime.datetime(
+                2024, 6, 1, tzinfo=datetime.timezone(datetime.timedelta(hours=5, minutes=30))
+            ),
+        ],
+    )
+    def test_fast_path_temporal_values(self, value):
+        pickled = []
+
+        def dumps(obj):
+            pickled.append(obj)
+            return pickle.dumps(obj)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(x):
+            return x
+
+        fn(value)
+        result = fn(value)
+        assert fn.cache_info().hits == 1
+        assert pickled == []
+        assert result == value
+        assert type(result) is type(value)
+        assert getattr(result, "fold", 0) == getattr(value, "fold", 0)
+        if isinstance(value, datetime.datetime):
+            assert result.utcoffset() == value.utcoffset()
+
+    def test_temporal_values_outside_the_fast_path(self):
+        pickled = []
+
+        def dumps(obj):
+            pickled.append(obj)
+            return pickle.dumps(obj)
+
+        named = datetime.timezone(datetime.timedelta(hours=1), "CET")
+        aware_time = datetime.time(12, tzinfo=datetime.timezone.utc)
+        # In UTC this is in the year 10000: fine as a value, not as a key.
+        late = datetime.datetime(
+            9999, 12, 31, 23, tzinfo=datetime.timezone(datetime.timedelta(hours=-5))
+        )
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(x):
+            return x
+
+        value = datetime.datetime(2024, 1, 1, tzinfo=named)
+        assert fn(value).tzname() == "CET"
+        assert fn(aware_time) == aware_time
+        assert fn(late) == late
+        assert pickled == [(value,), value, (aware_time,), aware_time, (late,)]
+
+    def test_equal_datetimes_make_the_same_key(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        utc = datetime.datetime(2024, 1, 1, 12, tzinfo=datetime.timezone.utc)
+        plus_two = utc.astimezone(datetime.timezone(datetime.timedelta(hours=2)))
+        naive = datetime.datetime(2024, 1, 1, 12)
+        assert utc == plus_two
+
+        fn = SharedCachedFunction(lambda d: d.hour, 16, shm_name="test_datetime_keys")
+        assert fn(utc) == 12
+        assert fn(plus_two) == 12
+        assert fn(naive) == 12
+        assert fn(naive.replace(fold=1)) == 12
+        assert fn.cache_info().hits == 2
+        # Two entries, one aware and one naive: the rest serialized to the same key bytes.
+        assert fn.cache_info().current_size == 2
+
+    def test_fast_path_decimals(self):
+        pickled = []
+
+        def dumps(obj):
+            pickled.append(obj)
+            return pickle.dumps(obj)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(*args):
+            return list(args)
+
+        values = ["19.99", "-0", "1E+400", "Infinity", "-Infinity", "sNaN", "NaN"]
+        key = tuple(decimal.Decimal(v) for v in values)
+        fn(*key)
+        result = fn(*key)
+        assert fn.cache_info().hits == 1
+        assert pickled == []
+        assert [str(d) for d in result] == values
+        assert all(type(d) is decimal.Decimal for d in result)
+
+    def test_decimals_keep_their_exponent(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        fn = SharedCachedFunction(lambda d: str(d), 16, shm_name="test_decimal_keys")
+        assert fn(decimal.Decimal("1.10")) == "1.10"
+        assert fn(decimal.Decimal("1.1")) == "1.1"
+        assert fn(decimal.Decimal("1.10")) == "1.10"
+        assert fn.cache_info().hits == 1
+        assert fn.cache_info().current_size == 2
+
+    def test_fast_path_uuids(self):
+        pickled = []
+
+        def dumps(obj):
+            pickled.append(obj)
+            return pickle.dumps(obj)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(x):
+            return [x, uuid.UUID(int=x.int + 1)]
+
+        value = uuid.uuid4()
+        fn(value)
+        result = fn(value)
+        assert fn.cache_info().hits == 1
+        assert pickled == []
+        assert result == [value, uuid.UUID(int=value.int + 1)]
+        assert all(type(u) is uuid.UUID for u in result)
+
+    def test_uuid_key_bytes_skip_pickle(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        value = uuid.UUID("12345678-1234-5678-1234-567812345678")
+        with tempfile.TemporaryDirectory() as tmp:
+            fn = SharedCachedFunction(lambda *a: 0, 16, shm_name="test_uuid_key", shm_dir=tmp)
+            fn("id", value)
+            # TAG_TUPLE, 2 elements, TAG_VSTR "id", then TAG_UUID and its 16 bytes.
+            key = bytes([8, 2, 28, 2]) + b"id" + bytes([20]) + value.bytes
+            path = os.path.join(tmp, _USER_DIR, "test_uuid_key.data")
+            with open(path, "rb") as f, mmap.mmap(f.fileno(), 0, access=mmap.ACCESS_READ) as data:
+                assert data.find(key) >= 0
+
+    def test_fast_path_numpy_arrays(self):
+        np = pytest.importorskip("numpy")
+        pickled = []
+
+        def dumps(obj):
+            pickled.append(obj)
+            return pickle.dumps(obj)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(x):
+            return {
+                "c": np.arange(12.0).reshape(3, 4) * x,
+                "f": np.asfortranarray(np.arange(12, dtype=">i4").reshape(3, 4)),
+                "when": np.array(["2024-01-01", "2024-06-30"], dtype="M8[D]"),
+                "scalar": np.array(7, dtype=np.uint16),
+            }
+
+        expected = fn(2.0)
+        result = fn(2.0)
+        assert fn.cache_info().hits == 1
+        assert pickled == []
+        for name, arr in expected.items():
+            assert type(result[name]) is np.ndarray
+            assert result[name].dtype == arr.dtype
+            np.testing.assert_array_equal(result[name], arr)
+        assert result["f"].flags.f_contiguous
+        result["c"][0, 0] = 1.0  # writable, like an unpickled array
+
+    def test_numpy_arrays_outside_the_fast_path(self):
+        np = pytest.importorskip("numpy")
+        pickled = []
+
+        def dumps(obj):
+            pickled.append(obj)
+            return pickle.dumps(obj)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(kind):
+            grid = np.arange(12).reshape(3, 4)
+            return {
+                "strided": grid[:, ::2],
+                "object": np.array([None, "x"], dtype=object),
+                "empty": np.zeros((0, 3)),
+                "masked": np.ma.masked_array([1, 2], mask=[0, 1]),
+            }[kind]
+
+        for kind in ("strided", "object", "empty", "masked"):
+            expected = fn(kind)
+            assert pickled[-1] is expected
+            result = fn(kind)
+            assert type(result) is type(expected)
+            np.testing.assert_array_equal(result, expected)
+
+    def test_numpy_arrays_respect_max_value_size(self):
+        np = pytest.importorskip("numpy")
+
+        @cache(max_size=8, backend="shared", max_value_size=1024)
+        def fn(n):
+            return np.zeros(n)
+
+        fn(1000)
+        fn(1000)
+        info = fn.cache_info()
+        assert (info.hits, info.oversize_value_skips) == (0, 2)
+
+    def test_fast_path_namedtuples_and_dataclasses(self):
+        pickled = []
+
+        def dumps(obj):
+            pickled.append(obj)
+            return pickle.dumps(obj)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(point):
+            span = _Span(datetime.date(2024, 1, 1), datetime.date(2024, 1, 31))
+            return _Report("jan", span, [point, _Point(3, 4)])
+
+        expected = fn(_Point(1, 2))
+        result = fn(_Point(1, 2))
+        assert fn.cache_info().hits == 1
+        assert pickled == []
+        assert result == expected
+        assert type(result.span) is _Span
+        assert type(result.points[0]) is _Point
+        assert result.total == 2
+
+    def test_namedtuple_key_bytes_skip_pickle(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            fn = SharedCachedFunction(
+                lambda p: p.x, 16, shm_name="test_namedtuple_key", shm_dir=tmp
+            )
+            assert fn(_Point(1, 2)) == 1
+            assert fn(_Point(x=1, y=2)) == 1
+            assert fn.cache_info().hits == 1
+            assert fn.keys() == [(_Point(1, 2),)]
+            assert type(fn.keys()[0][0]) is _Point
+
+            # TAG_NAMEDTUPLE, then the class's module and qualname.
+            module = _Point.__module__.encode()
+            key = bytes([22]) + struct.pack("<I", len(module)) + module
+            key += struct.pack("<I", 6) + b"_Point" + bytes([2, 27, 33, 27, 34])
+            path = os.path.join(tmp, _USER_DIR, "test_namedtuple_key.data")
+            with open(path, "rb") as f, mmap.mmap(f.fileno(), 0, access=mmap.ACCESS_READ) as data:
+                assert data.find(key) >= 0
+
+    def test_dataclasses_outside_the_fast_path(self):
+        pickled = []
+
+        def dumps(obj):
+            pickled.append(obj)
+            return pickle.dumps(obj)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(kind):
+            if kind == "mutable":
+                return _Mutable(1)
+            span = _Span(datetime.date(2024, 1, 1), datetime.date(2024, 1, 2))
+            object.__setattr__(span, "note", "extra")
+            return span
+
+        assert fn("mutable") == fn("mutable") == _Mutable(1)
+        assert fn("extra").note == "extra"
+        assert fn("extra").note == "extra"
+        assert len(pickled) == 2
+
+    def test_moved_class_raises_on_read(self):
+        @cache(max_size=16, backend="shared")
+        def fn(x):
+            return _Span(x, x)
+
+        day = datetime.date(2024, 1, 1)
+        span = fn(day)
+        module = sys.modules[_Span.__module__]
+        del module._Span
+        try:
+            with pytest.raises(ImportError, match="_Span, which can no longer be imported"):
+                fn(day)
+        finally:
+            module._Span = type(span)
+        assert fn(day) == span
+
+    def test_fast_path_enum_members(self):
+        pickled = []
+
+        def dumps(obj):
+            pickled.append(obj)
+            return pickle.dumps(obj)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(color, *, level, mode):
+            return [color, level, mode, _Color.BLUE]
+
+        fn(_Color.RED, level=_Level.HIGH, mode=_Mode.FAST)
+        result = fn(_Color.RED, level=_Level.HIGH, mode=_Mode.FAST)
+        assert fn.cache_info().hits == 1
+        assert pickled == []
+        assert result[0] is _Color.RED
+        assert result[1] is _Level.HIGH
+        assert result[2] is _Mode.FAST
+        assert result[3] is _Color.BLUE
+        # Members are written by name, so an equal int or str is another key.
+        assert fn(_Color.RED, level=2, mode="fast") == [_Color.RED, 2, "fast", _Color.BLUE]
+        assert fn.cache_info().current_size == 2
+
+    def test_enum_members_outside_the_fast_path(self):
+        pickled = []
+
+        def dumps(obj):
+            pickled.append(obj)
+            return pickle.dumps(obj)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(perm):
+            return perm
+
+        # A combination of flags has no single member name.
+        both = _Perm.R | _Perm.W
+        assert fn(both) is both
+        assert fn(both) is both
+        assert pickled == [(both,), both, (both,)]
+
+    def test_missing_enum_member_raises_on_read(self):
+        @cache(max_size=16, backend="shared")
+        def fn(x):
+            return _Color.RED
+
+        assert fn(1) is _Color.RED
+        module = sys.modules[_Color.__module__]
+        original = module._Color
+        module._Color = enum.Enum("_Color", "BLUE")
+        try:
+            with pytest.raises(KeyError, match="_Color.RED, which no longer exists"):
+                fn(1)
+        finally:
+            module._Color = original
+        assert fn(1) is _Color.RED
+
+    def test_fast_path_complex_numbers(self):
+        pickled = []
+
+        def dumps(obj):
+            pickled.append(obj)
+            return pickle.dumps(obj)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def fn(*args, **kwargs):
+            return [args, {"roots": [1 - 2j, -0.5j]}, {complex("nan+infj"), -1j}]
+
+        fn(1 + 2j, (3j, [complex(math.inf, -math.inf)]), scale=2j)
+        result = fn(1 + 2j, (3j, [complex(math.inf, -math.inf)]), scale=2j)
+        assert fn.cache_info().hits == 1
+        assert pickled == []
+        assert result[0] == (1 + 2j, (3j, [complex(math.inf, -math.inf)]))
+        assert result[1] == {"roots": [1 - 2j, -0.5j]}
+        assert -1j in result[2]
+        (nan_inf,) = result[2] - {-1j}
+        assert math.isnan(nan_inf.real)
+        assert nan_inf.imag == math.inf
+        # The sign of a zero part survives, as for a float.
+        assert math.copysign(1, result[1]["roots"][1].real) == -1
+
+    @pytest.mark.parametrize("size", [0, 255, 256, 100_000])
+    def test_fast_path_tuples_of_any_length(self, size):
+        pickled = []
+
+        def dumps(obj):
+            pickled.append(obj)
+            return pickle.dumps(obj)
+
+        @cache(
+            max_size=4,
+            backend="shared",
+            max_key_size=1 << 20,
+            max_value_size=1 << 20,
+            serializer=(dumps, pickle.loads),
+        )
+        def fn(ids):
+            return ids[::-1]
+
+        ids = tuple(range(size))
+        fn(ids)
+        assert fn(ids) == ids[::-1]
+        assert fn.cache_info().hits == 1
+        assert pickled == []
+
+    def test_short_tuples_keep_their_one_byte_count(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        def ints(n):
+            small = bytes(b for i in range(min(n, 224)) for b in (27, i + 32))
+            return small + b"".join(bytes([4]) + struct.pack("<q", i) for i in range(224, n))
+
+        with tempfile.TemporaryDirectory() as tmp:
+            fn = SharedCachedFunction(
+                lambda *a: 0,
+                16,
+                shm_name="test_tuple_counts",
+                shm_dir=tmp,
+                max_key_size=8192,
+            )
+            fn(tuple(range(255)), tuple(range(256)))
+            # Up to 255 elements: TAG_TUPLE and a 1-byte count, the format
+            # entries have always used. Past that, TAG_LONG_TUPLE and 4 bytes.
+            short = bytes([8, 255]) + ints(255)
+            long = bytes([26]) + struct.pack("<I", 256) + ints(256)
+            path = os.path.join(tmp, _USER_DIR, "test_tuple_counts.data")
+            with open(path, "rb") as f, mmap.mmap(f.fileno(), 0, access=mmap.ACCESS_READ) as data:
+                assert data.find(bytes([8, 2]) + short + long) >= 0
+            assert fn(tuple(range(255)), tuple(range(256))) == 0
+            assert fn.cache_info().hits == 1
+            assert fn.keys() == [(tuple(range(255)), tuple(range(256)))]
+
+    def test_compact_ints_strs_and_bytes(self):
+        pickled = []
+
+        def dumps(obj):
+            pickled.append(obj)
+            return pickle.dumps(obj)
+
+        @cache(
+            max_size=16,
+            backend="shared",
+            max_value_size=1 << 16,
+            serializer=(dumps, pickle.loads),
+        )
+        def fn(i):
+            return [-33, -32, 0, 223, 224, "", "x" * 127, "x" * 128, "é" * 9000, b"\0" * 16384][i]
+
+        for i in range(10):
+            expected = fn(i)
+            assert fn(i) == expected
+        assert fn.cache_info().hits == 10
+        assert pickled == []
+
+    def test_small_int_and_short_str_key_bytes(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            fn = SharedCachedFunction(lambda *a: 0, 16, shm_name="test_compact_key", shm_dir=tmp)
+            fn("user", 42, "en-US", 1, 0, "v2")
+            # 26 bytes: the format byte, TAG_TUPLE and a count, TAG_VSTR and a
+            # 1-byte length before each str, TAG_SMALL_INT and i + 32 for each int.
+            key = bytes([1, 8, 6, 28, 4]) + b"user" + bytes([27, 74, 28, 5]) + b"en-US"
+            key += bytes([27, 33, 27, 32, 28, 2]) + b"v2"
+            assert len(key) == 26
+            path = os.path.join(tmp, _USER_DIR, "test_compact_key.data")
+            with open(path, "rb") as f, mmap.mmap(f.fileno(), 0, access=mmap.ACCESS_READ) as data:
+                assert data.find(key) >= 0
+
+    def test_self_referencing_list_falls_back_to_pickle(self):
+        @cache(max_size=16, backend="shared")
+        def fn():
+            looped = [1]
+            looped.append(looped)
+            return looped
+
+        fn()
+        result = fn()
+        assert result[0] == 1
+        assert result[1] is result
+
     def test_shared_cache_info_repr(self):
         @cache(max_size=64, backend="shared")
         def fn(x):
@@ -272,44 +1057,70 @@ class TestSharedSieve:
         assert call_count == 0
 
 
-class TestSharedTTL:
+class TestSharedCounters:
+    """evictions / expirations counters in SharedCacheInfo."""
+
     def setup_method(self):
         _cleanup_shm()
 
     def teardown_method(self):
         _cleanup_shm()
 
-    def test_ttl_expiry(self):
+    def test_evictions_counted(self):
+        @cache(max_size=2, backend="shared")
+        def fn(x):
+            return x
+
+        fn(1)
+        fn(2)
+        assert fn.cache_info().evictions == 0
+        fn(3)
+        fn(4)
+        info = fn.cache_info()
+        assert info.evictions == 2
+        assert info.expirations == 0
+        assert "evictions=2" in repr(info)
+
+    def test_expirations_counted(self):
         import time
 
-        @cache(max_size=128, ttl=0.1, backend="shared")
+        @cache(max_size=16, ttl=0.1, backend="shared")
         def fn(x):
-            return x * 2
-
-        assert fn(1) == 2
-        assert fn.cache_info().misses == 1
+            return x
 
+        fn(1)
+        fn(2)
         time.sleep(0.15)
+        fn(1)  # expired on read
+        info = fn.cache_info()
+        assert info.expirations == 1
+        assert info.evictions == 0
 
-        # Should be expired
-        assert fn(1) == 2
-        assert fn.cache_info().misses == 2
-
-    def test_ttl_not_expired(self):
-        @cache(max_size=128, ttl=10.0, backend="shared")
+    def test_clear_resets_counters(self):
+        @cache(max_size=1, backend="shared")
         def fn(x):
-            return x * 2
+            return x
 
-        assert fn(1) == 2
-        assert fn(1) == 2
-        assert fn.cache_info().hits == 1
+        fn(1)
+        fn(2)
+        assert fn.cache_info().evictions == 1
+        fn.cache_clear()
+        info = fn.cache_info()
+        assert info.evictions == 0
+        assert info.expirations == 0
 
 
-class TestSharedTTLConfigMismatch:
-    """Regression for #42: opening an existing shm region with a different TTL
-    used to silently reuse the creator's region (and its TTL stored in the
-    header), ignoring the second caller's requested TTL. A TTL mismatch must now
-    recreate the region, just like a capacity/key/value-size mismatch."""
+class TestSharedBytesUsed:
+    """bytes_used / fill_ratio / avg_value_len accounting."""
+
+    # Serialized sizes, each after a format byte: an `(i,)` args tuple is
+    # tag + count + small int = 5 bytes; a short str value is tag + 1-byte
+    # varint length + UTF-8 bytes.
+    KEY_LEN = 5
+
+    @staticmethod
+    def _value_len(s):
+        return 3 + len(s)
 
     def setup_method(self):
         _cleanup_shm()
@@ -317,55 +1128,2731 @@ class TestSharedTTLConfigMismatch:
     def teardown_method(self):
         _cleanup_shm()
 
-    def test_ttl_mismatch_recreates_region(self):
+    def test_accounting_through_insert_update_evict_remove(self):
         import time
 
-        from warp_cache._warp_cache_rs import SharedCachedFunction
-
-        shm_name = "test_ttl_mismatch_42"
-
-        # First constructor fixes the region's TTL to 0.1s.
-        SharedCachedFunction(
-            lambda x: x, 16, ttl=0.1, max_key_size=512, max_value_size=4096, shm_name=shm_name
-        )
+        @cache(max_size=2, ttl=0.2, backend="shared", max_key_size=32, max_value_size=64)
+        def fn(x):
+            return "v" * x
 
-        # Second constructor requests ttl=None on the same region. After the fix
-        # the TTL mismatch recreates the region with no TTL; before the fix it
-        # silently reused the first region and honored ttl=0.1.
-        fn_b = SharedCachedFunction(
-            lambda x: x, 16, ttl=None, max_key_size=512, max_value_size=4096, shm_name=shm_name
-        )
+        fn(3)
+        fn(5)
+        expected = 2 * self.KEY_LEN + self._value_len("vvv") + self._value_len("vvvvv")
+        info = fn.cache_info()
+        assert info.bytes_used == expected
+        assert info.bytes_capacity == 2 * (32 + 64)
+        assert info.fill_ratio == pytest.approx(expected / (2 * (32 + 64)))
+        assert info.avg_value_len == pytest.approx((6 + 8) / 2)
+
+        # Update in place: value shrinks from "vvv" to "a"
+        fn.set("a", 3)
+        expected += self._value_len("a") - self._value_len("vvv")
+        assert fn.cache_info().bytes_used == expected
+
+        # Evict: inserting a third key pushes one out
+        fn(7)
+        info = fn.cache_info()
+        assert info.evictions == 1
+        # The in-place update marked 3 visited, so SIEVE evicts 5
+        expected += self._value_len("v" * 7) - self._value_len("vvvvv")
+        assert info.bytes_used == expected
+
+        # Remove: expired entries are dropped on read
+        time.sleep(0.25)
+        fn.get(3)
+        fn.get(7)
+        info = fn.cache_info()
+        assert info.current_size == 0
+        assert info.bytes_used == 0
+        assert info.avg_value_len == 0.0
 
-        assert fn_b(1) == 1  # miss -> store
-        time.sleep(0.3)  # well past the first constructor's 0.1s TTL
-        assert fn_b(1) == 1  # ttl=None -> still a hit; ttl=0.1 (bug) -> expired miss
-        assert fn_b.cache_info().hits == 1, (
-            "second constructor's ttl=None was ignored — entry expired per the "
-            "first constructor's ttl=0.1 (region was reused, not recreated)"
-        )
+    def test_value_size_histogram(self):
+        @cache(max_size=8, backend="shared", max_value_size=64)
+        def fn(x):
+            return "v" * x
+
+        fn(5)  # 8 bytes
+        fn(6)  # 9 bytes
+        fn(100)  # 103 bytes: over max_value_size, counted all the same
+        fn.set("v" * 20_000, 1)  # past every bounded bucket
+        histogram = fn.value_size_histogram()
+        buckets = histogram["buckets"]
+        # 4 × max_value_size is 256: bounds double from 1 byte past it.
+        assert [bound for bound, _ in buckets] == [2**i for i in range(15)] + [float("inf")]
+        counts = {bound: count for bound, count in buckets if count}
+        assert counts == {8: 1, 16: 1, 128: 1, float("inf"): 1}
+        assert histogram["largest"] > 20_000
+        assert fn.cache_info().oversize_value_skips == 2
+
+        fn.reset_stats()
+        histogram = fn.value_size_histogram()
+        assert sum(count for _, count in histogram["buckets"]) == 0
+        assert histogram["largest"] == 0
+
+    def test_clear_resets_bytes_used(self):
+        @cache(max_size=8, backend="shared")
+        def fn(x):
+            return x
 
+        fn(1)
+        assert fn.cache_info().bytes_used > 0
+        fn.cache_clear()
+        info = fn.cache_info()
+        assert info.bytes_used == 0
+        assert info.fill_ratio == 0.0
 
-class TestSharedMemoryBackend:
-    """Test backend='memory' vs backend='shared' routing."""
 
+class TestSharedKeys:
     def setup_method(self):
         _cleanup_shm()
 
     def teardown_method(self):
         _cleanup_shm()
 
-    def test_default_is_memory(self):
-        from warp_cache._warp_cache_rs import CacheInfo
-
-        @cache(max_size=128)
-        def fn(x):
-            return x
+    def test_keys_in_insertion_order(self):
+        @cache(max_size=16, backend="shared")
+        def fn(x, y=0):
+            return x + y
 
         fn(1)
-        info = fn.cache_info()
-        assert isinstance(info, CacheInfo)
+        fn("a", y="b")
+        fn(2.5)
+        assert fn.keys() == [(1,), (("a",), (("y", "b"),)), (2.5,)]
 
-    def test_shared_returns_shared_info(self):
+    def test_keys_does_not_touch_stats_or_order(self):
+        call_count = 0
+
+        @cache(max_size=2, backend="shared")
+        def fn(x):
+            nonlocal call_count
+            call_count += 1
+            return x
+
+        fn(1)
+        fn(2)
+        before = fn.cache_info()
+        assert fn.keys() == [(1,), (2,)]
+        after = fn.cache_info()
+        assert (after.hits, after.misses) == (before.hits, before.misses)
+
+        # keys() must not mark entries visited: 1 is still the SIEVE victim
+        fn(3)
+        assert fn.keys() == [(2,), (3,)]
+
+    def test_keys_empty_and_after_clear(self):
+        @cache(max_size=4, backend="shared")
+        def fn(x):
+            return x
+
+        assert fn.keys() == []
+        fn(1)
+        fn.cache_clear()
+        assert fn.keys() == []
+
+    def test_keys_with_pickled_args(self):
+        @cache(max_size=4, backend="shared")
+        def fn(x):
+            return len(x)
+
+        fn(frozenset({1, 2}))
+        assert fn.keys() == [(frozenset({1, 2}),)]
+
+
+class TestSharedItems:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_items_roundtrip(self):
+        @cache(max_size=16, backend="shared")
+        def fn(x):
+            return {"x": x}  # pickled value
+
+        fn(1)
+        fn("two")
+        assert fn.items() == [((1,), {"x": 1}), (("two",), {"x": "two"})]
+
+        # A dump replays into a cleared cache with set()
+        snapshot = fn.items()
+        fn.cache_clear()
+        for args, value in snapshot:
+            fn.set(value, *args)
+        assert fn.items() == snapshot
+
+    def test_items_skips_expired_without_removing(self):
+        import time
+
+        @cache(max_size=16, ttl=0.1, backend="shared")
+        def fn(x):
+            return x
+
+        fn(1)
+        time.sleep(0.15)
+        fn(2)
+        assert fn.items() == [((2,), 2)]
+        info = fn.cache_info()
+        assert info.current_size == 2  # expired entry still resident
+        assert info.expirations == 0
+
+
+class TestSharedGetMany:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_get_many_aligned_with_input(self):
+        @cache(max_size=16, backend="shared")
+        def fn(x, y=0):
+            return x * 10 + y
+
+        fn(1)
+        fn(2, 3)
+        fn(4)
+        assert fn.get_many([(1,), (9,), (2, 3), (4,), (2,)]) == [10, None, 23, 40, None]
+
+        info = fn.cache_info()
+        assert info.hits == 3
+        assert info.misses == 3 + 2  # three original misses + two batch misses
+
+    def test_get_many_marks_hits_visited(self):
+        @cache(max_size=2, backend="shared")
+        def fn(x):
+            return x
+
+        fn(1)
+        fn(2)
+        fn.get_many([(1,)])
+        fn(3)  # 1 was visited by the batch, so 2 is evicted
+        assert fn.keys() == [(1,), (3,)]
+
+    def test_get_many_drops_expired(self):
+        import time
+
+        @cache(max_size=16, ttl=0.1, backend="shared")
+        def fn(x):
+            return x
+
+        fn(1)
+        fn(2)
+        time.sleep(0.15)
+        fn(3)
+        assert fn.get_many([(1,), (2,), (3,)]) == [None, None, 3]
+        info = fn.cache_info()
+        assert info.expirations == 2
+        assert info.current_size == 1
+
+    def test_get_many_empty_and_non_tuple(self):
+        @cache(max_size=4, backend="shared")
+        def fn(x):
+            return x
+
+        assert fn.get_many([]) == []
+        with pytest.raises(TypeError):
+            fn.get_many([1])
+
+
+class TestSharedSetMany:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_set_many_warms_cache(self):
+        call_count = 0
+
+        @cache(max_size=10_000, backend="shared")
+        def fn(x):
+            nonlocal call_count
+            call_count += 1
+            return x
+
+        summary = fn.set_many(((i,), i * 2) for i in range(10_000))
+        assert summary == {"inserted": 10_000, "oversize_skipped": 0}
+        assert fn.cache_info().current_size == 10_000
+        assert fn(1234) == 2468
+        assert call_count == 0
+
+    def test_set_many_skips_oversize(self):
+        @cache(max_size=16, backend="shared", max_key_size=32, max_value_size=32)
+        def fn(x):
+            return x
+
+        summary = fn.set_many([((1,), "ok"), (("k" * 100,), "big key"), ((2,), "v" * 100)])
+        assert summary == {"inserted": 1, "oversize_skipped": 2}
+        info = fn.cache_info()
+        assert info.oversize_skips == 2
+        assert fn.items() == [((1,), "ok")]
+
+    def test_set_many_evicts_past_capacity(self):
+        @cache(max_size=4, backend="shared")
+        def fn(x):
+            return x
+
+        fn.set_many([((i,), i) for i in range(6)])
+        info = fn.cache_info()
+        assert info.current_size == 4
+        assert info.evictions == 2
+
+    def test_set_many_rejects_malformed_pairs(self):
+        @cache(max_size=4, backend="shared")
+        def fn(x):
+            return x
+
+        with pytest.raises(TypeError):
+            fn.set_many([(1, 2)])  # args must be a tuple
+        assert fn.cache_info().current_size == 0
+
+
+class TestSharedSetdefault:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_setdefault_inserts_when_absent(self):
+        @cache(max_size=16, backend="shared")
+        def fn(x):
+            return x
+
+        assert fn.setdefault("first", 1) == "first"
+        assert fn.setdefault("second", 1) == "first"
+        assert fn.get(1) == "first"
+        info = fn.cache_info()
+        assert info.current_size == 1
+
+    def test_setdefault_replaces_expired(self):
+        import time
+
+        @cache(max_size=16, ttl=0.1, backend="shared")
+        def fn(x):
+            return x
+
+        fn.setdefault("old", 1)
+        time.sleep(0.15)
+        assert fn.setdefault("new", 1) == "new"
+        assert fn.get(1) == "new"
+
+    def test_setdefault_oversize_returns_existing_or_value(self):
+        @cache(max_size=16, backend="shared", max_value_size=32)
+        def fn(x):
+            return x
+
+        big = "v" * 100
+        assert fn.setdefault(big, 1) == big
+        assert fn.get(1) is None
+        fn.set("small", 1)
+        assert fn.setdefault(big, 1) == "small"
+        assert fn.cache_info().oversize_skips == 2
+
+
+class TestSharedRefresh:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_refresh_overwrites_cached_value(self):
+        calls = []
+
+        @cache(max_size=16, backend="shared")
+        def fn(x):
+            calls.append(x)
+            return len(calls)
+
+        assert fn(1) == 1
+        assert fn(1) == 1
+        assert fn.refresh(1) == 2
+        assert fn(1) == 2
+        assert calls == [1, 1]
+        assert fn.cache_info().current_size == 1
+
+    def test_refresh_resets_ttl(self):
+        import time
+
+        @cache(max_size=16, ttl=0.3, backend="shared")
+        def fn(x):
+            return x
+
+        fn(1)
+        time.sleep(0.2)
+        fn.refresh(1)
+        time.sleep(0.2)
+        assert fn.get(1) == 1
+
+    def test_refresh_oversize_is_not_cached(self):
+        @cache(max_size=16, backend="shared", max_value_size=32)
+        def fn(x):
+            return "v" * x
+
+        assert fn.refresh(100) == "v" * 100
+        assert fn.get(100) is None
+        assert fn.cache_info().oversize_skips == 1
+
+
+class TestSharedTouch:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_touch_extends_ttl(self):
+        import time
+
+        @cache(max_size=16, ttl=0.3, backend="shared")
+        def fn(x):
+            return x
+
+        fn(1)
+        time.sleep(0.2)
+        assert fn.touch(1) is True
+        time.sleep(0.2)
+        assert fn.get(1) == 1
+
+    def test_touch_missing_or_expired(self):
+        import time
+
+        @cache(max_size=16, ttl=0.1, backend="shared")
+        def fn(x):
+            return x
+
+        assert fn.touch(1) is False
+        fn(1)
+        time.sleep(0.15)
+        assert fn.touch(1) is False
+
+    def test_touch_leaves_value_and_stats(self):
+        @cache(max_size=16, backend="shared")
+        def fn(x, *, tag=None):
+            return (x, tag)
+
+        fn(1, tag="a")
+        before = fn.cache_info()
+        assert fn.touch(1, tag="a") is True
+        assert fn.touch(1, tag="b") is False
+        after = fn.cache_info()
+        assert (after.hits, after.misses) == (before.hits, before.misses)
+        assert fn.get(1, tag="a") == (1, "a")
+
+
+class TestSharedIncr:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_incr_starts_at_initial_and_accumulates(self):
+        @cache(max_size=16, backend="shared")
+        def hits(page, *, site="a"):
+            raise AssertionError("incr never calls the function")
+
+        assert hits.incr("/") == 1
+        assert hits.incr("/", amount=5) == 6
+        assert hits.decr("/", amount=2) == 4
+        assert hits.incr("/about", site="b", initial=100) == 101
+        assert hits.get("/") == 4
+        assert hits.get("/about", site="b") == 101
+        assert hits.get("/about") is None
+
+    def test_incr_keeps_the_ttl_window(self):
+        import time
+
+        @cache(max_size=16, ttl=0.3, backend="shared")
+        def fn(x):
+            return x
+
+        fn.incr(1)
+        time.sleep(0.2)
+        assert fn.incr(1) == 2
+        time.sleep(0.15)
+        assert fn.incr(1) == 1
+
+    def test_incr_across_the_small_int_boundary(self):
+        import time
+
+        @cache(max_size=16, ttl=0.3, backend="shared")
+        def fn(x):
+            return x
+
+        # 200 is stored in 2 bytes, 300 in 9: the counter changes size but
+        # keeps its TTL window.
+        assert fn.incr(1, amount=200) == 200
+        time.sleep(0.2)
+        assert fn.incr(1, amount=100) == 300
+        time.sleep(0.15)
+        assert fn.get(1) is None
+
+        # Encoded like any other int, so cas() still matches it.
+        assert fn.incr(2, amount=-100) == -100
+        assert fn.cas(-100, 5, 2) is True
+        assert fn.incr(2) == 6
+
+    def test_incr_rejects_non_int_values(self):
+        @cache(max_size=16, backend="shared")
+        def fn(x):
+            return x
+
+        fn.set("text", 1)
+        fn.set(2**70, 2)
+        fn.set(1.5, 3)
+        for key in (1, 2, 3):
+            with pytest.raises(TypeError):
+                fn.incr(key)
+        assert fn.get(1) == "text"
+        assert fn.get(2) == 2**70
+
+    def test_incr_overflow_raises_and_leaves_value(self):
+        @cache(max_size=16, backend="shared")
+        def fn(x):
+            return x
+
+        fn.set(2**63 - 1, "n")
+        with pytest.raises(OverflowError):
+            fn.incr("n")
+        assert fn.get("n") == 2**63 - 1
+        with pytest.raises(OverflowError):
+            fn.incr("m", amount=-1, initial=-(2**63))
+        with pytest.raises(OverflowError):
+            fn.decr("m", amount=-(2**63))
+        with pytest.raises(OverflowError):
+            fn.incr("m", amount=2**63)
+        assert fn.get("m") is None
+
+    def test_incr_oversize_counter_raises(self):
+        @cache(max_size=16, max_value_size=8, backend="shared")
+        def fn(x):
+            return x
+
+        with pytest.raises(ValueError):
+            fn.incr(1)
+        assert fn.cache_info().oversize_skips == 1
+
+
+class TestSharedCas:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_cas_none_only_inserts_when_absent(self):
+        @cache(max_size=16, backend="shared")
+        def owner(shard):
+            return None
+
+        assert owner.cas(None, "a", 3) is True
+        assert owner.cas(None, "b", 3) is False
+        assert owner.get(3) == "a"
+
+    def test_cas_swaps_only_on_match(self):
+        @cache(max_size=16, backend="shared")
+        def fn(x, *, tag=None):
+            return x
+
+        fn.set({"v": 1}, 1, tag="t")
+        assert fn.cas({"v": 2}, {"v": 3}, 1, tag="t") is False
+        assert fn.cas({"v": 1}, {"v": 3}, 1) is False
+        assert fn.cas({"v": 1}, {"v": 3}, 1, tag="t") is True
+        assert fn.get(1, tag="t") == {"v": 3}
+        assert fn.cas("x", "y", 2) is False
+        assert fn.get(2) is None
+
+    def test_cas_treats_expired_as_absent(self):
+        import time
+
+        @cache(max_size=16, ttl=0.1, backend="shared")
+        def fn(x):
+            return x
+
+        fn.set("old", 1)
+        time.sleep(0.15)
+        assert fn.cas("old", "new", 1) is False
+        assert fn.cas(None, "new", 1) is True
+        assert fn.get(1) == "new"
+
+    def test_cas_oversize_new_value_raises(self):
+        @cache(max_size=16, max_value_size=64, backend="shared")
+        def fn(x):
+            return x
+
+        with pytest.raises(ValueError):
+            fn.cas(None, "x" * 100, 1)
+        assert fn.get(1) is None
+        assert fn.cache_info().oversize_skips == 1
+
+    def test_two_handles_race_for_one_winner(self):
+        import threading
+
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        handles = [
+            SharedCachedFunction(lambda x: x, 64, shm_name="test_cas_race") for _ in range(2)
+        ]
+        barrier = threading.Barrier(2)
+        wins = [[], []]
+
+        def race(i):
+            barrier.wait()
+            for key in range(50):
+                if handles[i].cas(None, i, key):
+                    wins[i].append(key)
+
+        threads = [threading.Thread(target=race, args=(i,)) for i in range(2)]
+        for t in threads:
+            t.start()
+        for t in threads:
+            t.join()
+
+        assert sorted(wins[0] + wins[1]) == list(range(50))
+        for i in range(2):
+            assert all(handles[0].get(key) == i for key in wins[i])
+
+
+class TestSharedResize:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    @_skip_on_windows
+    def test_grow_keeps_entries_and_stats(self):
+        @cache(max_size=4, backend="shared")
+        def fn(x):
+            return x * 2
+
+        for i in range(4):
+            fn(i)
+        fn(0)
+        fn.cache_resize(16)
+
+        info = fn.cache_info()
+        assert info.max_size == 16
+        assert info.current_size == 4
+        assert (info.hits, info.misses) == (1, 4)
+        assert sorted(fn.items()) == [((i,), i * 2) for i in range(4)]
+        for i in range(4, 16):
+            fn(i)
+        assert fn.cache_info().current_size == 16
+        assert fn.cache_info().evictions == 0
+
+    @_skip_on_windows
+    def test_shrink_keeps_newest_entries(self):
+        @cache(max_size=8, backend="shared")
+        def fn(x):
+            return x
+
+        for i in range(8):
+            fn(i)
+        fn.cache_resize(3)
+
+        assert fn.cache_info().max_size == 3
+        assert fn.keys() == [(5,), (6,), (7,)]
+        assert fn.cache_info().evictions == 0
+
+    @_skip_on_windows
+    def test_resize_keeps_ttl_age(self):
+        import time
+
+        @cache(max_size=4, ttl=0.3, backend="shared")
+        def fn(x):
+            return x
+
+        fn(1)
+        time.sleep(0.2)
+        fn.cache_resize(8)
+        assert fn.get(1) == 1
+        time.sleep(0.15)
+        assert fn.get(1) is None
+
+    @pytest.mark.skipif(sys.platform != "win32", reason="Windows-only behavior")
+    def test_resize_unsupported_on_windows(self):
+        @cache(max_size=4, backend="shared")
+        def fn(x):
+            return x
+
+        fn(1)
+        with pytest.raises(OSError, match="not supported on Windows"):
+            fn.cache_resize(8)
+        assert fn.get(1) == 1
+
+    def test_resize_rejects_bad_size(self):
+        @cache(max_size=4, backend="shared")
+        def fn(x):
+            return x
+
+        fn(1)
+        with pytest.raises(ValueError, match="max_size"):
+            fn.cache_resize(0)
+        with pytest.raises(ValueError, match="max_size"):
+            fn.cache_resize(2**30 + 1)
+        assert fn.cache_info().max_size == 4
+        assert fn.get(1) == 1
+
+
+class TestSharedTTL:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_ttl_expiry(self):
+        import time
+
+        @cache(max_size=128, ttl=0.1, backend="shared")
+        def fn(x):
+            return x * 2
+
+        assert fn(1) == 2
+        assert fn.cache_info().misses == 1
+
+        time.sleep(0.15)
+
+        # Should be expired
+        assert fn(1) == 2
+        assert fn.cache_info().misses == 2
+
+    def test_ttl_not_expired(self):
+        @cache(max_size=128, ttl=10.0, backend="shared")
+        def fn(x):
+            return x * 2
+
+        assert fn(1) == 2
+        assert fn(1) == 2
+        assert fn.cache_info().hits == 1
+
+    def test_insert_reclaims_expired_slot_before_evicting(self):
+        import time
+
+        call_count = 0
+
+        @cache(max_size=4, ttl=0.5, backend="shared")
+        def fn(x):
+            nonlocal call_count
+            call_count += 1
+            return x * 2
+
+        # 1 and 2 are visited, so plain SIEVE would skip them and evict 3.
+        fn(1)
+        fn(2)
+        fn(1)
+        fn(2)
+        time.sleep(0.35)
+        fn(3)
+        fn(4)
+        time.sleep(0.25)  # 1 and 2 are now expired; 3 and 4 are still live
+
+        fn(5)
+        info = fn.cache_info()
+        assert info.expired_reclaimed == 1
+        assert info.expirations == 1
+        assert info.evictions == 0
+        assert info.current_size == 4
+
+        call_count = 0
+        fn(3)
+        fn(4)
+        assert call_count == 0  # live entries were kept
+
+        fn.cache_clear()
+        assert fn.cache_info().expired_reclaimed == 0
+
+    def test_per_entry_ttl_mixed_with_default(self):
+        import time
+
+        @cache(max_size=16, ttl=10.0, backend="shared")
+        def fn(x):
+            return x
+
+        fn.set("short", 1, _warp_ttl=0.1)
+        fn.set("default", 2)
+        fn.set("long", 3, _warp_ttl=60.0)
+        time.sleep(0.15)
+
+        assert fn.get(1) is None
+        assert fn.get(2) == "default"
+        assert fn.get(3) == "long"
+        assert [v for _, v in fn.items()] == ["default", "long"]
+
+    def test_per_entry_ttl_without_default(self):
+        import time
+
+        @cache(max_size=16, backend="shared")
+        def fn(x):
+            return x
+
+        fn.set("short", 1, _warp_ttl=0.1)
+        fn.set("forever", 2)
+        time.sleep(0.15)
+
+        assert fn.get(1) is None
+        assert fn.get(2) == "forever"
+
+    def test_per_entry_ttl_overwritten_on_update(self):
+        import time
+
+        @cache(max_size=16, backend="shared")
+        def fn(x):
+            return x
+
+        fn.set("short", 1, _warp_ttl=0.1)
+        fn.set("replaced", 1)
+        time.sleep(0.15)
+        assert fn.get(1) == "replaced"
+
+    def test_per_entry_ttl_rejects_non_positive(self):
+        @cache(max_size=16, backend="shared")
+        def fn(x):
+            return x
+
+        with pytest.raises(ValueError, match="_warp_ttl"):
+            fn.set("v", 1, _warp_ttl=0)
+        assert fn.get(1) is None
+
+
+class TestSharedSlidingTTL:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    @staticmethod
+    def _access_every_half_ttl(sliding_ttl):
+        import time
+
+        calls = []
+
+        @cache(max_size=16, ttl=0.2, sliding_ttl=sliding_ttl, backend="shared")
+        def fn(x):
+            calls.append(x)
+            return x
+
+        for _ in range(5):
+            fn(1)
+            time.sleep(0.1)
+        return len(calls)
+
+    def test_sliding_ttl_keeps_accessed_entry_alive(self):
+        assert self._access_every_half_ttl(sliding_ttl=True) == 1
+
+    def test_absolute_ttl_expires_despite_access(self):
+        assert self._access_every_half_ttl(sliding_ttl=False) > 1
+
+    def test_sliding_ttl_applies_to_get_many(self):
+        import time
+
+        @cache(max_size=16, ttl=0.2, sliding_ttl=True, backend="shared")
+        def fn(x):
+            return x
+
+        fn(1)
+        for _ in range(4):
+            time.sleep(0.1)
+            assert fn.get_many([(1,)]) == [1]
+
+
+class TestSharedStaleWhileRevalidate:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_stale_entry_is_refreshed_by_one_caller(self):
+        import time
+
+        calls = []
+
+        @cache(max_size=16, ttl=0.1, stale_ttl=10.0, backend="shared")
+        def fn(x):
+            calls.append(x)
+            return len(calls)
+
+        assert fn(1) == 1
+        time.sleep(0.15)
+
+        # get() serves the stale value without claiming the refresh.
+        assert fn.get(1) == 1
+        # The first call claims the refresh and recomputes...
+        assert fn(1) == 2
+        # ...and the refreshed entry is fresh again.
+        assert fn(1) == 2
+        assert calls == [1, 1]
+
+    def test_past_stale_window_is_a_miss(self):
+        import time
+
+        @cache(max_size=16, ttl=0.05, stale_ttl=0.05, backend="shared")
+        def fn(x):
+            return x
+
+        fn(1)
+        time.sleep(0.15)
+        assert fn.get(1) is None
+        assert fn.cache_info().expirations == 1
+
+    def test_failed_refresh_releases_claim(self):
+        import time
+
+        fail = False
+
+        @cache(max_size=16, ttl=0.1, stale_ttl=10.0, backend="shared")
+        def fn(x):
+            if fail:
+                raise RuntimeError("boom")
+            return "fresh"
+
+        fn(1)
+        time.sleep(0.15)
+        fail = True
+        with pytest.raises(RuntimeError):
+            fn(1)
+        fail = False
+        # The claim was released, so the next caller refreshes instead of
+        # being served stale.
+        assert fn(1) == "fresh"
+        assert fn.cache_info().misses == 3
+
+    def test_stale_ttl_must_be_positive(self):
+        with pytest.raises(ValueError, match="stale_ttl"):
+
+            @cache(max_size=16, ttl=1.0, stale_ttl=0, backend="shared")
+            def fn(x):
+                return x
+
+
+class TestSharedTTLConfigMismatch:
+    """Regression for #42: opening an existing shm region with a different TTL
+    used to silently reuse the creator's region (and its TTL stored in the
+    header), ignoring the second caller's requested TTL. A TTL mismatch must now
+    be treated like a capacity/key/value-size mismatch."""
+
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_ttl_mismatch_recreates_region(self):
+        import time
+
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        shm_name = "test_ttl_mismatch_42"
+
+        # First constructor fixes the region's TTL to 0.1s.
+        SharedCachedFunction(
+            lambda x: x, 16, ttl=0.1, max_key_size=512, max_value_size=4096, shm_name=shm_name
+        )
+
+        # Second constructor requests ttl=None on the same region. After the fix
+        # the TTL mismatch recreates the region with no TTL; before the fix it
+        # silently reused the first region and honored ttl=0.1.
+        fn_b = SharedCachedFunction(
+            lambda x: x,
+            16,
+            ttl=None,
+            max_key_size=512,
+            max_value_size=4096,
+            shm_name=shm_name,
+            on_mismatch="recreate",
+        )
+
+        assert fn_b(1) == 1  # miss -> store
+        time.sleep(0.3)  # well past the first constructor's 0.1s TTL
+        assert fn_b(1) == 1  # ttl=None -> still a hit; ttl=0.1 (bug) -> expired miss
+        assert fn_b.cache_info().hits == 1, (
+            "second constructor's ttl=None was ignored — entry expired per the "
+            "first constructor's ttl=0.1 (region was reused, not recreated)"
+        )
+
+
+class TestSharedConfigMismatch:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_capacity_mismatch_raises_and_keeps_data(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        fn_a = SharedCachedFunction(lambda x: x, 16, shm_name="test_mismatch_error")
+        fn_a(1)
+
+        with pytest.raises(ValueError, match="capacity: existing 16, requested 32"):
+            SharedCachedFunction(lambda x: x, 32, shm_name="test_mismatch_error")
+
+        assert fn_a.get(1) == 1
+        assert fn_a.cache_info().current_size == 1
+
+    def test_recreate_replaces_region(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        fn_a = SharedCachedFunction(lambda x: x, 16, shm_name="test_mismatch_recreate")
+        fn_a(1)
+        fn_b = SharedCachedFunction(
+            lambda x: x, 32, shm_name="test_mismatch_recreate", on_mismatch="recreate"
+        )
+
+        assert fn_b.cache_info().max_size == 32
+        assert fn_b.get(1) is None
+
+    @_skip_on_windows
+    def test_live_handle_follows_recreated_region(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        fn_a = SharedCachedFunction(lambda x: x, 16, shm_name="test_mismatch_follow")
+        fn_a(1)
+        SharedCachedFunction(
+            lambda x: x, 32, shm_name="test_mismatch_follow", on_mismatch="recreate"
+        )
+
+        # cache_info() checks for replaced files; other calls only now and then.
+        info = fn_a.cache_info()
+        assert (info.max_size, info.remaps) == (32, 1)
+        fn_a.set("two", 2)
+        fresh = SharedCachedFunction(lambda x: x, 32, shm_name="test_mismatch_follow")
+        assert fresh.get(2) == "two"
+        assert fresh.get(1) is None
+
+    @_skip_on_windows
+    def test_live_handle_follows_deleted_and_recreated_files(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            fn_a = SharedCachedFunction(lambda x: x, 16, shm_name="test_follow", shm_dir=tmp)
+            for path in glob.glob(os.path.join(tmp, _USER_DIR, "test_follow.*")):
+                os.unlink(path)
+            fresh = SharedCachedFunction(lambda x: x, 16, shm_name="test_follow", shm_dir=tmp)
+
+            # Enough calls for the periodic check to notice, without cache_info().
+            for i in range(2048):
+                fn_a.set(i, i % 16)
+            assert fresh.get(15) == 2047
+            assert fn_a.cache_info().remaps == 1
+
+    def test_open_existing_adopts_parameters(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        fn_a = SharedCachedFunction(
+            lambda x: x, 16, max_value_size=64, shm_name="test_mismatch_open"
+        )
+        fn_a(1)
+        fn_b = SharedCachedFunction(
+            lambda x: "v" * x,
+            32,
+            max_value_size=4096,
+            shm_name="test_mismatch_open",
+            on_mismatch="open_existing",
+        )
+
+        assert fn_b.cache_info().max_size == 16
+        assert fn_b.get(1) == 1
+        # The existing 64-byte value limit applies, not the requested one.
+        assert fn_b(100) == "v" * 100
+        assert fn_b.get(100) is None
+        assert fn_b.cache_info().oversize_skips == 1
+
+    def test_decorator_passes_on_mismatch(self):
+        @cache(max_size=16, backend="shared")
+        def fn(x):
+            return x
+
+        fn(1)
+        with pytest.raises(ValueError, match="on_mismatch"):
+
+            @cache(max_size=32, backend="shared")
+            def fn(x):  # noqa: F811 — same qualname, so same shm file
+                return x
+
+        @cache(max_size=32, backend="shared", on_mismatch="open_existing")
+        def fn(x):  # noqa: F811
+            return x
+
+        assert fn.get(1) == 1
+
+    def test_invalid_on_mismatch(self):
+        with pytest.raises(ValueError, match="on_mismatch"):
+
+            @cache(max_size=16, backend="shared", on_mismatch="ignore")
+            def fn(x):
+                return x
+
+
+class TestSharedShmDir:
+    def test_caches_in_different_dirs_do_not_collide(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as a, tempfile.TemporaryDirectory() as b:
+            fn_a = SharedCachedFunction(lambda x: ("a", x), 16, shm_name="test_dir", shm_dir=a)
+            fn_b = SharedCachedFunction(lambda x: ("b", x), 32, shm_name="test_dir", shm_dir=b)
+            assert fn_a(1) == ("a", 1)
+            assert fn_b(1) == ("b", 1)
+            assert fn_a.cache_info().max_size == 16
+            assert fn_b.cache_info().max_size == 32
+            assert os.path.exists(os.path.join(a, _USER_DIR, "test_dir.data"))
+            assert os.path.exists(os.path.join(b, _USER_DIR, "test_dir.data"))
+
+    def test_missing_dir_is_created(self):
+        with tempfile.TemporaryDirectory() as tmp:
+            base = os.path.join(tmp, "nested", "shm")
+
+            @cache(max_size=16, backend="shared", shm_dir=base)
+            def fn(x):
+                return x
+
+            assert fn(1) == 1
+            assert fn.get(1) == 1
+            assert os.path.isdir(os.path.join(base, _USER_DIR))
+
+    def test_env_var_sets_default_dir(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            os.environ["WARP_CACHE_SHM_DIR"] = tmp
+            try:
+                fn = SharedCachedFunction(lambda x: x, 16, shm_name="test_env_dir")
+            finally:
+                del os.environ["WARP_CACHE_SHM_DIR"]
+            fn(1)
+            path = os.path.join(tmp, _USER_DIR, "test_env_dir.data")
+            assert os.path.exists(path)
+
+    def test_unusable_dir_raises_oserror(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.NamedTemporaryFile() as f:
+            with pytest.raises(OSError, match="shm directory"):
+                SharedCachedFunction(
+                    lambda x: x, 16, shm_name="test_bad_dir", shm_dir=os.path.join(f.name, "sub")
+                )
+
+    def test_truncated_data_file_is_replaced(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, _USER_DIR, "test_truncated.data")
+            fn = SharedCachedFunction(lambda x: x, 64, shm_name="test_truncated", shm_dir=tmp)
+            fn(1)
+            size = os.path.getsize(path)
+            del fn
+            os.truncate(path, size // 3)
+
+            fn = SharedCachedFunction(lambda x: -x, 64, shm_name="test_truncated", shm_dir=tmp)
+            assert [fn(i) for i in range(64)] == [-i for i in range(64)]
+            assert os.path.getsize(path) == size
+
+    def test_lock_held_past_lock_timeout(self):
+        """A live writer that never releases the lock: with lock_timeout set,
+        lookups fall back to calling the function and writes raise."""
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            fn = SharedCachedFunction(
+                lambda x: -x, 64, shm_name="test_lock_timeout", shm_dir=tmp, lock_timeout=0.05
+            )
+            assert fn(1) == -1
+            path = os.path.join(tmp, _USER_DIR, "test_lock_timeout.lock")
+            with open(path, "r+b") as f, mmap.mmap(f.fileno(), 0) as lock:
+                # Mid-write, held by a process in another PID namespace, so
+                # it can't be judged dead and taken over.
+                seq, owner = struct.unpack_from("<QQ", lock)
+                struct.pack_into("<QQ", lock, 0, seq | 1, 1 << 63 | 1 << 32 | 1)
+                try:
+                    assert fn.get(1) is None
+                    with pytest.raises(RuntimeError, match="test_lock_timeout"):
+                        fn(2)
+                    assert fn.cache_info().lock_timeouts == 2
+                finally:
+                    struct.pack_into("<QQ", lock, 0, seq + 2, owner)
+            assert fn.get(1) == -1
+            assert fn(2) == -2
+
+        with pytest.raises(ValueError, match="lock_timeout"):
+            SharedCachedFunction(lambda x: x, 16, shm_name="test_lock_timeout", lock_timeout=0)
+
+    def test_checksums_catch_a_corrupted_value(self):
+        """A value scribbled over in the file is recomputed, not returned."""
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            fn = SharedCachedFunction(
+                lambda x: f"value-{x}", 64, shm_name="test_checksums", shm_dir=tmp, checksums=True
+            )
+            assert fn(1) == "value-1"
+            path = os.path.join(tmp, _USER_DIR, "test_checksums.data")
+            with open(path, "r+b") as f, mmap.mmap(f.fileno(), 0) as data:
+                at = data.find(b"value-1")
+                assert at >= 0
+                data[at] = ord("V")
+            assert fn.get(1) is None
+            assert fn.cache_info().corruption_detected == 1
+            assert fn(1) == "value-1"
+            assert fn.get(1) == "value-1"
+
+            with pytest.raises(ValueError, match="checksums"):
+                SharedCachedFunction(lambda x: x, 64, shm_name="test_checksums", shm_dir=tmp)
+
+    def test_value_in_an_unknown_serde_format_is_a_miss(self):
+        """A value a newer release wrote in a format this one can't read is
+        recomputed, not misread or raised on."""
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        calls = []
+
+        def compute(x):
+            calls.append(x)
+            return "result"
+
+        with tempfile.TemporaryDirectory() as tmp:
+            fn = SharedCachedFunction(compute, 16, shm_name="test_unknown_format", shm_dir=tmp)
+            assert fn(7) == "result"
+            path = os.path.join(tmp, _USER_DIR, "test_unknown_format.data")
+            with open(path, "r+b") as f, mmap.mmap(f.fileno(), 0) as data:
+                at = data.find(bytes([1, 28, 6]) + b"result")
+                assert at >= 0
+                data[at] = 2
+            assert fn.get(7) is None
+            assert fn.items() == []
+            assert fn(7) == "result"
+            assert fn(7) == "result"
+            assert calls == [7, 7]
+
+    @pytest.mark.parametrize(
+        "value, at, byte, reason",
+        [
+            # The str's 1-byte length, now past the end of the value.
+            ("result", 2, 100, "truncated str at byte 1"),
+            # The tuple's count, now one more than its elements.
+            ((1, 2), 2, 3, "truncated tuple at byte 1"),
+            # ... or one fewer.
+            ((1, 2), 2, 1, "2 stray bytes after the value at byte 5"),
+            # TAG_SMALL_INT turned into TAG_I64, which needs 8 bytes.
+            (7, 1, 4, "truncated int at byte 1"),
+            ("result", 1, 99, "unknown tag 99 at byte 1"),
+        ],
+    )
+    def test_corrupt_value_is_dropped_as_a_miss(self, value, at, byte, reason):
+        """Bytes that don't parse are a miss with a warning saying where,
+        never handed to pickle."""
+        import warnings
+
+        with tempfile.TemporaryDirectory() as tmp:
+            d = SharedDict("test_corrupt_value", 16, shm_dir=tmp)
+            d.set("k", value)
+            path = os.path.join(tmp, _USER_DIR, "test_corrupt_value.data")
+            with open(path, "r+b") as f, mmap.mmap(f.fileno(), 0) as data:
+                # The key's bytes, then the value's.
+                at += data.find(bytes([1, 28, 1]) + b"k" + bytes([1])) + 4
+                data[at] = byte
+            with warnings.catch_warnings(record=True) as caught:
+                warnings.simplefilter("always")
+                assert d.get("k", "missing") == "missing"
+            assert [w.category for w in caught] == [RuntimeWarning]
+            assert str(caught[0].message).endswith(f"doesn't deserialize: {reason}")
+            assert "k" not in d
+            assert d.info().corrupt_values == 1
+
+    def test_corrupt_value_is_recomputed(self):
+        import warnings
+
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        calls = []
+
+        def compute(x):
+            calls.append(x)
+            return "result"
+
+        with tempfile.TemporaryDirectory() as tmp:
+            fn = SharedCachedFunction(compute, 16, shm_name="test_corrupt_call", shm_dir=tmp)
+            assert fn(7) == "result"
+            path = os.path.join(tmp, _USER_DIR, "test_corrupt_call.data")
+            with open(path, "r+b") as f, mmap.mmap(f.fileno(), 0) as data:
+                data[data.find(bytes([1, 28, 6]) + b"result") + 2] = 100
+            with warnings.catch_warnings(record=True) as caught:
+                warnings.simplefilter("always")
+                assert fn(7) == "result"
+            assert len(caught) == 1
+            assert fn(7) == "result"
+            assert calls == [7, 7]
+            assert fn.cache_info().corrupt_values == 1
+
+    @pytest.mark.parametrize(
+        "value, name, target, kind",
+        [
+            (_Point(1, 2), b"_Point", b"_Pwned", "namedtuple naming"),
+            (
+                _Span(datetime.date(2024, 1, 1), datetime.date(2024, 1, 2)),
+                b"_Span",
+                b"_Pwnd",
+                "dataclass naming",
+            ),
+            (_Color.RED, b"_Color", b"_Pwned", "enum member naming"),
+        ],
+    )
+    def test_entry_naming_a_non_class_is_not_called(self, value, name, target, kind):
+        """A value naming its class is rebuilt only if what it names is the
+        kind of class its tag stands for."""
+        import warnings
+
+        with tempfile.TemporaryDirectory() as tmp:
+            d = SharedDict("test_not_a_class", 16, shm_dir=tmp)
+            d.set("k", value)
+            path = os.path.join(tmp, _USER_DIR, "test_not_a_class.data")
+            with open(path, "r+b") as f, mmap.mmap(f.fileno(), 0) as data:
+                at = data.find(struct.pack("<I", len(name)) + name) + 4
+                data[at : at + len(name)] = target
+            with warnings.catch_warnings(record=True) as caught:
+                warnings.simplefilter("always")
+                assert d.get("k", "missing") == "missing"
+            assert _pwned_calls == []
+            assert [w.category for w in caught] == [RuntimeWarning]
+            message = str(caught[0].message)
+            assert f"{kind} {__name__}.{target.decode()}, which isn't a" in message
+            assert d.info().corrupt_values == 1
+
+    def test_warns_when_writers_disagree_on_the_serde_format(self):
+        import warnings
+
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            with warnings.catch_warnings():
+                warnings.simplefilter("error")
+                SharedCachedFunction(lambda x: x, 16, shm_name="test_format_warn", shm_dir=tmp)
+                SharedCachedFunction(lambda x: x, 16, shm_name="test_format_warn", shm_dir=tmp)
+            path = os.path.join(tmp, _USER_DIR, "test_format_warn.data")
+            # `Header::serde_format`, recorded by the writers above.
+            with open(path, "r+b") as f, mmap.mmap(f.fileno(), 0) as data:
+                assert data[276] == 1
+                data[276] = 2
+            with warnings.catch_warnings(record=True) as caught:
+                warnings.simplefilter("always")
+                SharedCachedFunction(lambda x: x, 16, shm_name="test_format_warn", shm_dir=tmp)
+            assert [w.category for w in caught] == [RuntimeWarning]
+            assert "serde format 2, newer than this release reads" in str(caught[0].message)
+
+    def test_lock_memory_and_prefault(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            fn = SharedCachedFunction(
+                lambda x: -x, 64, shm_name="test_pinned", shm_dir=tmp, prefault=True
+            )
+            assert fn(1) == -1
+            assert fn.cache_info().memory_locked is False
+            try:
+                locked = SharedCachedFunction(
+                    lambda x: -x, 64, shm_name="test_pinned", shm_dir=tmp, lock_memory=True
+                )
+            except OSError as e:
+                assert sys.platform == "win32" or "RLIMIT_MEMLOCK" in str(e)
+            else:
+                assert locked.cache_info().memory_locked is True
+                assert locked.get(1) == -1
+
+    def test_fallback_when_the_cache_file_cannot_be_opened(self):
+        import warnings
+
+        calls = []
+
+        def double(x):
+            calls.append(x)
+            return x * 2
+
+        with tempfile.NamedTemporaryFile() as f:
+            # A directory under a regular file can never be created.
+            bad_dir = os.path.join(f.name, "sub")
+            with pytest.raises(OSError):
+                cache(backend="shared", shm_dir=bad_dir)(double)
+
+            with warnings.catch_warnings(record=True) as caught:
+                warnings.simplefilter("always")
+                local = cache(backend="shared", shm_dir=bad_dir, fallback="local")(double)
+                uncached = cache(backend="shared", shm_dir=bad_dir, fallback="nocache")(double)
+            messages = [str(w.message) for w in caught]
+            assert messages[0].endswith("falling back to an in-process cache")
+            assert messages[1].endswith("falling back to no caching")
+            assert len(messages) == 2
+
+        assert (local(3), local(3)) == (6, 6)
+        assert calls == [3]
+        info = local.cache_info()
+        assert (info.backend, info.hits, info.misses) == ("local-fallback", 1, 1)
+        # Arguments the shared backend would have taken still work in-process.
+        assert local([1]) == local([1]) == [1, 1]
+        assert calls == [3, [1]]
+
+        calls.clear()
+        assert (uncached(3), uncached(3)) == (6, 6)
+        assert calls == [3, 3]
+        assert uncached.get(3) is None
+        assert uncached.cache_info().backend == "nocache-fallback"
+        assert uncached.cache_info().misses == 2
+
+        with pytest.raises(ValueError):
+            cache(backend="shared", fallback="sometimes")
+
+
+class TestSharedConfigured:
+    def test_constructors_read_the_configured_defaults(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            try:
+                configure(default_shm_dir=tmp, default_max_size=8, oversize="raise")
+                fn = SharedCachedFunction(lambda n: "x" * n, shm_name="test_configured")
+                assert fn.cache_info().max_size == 8
+                with pytest.raises(ValueError):
+                    fn(10_000)
+                explicit = SharedCachedFunction(
+                    lambda x: x, 16, shm_name="test_configured_explicit", oversize="skip"
+                )
+                assert explicit.cache_info().max_size == 16
+                assert explicit("x" * 10_000) == "x" * 10_000
+
+                shared = SharedDict("test_configured_dict")
+                assert shared.info().max_size == 8
+                assert os.path.exists(os.path.join(tmp, _USER_DIR, "test_configured_dict.data"))
+                assert SharedDict("test_configured_dict_16", 16).info().max_size == 16
+            finally:
+                configure(default_shm_dir=None, default_max_size=None, oversize=None)
+
+    def test_configured_strategy_picks_the_shared_backend(self):
+        with tempfile.TemporaryDirectory() as tmp:
+            try:
+                configure(default_strategy="shared", shm_dir=tmp)
+
+                @cache(max_size=16)
+                def fn(x):
+                    return x
+
+                @cache(max_size=16, backend="memory")
+                def local(x):
+                    return x
+
+            finally:
+                configure(default_strategy=None, shm_dir=None)
+            assert isinstance(fn.cache_info(), SharedCacheInfo)
+            assert not isinstance(local.cache_info(), SharedCacheInfo)
+
+
+class TestGcShared:
+    @staticmethod
+    def _make(tmp, name):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        fn = SharedCachedFunction(lambda x: x, 16, shm_name=name, shm_dir=tmp)
+        fn(1)
+        return fn
+
+    @staticmethod
+    def _files(tmp):
+        # Windows keeps the (empty) .init lock files; see region.rs::lock_init_file.
+        files = os.listdir(os.path.join(tmp, _USER_DIR))
+        return sorted(f for f in files if sys.platform != "win32" or not f.endswith(".init"))
+
+    @staticmethod
+    def _age(tmp, name, seconds):
+        past = os.path.getmtime(tmp) - seconds
+        for f in glob.glob(os.path.join(tmp, _USER_DIR, f"{name}.*")):
+            os.utime(f, (past, past))
+
+    def test_removes_only_closed_stale_caches(self):
+        from warp_cache import gc_shared
+
+        with tempfile.TemporaryDirectory() as tmp:
+            active = self._make(tmp, "active")
+            self._make(tmp, "stale")  # handle dropped immediately
+            self._make(tmp, "recent")
+            self._age(tmp, "active", 3600)
+            self._age(tmp, "stale", 3600)
+
+            assert gc_shared(600, shm_dir=tmp) == ["stale"]
+            assert not any(f.startswith("stale.") for f in self._files(tmp))
+            assert "recent.data" in self._files(tmp)
+            assert active.get(1) == 1
+
+            del active
+            assert gc_shared(600, shm_dir=tmp) == ["active"]
+            assert gc_shared(0, shm_dir=tmp) == ["recent"]
+            assert self._files(tmp) == []
+
+    def test_dry_run_keeps_files(self):
+        from warp_cache import gc_shared
+
+        with tempfile.TemporaryDirectory() as tmp:
+            self._make(tmp, "stale")
+            before = self._files(tmp)
+            assert gc_shared(0, dry_run=True, shm_dir=tmp) == ["stale"]
+            assert self._files(tmp) == before
+
+    def test_reopen_after_gc(self):
+        from warp_cache import gc_shared
+
+        with tempfile.TemporaryDirectory() as tmp:
+            self._make(tmp, "cache")
+            assert gc_shared(0, shm_dir=tmp) == ["cache"]
+            fn = self._make(tmp, "cache")
+            assert fn.cache_info().current_size == 1
+
+    def test_missing_dir_and_bad_age(self):
+        from warp_cache import gc_shared
+
+        with tempfile.TemporaryDirectory() as tmp:
+            assert gc_shared(0, shm_dir=os.path.join(tmp, "nope")) == []
+            with pytest.raises(ValueError, match="max_age_seconds"):
+                gc_shared(-1, shm_dir=tmp)
+
+
+class TestListShared:
+    def test_lists_caches_with_stats(self):
+        from warp_cache import list_shared
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            assert list_shared(shm_dir=tmp) == []
+            fn = SharedCachedFunction(lambda x: x, 16, ttl=30, shm_name="b_cache", shm_dir=tmp)
+            fn(1)
+            fn(1)
+            fn(2)
+            other = SharedCachedFunction(lambda x: x, 8, shm_name="a_cache", shm_dir=tmp)
+
+            listed = list_shared(shm_dir=tmp)
+            assert [c["name"] for c in listed] == ["a_cache", "b_cache"]
+            a, b = listed
+            assert a["ttl"] is None
+            assert a["current_size"] == 0
+            assert b["invalid"] is False
+            assert b["strategy"] == "sieve"
+            assert (b["capacity"], b["current_size"]) == (16, 2)
+            assert (b["hits"], b["misses"], b["oversize_skips"]) == (1, 2, 0)
+            assert b["ttl"] == 30.0
+            data = os.path.join(tmp, _USER_DIR, "b_cache.data")
+            assert b["file_size"] == os.path.getsize(data)
+            del fn, other
+
+    def test_foreign_files_are_flagged(self):
+        from warp_cache import list_shared
+
+        with tempfile.TemporaryDirectory() as tmp:
+            shm = os.path.join(tmp, _USER_DIR)
+            os.makedirs(shm, mode=0o700)
+            with open(os.path.join(shm, "junk.data"), "wb") as f:
+                f.write(b"hello")
+            with open(os.path.join(shm, "notes.txt"), "wb") as f:
+                f.write(b"ignored")
+
+            (junk,) = list_shared(shm_dir=tmp)
+            assert junk["name"] == "junk"
+            assert junk["invalid"] is True
+            assert junk["file_size"] == 5
+            assert junk["reason"]
+
+
+class TestSharedSnapshot:
+    def test_round_trip_across_recreate(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, "cache.snap")
+            fn = SharedCachedFunction(
+                lambda x, kw=None: {"n": x}, 32, ttl=3600, shm_name="test_snap"
+            )
+            for i in range(10):
+                fn(i)
+            fn(("tuple", 1), kw="arg")
+            assert fn.save(path) == 11
+            keys_before = fn.keys()
+
+            fn = SharedCachedFunction(
+                lambda x: None, 64, ttl=3600, shm_name="test_snap", on_mismatch="recreate"
+            )
+            assert fn.cache_info().current_size == 0
+            assert fn.load(path) == {"inserted": 11, "oversize_skipped": 0, "expired_skipped": 0}
+            assert fn.keys() == keys_before
+            assert [fn.get(i) for i in range(10)] == [{"n": i} for i in range(10)]
+            assert fn.get(("tuple", 1), kw="arg") == {"n": ("tuple", 1)}
+
+    def test_expired_and_oversize_entries_are_skipped(self):
+        import time
+
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, "cache.snap")
+            fn = SharedCachedFunction(lambda x: x, 16, shm_name="test_snap_skip")
+            fn.set("x" * 1000, "big")
+            fn.set("short-lived", "soon", _warp_ttl=0.05)
+            fn.set("kept", "kept")
+            assert fn.save(path) == 3
+            time.sleep(0.1)
+
+            small = SharedCachedFunction(
+                lambda x: x, 16, max_value_size=100, shm_name="test_snap_skip_small"
+            )
+            assert small.load(path) == {
+                "inserted": 1,
+                "oversize_skipped": 1,
+                "expired_skipped": 1,
+            }
+            assert small.get("kept") == "kept"
+            assert small.cache_info().oversize_skips == 1
+
+    def test_remaining_ttl_carries_over(self):
+        import time
+
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, "cache.snap")
+            fn = SharedCachedFunction(lambda x: x, 16, ttl=0.3, shm_name="test_snap_ttl")
+            fn(1)
+            time.sleep(0.15)
+            fn.save(path)
+
+            fresh = SharedCachedFunction(lambda x: x, 16, shm_name="test_snap_ttl_load")
+            assert fresh.load(path)["inserted"] == 1
+            assert fresh.get(1) == 1
+            time.sleep(0.25)
+            assert fresh.get(1) is None
+
+    def test_bad_file_raises(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        fn = SharedCachedFunction(lambda x: x, 16, shm_name="test_snap_bad")
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, "not-a-snapshot")
+            with open(path, "wb") as f:
+                f.write(b"hello world, definitely not a snapshot")
+            with pytest.raises(ValueError, match="not a warp_cache snapshot"):
+                fn.load(path)
+            with pytest.raises(OSError):
+                fn.load(os.path.join(tmp, "missing"))
+
+
+class TestSharedPersistent:
+    def test_entries_survive_reopen(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            fn = SharedCachedFunction(
+                lambda x: x * 2, 16, ttl=3600, shm_name="test_durable", shm_dir=tmp, persistent=True
+            )
+            for i in range(5):
+                fn(i)
+            fn.sync()
+            del fn
+
+            fn = SharedCachedFunction(
+                lambda x: -1, 16, ttl=3600, shm_name="test_durable", shm_dir=tmp, persistent=True
+            )
+            assert [fn.get(i) for i in range(5)] == [0, 2, 4, 6, 8]
+            assert fn.cache_info().current_size == 5
+
+    def test_default_dir_is_the_user_cache_dir(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        var = "LOCALAPPDATA" if sys.platform == "win32" else "XDG_CACHE_HOME"
+        with tempfile.TemporaryDirectory() as tmp:
+            old = os.environ.get(var)
+            os.environ[var] = tmp
+            try:
+                fn = SharedCachedFunction(lambda x: x, 16, shm_name="test_xdg", persistent=True)
+            finally:
+                if old is None:
+                    del os.environ[var]
+                else:
+                    os.environ[var] = old
+            fn(1)
+            user_dir = os.path.join(tmp, "warp_cache", _USER_DIR)
+            assert os.path.exists(os.path.join(user_dir, "test_xdg.data"))
+
+    def test_persistent_mismatch_is_a_config_mismatch(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            keep = SharedCachedFunction(lambda x: x, 16, shm_name="test_durable_mm", shm_dir=tmp)
+            with pytest.raises(ValueError, match="durable"):
+                SharedCachedFunction(
+                    lambda x: x, 16, shm_name="test_durable_mm", shm_dir=tmp, persistent=True
+                )
+            del keep
+
+    def test_sync_interval_validation(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with pytest.raises(ValueError, match="requires persistent"):
+            SharedCachedFunction(lambda x: x, 16, shm_name="test_sync_iv", sync_interval=1.0)
+        with tempfile.TemporaryDirectory() as tmp:
+            with pytest.raises(ValueError, match="sync_interval"):
+                SharedCachedFunction(
+                    lambda x: x,
+                    16,
+                    shm_name="test_sync_iv",
+                    shm_dir=tmp,
+                    persistent=True,
+                    sync_interval=0,
+                )
+            fn = SharedCachedFunction(
+                lambda x: x,
+                16,
+                shm_name="test_sync_iv",
+                shm_dir=tmp,
+                persistent=True,
+                sync_interval=0.01,
+            )
+            for i in range(20):
+                fn(i)
+            assert fn.cache_info().current_size == 16
+
+
+class TestSharedCompression:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    @pytest.mark.parametrize("codec", ["lz4", "zstd"])
+    def test_compressed_values_fit_under_max_value_size(self, codec):
+        blob = {"rows": [{"id": i, "name": "x" * 20} for i in range(500)]}
+
+        @cache(max_size=8, backend="shared", max_value_size=4096, compress=codec)
+        def fetch(key):
+            return blob
+
+        assert fetch("a") == blob
+        assert fetch("a") == blob
+        info = fetch.cache_info()
+        assert (info.hits, info.oversize_skips, info.current_size) == (1, 0, 1)
+        assert info.bytes_used < 4096
+
+        @cache(max_size=8, backend="shared", max_value_size=4096)
+        def fetch_raw(key):
+            return blob
+
+        fetch_raw("a")
+        assert fetch_raw.cache_info().oversize_skips == 1
+
+    def test_small_values_and_counters_are_stored_as_is(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        fn = SharedCachedFunction(
+            lambda x: x, 16, shm_name="test_compress_small", compress="zstd", compress_threshold=64
+        )
+        fn.set("short", "k")
+        assert fn.get("k") == "short"
+        assert fn.incr("n", amount=5) == 5
+        assert fn.cas(5, "y" * 1000, "n")
+        assert fn.get("n") == "y" * 1000
+        assert fn.cas("y" * 1000, 6, "n")
+        assert fn.setdefault("z" * 1000, "d") == "z" * 1000
+        assert fn.items() == [(("k",), "short"), (("n",), 6), (("d",), "z" * 1000)]
+
+    def test_codec_is_part_of_the_config(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        keep = SharedCachedFunction(lambda x: x, 16, shm_name="test_compress_mm", compress="lz4")
+        with pytest.raises(ValueError, match="compress: existing lz4, requested None"):
+            SharedCachedFunction(lambda x: x, 16, shm_name="test_compress_mm")
+        adopted = SharedCachedFunction(
+            lambda x: x, 16, shm_name="test_compress_mm", on_mismatch="open_existing"
+        )
+        adopted.set("v" * 5000, 1)
+        assert keep.get(1) == "v" * 5000
+        assert adopted.cache_info().oversize_skips == 0
+
+    def test_unknown_codec_raises(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with pytest.raises(ValueError, match="compress must be"):
+            SharedCachedFunction(lambda x: x, 16, shm_name="test_compress_bad", compress="gzip")
+
+    def test_snapshot_moves_between_codecs(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, "cache.snap")
+            packed = SharedCachedFunction(
+                lambda x: x, 16, shm_name="test_compress_snap", compress="lz4"
+            )
+            packed.set("w" * 3000, "big")
+            packed.save(path)
+
+            plain = SharedCachedFunction(lambda x: x, 16, shm_name="test_compress_snap_plain")
+            assert plain.load(path)["inserted"] == 1
+            assert plain.get("big") == "w" * 3000
+
+            other = SharedCachedFunction(
+                lambda x: x,
+                16,
+                max_value_size=512,
+                shm_name="test_compress_snap_zstd",
+                compress="zstd",
+            )
+            assert other.load(path)["inserted"] == 1
+            assert other.get("big") == "w" * 3000
+
+    def test_other_threads_run_while_a_large_value_is_stored(self):
+        # Compressing and copying 50 MB happens without the GIL: a pure-Python
+        # thread never goes more than a fraction of the call without running.
+        import threading
+        import time
+
+        blob = os.urandom(50 * 1024 * 1024)
+
+        @cache(max_size=2, backend="shared", max_value_size=64 * 1024 * 1024, compress="zstd")
+        def fetch(key):
+            return blob
+
+        stamps = []
+        done = threading.Event()
+
+        def spin():
+            while not done.is_set():
+                stamps.append(time.perf_counter())
+
+        thread = threading.Thread(target=spin)
+        thread.start()
+        try:
+            while not stamps:
+                time.sleep(0.001)
+            start = time.perf_counter()
+            fetch("big")
+            end = time.perf_counter()
+        finally:
+            done.set()
+            thread.join()
+        ticks = [start, *(t for t in stamps if start < t < end), end]
+        longest_stall = max(b - a for a, b in zip(ticks, ticks[1:]))
+        assert longest_stall < (end - start) / 2
+        assert fetch("big") == blob
+        assert fetch.cache_info().hits == 1
+
+
+def _json_dumps(value):
+    import json
+
+    return json.dumps(value, sort_keys=True).encode()
+
+
+def _json_loads(data):
+    import json
+
+    return json.loads(data)
+
+
+class TestSharedSerializer:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_custom_serializer_replaces_pickle(self):
+        calls = []
+
+        def dumps(value):
+            calls.append(value)
+            return _json_dumps(value)
+
+        @cache(max_size=8, backend="shared", serializer=(dumps, _json_loads))
+        def lookup(key):
+            return collections.OrderedDict(key=key, tags=["a", "b"])
+
+        expected = {"key": 1, "tags": ["a", "b"]}
+        assert lookup(1) == expected
+        assert lookup(1) == expected
+        assert lookup.cache_info().hits == 1
+        # Only the OrderedDict (a dict subclass) went through the fallback; the
+        # int key took the fast path.
+        assert calls == [expected]
+
+    def test_serializer_is_part_of_the_config(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        keep = SharedCachedFunction(
+            lambda x: x, 16, shm_name="test_serializer_mm", serializer=(_json_dumps, _json_loads)
+        )
+        expected = "serializer: existing .*_json_dumps, requested pickle"
+        with pytest.raises(ValueError, match=expected):
+            SharedCachedFunction(lambda x: x, 16, shm_name="test_serializer_mm")
+        del keep
+
+    def test_explicit_pickle_matches_the_default(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        a = SharedCachedFunction(lambda x: [x], 16, shm_name="test_serializer_pickle")
+        b = SharedCachedFunction(
+            lambda x: None,
+            16,
+            shm_name="test_serializer_pickle",
+            serializer=(pickle.dumps, pickle.loads),
+        )
+        a(3)
+        assert b.get(3) == [3]
+
+    def test_serializer_must_be_callables(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with pytest.raises(TypeError, match="pair of callables"):
+            SharedCachedFunction(
+                lambda x: x, 16, shm_name="test_serializer_bad", serializer=("json", _json_loads)
+            )
+
+
+def _encode_money(m):
+    return b"money:%d:%s" % (m.cents, m.currency.encode())
+
+
+def _decode_money(data):
+    _, cents, currency = data.decode().split(":")
+    return _Money(int(cents), currency)
+
+
+class TestRegisterSerializer:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_registered_type_skips_pickle(self):
+        from warp_cache import register_serializer
+
+        register_serializer(_Money, _encode_money, _decode_money)
+        pickled = []
+
+        def dumps(value):
+            pickled.append(value)
+            return pickle.dumps(value)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def convert(m):
+            return [m, _Money(m.cents * 2, "EUR")]
+
+        expected = [_Money(150, "USD"), _Money(300, "EUR")]
+        assert convert(_Money(150, "USD")) == expected  # miss
+        result = convert(_Money(150, "USD"))  # hit
+        assert result == expected
+        assert type(result[1]) is _Money
+        assert convert.cache_info().hits == 1
+        assert pickled == []
+
+    def test_registration_applies_to_existing_caches(self):
+        from warp_cache import register_serializer
+
+        pickled = []
+
+        def dumps(value):
+            pickled.append(value)
+            return pickle.dumps(value)
+
+        @cache(max_size=16, backend="shared", serializer=(dumps, pickle.loads))
+        def warmer(t):
+            return _Celsius(t + 1)
+
+        assert warmer(1.0) == _Celsius(2.0)
+        assert pickled == [_Celsius(2.0)]
+
+        register_serializer(_Celsius, lambda t: struct.pack("<d", t.degrees), _decode_celsius)
+        assert warmer(2.0) == _Celsius(3.0)
+        assert warmer(2.0) == _Celsius(3.0)
+        assert warmer.cache_info().hits == 1
+        assert pickled == [_Celsius(2.0)]
+
+    def test_unregistered_id_is_a_miss_left_in_place(self):
+        """A value whose id this process has no decoder for reads as a miss,
+        with a warning, and stays for the processes that do."""
+        import warnings
+
+        from warp_cache import register_serializer
+
+        register_serializer(_Money, _encode_money, _decode_money)
+        with tempfile.TemporaryDirectory() as tmp:
+            d = SharedDict("test_unregistered_id", 16, shm_dir=tmp)
+            d.set("k", _Money(5, "GBP"))
+            assert d.get("k") == _Money(5, "GBP")
+            path = os.path.join(tmp, _USER_DIR, "test_unregistered_id.data")
+            with open(path, "r+b") as f, mmap.mmap(f.fileno(), 0) as data:
+                # The last byte of the id, before the payload's 1-byte length.
+                at = data.find(b"money:5:GBP") - 2
+                data[at] ^= 0xFF
+            with warnings.catch_warnings(record=True) as caught:
+                warnings.simplefilter("always")
+                assert d.get("k", "missing") == "missing"
+            assert [w.category for w in caught] == [RuntimeWarning]
+            assert "registered with id 0x" in str(caught[0].message)
+            assert len(d) == 1
+            assert d.info().corrupt_values == 0
+
+    def test_encoder_must_return_bytes(self):
+        from warp_cache import register_serializer
+
+        class Odd:
+            pass
+
+        register_serializer(Odd, lambda o: "text", lambda data: Odd())
+        with tempfile.TemporaryDirectory() as tmp:
+            d = SharedDict("test_encoder_bytes", 16, shm_dir=tmp)
+            with pytest.raises(TypeError, match="returned str, not bytes"):
+                d.set("k", Odd())
+
+    def test_callables_are_checked(self):
+        from warp_cache import register_serializer
+
+        with pytest.raises(TypeError, match="must be callable"):
+            register_serializer(_Money, _encode_money, "decode")
+
+
+def _decode_celsius(data):
+    return _Celsius(struct.unpack("<d", data)[0])
+
+
+class TestSharedLocalStats:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_local_counts_diverge_across_handles(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        a = SharedCachedFunction(lambda x: x, 16, shm_name="test_local_stats", max_key_size=64)
+        b = SharedCachedFunction(lambda x: x, 16, shm_name="test_local_stats", max_key_size=64)
+        a(1)
+        a(1)
+        b(1)
+        b(2)
+        b("k" * 100)
+
+        total, la, lb = a.cache_info(), a.local_cache_info(), b.local_cache_info()
+        assert (total.hits, total.misses, total.oversize_key_skips) == (2, 2, 1)
+        assert (la.hits, la.misses, la.oversize_key_skips) == (1, 1, 0)
+        assert (lb.hits, lb.misses, lb.oversize_key_skips) == (1, 1, 1)
+        assert la.current_size == total.current_size == 2
+
+        b.reset_stats(local_only=True)
+        assert (b.local_cache_info().hits, b.local_cache_info().misses) == (0, 0)
+        assert a.cache_info().hits == 2
+
+        a.reset_stats()
+        assert (a.cache_info().hits, a.cache_info().misses) == (0, 0)
+        assert a.local_cache_info().hits == 0
+        assert a.cache_info().current_size == 2
+
+        b(3)
+        assert b.local_cache_info().misses == 1
+        b.cache_clear()
+        assert b.local_cache_info().misses == 0
+
+    def test_contention_info_stays_at_zero_single_threaded(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        fn = SharedCachedFunction(lambda x: x, 64, shm_name="test_contention")
+        for i in range(20):
+            fn(i)
+            fn(i)
+        info = fn.contention_info()
+        assert set(info) == {"read_waits", "read_retries", "write_spins", "write_locks"}
+        assert (info["read_waits"], info["read_retries"], info["write_spins"]) == (0, 0, 0)
+        # Each miss stored its result under the write lock.
+        assert info["write_locks"] >= 20
+
+        fn.reset_stats(local_only=True)
+        assert set(fn.contention_info().values()) == {0}
+
+
+class TestSharedDebugInfo:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_dump_matches_the_cache(self):
+        @cache(max_size=4, backend="shared", ttl=60)
+        def fn(x):
+            return x
+
+        fn(1)
+        fn(2)
+        fn(1)
+        dump = fn.debug_info()
+        header = dump["header"]
+        assert (header["capacity"], header["current_size"]) == (4, 2)
+        assert len(dump["slots"]) == 4
+        assert len(dump["eviction_order"]) == 2
+        assert len(dump["free_list"]) == 2
+        assert sorted(dump["eviction_order"] + dump["free_list"]) == [0, 1, 2, 3]
+
+        first, second = (dump["slots"][i] for i in dump["eviction_order"])
+        assert (first["occupied"], first["visited"], second["visited"]) == (1, True, False)
+        assert first["next"] == second["index"] and second["prev"] == first["index"]
+        assert 0 <= first["age"] < 60 and first["ttl"] is None
+        assert all(dump["slots"][i]["occupied"] == 0 for i in dump["free_list"])
+
+        capped = fn.debug_info(max_slots=1)
+        assert (len(capped["slots"]), capped["eviction_order"]) == (1, dump["eviction_order"][:1])
+
+    def test_verify_stays_clean_under_random_operations(self):
+        import random
+
+        @cache(max_size=8, backend="shared", max_value_size=64, chunk_size=16)
+        def fn(x):
+            return "v" * x
+
+        rng = random.Random(871)
+        for _ in range(500):
+            key = rng.randrange(16)
+            op = rng.random()
+            if op < 0.6:
+                fn.set("v" * rng.randrange(60), key)
+            elif op < 0.8:
+                fn(rng.randrange(40))
+            elif op < 0.98:
+                fn.get(key)
+            else:
+                fn.cache_clear()
+            assert fn.verify() == []
+
+
+class TestSharedReleaseMemory:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_clear_with_release_memory_shrinks_the_file(self):
+        @cache(max_size=512, backend="shared", max_value_size=4096)
+        def fn(x):
+            return str(x) * 100
+
+        for i in range(512):
+            fn(i)
+        before = fn.cache_info().allocated_bytes
+        assert before > 512 * 4096
+
+        fn.cache_clear(release_memory=True)
+        info = fn.cache_info()
+        assert info.current_size == 0
+        if sys.platform.startswith("linux"):
+            assert info.allocated_bytes < before // 4
+        assert fn.verify() == []
+
+        # The cache refills as usual
+        assert [fn(i) for i in range(600)] == [str(i) * 100 for i in range(600)]
+        assert fn.cache_info().current_size == 512
+        assert fn(599) == "599" * 100
+        assert fn.verify() == []
+
+    def test_memory_backend_accepts_release_memory(self):
+        @cache(max_size=16)
+        def fn(x):
+            return x
+
+        fn(1)
+        fn.cache_clear(release_memory=True)
+        assert fn.cache_info().current_size == 0
+
+
+class TestSharedReadonly:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_reader_sees_writes_without_changing_anything(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        calls = []
+
+        def fn(x):
+            calls.append(x)
+            return x * 2
+
+        writer = SharedCachedFunction(fn, 16, shm_name="test_readonly")
+        reader = SharedCachedFunction(fn, 16, shm_name="test_readonly", readonly=True)
+        writer(1)
+        assert reader(1) == 2 and reader.get(1) == 2
+        # A miss is computed every time, never stored
+        assert reader(5) == 10 and reader(5) == 10
+        assert calls == [1, 5, 5]
+        assert writer.get(5) is None
+
+        shared, local = writer.cache_info(), reader.local_cache_info()
+        assert (shared.hits, shared.misses, shared.current_size) == (0, 2, 1)
+        assert (local.hits, local.misses) == (2, 2)
+        (slot,) = (s for s in writer.debug_info()["slots"] if s["occupied"])
+        assert slot["visited"] is False
+
+        writer.set(30, 3)
+        assert reader.get(3) == 30
+
+    def test_writes_raise(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        writer = SharedCachedFunction(lambda x: x, 16, shm_name="test_readonly_w")
+        writer(1)
+        reader = SharedCachedFunction(lambda x: x, 16, shm_name="test_readonly_w", readonly=True)
+        for write in (
+            lambda: reader.set(1, 2),
+            lambda: reader.cache_clear(),
+            lambda: reader.incr(3),
+            lambda: reader.touch(1),
+        ):
+            with pytest.raises(RuntimeError, match="read-only"):
+                write()
+        assert writer.get(1) == 1
+
+        d = SharedDict("test_readonly_dict", 16)
+        d.set("a", 1)
+        ro = SharedDict("test_readonly_dict", 16, readonly=True)
+        assert ro.get("a") == 1 and "a" in ro and len(ro) == 1
+        for write in (lambda: ro.set("b", 2), lambda: ro.delete("a"), ro.clear):
+            with pytest.raises(RuntimeError, match="read-only"):
+                write()
+        assert d.get("a") == 1
+
+    def test_open_never_creates_or_replaces(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with pytest.raises(FileNotFoundError):
+            SharedCachedFunction(lambda x: x, 16, shm_name="test_readonly_none", readonly=True)
+
+        writer = SharedCachedFunction(lambda x: x, 16, shm_name="test_readonly_mm")
+        writer(1)
+        with pytest.raises(ValueError, match="open_existing"):
+            SharedCachedFunction(lambda x: x, 32, shm_name="test_readonly_mm", readonly=True)
+        with pytest.raises(ValueError, match="readonly"):
+            SharedCachedFunction(
+                lambda x: x, 32, shm_name="test_readonly_mm", readonly=True, on_mismatch="recreate"
+            )
+        reader = SharedCachedFunction(
+            lambda x: x, 32, shm_name="test_readonly_mm", readonly=True, on_mismatch="open_existing"
+        )
+        assert reader.cache_info().max_size == 16
+        assert writer.get(1) == 1
+
+
+class TestSharedCacheVersion:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_version_bump_never_hits_old_entries(self):
+        from warp_cache import list_shared
+
+        with tempfile.TemporaryDirectory() as tmp:
+            calls = []
+
+            def price(x):
+                calls.append(x)
+                return x * 10
+
+            v1 = cache(max_size=16, backend="shared", shm_dir=tmp, cache_version=1)(price)
+            v2 = cache(max_size=16, backend="shared", shm_dir=tmp, cache_version="2")(price)
+            same = cache(max_size=16, backend="shared", shm_dir=tmp, cache_version="1")(price)
+            v1(1)
+            assert v2(1) == 10 and calls == [1, 1]
+            assert v2.cache_info().hits == 0
+            # 1 and "1" name the same version.
+            assert same(1) == 10 and calls == [1, 1]
+            assert v1.cache_info().cache_version == "1"
+
+            listed = {c["cache_version"] for c in list_shared(shm_dir=tmp)}
+            assert listed == {"1", "2"}
+
+    def test_explicit_name_checks_the_stored_version(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        fn_a = SharedCachedFunction(lambda x: x, 16, shm_name="test_version", cache_version="a")
+        fn_a(1)
+        with pytest.raises(ValueError, match="cache_version"):
+            SharedCachedFunction(lambda x: x, 16, shm_name="test_version")
+        with pytest.raises(ValueError, match="recreate"):
+            SharedCachedFunction(
+                lambda x: x,
+                16,
+                shm_name="test_version",
+                cache_version="b",
+                on_mismatch="open_existing",
+            )
+        assert fn_a.get(1) == 1
+
+        fn_b = SharedCachedFunction(
+            lambda x: x, 16, shm_name="test_version", cache_version="b", on_mismatch="recreate"
+        )
+        assert fn_b.get(1) is None
+        assert fn_b.cache_info().cache_version == "b"
+
+    @pytest.mark.parametrize(
+        "version, exc",
+        [(1.5, TypeError), (True, TypeError), ("", ValueError), ("x" * 65, ValueError)],
+    )
+    def test_invalid_version(self, version, exc):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with pytest.raises(exc, match="cache_version"):
+            SharedCachedFunction(
+                lambda x: x, 16, shm_name="test_bad_version", cache_version=version
+            )
+
+
+class TestSharedTags:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_invalidate_tag_removes_exactly_the_tagged_entries(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        def tags(args, kwargs, result):
+            return [f"user:{args[0]}"] if kwargs.get("tag", True) else []
+
+        def load(user, n, tag=True):
+            return n
+
+        fn = SharedCachedFunction(load, 16, shm_name="test_tags", tags_func=tags)
+        other = SharedCachedFunction(load, 16, shm_name="test_tags")
+        fn(42, 0)
+        fn(42, 1)
+        fn(7, 0)
+        fn(42, 2, tag=False)
+        other.set(3, 42, 3, _warp_tags=["user:42", "bulk"])
+
+        # Any process attached to the cache sees the removal.
+        assert other.invalidate_tag("user:42") == 3
+        assert fn.get(42, 0) is None and fn.get(42, 1) is None and fn.get(42, 3) is None
+        assert fn.get(7, 0) == 0 and fn.get(42, 2, tag=False) == 2
+        assert fn.invalidate_tag("bulk") == 0
+        assert fn.verify() == []
+
+    def test_tag_limits(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        fn = SharedCachedFunction(lambda x: x, 16, shm_name="test_tags_limits")
+        fn.set(1, 1, _warp_tags=["a", "b", "a", "c", "d"])
+        assert fn.invalidate_tag("d") == 1
+        with pytest.raises(ValueError, match="at most 4 tags"):
+            fn.set(1, 1, _warp_tags=["a", "b", "c", "d", "e"])
+        with pytest.raises(TypeError, match="tags must be str"):
+            fn.set(1, 1, _warp_tags=[1])
+        with pytest.raises(TypeError, match="tags_func must be callable"):
+            SharedCachedFunction(lambda x: x, 16, shm_name="test_tags_limits", tags_func=1)
+
+    def test_decorator_passes_tags_func(self):
+        @cache(max_size=16, backend="shared", tags_func=lambda args, kwargs, result: ["all"])
+        def fn(x):
+            return x
+
+        fn(1)
+        fn(2)
+        assert fn.invalidate_tag("all") == 2
+        assert fn.cache_info().current_size == 0
+
+
+class TestSharedMinComputeTime:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_only_slow_results_are_stored(self):
+        import time
+
+        calls = []
+
+        @cache(max_size=16, backend="shared", min_compute_time=0.02)
+        def compute(x):
+            calls.append(x)
+            if x == "slow":
+                time.sleep(0.03)
+            return x
+
+        assert compute("fast") == "fast" and compute("fast") == "fast"
+        assert compute("slow") == "slow" and compute("slow") == "slow"
+        assert calls == ["fast", "fast", "slow"]
+        info = compute.cache_info()
+        assert (info.hits, info.misses, info.skipped_fast, info.current_size) == (1, 3, 2, 1)
+        assert compute.metrics()["skipped_fast"] == 2
+
+        # An explicit set() always stores.
+        compute.set("stored", "fast")
+        assert compute("fast") == "stored" and len(calls) == 3
+
+        compute.reset_stats()
+        assert compute.cache_info().skipped_fast == 0
+
+
+class TestSharedOnEvict:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_sees_each_victim_once(self):
+        evicted = []
+
+        @cache(max_size=2, backend="shared", on_evict=lambda k, v: evicted.append((k, v)))
+        def fn(x):
+            return x * 10
+
+        fn(1)
+        fn(2)
+        assert evicted == []
+        fn(3)
+        fn.set(40, 4)
+        assert evicted == [((1,), 10), ((2,), 20)]
+        assert fn.cache_info().evictions == 2
+
+    def test_exception_does_not_fail_the_insert(self):
+        import sys
+
+        def boom(key, value):
+            raise RuntimeError("callback failed")
+
+        @cache(max_size=2, backend="shared", on_evict=boom)
+        def fn(x):
+            return x
+
+        seen = []
+        old_hook = sys.unraisablehook
+        sys.unraisablehook = lambda unraisable: seen.append(unraisable.exc_value)
+        try:
+            for x in range(3):
+                assert fn(x) == x
+        finally:
+            sys.unraisablehook = old_hook
+        assert fn.get(2) == 2
+        assert [str(e) for e in seen] == ["callback failed"]
+
+    def test_must_be_callable(self):
+        with pytest.raises(TypeError, match="on_evict must be callable"):
+            cache(max_size=2, backend="shared", on_evict=1)(lambda x: x)
+
+
+class TestSharedOversizePolicy:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_skip_is_silent(self):
+        import warnings
+
+        @cache(max_size=8, backend="shared", max_value_size=64, oversize="skip")
+        def fn(x):
+            return "v" * x
+
+        with warnings.catch_warnings():
+            warnings.simplefilter("error")
+            assert fn(100) == "v" * 100
+            fn.set("w" * 100, 1)
+        assert fn.get(1) is None
+        assert fn.cache_info().oversize_skips == 2
+
+    def test_key_and_value_skips_are_counted_separately(self):
+        @cache(max_size=8, backend="shared", max_key_size=64, max_value_size=64)
+        def fn(x, n):
+            return "v" * n
+
+        fn("k" * 100, 1)
+        info = fn.cache_info()
+        assert (info.oversize_key_skips, info.oversize_value_skips) == (1, 0)
+
+        fn("k", 100)
+        fn.set("w" * 100, "k", 1)
+        info = fn.cache_info()
+        assert (info.oversize_key_skips, info.oversize_value_skips) == (1, 2)
+        assert info.oversize_skips == 3
+        assert "oversize_key_skips=1, oversize_value_skips=2" in repr(info)
+
+    def test_warn_warns_once_per_function(self):
+        import warnings
+
+        @cache(max_size=8, backend="shared", max_key_size=64, max_value_size=64, oversize="warn")
+        def fn(x):
+            return "v" * x
+
+        with warnings.catch_warnings(record=True) as caught:
+            warnings.simplefilter("always")
+            assert fn(100) == "v" * 100
+            assert fn(200) == "v" * 200
+            fn.set("w" * 100, 1)
+            fn.set("ok", "k" * 100)
+        assert len(caught) == 1
+        assert issubclass(caught[0].category, UserWarning)
+        assert "over max_value_size=64" in str(caught[0].message)
+        assert "fn" in str(caught[0].message)
+        assert fn.cache_info().oversize_skips == 4
+
+    def test_raise_on_oversize_value(self):
+        calls = []
+
+        @cache(max_size=8, backend="shared", max_value_size=64, oversize="raise")
+        def fn(x):
+            calls.append(x)
+            return "v" * x
+
+        assert fn(10) == "v" * 10
+        with pytest.raises(ValueError, match=r"value is \d+ bytes serialized, over max_value_size"):
+            fn(100)
+        with pytest.raises(ValueError, match="max_value_size=64"):
+            fn.set("w" * 100, 1)
+        with pytest.raises(ValueError, match="max_value_size=64"):
+            fn.set_many([((2,), "ok"), ((3,), "w" * 100)])
+        # set_many serializes everything first, so nothing was stored.
+        assert fn.get(2) is None
+        assert calls == [10, 100]
+        assert fn.cache_info().oversize_skips == 3
+
+    def test_raise_on_oversize_key_skips_the_call(self):
+        calls = []
+
+        @cache(max_size=8, backend="shared", max_key_size=32, oversize="raise")
+        def fn(x):
+            calls.append(x)
+            return x
+
+        with pytest.raises(ValueError, match="over max_key_size=32"):
+            fn("k" * 100)
+        assert calls == []
+
+    def test_keys_just_under_and_just_over_max_key_size(self):
+        calls = []
+
+        @cache(max_size=8, backend="shared", max_key_size=48)
+        def fn(x):
+            calls.append(x)
+            return len(x)
+
+        # Grow the key a byte at a time until the first call that is skipped.
+        n = 0
+        while fn.cache_info().oversize_skips == 0:
+            n += 1
+            fn("k" * n)
+        under, over = "k" * (n - 1), "k" * n
+        assert n > 1
+
+        calls.clear()
+        skips = fn.cache_info().oversize_skips
+        assert (fn(under), fn(over), fn(over)) == (n - 1, n, n)
+        assert calls == [over, over]
+        assert fn.cache_info().oversize_skips == skips + 2
+
+        # get() of an oversize key misses without counting a skip; set() counts one.
+        misses = fn.cache_info().misses
+        assert fn.get(over) is None
+        assert fn.cache_info().misses == misses + 1
+        fn.set(0, over)
+        assert fn.get(over) is None
+        assert fn.cache_info().oversize_skips == skips + 3
+        fn.set(0, under)
+        assert fn.get(under) == 0
+
+    def test_raise_releases_the_refresh_claim(self):
+        import time
+
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        sizes = {"n": 1}
+        fn = SharedCachedFunction(
+            lambda x: "v" * sizes["n"],
+            8,
+            ttl=0.05,
+            stale_ttl=10,
+            max_value_size=64,
+            shm_name="test_oversize_stale",
+            oversize="raise",
+        )
+        fn(1)
+        time.sleep(0.1)
+        sizes["n"] = 100
+        with pytest.raises(ValueError):
+            fn(1)
+        # The claim was handed back, so the next caller recomputes.
+        sizes["n"] = 2
+        fn(1)
+        assert fn.get(1) == "vv"
+
+    def test_unknown_policy_raises(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with pytest.raises(ValueError, match="oversize must be"):
+            SharedCachedFunction(lambda x: x, 8, shm_name="test_oversize_bad", oversize="drop")
+
+
+class TestSharedChunking:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def _fn(self, name, max_size=8, chunk_size=256, **kwargs):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        return SharedCachedFunction(
+            lambda x: "v" * x,
+            max_size,
+            max_key_size=64,
+            shm_name=name,
+            chunk_size=chunk_size,
+            **kwargs,
+        )
+
+    def test_large_values_span_slots(self):
+        fn = self._fn("test_chunk_span")
+        assert fn(1000) == "v" * 1000
+        assert fn(3) == "vvv"
+        assert fn.get(1000) == "v" * 1000
+        info = fn.cache_info()
+        assert (info.current_size, info.evictions) == (2, 0)
+        assert info.bytes_capacity == 8 * (64 + 256)
+
+    def test_eviction_frees_the_whole_chain(self):
+        # 64 + 256 data bytes per slot, so a 1000-char value takes 4 of the 8.
+        fn = self._fn("test_chunk_evict")
+        fn.set("a" * 1000, "a")
+        fn.set("b" * 1000, "b")
+        for i in range(4):
+            fn.set(i, i)
+        info = fn.cache_info()
+        assert (info.current_size, info.evictions) == (5, 1)
+        assert [fn.get(i) for i in range(4)] == [0, 1, 2, 3]
+
+    def test_overwrite_with_a_different_size(self):
+        fn = self._fn("test_chunk_overwrite")
+        for value in ["x", "y" * 2000, "z" * 700, "w"]:
+            fn.set(value, "k")
+            assert fn.get("k") == value
+        # The longer chains were all given back: 7 more entries fit.
+        for i in range(7):
+            fn.set(i, i)
+        info = fn.cache_info()
+        assert (info.current_size, info.evictions) == (8, 0)
+
+    def test_value_needing_more_slots_than_the_cache_is_skipped(self):
+        fn = self._fn("test_chunk_too_big", max_size=2, chunk_size=16)
+        fn.set("v" * 1000, 1)
+        assert fn.get(1) is None
+        assert fn.cache_info().oversize_skips == 1
+
+    def test_chunk_size_mismatch_raises(self):
+        self._fn("test_chunk_mismatch")
+        with pytest.raises(ValueError, match="chunk_size"):
+            self._fn("test_chunk_mismatch", chunk_size=512)
+        with pytest.raises(ValueError, match="chunk_size"):
+            self._fn("test_chunk_mismatch", chunk_size=None)
+
+    def test_zero_chunk_size_raises(self):
+        with pytest.raises(ValueError, match="chunk_size must be"):
+            self._fn("test_chunk_zero", chunk_size=0)
+
+
+class TestSharedOverflow:
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_large_values_use_overflow_chunks(self):
+        @cache(
+            max_size=16,
+            backend="shared",
+            max_value_size=64,
+            overflow_count=2,
+            overflow_size=4096,
+        )
+        def fn(x):
+            return "v" * x
+
+        assert fn(10) == "v" * 10
+        assert fn(1000) == "v" * 1000
+        assert fn(2000) == "v" * 2000
+        assert fn.get(1000) == "v" * 1000
+        info = fn.cache_info()
+        assert (info.current_size, info.overflow_used, info.oversize_skips) == (3, 2, 0)
+
+        # Both chunks taken: the oldest entry holding one is evicted.
+        assert fn(3000) == "v" * 3000
+        assert fn.get(1000) is None
+        assert fn.get(10) == "v" * 10
+        info = fn.cache_info()
+        assert (info.evictions, info.overflow_used) == (1, 2)
+
+        # Bigger than a chunk is still an oversize skip.
+        fn(5000)
+        assert fn.cache_info().oversize_skips == 1
+
+    def test_overflow_size_must_exceed_max_value_size(self):
+        with pytest.raises(ValueError, match="over max_value_size=64"):
+            cache(max_size=8, backend="shared", max_value_size=64, overflow_count=2)(len)
+        with pytest.raises(ValueError, match="over max_value_size=64"):
+            cache(
+                max_size=8, backend="shared", max_value_size=64, overflow_count=2, overflow_size=64
+            )(len)
+        with pytest.raises(ValueError, match="requires overflow_count"):
+            cache(max_size=8, backend="shared", overflow_size=8192)(len)
+
+    def test_overflow_mismatch_raises(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        SharedCachedFunction(
+            len, 8, shm_name="test_overflow_mismatch", overflow_count=2, overflow_size=8192
+        )
+        with pytest.raises(ValueError, match="overflow_count"):
+            SharedCachedFunction(
+                len, 8, shm_name="test_overflow_mismatch", overflow_count=4, overflow_size=8192
+            )
+
+
+class TestSharedMemoryBackend:
+    """Test backend='memory' vs backend='shared' routing."""
+
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    def test_default_is_memory(self):
+        from warp_cache._warp_cache_rs import CacheInfo
+
+        @cache(max_size=128)
+        def fn(x):
+            return x
+
+        fn(1)
+        info = fn.cache_info()
+        assert isinstance(info, CacheInfo)
+
+    def test_shared_returns_shared_info(self):
         @cache(max_size=128, backend="shared")
         def fn(x):
             return x
@@ -429,6 +3916,54 @@ class TestSharedMaxSizeTooLarge:
                 return x
 
 
+class TestSharedParameterValidation:
+    """Arguments that can't describe a usable cache are rejected by name
+    before any file is created."""
+
+    def setup_method(self):
+        _cleanup_shm()
+
+    def teardown_method(self):
+        _cleanup_shm()
+
+    @pytest.mark.parametrize("ttl", [0, -5, float("nan"), float("inf")])
+    def test_bad_ttl_raises(self, ttl):
+        with pytest.raises(ValueError, match="ttl must be a positive number"):
+            cache(max_size=16, ttl=ttl, backend="shared")(lambda x: x)
+
+    @pytest.mark.parametrize(
+        "kwargs, message",
+        [
+            ({"max_key_size": 0}, "max_key_size must be between 1 and"),
+            ({"max_value_size": 0}, "max_value_size must be between 1 and"),
+            ({"max_value_size": 2**32}, "max_value_size must be between 1 and"),
+            ({"max_key_size": 2**31, "max_value_size": 2**31}, "must add up to under"),
+        ],
+    )
+    def test_bad_size_limits_raise(self, kwargs, message):
+        with pytest.raises(ValueError, match=message):
+            cache(max_size=16, backend="shared", **kwargs)(lambda x: x)
+
+    def test_shared_dict_checks_the_same_limits(self):
+        with pytest.raises(ValueError, match="max_key_size must be between 1 and"):
+            SharedDict("test_validation_dict", 16, max_key_size=0)
+        with pytest.raises(ValueError, match="ttl must be a positive number"):
+            SharedDict("test_validation_dict", 16, ttl=-1.0)
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="statvfs is Unix-only")
+    def test_region_larger_than_free_space_raises(self):
+        # ~1 PiB: more than any filesystem the tests run on has free.
+        with tempfile.TemporaryDirectory() as shm_dir:
+            with pytest.raises(OSError, match="bytes but only"):
+                cache(
+                    max_size=2**30,
+                    max_value_size=2**20,
+                    backend="shared",
+                    shm_dir=shm_dir,
+                )(lambda x: x)
+            assert not glob.glob(os.path.join(shm_dir, "**", "*.data*"), recursive=True)
+
+
 class TestSharedFilePermissions:
     """Regression for #39: the mmap cache files hold serialized (and possibly
     pickled) return values, so they must be owner-only (0o600) inside a per-user
@@ -441,11 +3976,11 @@ class TestSharedFilePermissions:
     def teardown_method(self):
         _cleanup_shm()
 
-    @pytest.mark.skipif(sys.platform == "win32", reason="shared memory is Unix-only")
+    @pytest.mark.skipif(sys.platform == "win32", reason="file modes are Unix-only")
     def test_shm_dir_and_files_are_owner_only(self):
         # Mirror src/shm/region.rs::shm_dir.
         base = "/dev/shm" if sys.platform.startswith("linux") else tempfile.gettempdir()
-        shm_dir = os.path.join(base, f"warp_cache-{os.getuid()}")
+        shm_dir = os.path.join(base, _USER_DIR)
 
         @cache(max_size=16, backend="shared")
         def fn(x):
@@ -463,3 +3998,59 @@ class TestSharedFilePermissions:
         for f in files:
             fmode = stat.S_IMODE(os.stat(f).st_mode)
             assert fmode & 0o077 == 0, f"{f} is group/other-accessible: {oct(fmode)}"
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="file modes are Unix-only")
+    def test_file_mode_is_applied_regardless_of_umask(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            old_umask = os.umask(0o077)
+            try:
+                fn = SharedCachedFunction(
+                    lambda x: x, 16, shm_name="test_mode", shm_dir=tmp, file_mode=0o640
+                )
+            finally:
+                os.umask(old_umask)
+            fn(1)
+            shm_dir = os.path.join(tmp, _USER_DIR)
+            for suffix in (".data", ".lock"):
+                path = os.path.join(shm_dir, f"test_mode{suffix}")
+                assert stat.S_IMODE(os.stat(path).st_mode) == 0o640
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="file modes are Unix-only")
+    def test_explicit_owner_only_mode(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            SharedCachedFunction(
+                lambda x: x, 16, shm_name="test_mode", shm_dir=tmp, file_mode=0o600
+            )
+            path = os.path.join(tmp, _USER_DIR, "test_mode.data")
+            assert stat.S_IMODE(os.stat(path).st_mode) == 0o600
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="file modes are Unix-only")
+    def test_world_writable_file_is_refused(self):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with tempfile.TemporaryDirectory() as tmp:
+            fn = SharedCachedFunction(lambda x: x, 16, shm_name="test_insecure", shm_dir=tmp)
+            fn(1)
+            path = os.path.join(tmp, _USER_DIR, "test_insecure.data")
+            os.chmod(path, 0o666)
+
+            with pytest.raises(OSError, match="world-writable"):
+                SharedCachedFunction(lambda x: x, 16, shm_name="test_insecure", shm_dir=tmp)
+            # The refused file is left as it was, not recreated.
+            assert stat.S_IMODE(os.stat(path).st_mode) == 0o666
+
+            fn_b = SharedCachedFunction(
+                lambda x: x, 16, shm_name="test_insecure", shm_dir=tmp, allow_insecure=True
+            )
+            assert fn_b.get(1) == 1
+
+    @pytest.mark.parametrize("mode", [0o400, 0o1600, -1])
+    def test_invalid_file_mode(self, mode):
+        from warp_cache._warp_cache_rs import SharedCachedFunction
+
+        with pytest.raises((ValueError, OverflowError)):
+            SharedCachedFunction(lambda x: x, 16, shm_name="test_bad_mode", file_mode=mode)
@@ -0,0 +1,211 @@
+"""Tests for SharedDict, the cross-process dict over the shared memory backend."""
+
+import os
+import pickle
+import subprocess
+import sys
+import tempfile
+import textwrap
+
+import pytest
+
+from warp_cache import SharedCacheInfo, SharedDict
+
+
+class _TempShmDir:
+    def setup_method(self):
+        self._tmp = tempfile.TemporaryDirectory()
+        self.shm_dir = self._tmp.name
+
+    def teardown_method(self):
+        self._tmp.cleanup()
+
+
+class TestSharedDictBasics(_TempShmDir):
+    def test_set_get_and_default(self):
+        d = SharedDict("test_dict_basic", 16, shm_dir=self.shm_dir)
+        assert d.get("missing") is None
+        assert d.get("missing", 5) == 5
+        d.set("a", 1)
+        d.set(("tuple", 2), {"nested": [1, 2]})
+        assert d.get("a") == 1
+        assert d.get(("tuple", 2)) == {"nested": [1, 2]}
+
+    def test_stored_none_is_not_the_default(self):
+        d = SharedDict("test_dict_none", 16, shm_dir=self.shm_dir)
+        d.set("k", None)
+        assert "k" in d
+        assert d.get("k", "default") is None
+
+    def test_overwrite(self):
+        d = SharedDict("test_dict_overwrite", 16, shm_dir=self.shm_dir)
+        d.set("k", "old")
+        d.set("k", "new")
+        assert d.get("k") == "new"
+        assert len(d) == 1
+
+    def test_delete_contains_len(self):
+        d = SharedDict("test_dict_delete", 16, shm_dir=self.shm_dir)
+        for i in range(5):
+            d.set(i, i * i)
+        assert len(d) == 5
+        assert 3 in d
+        assert d.delete(3) is True
+        assert 3 not in d
+        assert d.delete(3) is False
+        assert len(d) == 4
+        assert d.info().evictions == 0
+        assert d.info().expirations == 0
+
+    def test_incr_decr(self):
+        d = SharedDict("test_dict_incr", 16, shm_dir=self.shm_dir)
+        assert d.incr("n") == 1
+        assert d.incr("n", 10) == 11
+        assert d.decr("n", amount=3) == 8
+        assert d.decr("m", initial=5) == 4
+        assert d.get("n") == 8
+        d.set("s", "text")
+        with pytest.raises(TypeError):
+            d.incr("s")
+
+    def test_cas(self):
+        d = SharedDict("test_dict_cas", 16, shm_dir=self.shm_dir)
+        assert d.cas("lock", None, "me") is True
+        assert d.cas("lock", None, "you") is False
+        assert d.cas("lock", "you", "me again") is False
+        assert d.cas("lock", "me", "released") is True
+        assert d.get("lock") == "released"
+
+    def test_clear(self):
+        d = SharedDict("test_dict_clear", 16, shm_dir=self.shm_dir)
+        for i in range(5):
+            d.set(i, i)
+        d.clear()
+        assert len(d) == 0
+        assert d.get(0) is None
+
+    def test_eviction_at_capacity(self):
+        d = SharedDict("test_dict_evict", 4, shm_dir=self.shm_dir)
+        for i in range(10):
+            d.set(i, i)
+        assert len(d) == 4
+        info = d.info()
+        assert isinstance(info, SharedCacheInfo)
+        assert info.evictions == 6
+
+    def test_ttl(self):
+        import time
+
+        d = SharedDict("test_dict_ttl", 16, ttl=0.1, shm_dir=self.shm_dir)
+        d.set("k", "v")
+        assert d.get("k") == "v"
+        time.sleep(0.2)
+        assert d.get("k") is None
+        assert "k" not in d
+
+    def test_oversize_raises(self):
+        d = SharedDict(
+            "test_dict_oversize", 16, max_key_size=32, max_value_size=64, shm_dir=self.shm_dir
+        )
+        with pytest.raises(ValueError, match="max_value_size"):
+            d.set("k", "x" * 100)
+        with pytest.raises(ValueError, match="max_key_size"):
+            d.set("k" * 100, 1)
+        assert len(d) == 0
+        assert d.info().oversize_skips == 2
+
+    def test_unpicklable_value_raises(self):
+        d = SharedDict("test_dict_unpicklable", 16, shm_dir=self.shm_dir)
+        with pytest.raises((TypeError, AttributeError, pickle.PicklingError)):
+            d.set("k", lambda: None)
+
+    def test_two_handles_share_entries(self):
+        a = SharedDict("test_dict_handles", 16, shm_dir=self.shm_dir)
+        b = SharedDict("test_dict_handles", 16, shm_dir=self.shm_dir)
+        a.set("k", [1, 2, 3])
+        assert b.get("k") == [1, 2, 3]
+        b.delete("k")
+        assert "k" not in a
+
+    def test_mismatch_raises(self):
+        keep = SharedDict("test_dict_mismatch", 16, shm_dir=self.shm_dir)
+        with pytest.raises(ValueError, match="capacity"):
+            SharedDict("test_dict_mismatch", 32, shm_dir=self.shm_dir)
+        del keep
+
+    def test_max_size_zero_raises(self):
+        with pytest.raises(ValueError, match="max_size"):
+            SharedDict("test_dict_zero", 0, shm_dir=self.shm_dir)
+
+
+class TestSharedDictMultiprocess(_TempShmDir):
+    def test_visible_across_processes(self):
+        """A second process (with a different hash seed) reads and updates the
+        entries the first one wrote."""
+        d = SharedDict("test_dict_multiproc", 64, shm_dir=self.shm_dir)
+        d.set("greeting", "hello")
+        d.set(("worker", 1), {"state": "running"})
+
+        child_src = textwrap.dedent(f"""\
+            from warp_cache import SharedDict
+
+            d = SharedDict("test_dict_multiproc", 64, shm_dir={self.shm_dir!r})
+            assert d.get("greeting") == "hello"
+            assert d.get(("worker", 1)) == {{"state": "running"}}
+            d.set(("worker", 1), {{"state": "done"}})
+            d.delete("greeting")
+            d.set("from_child", 42)
+        """)
+        subprocess.run(
+            [sys.executable, "-c", child_src],
+            check=True,
+            timeout=30,
+            env={**os.environ, "PYTHONHASHSEED": "12345"},
+        )
+
+        assert d.get(("worker", 1)) == {"state": "done"}
+        assert "greeting" not in d
+        assert d.get("from_child") == 42
+        assert len(d) == 2
+
+    def test_concurrent_incr_loses_no_updates(self):
+        d = SharedDict("test_dict_counter", 64, shm_dir=self.shm_dir)
+
+        child_src = textwrap.dedent(f"""\
+            from warp_cache import SharedDict
+
+            d = SharedDict("test_dict_counter", 64, shm_dir={self.shm_dir!r})
+            for _ in range(500):
+                d.incr("count")
+                d.decr(("gauge", 1), amount=2)
+        """)
+        procs = [subprocess.Popen([sys.executable, "-c", child_src]) for _ in range(4)]
+        for p in procs:
+            assert p.wait(timeout=60) == 0
+
+        assert d.get("count") == 2000
+        assert d.get(("gauge", 1)) == -4000
+
+    def test_cas_across_processes_has_one_winner(self):
+        d = SharedDict("test_dict_cas_race", 64, shm_dir=self.shm_dir)
+
+        child_src = textwrap.dedent(f"""\
+            import os
+
+            from warp_cache import SharedDict
+
+            d = SharedDict("test_dict_cas_race", 64, shm_dir={self.shm_dir!r})
+            won = d.cas("leader", None, os.getpid())
+            # CAS-based increment: retry until our update lands
+            while True:
+                current = d.get("total", 0)
+                if d.cas("total", None if current == 0 else current, current + 1):
+                    break
+            raise SystemExit(0 if won else 3)
+        """)
+        procs = [subprocess.Popen([sys.executable, "-c", child_src]) for _ in range(6)]
+        codes = [p.wait(timeout=60) for p in procs]
+
+        assert sorted(codes) == [0, 3, 3, 3, 3, 3]
+        assert d.get("leader") == procs[codes.index(0)].pid
+        assert d.get("total") == 6
@@ -22,9 +22,10 @@ from warp_cache._warp_cache_rs import SharedCachedFunction
 
 def _shm_dir():
     # Must match src/shm/region.rs::shm_dir: per-user dir (#39) — warp_cache-<uid>
-    # under /dev/shm on Linux, $TMPDIR otherwise.
+    # under /dev/shm on Linux, $TMPDIR otherwise; plain warp_cache on Windows.
     base = "/dev/shm" if sys.platform.startswith("linux") else tempfile.gettempdir()
-    return os.path.join(base, f"warp_cache-{os.getuid()}")
+    user_dir = "warp_cache" if sys.platform == "win32" else f"warp_cache-{os.getuid()}"
+    return os.path.join(base, user_dir)
 
 
 def _cleanup_shm():
@@ -117,6 +118,29 @@ def _cold_start_worker(shm_name, barrier, q):
         q.put(f"ERR:{e!r}")
 
 
+# Result encodes the pid that computed it, so a refresh from another process
+# is observable.
+_pid_fn = SharedCachedFunction(
+    lambda x: (x, os.getpid()),
+    16,
+    ttl=None,
+    max_key_size=512,
+    max_value_size=4096,
+    shm_name="test_multiproc_refresh",
+)
+
+
+def _refresh_worker(x):
+    """Recompute an entry from a separate process."""
+    _pid_fn.refresh(x)
+
+
+def _fill_worker(start, count):
+    """Populate the shared cache from a separate process."""
+    for i in range(start, start + count):
+        _shared_fn(i)
+
+
 class TestMultiprocess:
     def setup_method(self):
         _cleanup_shm()
@@ -125,6 +149,247 @@ class TestMultiprocess:
     def teardown_method(self):
         _cleanup_shm()
 
+    @pytest.mark.skipif(sys.platform == "win32", reason="No fork on Windows")
+    def test_items_visible_across_processes(self):
+        """Entries written by one process are listed by items() in another."""
+        ctx = multiprocessing.get_context("fork")
+        p = ctx.Process(target=_fill_worker, args=(0, 5))
+        p.start()
+        p.join(timeout=30)
+        assert p.exitcode == 0
+
+        assert sorted(_shared_fn.items()) == [((i,), i * i) for i in range(5)]
+        assert sorted(_shared_fn.keys()) == [(i,) for i in range(5)]
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="No fork on Windows")
+    def test_debug_info_names_the_last_writer(self):
+        """The header records which process took the write lock last, and when."""
+        _shared_fn(100)
+        before = _shared_fn.debug_info(max_slots=0)["header"]
+        assert before["last_writer_pid"] == os.getpid()
+        assert 0 < before["creator_pid"]
+        assert 0 < before["created_at_wallclock"] <= before["last_write_nanos"] <= time.time_ns()
+
+        ctx = multiprocessing.get_context("fork")
+        p = ctx.Process(target=_fill_worker, args=(0, 5))
+        p.start()
+        p.join(timeout=30)
+        assert p.exitcode == 0
+
+        after = _shared_fn.debug_info(max_slots=0)["header"]
+        assert after["last_writer_pid"] == p.pid
+        assert after["last_write_nanos"] >= before["last_write_nanos"]
+        created = (after["creator_pid"], after["created_at_wallclock"])
+        assert created == (before["creator_pid"], before["created_at_wallclock"])
+
+        # debug_info takes the write lock itself but reports who had it before.
+        assert _shared_fn.debug_info(max_slots=0)["header"]["last_writer_pid"] == p.pid
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="No fork on Windows")
+    def test_parent_and_child_write_after_fork(self):
+        """The mappings survive a fork: parent and child read and write the same
+        cache at once without corrupting it."""
+        for i in range(8):
+            _shared_fn(i)
+        pid = os.fork()
+        if pid == 0:
+            ok = all(_shared_fn(i % 24) == (i % 24) ** 2 for i in range(2000))
+            os._exit(0 if ok else 1)
+        assert all(_shared_fn(i % 24 + 8) == (i % 24 + 8) ** 2 for i in range(2000))
+        _, status = os.waitpid(pid, 0)
+        assert os.waitstatus_to_exitcode(status) == 0
+        assert _shared_fn.verify() == []
+        info = _shared_fn.cache_info()
+        assert info.current_size == 16 and info.hits > 0
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="No fork on Windows")
+    def test_refresh_visible_across_processes(self):
+        """A refresh in one process replaces the value another process sees."""
+        _pid_fn.cache_clear()
+        assert _pid_fn(1) == (1, os.getpid())
+
+        ctx = multiprocessing.get_context("fork")
+        p = ctx.Process(target=_refresh_worker, args=(1,))
+        p.start()
+        p.join(timeout=30)
+        assert p.exitcode == 0
+
+        assert _pid_fn(1) == (1, p.pid)
+        assert _pid_fn.cache_info().current_size == 1
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="No fork on Windows")
+    def test_resize_visible_across_processes(self):
+        """A resize in one process is picked up by a process already attached."""
+        _unlink_shm("test_resize")
+        fn = SharedCachedFunction(lambda x: x + 1, 4, shm_name="test_resize")
+        for i in range(4):
+            fn(i)
+
+        def grow_and_fill():
+            fn.cache_resize(32)
+            for i in range(4, 20):
+                fn(i)
+
+        ctx = multiprocessing.get_context("fork")
+        p = ctx.Process(target=grow_and_fill)
+        p.start()
+        p.join(timeout=30)
+        assert p.exitcode == 0
+
+        info = fn.cache_info()
+        assert info.max_size == 32
+        assert info.current_size == 20
+        assert sorted(fn.items()) == [((i,), i + 1) for i in range(20)]
+        fn(20)
+        assert fn.cache_info().current_size == 21
+        _unlink_shm("test_resize")
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="No fork on Windows")
+    def test_gc_skips_cache_open_in_another_process(self):
+        """gc_shared never removes a cache another process still has mapped."""
+        from warp_cache import gc_shared
+
+        with tempfile.TemporaryDirectory() as tmp:
+            ctx = multiprocessing.get_context("fork")
+            ready, done = ctx.Event(), ctx.Event()
+
+            def hold_open():
+                fn = SharedCachedFunction(lambda x: x, 16, shm_name="held", shm_dir=tmp)
+                fn(1)
+                ready.set()
+                done.wait(30)
+
+            p = ctx.Process(target=hold_open)
+            p.start()
+            try:
+                assert ready.wait(30)
+                assert gc_shared(0, shm_dir=tmp) == []
+            finally:
+                done.set()
+                p.join(timeout=30)
+            assert p.exitcode == 0
+            assert gc_shared(0, shm_dir=tmp) == ["held"]
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="No fork on Windows")
+    def test_persistent_cache_survives_writer_killed_mid_insert(self):
+        """A process SIGKILLed while inserting into a persistent cache must not
+        leave it corrupt: the next opener repairs it and every entry it serves
+        is one that was fully written."""
+        with tempfile.TemporaryDirectory() as tmp:
+            writer_src = textwrap.dedent(f"""\
+                import itertools
+                from warp_cache._warp_cache_rs import SharedCachedFunction
+
+                fn = SharedCachedFunction(
+                    lambda x: bytes([x % 251]) * 3000, 64, max_value_size=4096,
+                    shm_name="test_durable_kill", shm_dir={tmp!r}, persistent=True,
+                )
+                print("READY", flush=True)
+                for i in itertools.count():
+                    fn(i % 500)
+            """)
+            writer = subprocess.Popen(
+                [sys.executable, "-c", writer_src], stdout=subprocess.PIPE, text=True
+            )
+            try:
+                assert writer.stdout.readline().strip() == "READY"
+                time.sleep(0.5)
+            finally:
+                writer.kill()
+                writer.wait()
+
+            fn = SharedCachedFunction(
+                lambda x: None,
+                64,
+                max_value_size=4096,
+                shm_name="test_durable_kill",
+                shm_dir=tmp,
+                persistent=True,
+            )
+            found = {x: fn.get(x) for x in range(500)}
+            hits = {x: v for x, v in found.items() if v is not None}
+            assert hits, "no entries survived"
+            assert all(v == bytes([x % 251]) * 3000 for x, v in hits.items())
+            assert fn.cache_info().current_size == len(hits)
+            # The dead writer's lock was released: writes go through.
+            fn.set(b"after", 1000)
+            assert fn.get(1000) == b"after"
+
+    def test_writers_killed_while_cache_stays_open(self):
+        """Writers SIGKILLed over and over, possibly holding the write lock,
+        while this process keeps the cache open: its reads and writes keep
+        going (a lock left held by a dead writer is taken over and the cache
+        repaired), and every value served is one that was fully written."""
+        shm_name = "test_writer_killed_open"
+        _unlink_shm(shm_name)
+        writer_src = textwrap.dedent(f"""\
+            import itertools
+            from warp_cache._warp_cache_rs import SharedCachedFunction
+
+            fn = SharedCachedFunction(
+                lambda x: bytes([x % 251]) * 3000, 64, max_value_size=4096,
+                shm_name="{shm_name}",
+            )
+            print("READY", flush=True)
+            for i in itertools.count():
+                fn(i % 500)
+        """)
+        fn = SharedCachedFunction(lambda x: None, 64, max_value_size=4096, shm_name=shm_name)
+        try:
+            for _ in range(5):
+                writer = subprocess.Popen(
+                    [sys.executable, "-c", writer_src], stdout=subprocess.PIPE, text=True
+                )
+                try:
+                    assert writer.stdout.readline().strip() == "READY"
+                    time.sleep(0.2)
+                finally:
+                    writer.kill()
+                    writer.wait()
+
+                hits = {x: v for x in range(500) if (v := fn.get(x)) is not None}
+                assert all(v == bytes([x % 251]) * 3000 for x, v in hits.items())
+                fn.set(b"after", 1000)
+                assert fn.get(1000) == b"after"
+        finally:
+            _unlink_shm(shm_name)
+
+    def test_stale_while_revalidate_single_recompute(self):
+        """When a hot entry goes stale, exactly one process recomputes it while
+        the others are served the stale value."""
+        ctx = multiprocessing.get_context("fork")
+        calls = ctx.Value("i", 0)
+
+        def compute(x):
+            with calls.get_lock():
+                calls.value += 1
+            time.sleep(0.3)  # keep the recompute in flight while others read
+            return x * 10
+
+        _unlink_shm("test_swr")
+        fn = SharedCachedFunction(compute, 16, ttl=0.2, stale_ttl=30.0, shm_name="test_swr")
+        assert fn(1) == 10
+        time.sleep(0.3)  # now stale
+
+        n_procs = 6
+        barrier = ctx.Barrier(n_procs)
+        q = ctx.Queue()
+
+        def worker():
+            barrier.wait(timeout=15)
+            q.put(fn(1))
+
+        procs = [ctx.Process(target=worker) for _ in range(n_procs)]
+        for p in procs:
+            p.start()
+        for p in procs:
+            p.join(timeout=30)
+
+        assert all(p.exitcode == 0 for p in procs)
+        assert [q.get(timeout=5) for _ in range(n_procs)] == [10] * n_procs
+        assert calls.value == 2  # initial fill + one refresh
+        _unlink_shm("test_swr")
+
     @pytest.mark.skipif(sys.platform == "win32", reason="No fork on Windows")
     def test_concurrent_cold_start_no_corruption(self):
         """Many processes cold-starting the SAME fresh cache at once must not
@@ -159,6 +424,8 @@ class TestMultiprocess:
             assert len(results) == n_procs and all(r is True for r in results), (
                 f"round {round_i}: bad results {results}"
             )
+            # One creator won; no half-built files were left behind.
+            assert glob.glob(os.path.join(_shm_dir(), f"{shm_name}.*.create")) == []
             _unlink_shm(shm_name)
 
     @pytest.mark.skipif(sys.platform == "win32", reason="No fork on Windows")
@@ -199,6 +466,38 @@ class TestMultiprocess:
         assert len(results) == len(specs), f"missing worker results: {results}"
         assert all(r is True for r in results), f"reader saw a wrong value: {results}"
 
+    @pytest.mark.skipif(sys.platform == "win32", reason="No fork on Windows")
+    def test_readers_survive_clears_that_release_memory(self):
+        """Readers get() hot keys while this process keeps clearing the cache
+        with ``release_memory=True``, zeroing the slab under them. A reader
+        that lands on a zeroed slot must see a miss and recompute, never
+        crash or return a wrong value."""
+        ctx = multiprocessing.get_context("fork")
+        _race_fn.cache_clear()
+        hot_keys = list(range(16))
+        q = ctx.Queue()
+        procs = [
+            ctx.Process(target=_race_worker, args=("r", (hot_keys, 20000), q)) for _ in range(4)
+        ]
+        for p in procs:
+            p.start()
+        while any(p.is_alive() for p in procs):
+            _race_fn.cache_clear(release_memory=True)
+            time.sleep(0.001)
+        for p in procs:
+            p.join(timeout=60)
+
+        exitcodes = [p.exitcode for p in procs]
+        results = []
+        with contextlib.suppress(queue.Empty):
+            while True:
+                results.append(q.get_nowait())
+        assert all(c == 0 for c in exitcodes), (
+            f"reader crashed (exitcodes={exitcodes}, results={results})"
+        )
+        assert results == [True] * len(procs), f"reader saw a wrong value: {results}"
+        assert _race_fn.verify() == []
+
     @pytest.mark.skipif(sys.platform == "win32", reason="No fork on Windows")
     def test_cross_process_visibility(self):
         """Values written by one process should be visible to another."""
@@ -246,7 +545,6 @@ class TestMultiprocess:
         info = _shared_fn.cache_info()
         assert info.current_size == 16  # still at capacity
 
-    @pytest.mark.skipif(sys.platform == "win32", reason="No shared memory on Windows")
     def test_cross_process_ttl_uses_systemwide_clock(self):
         """An entry's TTL must be judged by a system-wide clock so a value
         written by one process is aged correctly by another (#32).
@@ -315,7 +613,6 @@ class TestMultiprocess:
             f"cross-process TTL judged a fresh entry expired (stale clock base): {out!r}"
         )
 
-    @pytest.mark.skipif(sys.platform == "win32", reason="No shared memory on Windows")
     def test_cross_process_str_key_different_hashseed(self):
         """String keys must be found across processes with different PYTHONHASHSEED.
 
@@ -367,3 +664,46 @@ class TestMultiprocess:
         )
         assert proc.returncode == 0, f"Child failed: {proc.stderr}"
         assert proc.stdout.strip() == "HIT:hello-world"
+
+    def test_keys_written_and_read_under_different_hashseeds(self):
+        """Two child processes with different fixed PYTHONHASHSEEDs: the second
+        hits on str, bytes, keyword-argument and frozenset keys inserted by the
+        first (a set of str iterates in a different order under each seed)."""
+        shm_name = "test_keys_two_hashseeds"
+        script = textwrap.dedent(f"""\
+            import sys
+            from warp_cache._warp_cache_rs import SharedCachedFunction
+
+            fn = SharedCachedFunction(lambda *a, **kw: "computed", 64, shm_name="{shm_name}")
+            keys = [
+                (("alpha",), {{}}),
+                ((b"beta",), {{}}),
+                (("g",), {{"mode": "fast"}}),
+                ((frozenset(["w", "x", "y", "z"]),), {{}}),
+            ]
+            if sys.argv[1] == "write":
+                for i, (args, kwargs) in enumerate(keys):
+                    fn.set(f"value-{{i}}", *args, **kwargs)
+            else:
+                print([fn.get(*args, **kwargs) for args, kwargs in keys])
+        """)
+
+        def run(seed, mode):
+            env = os.environ.copy()
+            env["PYTHONHASHSEED"] = seed
+            return subprocess.run(
+                [sys.executable, "-c", script, mode],
+                capture_output=True,
+                text=True,
+                env=env,
+                timeout=10,
+            )
+
+        try:
+            writer = run("1", "write")
+            assert writer.returncode == 0, writer.stderr
+            reader = run("987654", "read")
+            assert reader.returncode == 0, reader.stderr
+            assert reader.stdout.strip() == "['value-0', 'value-1', 'value-2', 'value-3']"
+        finally:
+            _unlink_shm(shm_name)
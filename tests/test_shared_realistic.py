@@ -15,8 +15,10 @@ from warp_cache._warp_cache_rs import SharedCachedFunction
 
 
 def _cleanup_shm():
-    tmpdir = tempfile.gettempdir()
-    shm_dir = os.path.join(tmpdir, f"warp_cache-{os.getuid()}")
+    # Must match src/shm/region.rs::shm_dir.
+    base = "/dev/shm" if sys.platform.startswith("linux") else tempfile.gettempdir()
+    user_dir = "warp_cache" if sys.platform == "win32" else f"warp_cache-{os.getuid()}"
+    shm_dir = os.path.join(base, user_dir)
     if os.path.isdir(shm_dir):
         for f in glob.glob(os.path.join(shm_dir, "*")):
             with contextlib.suppress(OSError):
@@ -102,3 +102,23 @@ def test_sieve_hit_sets_visited():
     call_count = 0
     fn(1)  # hit
     assert call_count == 0
+
+
+def test_sieve_hot_key_survives_pure_hit_stretches():
+    """Hits mark an entry straight away, with nothing deferred to the next
+    write: a key hit thousands of times between misses is never evicted, and
+    a hit after the hand gives it its second chance marks it again."""
+    calls = []
+
+    @cache(max_size=4)
+    def fn(x):
+        calls.append(x)
+        return x
+
+    fn("hot")
+    for i in range(50):
+        for _ in range(1000):
+            fn("hot")
+        fn(i)  # miss, evicts once the cache is full
+    assert calls.count("hot") == 1
+    assert fn.cache_info().hits == 50 * 1000
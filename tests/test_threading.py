@@ -1,6 +1,17 @@
+import gc
+import glob
+import mmap
+import os
+import struct
+import sys
+import sysconfig
+import tempfile
 import threading
+import time
 from concurrent.futures import ThreadPoolExecutor
 
+import pytest
+
 from warp_cache import cache
 
 
@@ -61,3 +72,126 @@ def test_concurrent_cache_clear():
     stop.set()
     for t in threads:
         t.join(timeout=5)
+
+
+@pytest.mark.skipif(
+    not sysconfig.get_config_var("Py_GIL_DISABLED"), reason="needs a free-threaded build"
+)
+def test_import_leaves_the_gil_disabled():
+    """The extension declares free-threading support, so importing it doesn't
+    turn the GIL back on."""
+    assert not sys._is_gil_enabled()
+
+
+class _CollectingKey:
+    """A key whose every comparison runs the garbage collector, which on a
+    free-threaded build stops every thread attached to the interpreter."""
+
+    def __init__(self, n):
+        self.n = n
+
+    def __hash__(self):
+        return 0
+
+    def __eq__(self, other):
+        gc.collect(0)
+        return isinstance(other, _CollectingKey) and self.n == other.n
+
+
+def test_key_eq_stopping_the_world_while_others_wait_on_the_shard():
+    """Threads blocked on a shard held by a lookup whose `__eq__` collects
+    garbage get out of the collector's way instead of deadlocking it."""
+
+    @cache(max_size=8)
+    def fn(key):
+        return key.n
+
+    def worker(i):
+        for j in range(20):
+            assert fn(_CollectingKey((i + j) % 6)) == (i + j) % 6
+
+    with ThreadPoolExecutor(max_workers=4) as pool:
+        for f in [pool.submit(worker, i) for i in range(4)]:
+            f.result(timeout=60)
+    assert fn.cache_info().current_size <= 8
+
+
+class _BlockingKey:
+    """Compares equal only after `release` is set, holding up the lookup."""
+
+    def __init__(self, n, entered=None, release=None):
+        self.n = n
+        self.entered = entered
+        self.release = release
+
+    def __hash__(self):
+        return 0
+
+    def __eq__(self, other):
+        if self.entered is not None:
+            self.entered.set()
+            self.release.wait()
+        return isinstance(other, _BlockingKey) and self.n == other.n
+
+
+@pytest.mark.skipif(not hasattr(os, "fork"), reason="needs fork")
+@pytest.mark.filterwarnings("ignore::DeprecationWarning")
+def test_fork_while_another_thread_is_inside_the_cache():
+    """A thread caught mid-lookup by a fork doesn't exist in the child, and
+    must not leave the cache locked there."""
+
+    @cache(max_size=8)
+    def fn(key):
+        return key.n
+
+    fn(_BlockingKey(1))
+    entered, release = threading.Event(), threading.Event()
+    reader = threading.Thread(target=fn, args=(_BlockingKey(1, entered, release),))
+    reader.start()
+    try:
+        assert entered.wait(10)
+        pid = os.fork()
+        if pid == 0:
+            os._exit(0 if fn(_BlockingKey(2)) == 2 else 1)
+        deadline = time.monotonic() + 30
+        while (done := os.waitpid(pid, os.WNOHANG))[0] == 0 and time.monotonic() < deadline:
+            time.sleep(0.01)
+        if done[0] == 0:
+            os.kill(pid, 9)
+            os.waitpid(pid, 0)
+            pytest.fail("the child hung on the cache")
+        assert os.waitstatus_to_exitcode(done[1]) == 0
+    finally:
+        release.set()
+        reader.join()
+
+
+def test_shared_write_waits_without_the_gil():
+    """A thread stuck waiting on a shared cache's lock, held by another
+    process, lets the other threads of this process keep running."""
+    with tempfile.TemporaryDirectory() as tmp:
+
+        @cache(max_size=16, backend="shared", shm_dir=tmp, lock_timeout=10)
+        def fn(x):
+            return x
+
+        fn(1)
+        [path] = glob.glob(os.path.join(tmp, "*", "*.lock"))
+        with open(path, "r+b") as f, mmap.mmap(f.fileno(), 0) as lock:
+            # Mid-write, held by a live process in another PID namespace, so
+            # it is never taken over.
+            seq, owner = struct.unpack_from("<QQ", lock)
+            struct.pack_into("<QQ", lock, 0, seq | 1, 1 << 63 | 1 << 32 | 1)
+            writer = threading.Thread(target=fn.set, args=("stored", 2))
+            start = time.monotonic()
+            writer.start()
+            ticks = 0
+            while time.monotonic() - start < 0.2:
+                ticks += sum(range(100))  # pure-Python work
+            assert time.monotonic() - start < 1.0
+            assert ticks > 0
+            assert writer.is_alive()
+
+            struct.pack_into("<QQ", lock, 0, seq + 2, owner)
+            writer.join()
+        assert fn.get(2) == "stored"
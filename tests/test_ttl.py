@@ -51,3 +51,38 @@ def test_no_ttl():
     time.sleep(0.05)
     compute(1)
     assert call_count == 1
+
+
+def _access_every_half_ttl(sliding_ttl):
+    """Access a key every ttl/2 for 2×ttl; return how often it was computed."""
+    call_count = 0
+
+    @cache(max_size=128, ttl=0.2, sliding_ttl=sliding_ttl)
+    def compute(x):
+        nonlocal call_count
+        call_count += 1
+        return x
+
+    for _ in range(5):
+        compute(1)
+        time.sleep(0.1)
+    return call_count
+
+
+def test_sliding_ttl_keeps_accessed_entry_alive():
+    assert _access_every_half_ttl(sliding_ttl=True) == 1
+
+
+def test_absolute_ttl_expires_despite_access():
+    assert _access_every_half_ttl(sliding_ttl=False) > 1
+
+
+def test_sliding_ttl_expires_when_idle():
+    @cache(max_size=128, ttl=0.1, sliding_ttl=True)
+    def compute(x):
+        return x
+
+    compute(1)
+    compute.get(1)
+    time.sleep(0.15)
+    assert compute.get(1) is None
@@ -1,12 +1,43 @@
-from warp_cache._decorator import BaseCacheInfo, CachedCallable, cache
+from warp_cache._decorator import (
+    BaseCacheInfo,
+    CachedCallable,
+    aggregate_info,
+    all_caches,
+    cache,
+    cached_property,
+    clear_all,
+    collect_all_metrics,
+    configure,
+    get_config,
+)
 from warp_cache._strategies import Backend
-from warp_cache._warp_cache_rs import CacheInfo, SharedCacheInfo
+from warp_cache._warp_cache_rs import (
+    CachedProperty,
+    CacheInfo,
+    SharedCacheInfo,
+    SharedDict,
+    gc_shared,
+    list_shared,
+    register_serializer,
+)
 
 __all__ = [
     "Backend",
     "BaseCacheInfo",
     "CachedCallable",
+    "CachedProperty",
     "CacheInfo",
     "SharedCacheInfo",
+    "SharedDict",
+    "aggregate_info",
+    "all_caches",
     "cache",
+    "cached_property",
+    "clear_all",
+    "collect_all_metrics",
+    "configure",
+    "gc_shared",
+    "get_config",
+    "list_shared",
+    "register_serializer",
 ]
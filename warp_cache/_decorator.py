@@ -3,22 +3,37 @@ from __future__ import annotations
 import asyncio
 import functools
 import inspect
+import os
+import time
 import warnings
-from collections.abc import Callable
-from typing import Any, ParamSpec, Protocol, TypeVar, runtime_checkable
+import weakref
+from collections.abc import Callable, Iterable
+from typing import Any, Literal, ParamSpec, Protocol, TypeVar, overload, runtime_checkable
 
 from warp_cache._strategies import Backend
 from warp_cache._warp_cache_rs import (
     CachedFunction,
     CacheInfo,
+    CachedProperty,
     SharedCachedFunction,
     SharedCacheInfo,
+    configure,
+    get_config,
 )
 
 F = TypeVar("F", bound=Callable[..., Any])
 P = ParamSpec("P")
 R = TypeVar("R")
 
+# Every live cache cache() and cached_property() have made, in order, with
+# its `module.qualname`: one per decoration, even where a name repeats. Each
+# weak reference removes itself once its cache is collected.
+_all_caches: dict[weakref.ref[Any], str] = {}
+
+# Separates positional from keyword arguments in single-flight keys, as in the
+# Rust-built cache keys, so that f((), (("x", 1),)) and f(x=1) don't share one.
+_KWARGS_MARK = object()
+
 
 @runtime_checkable
 class BaseCacheInfo(Protocol):
@@ -39,7 +54,11 @@ class CachedCallable(Protocol[P, R]):
 
     def __call__(self, *args: P.args, **kwargs: P.kwargs) -> R: ...
     def cache_info(self) -> BaseCacheInfo: ...
-    def cache_clear(self) -> None: ...
+    def cache_clear(self, release_memory: bool = False) -> None: ...
+    def invalidate_tag(self, tag: str) -> int: ...
+    def metrics(self) -> dict[str, int | float]: ...
+    def reset_stats(self, local_only: bool = False) -> None: ...
+    def latency_histogram(self) -> dict[str, list[int | float]] | None: ...
 
 
 class AsyncCachedFunction:
@@ -54,11 +73,16 @@ class AsyncCachedFunction:
     """
 
     def __init__(
-        self, fn: Callable[..., Any], inner: CachedFunction | SharedCachedFunction
+        self,
+        fn: Callable[..., Any],
+        inner: CachedFunction | SharedCachedFunction | _Uncached,
     ) -> None:
         self._fn = fn
         self._inner = inner
         self._inflight: dict[Any, asyncio.Event] = {}
+        # With record_latencies=True, time calls here: the inner __call__ that
+        # would time them isn't used.
+        self._timed = inner.latency_histogram() is not None
         self.__wrapped__ = fn
         self.__name__ = getattr(fn, "__name__", repr(fn))
         self.__qualname__ = getattr(fn, "__qualname__", self.__name__)
@@ -75,14 +99,22 @@ class AsyncCachedFunction:
     @staticmethod
     def _make_inflight_key(args: tuple[Any, ...], kwargs: dict[str, Any] | None) -> Any:
         if kwargs:
-            return (args, tuple(sorted(kwargs.items())))
+            return (args, _KWARGS_MARK, tuple(sorted(kwargs.items())))
         return args
 
     async def __call__(self, *args: Any, **kwargs: Any) -> Any:
+        start = time.perf_counter_ns() if self._timed else 0
         hit, cached = self._inner._probe(*args, **kwargs)
         if hit:
+            if self._timed:
+                self._inner._record_latency(True, time.perf_counter_ns() - start)
             return cached
+        result = await self._call_miss(args, kwargs)
+        if self._timed:
+            self._inner._record_latency(False, time.perf_counter_ns() - start)
+        return result
 
+    async def _call_miss(self, args: tuple[Any, ...], kwargs: dict[str, Any]) -> Any:
         # Partition single-flight state per event loop. An asyncio.Event binds to
         # the loop that first awaits it, so a follower running in a different loop
         # (e.g. another thread's asyncio.run) must not reuse a leader's Event — doing
@@ -92,6 +124,12 @@ class AsyncCachedFunction:
         # ponytail: dict ops are atomic under the GIL and keys are disjoint per loop;
         # add a lock only if free-threaded builds become supported.
         key = (id(asyncio.get_running_loop()), self._make_inflight_key(args, kwargs or None))
+        try:
+            hash(key)
+        except TypeError:
+            # Arguments that don't hash (the shared backend, or unhashable=
+            # "bypass"/"serialize") can't key the in-flight table: no coalescing.
+            return await self._compute(args, kwargs)
 
         while True:
             event = self._inflight.get(key)
@@ -107,23 +145,184 @@ class AsyncCachedFunction:
             event = asyncio.Event()
             self._inflight[key] = event
             try:
-                result = await self._fn(*args, **kwargs)
-                self._inner.set(result, *args, **kwargs)
-                return result
+                return await self._compute(args, kwargs)
             finally:
                 event.set()
                 self._inflight.pop(key, None)
 
+    async def _compute(self, args: tuple[Any, ...], kwargs: dict[str, Any]) -> Any:
+        """Await the function and store its result. A result quicker than
+        ``min_compute_time`` isn't stored, so followers waiting on it recompute."""
+        # The inner set() can't time the coroutine, so it's told how long the
+        # result took (`_warp_compute_time`) and skips storing quick ones.
+        start = time.perf_counter()
+        result = await self._fn(*args, **kwargs)
+        elapsed = time.perf_counter() - start
+        self._inner.set(result, *args, _warp_compute_time=elapsed, **kwargs)
+        return result
+
     def cache_info(self) -> CacheInfo | SharedCacheInfo:
         return self._inner.cache_info()
 
-    def cache_clear(self) -> None:
-        return self._inner.cache_clear()
+    def cache_clear(self, release_memory: bool = False) -> None:
+        return self._inner.cache_clear(release_memory=release_memory)
+
+    def invalidate_tag(self, tag: str) -> int:
+        return self._inner.invalidate_tag(tag)
+
+    def metrics(self) -> dict[str, int | float]:
+        return self._inner.metrics()
+
+    def reset_stats(self, local_only: bool = False) -> None:
+        self._inner.reset_stats(local_only=local_only)
+
+    def latency_histogram(self) -> dict[str, list[int | float]] | None:
+        return self._inner.latency_histogram()
 
     def __repr__(self) -> str:
         return f"<AsyncCachedFunction {self.__qualname__}>"
 
 
+class _Uncached:
+    """Stand-in for a shared cache that couldn't be set up, with
+    ``fallback="nocache"``: every call runs the function."""
+
+    def __init__(self, fn: Callable[..., Any]) -> None:
+        self._fn = fn
+        self._calls = 0
+
+    def __call__(self, *args: Any, **kwargs: Any) -> Any:
+        self._calls += 1
+        return self._fn(*args, **kwargs)
+
+    def get(self, *args: Any, **kwargs: Any) -> Any | None:
+        return None
+
+    def _probe(self, *args: Any, **kwargs: Any) -> tuple[bool, Any]:
+        self._calls += 1
+        return False, None
+
+    def set(self, value: Any, *args: Any, **kwargs: Any) -> None:
+        pass
+
+    def cache_info(self) -> CacheInfo:
+        return CacheInfo(0, self._calls, 0, 0, backend="nocache-fallback")
+
+    def cache_clear(self, release_memory: bool = False) -> None:
+        self._calls = 0
+
+    def reset_stats(self, local_only: bool = False) -> None:
+        self._calls = 0
+
+    def latency_histogram(self) -> dict[str, list[int | float]] | None:
+        return None
+
+    def _record_latency(self, hit: bool, nanos: int) -> None:
+        pass
+
+    def invalidate_tag(self, tag: str) -> int:
+        return 0
+
+    def metrics(self) -> dict[str, int | float]:
+        return {
+            "hits": 0,
+            "misses": self._calls,
+            "hit_rate": 0.0,
+            "current_size": 0,
+            "max_size": 0,
+            "uncacheable_calls": 0,
+            "skipped_fast": 0,
+        }
+
+
+def collect_all_metrics() -> dict[str, dict[str, int | float]]:
+    """``metrics()`` of every cache in ``all_caches()``, keyed by the
+    function's ``module.qualname``. If two live functions share a name, the
+    one decorated last is reported.
+    """
+    return {
+        name: cached.metrics()
+        for ref, name in list(_all_caches.items())
+        if (cached := ref()) is not None
+    }
+
+
+def all_caches() -> list[CachedCallable[..., Any] | CachedProperty[Any]]:
+    """Every live decorated function and ``cached_property``, in the order
+    they were decorated.
+
+    Caches are tracked weakly: one whose function (or class) has been
+    garbage collected drops out, but a redefined function doesn't replace
+    the earlier one while it is alive.
+    """
+    return [cached for ref in list(_all_caches) if (cached := ref()) is not None]
+
+
+def clear_all() -> None:
+    """``cache_clear()`` and ``reset_stats()`` on every cache in
+    ``all_caches()``, e.g. between tests. A shared cache is emptied for
+    every process using it."""
+    for cached in all_caches():
+        cached.cache_clear()
+        cached.reset_stats()
+
+
+def aggregate_info() -> dict[str, int | float]:
+    """``metrics()`` summed over every cache in ``all_caches()``: each
+    counter and size totalled (a metric one backend lacks counts as 0),
+    ``hit_rate`` recomputed from the totals, and ``caches``, how many there
+    are."""
+    caches = all_caches()
+    totals: dict[str, int | float] = {"caches": len(caches), "hits": 0, "misses": 0}
+    for cached in caches:
+        for metric, value in cached.metrics().items():
+            if metric != "hit_rate":
+                totals[metric] = totals.get(metric, 0) + value
+    lookups = totals["hits"] + totals["misses"]
+    totals["hit_rate"] = totals["hits"] / lookups if lookups else 0.0
+    return totals
+
+
+def _track(fn: Any, cached: Any) -> None:
+    """Record a cache made for ``fn``, for the functions above."""
+    name = f"{getattr(fn, '__module__', None)}.{getattr(fn, '__qualname__', repr(fn))}"
+    _all_caches[weakref.ref(cached, _untrack)] = name
+
+
+def _untrack(ref: weakref.ref[Any]) -> None:
+    _all_caches.pop(ref, None)
+
+
+# The cache() arguments only the shared backend uses; a memory-backend
+# cache warns about any of them passed as something other than None/False.
+_SHARED_ONLY = frozenset(
+    {
+        "max_key_size",
+        "max_value_size",
+        "stale_ttl",
+        "on_mismatch",
+        "shm_dir",
+        "file_mode",
+        "allow_insecure",
+        "persistent",
+        "sync_interval",
+        "compress",
+        "compress_threshold",
+        "serializer",
+        "oversize",
+        "chunk_size",
+        "overflow_count",
+        "overflow_size",
+        "lock_timeout",
+        "checksums",
+        "lock_memory",
+        "prefault",
+        "on_evict",
+        "readonly",
+        "fallback",
+    }
+)
+
 _BACKEND_STR_MAP = {"memory": Backend.MEMORY, "shared": Backend.SHARED}
 
 
@@ -142,14 +341,45 @@ def _resolve_backend(backend: str | int | Backend) -> Backend:
 
 
 def cache(
-    max_size: int = 128,
+    max_size: int | None = None,
     ttl: float | None = None,
-    backend: str | int | Backend = Backend.MEMORY,
+    backend: str | int | Backend | None = None,
     max_key_size: int | None = None,
     max_value_size: int | None = None,
+    stale_ttl: float | None = None,
+    sliding_ttl: bool | None = None,
+    on_mismatch: Literal["error", "recreate", "open_existing"] | None = None,
+    shm_dir: str | os.PathLike[str] | None = None,
+    file_mode: int | None = None,
+    allow_insecure: bool | None = None,
+    persistent: bool | None = None,
+    sync_interval: float | None = None,
+    compress: Literal["lz4", "zstd"] | None = None,
+    compress_threshold: int | None = None,
+    serializer: tuple[Callable[[Any], bytes], Callable[[bytes], Any]] | None = None,
+    oversize: Literal["skip", "warn", "raise"] | None = None,
+    chunk_size: int | None = None,
+    overflow_count: int | None = None,
+    overflow_size: int | None = None,
+    lock_timeout: float | None = None,
+    checksums: bool | None = None,
+    lock_memory: bool | None = None,
+    prefault: bool | None = None,
+    on_evict: Callable[[Any, Any], object] | None = None,
+    readonly: bool | None = None,
+    cache_version: str | int | None = None,
+    tags_func: Callable[[tuple[Any, ...], dict[str, Any], Any], Iterable[str]] | None = None,
+    fallback: Literal["error", "local", "nocache"] | None = None,
+    unhashable: Literal["error", "bypass", "serialize"] | None = None,
+    record_latencies: bool | None = None,
+    min_compute_time: float | None = None,
+    compact_keys: bool | None = None,
 ) -> Callable[[Callable[P, R]], CachedCallable[P, R]]:
     """Caching decorator backed by a Rust store.
 
+    Arguments left out or ``None`` take their default from ``configure()`` if
+    set there, else the defaults below.
+
     Supports both sync and async functions. The async detection happens
     once at decoration time — zero overhead on the sync path.
 
@@ -157,46 +387,288 @@ def cache(
     near-optimal hit rates with O(1) overhead per access.
 
     Args:
-        max_size: Maximum number of cached entries.
+        max_size: Maximum number of cached entries (default 128).
         ttl: Time-to-live in seconds (None = no expiry).
         backend: Backend.MEMORY (default) for in-process cache,
                  Backend.SHARED for cross-process shared memory cache.
                  Also accepts the strings "memory" and "shared".
         max_key_size: Max serialized key size in bytes (shared backend only).
         max_value_size: Max serialized value size in bytes (shared backend only).
+        stale_ttl: Seconds past ``ttl`` during which an expired entry is still
+                   served while one caller recomputes it (shared backend only).
+        sliding_ttl: Restart an entry's ``ttl`` clock on every hit, so only
+                     entries left unused for ``ttl`` seconds expire.
+        on_mismatch: What to do when the shared cache already exists with
+                     different parameters: ``"error"`` (default) raises
+                     ``ValueError``, ``"recreate"`` replaces it with an empty
+                     cache, ``"open_existing"`` adopts the existing parameters
+                     (shared backend only).
+        shm_dir: Directory for the cache files, overriding the
+                 ``WARP_CACHE_SHM_DIR`` environment variable and the platform
+                 default (shared backend only).
+        file_mode: Permission bits for newly created cache files, applied
+                   regardless of the umask (default ``0o600``; shared backend
+                   only).
+        allow_insecure: Open existing cache files even if they are
+                        world-writable or owned by another user (shared
+                        backend only).
+        persistent: Keep the cache in a file on disk so it survives reboots,
+                    under ``shm_dir`` or else ``$XDG_CACHE_HOME/warp_cache``
+                    (``~/.cache/warp_cache``). Entries are timestamped with
+                    wall-clock time and the cache is repaired on open after a
+                    crash (shared backend only).
+        sync_interval: With ``persistent``, start flushing writes to disk at
+                       most this often, in seconds. Without it, writes reach
+                       disk on ``sync()`` or when the OS writes them back
+                       (shared backend only).
+        compress: Compress stored values with ``"lz4"`` or ``"zstd"``, so
+                  large compressible values fit under ``max_value_size``
+                  (shared backend only).
+        compress_threshold: With ``compress``, only values whose serialized
+                            form exceeds this many bytes are compressed
+                            (default 1024; shared backend only).
+        serializer: A ``(dumps, loads)`` pair used instead of pickle for
+                    keys and values the built-in fast path can't encode, e.g.
+                    ``(cloudpickle.dumps, cloudpickle.loads)`` (shared backend
+                    only).
+        oversize: What to do with a key or value over ``max_key_size`` /
+                  ``max_value_size``: ``"skip"`` (default) leaves it uncached,
+                  ``"warn"`` also warns the first time, ``"raise"`` raises
+                  ``ValueError`` (shared backend only).
+        chunk_size: Size slots for this many value bytes instead of
+                    ``max_value_size``; larger values span several slots, and
+                    ``max_size`` then counts slots (shared backend only).
+        overflow_count: Number of extra ``overflow_size``-byte chunks for
+                        values over ``max_value_size``, one value each; when
+                        all are taken the oldest such entry is evicted
+                        (shared backend only).
+        overflow_size: Largest value an overflow chunk holds; must exceed
+                       ``max_value_size`` (shared backend only).
+        lock_timeout: Seconds to wait for another process to release the
+                      cache's lock before giving up: a lookup counts as a
+                      miss (and in ``lock_timeouts``), a write raises
+                      ``RuntimeError``. ``None`` (default) waits indefinitely
+                      (shared backend only).
+        checksums: Store a checksum of each entry and verify it on every hit,
+                   so a cache file corrupted by another process yields a miss
+                   (counted in ``corruption_detected``) instead of a wrong
+                   value. Costs one hash over the value per hit (shared
+                   backend only).
+        lock_memory: ``mlock`` the cache file's mapping so it is never paged
+                     out; raises ``OSError`` if ``RLIMIT_MEMLOCK`` is too low.
+                     Not supported on Windows (shared backend only).
+        prefault: Fault in every page of the cache file when opening it, so
+                  first accesses don't (shared backend only).
+        on_evict: Called as ``on_evict(key, value)`` for each entry this
+                  process evicts to make room, after the write lock is
+                  released. ``key`` is the argument tuple, as in ``keys()``.
+                  Evictions by other processes aren't reported, and an
+                  exception it raises goes to ``sys.unraisablehook``
+                  (shared backend only).
+        readonly: Attach to a cache another process created without ever
+                  writing to it: hits and misses count only in
+                  ``local_cache_info()``, misses are computed but not stored,
+                  and explicit writes raise ``RuntimeError``. Opening fails
+                  if the cache doesn't exist or its parameters differ, and
+                  ``on_mismatch="recreate"`` is rejected (shared backend
+                  only).
+        cache_version: Version of the function's results. Bump it when the
+                       implementation changes: entries cached under another
+                       version are never returned. The shared backend moves
+                       to a new cache file (the old one is left for
+                       ``gc_shared``).
+        tags_func: Called as ``tags_func(args, kwargs, result)`` for each
+                   result stored, returning the tags (str) to store it
+                   under; ``invalidate_tag(tag)`` then removes every entry
+                   carrying a tag. ``set(..., _warp_tags=[...])`` tags an
+                   entry directly. The shared backend keeps at most 4 tags
+                   per entry and raises ``ValueError`` past that.
+        fallback: What to do if the shared cache can't be set up (``OSError``,
+                  e.g. an unwritable or full ``shm_dir``): ``"error"``
+                  (default) raises, ``"local"`` warns and caches in-process
+                  instead, ``"nocache"`` warns and calls the function every
+                  time. ``cache_info().backend`` then says which (shared
+                  backend only).
+        unhashable: What to do when the arguments don't hash (a list or dict
+                    argument): ``"error"`` (default) raises ``TypeError`` as
+                    ``functools.lru_cache`` does, ``"bypass"`` calls the
+                    function uncached and counts it in
+                    ``cache_info().uncacheable_calls``, ``"serialize"`` keys
+                    the call on a hashable equivalent (lists and dicts as
+                    tuples, sets as frozensets). The shared backend keys on
+                    serialized arguments and never needs them to hash; with
+                    ``fallback="local"`` its stand-in defaults to
+                    ``"serialize"`` so they still work.
+        record_latencies: Keep histograms of how long calls take, one for
+                          hits and one for misses (which run the function),
+                          in power-of-two nanosecond buckets; read them with
+                          ``latency_histogram()`` and zero them with
+                          ``reset_stats()``. Costs two clock reads per call.
+                          The shared backend's are per process.
+        min_compute_time: Only store results that took at least this many
+                          seconds to compute; quicker ones are returned but
+                          not stored, and counted in
+                          ``cache_info().skipped_fast`` (still as misses).
+                          Explicit ``set()`` calls always store. ``None``
+                          (default) stores every result.
+        compact_keys: Store keys made only of exact ints, strs, bytes and
+                      ``None`` (and tuples of them) as bytes rather than as
+                      the argument tuple, so the cache keeps no argument
+                      objects alive. A hit on one serializes the arguments
+                      to compare them, some 15-25 ns more than ``==``
+                      (memory backend only).
     """
+    # First, while the locals are just the arguments.
+    shared_only = [
+        name
+        for name, value in locals().items()
+        if name in _SHARED_ONLY and value is not None and value is not False
+    ]
+    # The constructors look up the defaults of the other arguments left out
+    # (passed on as None) themselves.
+    if backend is None or fallback is None:
+        config = get_config()
+        backend = config["backend"] if backend is None else backend
+        fallback = config["fallback"] if fallback is None else fallback
+    if fallback not in (None, "error", "local", "nocache"):
+        raise ValueError(f"fallback must be 'error', 'local' or 'nocache', got {fallback!r}")
+    if unhashable not in (None, "error", "bypass", "serialize"):
+        raise ValueError(
+            f"unhashable must be 'error', 'bypass' or 'serialize', got {unhashable!r}"
+        )
     resolved_backend = _resolve_backend(backend)
 
     def decorator(fn: Callable[P, R]) -> CachedCallable[P, R]:
+        inner: CachedFunction | SharedCachedFunction | _Uncached
         if resolved_backend == Backend.SHARED:
-            inner = SharedCachedFunction(
-                fn,
-                max_size,
-                ttl=ttl,
-                max_key_size=max_key_size if max_key_size is not None else 512,
-                max_value_size=max_value_size if max_value_size is not None else 4096,
-            )
-        else:
-            if max_key_size is not None:
-                warnings.warn(
-                    "max_key_size has no effect with the memory backend",
-                    stacklevel=2,
+            try:
+                inner = SharedCachedFunction(
+                    fn,
+                    max_size,
+                    ttl=ttl,
+                    max_key_size=max_key_size,
+                    max_value_size=max_value_size,
+                    stale_ttl=stale_ttl,
+                    sliding_ttl=sliding_ttl,
+                    on_mismatch=on_mismatch,
+                    shm_dir=shm_dir,
+                    file_mode=file_mode,
+                    allow_insecure=allow_insecure,
+                    persistent=persistent,
+                    sync_interval=sync_interval,
+                    compress=compress,
+                    compress_threshold=compress_threshold,
+                    serializer=serializer,
+                    oversize=oversize,
+                    chunk_size=chunk_size,
+                    overflow_count=overflow_count,
+                    overflow_size=overflow_size,
+                    lock_timeout=lock_timeout,
+                    checksums=checksums,
+                    lock_memory=lock_memory,
+                    prefault=prefault,
+                    on_evict=on_evict,
+                    readonly=readonly,
+                    cache_version=cache_version,
+                    tags_func=tags_func,
+                    record_latencies=record_latencies,
+                    min_compute_time=min_compute_time,
                 )
-            if max_value_size is not None:
+            except OSError as e:
+                if fallback == "error":
+                    raise
+                instead = "an in-process cache" if fallback == "local" else "no caching"
                 warnings.warn(
-                    "max_value_size has no effect with the memory backend",
+                    f"shared cache for {getattr(fn, '__qualname__', fn)!r} is unavailable "
+                    f"({e}); falling back to {instead}",
                     stacklevel=2,
                 )
-            inner = CachedFunction(fn, max_size, ttl=ttl)
+                if fallback == "local":
+                    inner = CachedFunction(
+                        fn,
+                        max_size,
+                        ttl=ttl,
+                        sliding_ttl=sliding_ttl,
+                        fallback=True,
+                        cache_version=cache_version,
+                        tags_func=tags_func,
+                        unhashable=unhashable,
+                        record_latencies=record_latencies,
+                        min_compute_time=min_compute_time,
+                        compact_keys=compact_keys,
+                    )
+                else:
+                    inner = _Uncached(fn)
+        else:
+            for name in shared_only:
+                warnings.warn(f"{name} has no effect with the memory backend", stacklevel=2)
+            inner = CachedFunction(
+                fn,
+                max_size,
+                ttl=ttl,
+                sliding_ttl=sliding_ttl,
+                cache_version=cache_version,
+                tags_func=tags_func,
+                unhashable=unhashable,
+                record_latencies=record_latencies,
+                min_compute_time=min_compute_time,
+                compact_keys=compact_keys,
+            )
 
         if asyncio.iscoroutinefunction(fn):
-            return AsyncCachedFunction(fn, inner)  # type: ignore[return-value]
+            wrapper = AsyncCachedFunction(fn, inner)
+            _track(fn, wrapper)
+            return wrapper  # type: ignore[return-value]
 
         # Sync path returns the Rust object directly (no Python wrapper, so __call__
         # stays a single FFI crossing). Copy introspection metadata onto it —
         # __name__/__qualname__/__module__/__doc__ and __wrapped__ so inspect.signature
         # resolves to the original (#43). The pyclass carries a __dict__ for this.
         functools.wraps(fn)(inner)
+        _track(fn, inner)
         return inner
 
     return decorator
+
+
+@overload
+def cached_property(func: Callable[[Any], R], /) -> CachedProperty[R]: ...
+
+
+@overload
+def cached_property(
+    *, ttl: float | None = None, max_size: int | None = None
+) -> Callable[[Callable[[Any], R]], CachedProperty[R]]: ...
+
+
+def cached_property(
+    func: Callable[[Any], R] | None = None,
+    /,
+    *,
+    ttl: float | None = None,
+    max_size: int | None = None,
+) -> CachedProperty[R] | Callable[[Callable[[Any], R]], CachedProperty[R]]:
+    """``functools.cached_property`` backed by the Rust store, as
+    ``@cached_property`` or ``@cached_property(ttl=..., max_size=...)``.
+
+    Args:
+        ttl: Seconds an instance's value is kept before the next access
+            computes it again. ``None`` keeps it for the instance's lifetime.
+        max_size: Most instances whose values are kept at once, evicting by
+            SIEVE beyond that. ``None`` keeps every live instance's value.
+
+    Values are held outside the instances (which must support weak
+    references) and dropped when an instance is garbage collected. Threads
+    racing on an instance's first access may each run the getter, but all
+    get the same value. The descriptor, read from the class, has
+    ``cache_info()``, ``metrics()``, ``cache_clear()`` and ``reset_stats()``,
+    and is listed in ``all_caches()``.
+    """
+
+    def decorator(func: Callable[[Any], R]) -> CachedProperty[R]:
+        prop = CachedProperty(func, ttl, max_size)
+        functools.update_wrapper(prop, func)  # type: ignore[arg-type]
+        _track(func, prop)
+        return prop
+
+    return decorator if func is None else decorator(func)
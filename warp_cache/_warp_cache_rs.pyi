@@ -1,7 +1,20 @@
-from collections.abc import Callable
-from typing import Any
+import os
+from collections.abc import Callable, Iterable
+from typing import Any, Generic, Literal, TypeVar, overload
+
+_T = TypeVar("_T")
 
 class CacheInfo:
+    def __init__(
+        self,
+        hits: int,
+        misses: int,
+        max_size: int,
+        current_size: int,
+        backend: str = "memory",
+        uncacheable_calls: int = 0,
+        skipped_fast: int = 0,
+    ) -> None: ...
     @property
     def hits(self) -> int: ...
     @property
@@ -10,6 +23,12 @@ class CacheInfo:
     def max_size(self) -> int: ...
     @property
     def current_size(self) -> int: ...
+    @property
+    def backend(self) -> str: ...
+    @property
+    def uncacheable_calls(self) -> int: ...
+    @property
+    def skipped_fast(self) -> int: ...
     def __repr__(self) -> str: ...
 
 class SharedCacheInfo:
@@ -22,36 +41,215 @@ class SharedCacheInfo:
     @property
     def current_size(self) -> int: ...
     @property
+    def oversize_key_skips(self) -> int: ...
+    @property
+    def oversize_value_skips(self) -> int: ...
+    @property
     def oversize_skips(self) -> int: ...
+    @property
+    def lock_timeouts(self) -> int: ...
+    @property
+    def corruption_detected(self) -> int: ...
+    @property
+    def corrupt_values(self) -> int: ...
+    @property
+    def skipped_fast(self) -> int: ...
+    @property
+    def insert_failures(self) -> int: ...
+    @property
+    def expired_reclaimed(self) -> int: ...
+    @property
+    def evictions(self) -> int: ...
+    @property
+    def expirations(self) -> int: ...
+    @property
+    def bytes_used(self) -> int: ...
+    @property
+    def bytes_capacity(self) -> int: ...
+    @property
+    def fill_ratio(self) -> float: ...
+    @property
+    def avg_value_len(self) -> float: ...
+    @property
+    def overflow_used(self) -> int: ...
+    @property
+    def memory_locked(self) -> bool: ...
+    @property
+    def allocated_bytes(self) -> int: ...
+    @property
+    def cache_version(self) -> str | None: ...
+    @property
+    def remaps(self) -> int: ...
     def __repr__(self) -> str: ...
 
 class CachedFunction:
     def __init__(
         self,
         fn_obj: Callable[..., Any],
-        max_size: int,
+        max_size: int | None = None,
         ttl: float | None = None,
+        sliding_ttl: bool | None = None,
+        fallback: bool = False,
+        cache_version: str | int | None = None,
+        tags_func: Callable[[tuple[Any, ...], dict[str, Any], Any], Iterable[str]] | None = None,
+        unhashable: Literal["error", "bypass", "serialize"] | None = None,
+        record_latencies: bool | None = None,
+        min_compute_time: float | None = None,
+        compact_keys: bool | None = None,
     ) -> None: ...
     def __call__(self, *args: Any, **kwargs: Any) -> Any: ...
     def get(self, *args: Any, **kwargs: Any) -> Any | None: ...
     def _probe(self, *args: Any, **kwargs: Any) -> tuple[bool, Any]: ...
-    def set(self, value: Any, *args: Any, **kwargs: Any) -> None: ...
+    def set(
+        self,
+        value: Any,
+        *args: Any,
+        _warp_tags: Iterable[str] | None = None,
+        _warp_compute_time: float | None = None,
+        **kwargs: Any,
+    ) -> None: ...
+    def invalidate_tag(self, tag: str) -> int: ...
+    def cache_info(self) -> CacheInfo: ...
+    def metrics(self) -> dict[str, int | float]: ...
+    def cache_clear(self, release_memory: bool = False) -> None: ...
+    def reset_stats(self, local_only: bool = False) -> None: ...
+    def latency_histogram(self) -> dict[str, list[int | float]] | None: ...
+    def _record_latency(self, hit: bool, nanos: int) -> None: ...
+
+class CachedProperty(Generic[_T]):
+    def __init__(
+        self,
+        func: Callable[[Any], _T],
+        ttl: float | None = None,
+        max_size: int | None = None,
+    ) -> None: ...
+    @property
+    def func(self) -> Callable[[Any], _T]: ...
+    @property
+    def attrname(self) -> str | None: ...
+    def __set_name__(self, owner: type, name: str) -> None: ...
+    @overload
+    def __get__(self, instance: None, owner: type | None = None) -> CachedProperty[_T]: ...
+    @overload
+    def __get__(self, instance: object, owner: type | None = None) -> _T: ...
     def cache_info(self) -> CacheInfo: ...
+    def metrics(self) -> dict[str, int | float]: ...
     def cache_clear(self) -> None: ...
+    def reset_stats(self) -> None: ...
 
 class SharedCachedFunction:
     def __init__(
         self,
         fn_obj: Callable[..., Any],
-        max_size: int,
+        max_size: int | None = None,
         ttl: float | None = None,
-        max_key_size: int = 512,
-        max_value_size: int = 4096,
+        max_key_size: int | None = None,
+        max_value_size: int | None = None,
         shm_name: str | None = None,
+        stale_ttl: float | None = None,
+        sliding_ttl: bool | None = None,
+        on_mismatch: Literal["error", "recreate", "open_existing"] | None = None,
+        shm_dir: str | os.PathLike[str] | None = None,
+        file_mode: int | None = None,
+        allow_insecure: bool | None = None,
+        persistent: bool | None = None,
+        sync_interval: float | None = None,
+        compress: Literal["lz4", "zstd"] | None = None,
+        compress_threshold: int | None = None,
+        serializer: tuple[Callable[[Any], bytes], Callable[[bytes], Any]] | None = None,
+        oversize: Literal["skip", "warn", "raise"] | None = None,
+        chunk_size: int | None = None,
+        overflow_count: int | None = None,
+        overflow_size: int | None = None,
+        lock_timeout: float | None = None,
+        checksums: bool | None = None,
+        lock_memory: bool | None = None,
+        prefault: bool | None = None,
+        on_evict: Callable[[Any, Any], object] | None = None,
+        readonly: bool | None = None,
+        cache_version: str | int | None = None,
+        tags_func: Callable[[tuple[Any, ...], dict[str, Any], Any], Iterable[str]] | None = None,
+        record_latencies: bool | None = None,
+        min_compute_time: float | None = None,
     ) -> None: ...
     def __call__(self, *args: Any, **kwargs: Any) -> Any: ...
     def get(self, *args: Any, **kwargs: Any) -> Any | None: ...
     def _probe(self, *args: Any, **kwargs: Any) -> tuple[bool, Any]: ...
-    def set(self, value: Any, *args: Any, **kwargs: Any) -> None: ...
+    def get_many(self, keys: list[tuple[Any, ...]]) -> list[Any | None]: ...
+    def set(
+        self,
+        value: Any,
+        *args: Any,
+        _warp_ttl: float | None = None,
+        _warp_tags: Iterable[str] | None = None,
+        _warp_compute_time: float | None = None,
+        **kwargs: Any,
+    ) -> None: ...
+    def invalidate_tag(self, tag: str) -> int: ...
+    def refresh(self, *args: Any, **kwargs: Any) -> Any: ...
+    def touch(self, *args: Any, **kwargs: Any) -> bool: ...
+    def cas(self, expected: Any, new: Any, *args: Any, **kwargs: Any) -> bool: ...
+    def incr(self, *args: Any, amount: int = 1, initial: int = 0, **kwargs: Any) -> int: ...
+    def decr(self, *args: Any, amount: int = 1, initial: int = 0, **kwargs: Any) -> int: ...
+    def setdefault(self, value: Any, *args: Any, **kwargs: Any) -> Any: ...
+    def set_many(self, pairs: Iterable[tuple[tuple[Any, ...], Any]]) -> dict[str, int]: ...
+    def keys(self) -> list[Any]: ...
+    def items(self) -> list[tuple[Any, Any]]: ...
     def cache_info(self) -> SharedCacheInfo: ...
-    def cache_clear(self) -> None: ...
+    def local_cache_info(self) -> SharedCacheInfo: ...
+    def contention_info(self) -> dict[str, int]: ...
+    def metrics(self) -> dict[str, int | float]: ...
+    def cache_clear(self, release_memory: bool = False) -> None: ...
+    def reset_stats(self, local_only: bool = False) -> None: ...
+    def latency_histogram(self) -> dict[str, list[int | float]] | None: ...
+    def _record_latency(self, hit: bool, nanos: int) -> None: ...
+    def value_size_histogram(self) -> dict[str, Any]: ...
+    def debug_info(self, max_slots: int = 1000) -> dict[str, Any]: ...
+    def verify(self) -> list[str]: ...
+    def cache_resize(self, max_size: int) -> None: ...
+    def sync(self) -> None: ...
+    def save(self, path: str | os.PathLike[str]) -> int: ...
+    def load(self, path: str | os.PathLike[str]) -> dict[str, int]: ...
+
+class SharedDict:
+    def __init__(
+        self,
+        name: str,
+        max_size: int | None = None,
+        ttl: float | None = None,
+        max_key_size: int | None = None,
+        max_value_size: int | None = None,
+        on_mismatch: Literal["error", "recreate", "open_existing"] | None = None,
+        shm_dir: str | os.PathLike[str] | None = None,
+        lock_timeout: float | None = None,
+        checksums: bool | None = None,
+        readonly: bool | None = None,
+    ) -> None: ...
+    def get(self, key: Any, default: Any = None) -> Any: ...
+    def set(self, key: Any, value: Any) -> None: ...
+    def delete(self, key: Any) -> bool: ...
+    def cas(self, key: Any, expected: Any, new: Any) -> bool: ...
+    def incr(self, key: Any, amount: int = 1, initial: int = 0) -> int: ...
+    def decr(self, key: Any, amount: int = 1, initial: int = 0) -> int: ...
+    def __contains__(self, key: Any) -> bool: ...
+    def __len__(self) -> int: ...
+    def clear(self) -> None: ...
+    def info(self) -> SharedCacheInfo: ...
+
+def configure(**defaults: Any) -> None: ...
+def get_config() -> dict[str, Any]: ...
+def gc_shared(
+    max_age_seconds: float,
+    dry_run: bool = False,
+    shm_dir: str | os.PathLike[str] | None = None,
+) -> list[str]: ...
+def list_shared(
+    shm_dir: str | os.PathLike[str] | None = None,
+) -> list[dict[str, Any]]: ...
+def register_serializer(
+    cls: type,
+    encode: Callable[[Any], bytes],
+    decode: Callable[[bytes], Any],
+    *,
+    name: str | None = None,
+) -> None: ...